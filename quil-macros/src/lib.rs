@@ -0,0 +1,287 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Procedural macros for `quil-rs`.
+
+use std::str::FromStr;
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+
+/// Write Quil inline within Rust, producing a `quil_rs::Program`.
+///
+/// Since Rust discards line breaks within macro input, instructions are separated by `;`. The
+/// body of a block definition (such as `DEFCAL` or `DEFFRAME`) is written within braces after
+/// its colon, with its own instructions likewise separated by `;`:
+///
+/// ```
+/// use quil_macros::quil;
+///
+/// let program = quil! {
+///     DECLARE ro BIT[2];
+///     DEFCAL RX(%theta) q: {
+///         SHIFT-PHASE q "rf" %theta
+///     };
+///     RX(pi/2) 0;
+///     MEASURE 0 ro[0]
+/// };
+/// assert_eq!(program.instructions.len(), 2);
+/// ```
+///
+/// The Quil is parsed when the macro is expanded, and any syntax error within it is reported at
+/// compile time, as is input which cannot be written as Quil, such as a Rust character literal.
+/// The macro expands to a call to `Program::from_str` on the checked source, so the Quil is parsed
+/// again each time the expression is evaluated; build the program once, outside of any loop, where
+/// that matters.
+///
+/// `quil-rs` depends on nothing from this crate, so it cannot re-export the macro. Depend on both
+/// crates, with `quil-rs` under its own name, since the expansion refers to `::quil_rs::Program`.
+///
+/// As in Quil, a `-` written directly between two words, with no space on either side, joins them
+/// into one word, as in `SET-PHASE` or `JUMP-WHEN`. Written with spaces, as in `pi - theta[0]`, it
+/// is a subtraction.
+#[proc_macro]
+pub fn quil(input: TokenStream) -> TokenStream {
+    let mut writer = QuilWriter::default();
+    match writer.write_statements(input, 0) {
+        Ok(()) => {
+            writer.end_line();
+            let source = writer.lines.join("\n");
+            match quil_rs::Program::from_str(&source) {
+                Ok(_) => parse_program(&source),
+                Err(error) => compile_error(
+                    Span::call_site(),
+                    &format!("invalid Quil: {}\n{}", error, source),
+                ),
+            }
+        }
+        Err((span, message)) => compile_error(span, &message),
+    }
+}
+
+type WriteResult = Result<(), (Span, String)>;
+
+/// Converts macro input into Quil source, one line at a time.
+#[derive(Default)]
+struct QuilWriter {
+    lines: Vec<String>,
+    line: String,
+    indent: usize,
+
+    /// Whether the next token should be written without a separating space.
+    glue: bool,
+}
+
+impl QuilWriter {
+    /// Finish the current line, if anything has been written to it.
+    fn end_line(&mut self) {
+        if !self.line.is_empty() {
+            let line = std::mem::take(&mut self.line);
+            self.lines
+                .push(format!("{}{}", "    ".repeat(self.indent), line));
+        }
+        self.glue = false;
+    }
+
+    fn push(&mut self, text: &str) {
+        if !self.glue && !self.line.is_empty() {
+            self.line.push(' ');
+        }
+        self.line.push_str(text);
+        self.glue = false;
+    }
+
+    /// Write a sequence of `;`-separated instructions, each on its own line at the given level of
+    /// indentation.
+    fn write_statements(&mut self, input: TokenStream, indent: usize) -> WriteResult {
+        self.indent = indent;
+        let tokens: Vec<TokenTree> = input.into_iter().collect();
+        for (index, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Punct(punct) if punct.as_char() == ';' => self.end_line(),
+                TokenTree::Group(group) if group.delimiter() == Delimiter::Brace => {
+                    self.end_line();
+                    self.write_statements(group.stream(), indent + 1)?;
+                    self.end_line();
+                    self.indent = indent;
+                }
+                token => self.write_token(token, &tokens, index)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the tokens of a single line, such as those within parentheses.
+    fn write_inline(&mut self, input: TokenStream) -> WriteResult {
+        let tokens: Vec<TokenTree> = input.into_iter().collect();
+        for (index, token) in tokens.iter().enumerate() {
+            self.write_token(token, &tokens, index)?;
+        }
+        Ok(())
+    }
+
+    /// Write the token found at `index` within `tokens`, which are the tokens of the current
+    /// group, for context.
+    fn write_token(
+        &mut self,
+        token: &TokenTree,
+        tokens: &[TokenTree],
+        index: usize,
+    ) -> WriteResult {
+        match token {
+            TokenTree::Ident(ident) => self.push(&ident.to_string()),
+            TokenTree::Literal(literal) => {
+                let text = literal.to_string();
+                if text.starts_with('\'') || text.starts_with('b') || text.starts_with('r') {
+                    return Err((
+                        literal.span(),
+                        format!("`{}` cannot be written in Quil", text),
+                    ));
+                }
+                self.push(&text);
+            }
+            TokenTree::Punct(punct) => match punct.as_char() {
+                ';' => {
+                    return Err((
+                        punct.span(),
+                        "instructions may not be separated within parentheses or brackets"
+                            .to_owned(),
+                    ))
+                }
+                '%' | '@' => {
+                    self.push(&punct.to_string());
+                    self.glue = true;
+                }
+                '-' if joins_words(tokens, index) => {
+                    self.glue = true;
+                    self.push("-");
+                    self.glue = true;
+                }
+                ',' | ':' => {
+                    self.glue = true;
+                    self.push(&punct.to_string());
+                }
+                _ => self.push(&punct.to_string()),
+            },
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", ""),
+                    Delimiter::Brace => {
+                        return Err((
+                            group.span(),
+                            "a block must follow the `:` of a definition".to_owned(),
+                        ))
+                    }
+                };
+                // Parameters and memory indices directly follow the name they belong to.
+                self.glue = is_ident(tokens.get(index.wrapping_sub(1)));
+                self.push(open);
+                self.glue = true;
+                self.write_inline(group.stream())?;
+                self.glue = true;
+                self.push(close);
+            }
+        }
+        Ok(())
+    }
+}
+
+fn is_ident(token: Option<&TokenTree>) -> bool {
+    matches!(token, Some(TokenTree::Ident(_)))
+}
+
+/// Whether the `-` found at `index` within `tokens` is written directly between two words, with
+/// no space on either side, and so is part of a single word.
+fn joins_words(tokens: &[TokenTree], index: usize) -> bool {
+    let (previous, next) = (tokens.get(index.wrapping_sub(1)), tokens.get(index + 1));
+    match (previous, next) {
+        (Some(previous), Some(next)) if is_ident(Some(previous)) && is_ident(Some(next)) => {
+            let hyphen = tokens[index].span();
+            adjacent(previous.span(), hyphen) && adjacent(hyphen, next.span())
+        }
+        _ => false,
+    }
+}
+
+/// Whether `second` begins exactly where `first` ends in the source.
+fn adjacent(first: Span, second: Span) -> bool {
+    let (end, start) = (first.end(), second.start());
+    end.line() == start.line() && end.column() == start.column()
+}
+
+/// Produce the expression which builds a `Program` from the Quil source, which has already been
+/// found to be valid:
+///
+/// ```text
+/// ::std::result::Result::expect(
+///     <::quil_rs::Program as ::std::str::FromStr>::from_str(source),
+///     "...",
+/// )
+/// ```
+fn parse_program(source: &str) -> TokenStream {
+    let mut arguments = vec![TokenTree::Punct(Punct::new('<', Spacing::Alone))];
+    arguments.extend(path(&["quil_rs", "Program"]));
+    arguments.push(TokenTree::Ident(Ident::new("as", Span::call_site())));
+    arguments.extend(path(&["std", "str", "FromStr"]));
+    arguments.push(TokenTree::Punct(Punct::new('>', Spacing::Alone)));
+    arguments.extend(path(&["from_str"]));
+    arguments.push(TokenTree::Group(Group::new(
+        Delimiter::Parenthesis,
+        TokenTree::Literal(Literal::string(source)).into(),
+    )));
+    arguments.push(TokenTree::Punct(Punct::new(',', Spacing::Alone)));
+    arguments.push(TokenTree::Literal(Literal::string(
+        "the input to quil! was parsed when the macro was expanded",
+    )));
+
+    let mut output = path(&["std", "result", "Result", "expect"]);
+    output.push(TokenTree::Group(Group::new(
+        Delimiter::Parenthesis,
+        arguments.into_iter().collect(),
+    )));
+    output.into_iter().collect()
+}
+
+/// Produce an absolute path, such as `::std::str::FromStr`, from its segments.
+fn path(segments: &[&str]) -> Vec<TokenTree> {
+    segments
+        .iter()
+        .flat_map(|segment| {
+            vec![
+                TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+                TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+                TokenTree::Ident(Ident::new(segment, Span::call_site())),
+            ]
+        })
+        .collect()
+}
+
+/// Produce a `compile_error!` reporting the message at the given span.
+fn compile_error(span: Span, message: &str) -> TokenStream {
+    let mut literal = Literal::string(message);
+    literal.set_span(span);
+    let mut group = Group::new(Delimiter::Parenthesis, TokenTree::Literal(literal).into());
+    group.set_span(span);
+    let mut bang = Punct::new('!', Spacing::Alone);
+    bang.set_span(span);
+
+    vec![
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(bang),
+        TokenTree::Group(group),
+    ]
+    .into_iter()
+    .collect()
+}
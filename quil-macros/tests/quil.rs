@@ -0,0 +1,58 @@
+use std::str::FromStr;
+
+use quil_macros::quil;
+use quil_rs::Program;
+
+#[test]
+fn program() {
+    let program = quil! {
+        DECLARE ro BIT[2];
+        DEFFRAME 0 "rf": {
+            SAMPLE-RATE: 1.0
+        };
+        DEFCAL RX(%theta) q: {
+            SHIFT-PHASE q "rf" -%theta;
+            PULSE q "rf" flat(duration: 1, iq: 1)
+        }
+        LABEL @start;
+        RX(pi/2) 0;
+        CONTROLLED X 0 1;
+        MEASURE 0 ro[0];
+        JUMP-UNLESS @start ro[0]
+    };
+    let expected = Program::from_str(
+        "DECLARE ro BIT[2]
+DEFFRAME 0 \"rf\":
+    SAMPLE-RATE: 1.0
+DEFCAL RX(%theta) q:
+    SHIFT-PHASE q \"rf\" -%theta
+    PULSE q \"rf\" flat(duration: 1, iq: 1)
+LABEL @start
+RX(pi/2) 0
+CONTROLLED X 0 1
+MEASURE 0 ro[0]
+JUMP-UNLESS @start ro[0]
+",
+    )
+    .unwrap();
+    assert_eq!(program, expected);
+}
+
+#[test]
+fn hyphens() {
+    let program = quil! {
+        DECLARE theta-offset REAL;
+        DECLARE pi-theta REAL;
+        RX(pi - theta-offset[0]) 0;
+        RX(pi-theta[0]) 0
+    };
+    let expected = Program::from_str(
+        "DECLARE theta-offset REAL
+DECLARE pi-theta REAL
+RX(pi - theta-offset[0]) 0
+RX(pi-theta[0]) 0
+",
+    )
+    .unwrap();
+    assert_eq!(program, expected);
+}
@@ -0,0 +1,8 @@
+use quil_macros::quil;
+
+fn main() {
+    let _ = quil! {
+        DECLARE ro BIT;
+        MEASURE 0 ro[0] 1
+    };
+}
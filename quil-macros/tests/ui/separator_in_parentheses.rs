@@ -0,0 +1,7 @@
+use quil_macros::quil;
+
+fn main() {
+    let _ = quil! {
+        RX(pi; 2) 0
+    };
+}
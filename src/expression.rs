@@ -14,32 +14,422 @@
 
 use lexical::{format, to_string_with_options, WriteFloatOptions};
 use num_complex::Complex64;
-use std::collections::{hash_map::DefaultHasher, HashMap};
+use std::collections::{hash_map::DefaultHasher, BTreeMap, HashMap, HashSet};
+use std::convert::TryFrom;
 use std::f64::consts::PI;
 use std::fmt;
 use std::hash::{Hash, Hasher};
-use std::num::NonZeroI32;
+use std::num::{NonZeroI32, NonZeroUsize};
+use std::ops::Range;
 use std::str::FromStr;
 
 #[cfg(test)]
 use proptest_derive::Arbitrary;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-use crate::parser::{lex, parse_expression};
-use crate::program::{disallow_leftover, ProgramError};
-use crate::{imag, instruction::MemoryReference, real};
+use crate::instruction::{MemoryReference, ScalarType, Vector};
+use crate::parser::{self, lex, lex_prefix, Token};
+use crate::program::{disallow_leftover, MemoryRegion, ProgramError};
+use crate::{imag, real};
+
+mod arena;
+#[cfg(feature = "arbitrary-precision")]
+mod precise;
+#[cfg(any(test, feature = "proptest"))]
+pub mod strategies;
+
+pub use arena::{ExpressionArena, ExpressionId, ExpressionNode};
+#[cfg(feature = "arbitrary-precision")]
+pub use precise::PreciseValue;
 
 /// The different possible types of errors that could occur during expression evaluation.
-#[derive(Clone, Debug, PartialEq, Eq)]
+#[derive(Clone, Debug, PartialEq, Eq, Error)]
 pub enum EvaluationError {
     /// There wasn't enough information to completely evaluate an expression.
-    Incomplete,
+    #[error(
+        "could not evaluate {expression}: {}",
+        format_unbound(.unbound_variables, .unbound_memory_references)
+    )]
+    Incomplete {
+        /// The smallest subexpression containing every unbound value, which therefore could not
+        /// be reduced to a number.
+        expression: Box<Expression>,
+        /// The variables which had no value, in order of first appearance.
+        unbound_variables: Vec<String>,
+        /// The memory references which had no value, in order of first appearance.
+        unbound_memory_references: Vec<MemoryReference>,
+    },
     /// An operation expected a real number but received a complex one.
+    #[error("expected a real number but received a complex one")]
     NumberNotReal,
     /// An operation expected a number but received a different type of expression.
+    #[error("expected a number but received a different type of expression")]
     NotANumber,
+    /// A function was called which is neither built into Quil nor provided for evaluation.
+    #[error("function {0} is not defined")]
+    UndefinedFunction(String),
+    /// The divisor of a division evaluated to zero. Only reported by
+    /// [`Expression::evaluate_checked`].
+    #[error("division by zero in {expression}")]
+    DivisionByZero { expression: Box<Expression> },
+    /// A subexpression evaluated to an infinite or `NaN` value, such as by overflow. Only reported
+    /// by [`Expression::evaluate_checked`].
+    #[error("{expression} does not evaluate to a finite number")]
+    NotFinite { expression: Box<Expression> },
+    /// A memory reference was read as a real number from a region of another type.
+    #[error("cannot read {memory_reference} as a real number from a region of type {data_type}")]
+    NotRealMemory {
+        memory_reference: MemoryReference,
+        data_type: ScalarType,
+    },
+    /// A memory reference was read beyond the end of its region.
+    #[error("cannot read {memory_reference} from a region of length {length}")]
+    IndexOutOfBounds {
+        memory_reference: MemoryReference,
+        length: u64,
+    },
+}
+
+/// Describe the values missing from an incomplete evaluation, such as
+/// `no value for variables %a, %b or memory reference ro[0]`.
+fn format_unbound(variables: &[String], memory_references: &[MemoryReference]) -> String {
+    let list = |kind: &str, items: Vec<String>| match items.len() {
+        0 => None,
+        1 => Some(format!("{} {}", kind, items[0])),
+        _ => Some(format!("{}s {}", kind, items.join(", "))),
+    };
+    let variables = list(
+        "variable",
+        variables.iter().map(|name| format!("%{}", name)).collect(),
+    );
+    let memory_references = list(
+        "memory reference",
+        memory_references.iter().map(ToString::to_string).collect(),
+    );
+    match (variables, memory_references) {
+        (Some(variables), Some(memory_references)) => {
+            format!("no value for {} or {}", variables, memory_references)
+        }
+        (Some(missing), None) | (None, Some(missing)) => format!("no value for {}", missing),
+        (None, None) => "no value for some subexpression".to_owned(),
+    }
+}
+
+impl EvaluationError {
+    /// The error for a single variable or memory reference `expression` which has no value.
+    fn unbound(expression: &Expression) -> Self {
+        let (unbound_variables, unbound_memory_references) = match expression {
+            Expression::Variable(name) => (vec![name.clone()], vec![]),
+            Expression::Address(memory_reference) => (vec![], vec![memory_reference.clone()]),
+            _ => (vec![], vec![]),
+        };
+        EvaluationError::Incomplete {
+            expression: Box::new(expression.clone()),
+            unbound_variables,
+            unbound_memory_references,
+        }
+    }
+
+    /// Combine the errors from evaluating both operands of `expression`. If both are incomplete,
+    /// the result is incomplete on the whole `expression`; otherwise, the left error is kept.
+    fn combine(self, other: Self, expression: &Expression) -> Self {
+        match (self, other) {
+            (
+                EvaluationError::Incomplete {
+                    mut unbound_variables,
+                    mut unbound_memory_references,
+                    ..
+                },
+                EvaluationError::Incomplete {
+                    unbound_variables: other_variables,
+                    unbound_memory_references: other_memory_references,
+                    ..
+                },
+            ) => {
+                for variable in other_variables {
+                    if !unbound_variables.contains(&variable) {
+                        unbound_variables.push(variable);
+                    }
+                }
+                for memory_reference in other_memory_references {
+                    if !unbound_memory_references.contains(&memory_reference) {
+                        unbound_memory_references.push(memory_reference);
+                    }
+                }
+                EvaluationError::Incomplete {
+                    expression: Box::new(expression.clone()),
+                    unbound_variables,
+                    unbound_memory_references,
+                }
+            }
+            (error, _) => error,
+        }
+    }
+}
+
+/// Values for memory references, by region name and then by index, to use in evaluation.
+/// Unlike the `HashMap<&str, Vec<f64>>` accepted by [`Expression::evaluate`], these values may be
+/// complex, and a region need only have values for the indices which are used.
+///
+/// # Example
+///
+/// ```rust
+/// use quil_rs::{expression::{Expression, MemoryValues}, instruction::MemoryReference};
+/// use std::str::FromStr;
+/// use std::collections::HashMap;
+/// use num_complex::Complex64;
+///
+/// let mut memory = MemoryValues::new();
+/// memory.set_region("theta", vec![0.5, 1.5]);
+/// memory.insert(MemoryReference { name: "iq".to_owned(), index: 3 }, Complex64::new(0.0, 1.0));
+///
+/// let expression = Expression::from_str("theta[1] * iq[3]").unwrap();
+/// let evaluated = expression.evaluate_with_memory(&HashMap::new(), &memory).unwrap();
+///
+/// assert_eq!(evaluated, Complex64::new(0.0, 1.5));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryValues {
+    regions: HashMap<String, HashMap<u64, Complex64>>,
+}
+
+impl MemoryValues {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The value of the given memory reference, if there is one.
+    pub fn get(&self, memory_reference: &MemoryReference) -> Option<Complex64> {
+        self.regions
+            .get(&memory_reference.name)?
+            .get(&memory_reference.index)
+            .copied()
+    }
+
+    /// Set the value of a single memory reference, returning its previous value if any.
+    pub fn insert<T: Into<Complex64>>(
+        &mut self,
+        memory_reference: MemoryReference,
+        value: T,
+    ) -> Option<Complex64> {
+        self.regions
+            .entry(memory_reference.name)
+            .or_default()
+            .insert(memory_reference.index, value.into())
+    }
+
+    /// Replace all values of the named region with `values`, starting from index 0.
+    pub fn set_region<T: Into<Complex64>>(&mut self, name: &str, values: Vec<T>) {
+        let region = values
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| (index as u64, value.into()))
+            .collect();
+        self.regions.insert(name.to_owned(), region);
+    }
+
+    /// Remove the named region, returning whether it had any values.
+    pub fn remove_region(&mut self, name: &str) -> bool {
+        self.regions.remove(name).is_some()
+    }
+}
+
+impl From<&HashMap<&str, Vec<f64>>> for MemoryValues {
+    fn from(memory_references: &HashMap<&str, Vec<f64>>) -> Self {
+        let mut values = Self::new();
+        for (name, region) in memory_references {
+            values.set_region(name, region.clone());
+        }
+        values
+    }
+}
+
+/// The values within a memory region, as declared with `DECLARE`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MemoryRegionContents {
+    Bit(Vec<bool>),
+    Integer(Vec<i64>),
+    Octet(Vec<u8>),
+    Real(Vec<f64>),
+}
+
+impl MemoryRegionContents {
+    /// The contents of a newly-declared region of the given type and length, with every value
+    /// zero.
+    pub fn zeroed(size: &Vector) -> Self {
+        let length = size.length as usize;
+        match size.data_type {
+            ScalarType::Bit => Self::Bit(vec![false; length]),
+            ScalarType::Integer => Self::Integer(vec![0; length]),
+            ScalarType::Octet => Self::Octet(vec![0; length]),
+            ScalarType::Real => Self::Real(vec![0f64; length]),
+        }
+    }
+
+    pub fn data_type(&self) -> ScalarType {
+        match self {
+            Self::Bit(_) => ScalarType::Bit,
+            Self::Integer(_) => ScalarType::Integer,
+            Self::Octet(_) => ScalarType::Octet,
+            Self::Real(_) => ScalarType::Real,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Bit(values) => values.len(),
+            Self::Integer(values) => values.len(),
+            Self::Octet(values) => values.len(),
+            Self::Real(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The contents of classical memory, by region name, for evaluating expressions against the
+/// memory model of a program.
+///
+/// # Example
+///
+/// ```rust
+/// use quil_rs::expression::{EvaluationError, Expression, MemoryContents, MemoryRegionContents};
+/// use quil_rs::Program;
+/// use std::str::FromStr;
+/// use std::collections::HashMap;
+/// use num_complex::Complex64;
+///
+/// let program = Program::from_str("DECLARE theta REAL[2]\nDECLARE ro BIT").unwrap();
+/// let mut memory = MemoryContents::from_declarations(&program.memory_regions);
+/// memory.insert("theta", MemoryRegionContents::Real(vec![0.5, 1.5]));
+///
+/// let expression = Expression::from_str("theta[0] + theta[1]").unwrap();
+/// let evaluated = expression.evaluate_with_memory_contents(&HashMap::new(), &memory);
+/// assert_eq!(evaluated, Ok(Complex64::from(2.0)));
+///
+/// let expression = Expression::from_str("theta[0] + ro[0]").unwrap();
+/// let evaluated = expression.evaluate_with_memory_contents(&HashMap::new(), &memory);
+/// assert!(matches!(evaluated, Err(EvaluationError::NotRealMemory { .. })));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemoryContents {
+    regions: HashMap<String, MemoryRegionContents>,
+}
+
+impl MemoryContents {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Memory with each of the given regions declared, and every value zero.
+    pub fn from_declarations(memory_regions: &BTreeMap<String, MemoryRegion>) -> Self {
+        let regions = memory_regions
+            .iter()
+            .map(|(name, region)| (name.clone(), MemoryRegionContents::zeroed(&region.size)))
+            .collect();
+        Self { regions }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&MemoryRegionContents> {
+        self.regions.get(name)
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut MemoryRegionContents> {
+        self.regions.get_mut(name)
+    }
+
+    /// Set the contents of the named region, returning its previous contents if any.
+    pub fn insert(
+        &mut self,
+        name: &str,
+        contents: MemoryRegionContents,
+    ) -> Option<MemoryRegionContents> {
+        self.regions.insert(name.to_owned(), contents)
+    }
+}
+
+/// A source of values for memory references during evaluation.
+trait MemoryLookup {
+    /// The value of the memory reference, or `None` if it has no value.
+    fn value(
+        &self,
+        memory_reference: &MemoryReference,
+    ) -> Result<Option<Complex64>, EvaluationError>;
+}
+
+impl MemoryLookup for HashMap<&str, Vec<f64>> {
+    fn value(
+        &self,
+        memory_reference: &MemoryReference,
+    ) -> Result<Option<Complex64>, EvaluationError> {
+        Ok(self
+            .get(memory_reference.name.as_str())
+            .and_then(|values| values.get(memory_reference.index as usize))
+            .map(|value| real!(*value)))
+    }
+}
+
+impl MemoryLookup for MemoryValues {
+    fn value(
+        &self,
+        memory_reference: &MemoryReference,
+    ) -> Result<Option<Complex64>, EvaluationError> {
+        Ok(self.get(memory_reference))
+    }
+}
+
+impl MemoryLookup for MemoryContents {
+    fn value(
+        &self,
+        memory_reference: &MemoryReference,
+    ) -> Result<Option<Complex64>, EvaluationError> {
+        let values = match self.get(&memory_reference.name) {
+            Some(MemoryRegionContents::Real(values)) => values,
+            Some(contents) => {
+                return Err(EvaluationError::NotRealMemory {
+                    memory_reference: memory_reference.clone(),
+                    data_type: contents.data_type(),
+                })
+            }
+            None => return Ok(None),
+        };
+        match values.get(memory_reference.index as usize) {
+            Some(value) => Ok(Some(real!(*value))),
+            None => Err(EvaluationError::IndexOutOfBounds {
+                memory_reference: memory_reference.clone(),
+                length: values.len() as u64,
+            }),
+        }
+    }
+}
+
+/// Everything needed to evaluate an expression, besides the expression itself.
+struct EvaluationContext<'a> {
+    variables: &'a HashMap<String, Complex64>,
+    memory_references: &'a dyn MemoryLookup,
+    functions: &'a FunctionRegistry,
+    /// Whether to fail on division by zero and non-finite values.
+    checked: bool,
 }
 
+/// Functions, by name, which may be called within an expression in addition to those built into
+/// Quil. These are only used for evaluation; see [`Expression::evaluate_with_functions`].
+pub type FunctionRegistry = HashMap<String, fn(Complex64) -> Complex64>;
+
+/// An expression within Quil.
+///
+/// With the `serde` feature, expressions serialize with the kind of node under `type` and its
+/// contents under `value`, such as `{"type": "variable", "value": "theta"}`.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum Expression {
     Address(MemoryReference),
     FunctionCall {
@@ -112,13 +502,13 @@ impl Hash for Expression {
             }
             Number(n) => {
                 "Number".hash(state);
-                // Skip zero values (akin to `format_complex`).
+                // Skip zero values (akin to `format_complex`), so that `0` and `-0` hash the same.
                 // Also, since f64 isn't hashable, use the u64 binary representation.
                 // The docs claim this is rather portable: https://doc.rust-lang.org/std/primitive.f64.html#method.to_bits
-                if n.re.abs() > 0f64 {
+                if n.re != 0f64 {
                     n.re.to_bits().hash(state)
                 }
-                if n.im.abs() > 0f64 {
+                if n.im != 0f64 {
                     n.im.to_bits().hash(state)
                 }
             }
@@ -141,10 +531,64 @@ impl Hash for Expression {
     }
 }
 
+/// Are these parts of complex numbers equal, in the same sense as they are hashed? That is, all
+/// zeroes are equal, and otherwise values must be bitwise equal (so `NaN` equals itself).
+fn number_parts_eq(left: f64, right: f64) -> bool {
+    (left == 0f64 && right == 0f64) || left.to_bits() == right.to_bits()
+}
+
 impl PartialEq for Expression {
-    // Partial equality by hash value
+    // Structural equality, consistent with `Hash`: the operands of commutative operators may
+    // appear in either order.
     fn eq(&self, other: &Self) -> bool {
-        hash_to_u64(self) == hash_to_u64(other)
+        use Expression::*;
+        match (self, other) {
+            (Address(left), Address(right)) => left == right,
+            (
+                FunctionCall {
+                    function,
+                    expression,
+                },
+                FunctionCall {
+                    function: other_function,
+                    expression: other_expression,
+                },
+            ) => function == other_function && expression == other_expression,
+            (
+                Infix {
+                    left,
+                    operator,
+                    right,
+                },
+                Infix {
+                    left: other_left,
+                    operator: other_operator,
+                    right: other_right,
+                },
+            ) => {
+                operator == other_operator
+                    && ((left == other_left && right == other_right)
+                        || (matches!(operator, InfixOperator::Plus | InfixOperator::Star)
+                            && left == other_right
+                            && right == other_left))
+            }
+            (Number(left), Number(right)) => {
+                number_parts_eq(left.re, right.re) && number_parts_eq(left.im, right.im)
+            }
+            (PiConstant, PiConstant) => true,
+            (
+                Prefix {
+                    operator,
+                    expression,
+                },
+                Prefix {
+                    operator: other_operator,
+                    expression: other_expression,
+                },
+            ) => operator == other_operator && expression == other_expression,
+            (Variable(left), Variable(right)) => left == right,
+            _ => false,
+        }
     }
 }
 
@@ -158,6 +602,9 @@ fn calculate_infix(
 ) -> num_complex::Complex64 {
     use InfixOperator::*;
     match operator {
+        // Exact, even where `powc` would round or produce `NaN`, as for `0^0`.
+        Caret if *right == real!(0f64) => real!(1f64),
+        Caret if *right == real!(1f64) => *left,
         Caret => left.powc(*right),
         Plus => left + right,
         Minus => left - right,
@@ -166,19 +613,47 @@ fn calculate_infix(
     }
 }
 
-/// Compute the result of a Quil-defined expression function where the operand is complex.
+/// Compute the result of an expression function where the operand is complex, looking up any
+/// function not built into Quil within `functions`.
 fn calculate_function(
     function: &ExpressionFunction,
     argument: &num_complex::Complex64,
-) -> num_complex::Complex64 {
+    functions: &FunctionRegistry,
+) -> Result<num_complex::Complex64, EvaluationError> {
     use ExpressionFunction::*;
-    match function {
+    Ok(match function {
+        AbsoluteValue => real!(argument.norm()),
+        ArcCosine => argument.acos(),
+        ArcSine => argument.asin(),
+        ArcTangent => argument.atan(),
         Sine => argument.sin(),
         Cis => argument.cos() + imag!(1f64) * argument.sin(),
         Cosine => argument.cos(),
         Exponent => argument.exp(),
+        NaturalLogarithm => argument.ln(),
         SquareRoot => argument.sqrt(),
-    }
+        Tangent => argument.tan(),
+        Custom(name) => match functions.get(name) {
+            Some(implementation) => implementation(*argument),
+            None => return Err(EvaluationError::UndefinedFunction(name.clone())),
+        },
+    })
+}
+
+/// Are these complex numbers equal to within `tolerance`, measured as the magnitude of their
+/// difference?
+///
+/// # Example
+///
+/// ```rust
+/// use quil_rs::expression::complex_approx_eq;
+/// use num_complex::Complex64;
+///
+/// assert!(complex_approx_eq(Complex64::new(0.1 + 0.2, 1.0), Complex64::new(0.3, 1.0), 1e-12));
+/// assert!(!complex_approx_eq(Complex64::new(0.3, 1.0), Complex64::new(0.3, 1.1), 1e-12));
+/// ```
+pub fn complex_approx_eq(left: Complex64, right: Complex64, tolerance: f64) -> bool {
+    (left - right).norm() <= tolerance
 }
 
 /// Is this a small floating point number?
@@ -187,9 +662,418 @@ fn is_small(x: f64) -> bool {
     x.abs() < 1e-16
 }
 
+/// The largest magnitude of integer which every `f64` between zero and it can represent exactly.
+const MAX_EXACT_INTEGER: i128 = 1 << 53;
+
+/// An exact rational number, kept in lowest terms with a positive denominator. Both parts are kept
+/// within [`MAX_EXACT_INTEGER`] so that they may be written out as expression numbers exactly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct Rational {
+    numerator: i64,
+    denominator: i64,
+}
+
+impl Rational {
+    fn new(numerator: i128, denominator: i128) -> Option<Self> {
+        fn gcd(a: u128, b: u128) -> u128 {
+            if b == 0 {
+                a
+            } else {
+                gcd(b, a % b)
+            }
+        }
+
+        if denominator == 0 {
+            return None;
+        }
+        let divisor = gcd(numerator.unsigned_abs(), denominator.unsigned_abs()) as i128;
+        let sign = denominator.signum();
+        let (numerator, denominator) = (sign * numerator / divisor, sign * denominator / divisor);
+        if numerator.abs() > MAX_EXACT_INTEGER || denominator > MAX_EXACT_INTEGER {
+            return None;
+        }
+        Some(Self {
+            numerator: i64::try_from(numerator).ok()?,
+            denominator: i64::try_from(denominator).ok()?,
+        })
+    }
+
+    fn from_f64(value: f64) -> Option<Self> {
+        if value.fract() == 0f64 && value.abs() <= MAX_EXACT_INTEGER as f64 {
+            Self::new(value as i128, 1)
+        } else {
+            None
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.numerator == 0
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        let (a, b) = (self.numerator as i128, self.denominator as i128);
+        let (c, d) = (other.numerator as i128, other.denominator as i128);
+        Self::new(a * d + c * b, b * d)
+    }
+
+    fn checked_multiply(self, other: Self) -> Option<Self> {
+        Self::new(
+            self.numerator as i128 * other.numerator as i128,
+            self.denominator as i128 * other.denominator as i128,
+        )
+    }
+
+    fn reciprocal(self) -> Option<Self> {
+        Self::new(self.denominator as i128, self.numerator as i128)
+    }
+
+    fn negate(self) -> Self {
+        Self {
+            numerator: -self.numerator,
+            ..self
+        }
+    }
+}
+
+/// A rational multiple of pi, such as `3*pi/4`, represented exactly rather than as a
+/// floating-point number, so that values such as gate angles may be compared exactly.
+///
+/// Within an [`Expression`], this is written as `numerator*pi/denominator`; see
+/// [`Expression::to_pi_multiple`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct PiMultiple(Rational);
+
+impl PiMultiple {
+    /// Create the multiple `numerator/denominator` of pi, reduced to lowest terms. Return `None`
+    /// if the denominator is zero or either part is too large to be represented exactly.
+    pub fn new(numerator: i64, denominator: i64) -> Option<Self> {
+        Rational::new(numerator.into(), denominator.into()).map(Self)
+    }
+
+    /// The numerator of the multiple, in lowest terms. This carries the sign of the multiple.
+    pub fn numerator(&self) -> i64 {
+        self.0.numerator
+    }
+
+    /// The denominator of the multiple, in lowest terms. This is always positive.
+    pub fn denominator(&self) -> i64 {
+        self.0.denominator
+    }
+
+    /// Convert to the nearest floating-point value, losing exactness.
+    pub fn to_f64(&self) -> f64 {
+        self.numerator() as f64 * PI / self.denominator() as f64
+    }
+}
+
+impl From<PiMultiple> for Expression {
+    fn from(multiple: PiMultiple) -> Self {
+        let (numerator, denominator) = (multiple.numerator(), multiple.denominator());
+        let coefficient = match numerator {
+            0 => return Expression::Number(real!(0f64)),
+            1 => Expression::PiConstant,
+            -1 => -Expression::PiConstant,
+            _ => Expression::from(numerator as f64) * Expression::PiConstant,
+        };
+        if denominator == 1 {
+            coefficient
+        } else {
+            coefficient / denominator as f64
+        }
+    }
+}
+
+impl fmt::Display for PiMultiple {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Expression::from(*self))
+    }
+}
+
+/// Is this expression a number exactly equal to `value`?
+fn is_number(expression: &Expression, value: f64) -> bool {
+    matches!(expression, Expression::Number(number) if *number == real!(value))
+}
+
+/// If this is a negation, the expression being negated.
+fn as_negation(expression: &mut Expression) -> Option<&mut Expression> {
+    match expression {
+        Expression::Prefix {
+            operator: PrefixOperator::Minus,
+            expression,
+        } => Some(expression),
+        _ => None,
+    }
+}
+
+/// The simplified negation of an expression which has already been simplified.
+fn negate(expression: Expression) -> Expression {
+    Expression::Prefix {
+        operator: PrefixOperator::Minus,
+        expression: Box::new(expression),
+    }
+    .into_simplified()
+}
+
+/// Move the expression out from behind a mutable reference, leaving a cheap placeholder behind.
+fn take(expression: &mut Expression) -> Expression {
+    std::mem::replace(expression, Expression::PiConstant)
+}
+
+/// Apply algebraic identities to an infix expression whose operands have already been simplified.
+/// Return `None` if no identity applies.
+fn simplify_infix(
+    left: &mut Expression,
+    operator: &InfixOperator,
+    right: &mut Expression,
+) -> Option<Expression> {
+    use InfixOperator::*;
+
+    match operator {
+        Plus if is_number(left, 0f64) => Some(take(right)),
+        Plus | Minus if is_number(right, 0f64) => Some(take(left)),
+        Minus if is_number(left, 0f64) => Some(negate(take(right))),
+        Minus if left == right => Some(Expression::Number(real!(0f64))),
+        Star if is_number(left, 0f64) || is_number(right, 0f64) => {
+            Some(Expression::Number(real!(0f64)))
+        }
+        Star if is_number(left, 1f64) => Some(take(right)),
+        Star | Slash | Caret if is_number(right, 1f64) => Some(take(left)),
+        Star if is_number(left, -1f64) => Some(negate(take(right))),
+        Star | Slash if is_number(right, -1f64) => Some(negate(take(left))),
+        Star | Slash => match (as_negation(left), as_negation(right)) {
+            (Some(left), Some(right)) => Some(Expression::Infix {
+                left: Box::new(take(left)),
+                operator: operator.clone(),
+                right: Box::new(take(right)),
+            }),
+            _ if *operator == Star => fold_nested_constant(left, operator, right),
+            _ => None,
+        },
+        Caret if is_number(right, 0f64) => Some(Expression::Number(real!(1f64))),
+        Plus => fold_nested_constant(left, operator, right),
+        _ => None,
+    }
+}
+
+/// Fold a constant into a nested expression using the same operator, such as `(%x + 1) + 2` into
+/// `%x + 3`, so that constants spread across a chain are combined. The operator must be
+/// associative and commutative, so this is only for `+` and `*`.
+fn fold_nested_constant(
+    left: &mut Expression,
+    operator: &InfixOperator,
+    right: &mut Expression,
+) -> Option<Expression> {
+    use Expression::*;
+
+    let (nested, constant) = match (left, right) {
+        (Number(constant), nested) | (nested, Number(constant)) => (nested, *constant),
+        _ => return None,
+    };
+
+    match nested {
+        Infix {
+            left: inner_left,
+            operator: inner_operator,
+            right: inner_right,
+        } if *inner_operator == *operator => {
+            let (rest, inner_constant) = match (inner_left.as_mut(), inner_right.as_mut()) {
+                (Number(inner_constant), rest) | (rest, Number(inner_constant)) => {
+                    (rest, *inner_constant)
+                }
+                _ => return None,
+            };
+            Some(Infix {
+                left: Box::new(take(rest)),
+                operator: operator.clone(),
+                right: Box::new(Number(calculate_infix(
+                    &inner_constant,
+                    operator,
+                    &constant,
+                ))),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// A term of a canonical sum: a numeric coefficient multiplied by canonical factors, which are
+/// sorted and contain no numbers.
+struct Term {
+    coefficient: Complex64,
+    factors: Vec<Expression>,
+}
+
+impl Term {
+    /// The key by which terms are sorted and like terms are identified.
+    fn key(&self) -> String {
+        self.factors
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("*")
+    }
+
+    /// Build the term as an expression, omitting a coefficient of one.
+    fn into_expression(self) -> Expression {
+        let mut factors = self.factors.into_iter();
+        let first = match (self.coefficient == real!(1f64), factors.next()) {
+            (_, None) => return Expression::Number(self.coefficient),
+            (true, Some(first)) => first,
+            (false, Some(first)) => Expression::Number(self.coefficient) * first,
+        };
+        factors.fold(first, |product, factor| product * factor)
+    }
+}
+
+/// Collect the terms of a sum, with each multiplied by `sign`.
+fn collect_terms(expression: Expression, sign: Complex64, terms: &mut Vec<Term>) {
+    use Expression::*;
+
+    match expression {
+        Infix {
+            left,
+            operator: InfixOperator::Plus,
+            right,
+        } => {
+            collect_terms(*left, sign, terms);
+            collect_terms(*right, sign, terms);
+        }
+        Infix {
+            left,
+            operator: InfixOperator::Minus,
+            right,
+        } => {
+            collect_terms(*left, sign, terms);
+            collect_terms(*right, -sign, terms);
+        }
+        Prefix {
+            operator,
+            expression,
+        } => {
+            let sign = match operator {
+                PrefixOperator::Plus => sign,
+                PrefixOperator::Minus => -sign,
+            };
+            collect_terms(*expression, sign, terms)
+        }
+        other => {
+            let mut term = Term {
+                coefficient: sign,
+                factors: vec![],
+            };
+            collect_factors(other, &mut term);
+            term.factors.sort_by_cached_key(ToString::to_string);
+            terms.push(term);
+        }
+    }
+}
+
+/// Collect the factors of a product into `term`, combining numbers into its coefficient.
+fn collect_factors(expression: Expression, term: &mut Term) {
+    use Expression::*;
+
+    match expression {
+        Infix {
+            left,
+            operator: InfixOperator::Star,
+            right,
+        } => {
+            collect_factors(*left, term);
+            collect_factors(*right, term);
+        }
+        Prefix {
+            operator,
+            expression,
+        } => {
+            if let PrefixOperator::Minus = operator {
+                term.coefficient = -term.coefficient;
+            }
+            collect_factors(*expression, term)
+        }
+        Number(number) => term.coefficient *= number,
+        sum @ Infix {
+            operator: InfixOperator::Plus | InfixOperator::Minus,
+            ..
+        } => term.factors.push(canonical_sum(sum)),
+        Infix {
+            left,
+            operator,
+            right,
+        } => term.factors.push(Infix {
+            left: Box::new(canonical_sum(*left)),
+            operator,
+            right: Box::new(canonical_sum(*right)),
+        }),
+        FunctionCall {
+            function,
+            expression,
+        } => term.factors.push(FunctionCall {
+            function,
+            expression: Box::new(canonical_sum(*expression)),
+        }),
+        leaf @ (Address(_) | PiConstant | Variable(_)) => term.factors.push(leaf),
+    }
+}
+
+/// Build the canonical form of an expression, as a sum of sorted terms followed by a constant.
+fn canonical_sum(expression: Expression) -> Expression {
+    let mut terms = vec![];
+    collect_terms(expression, real!(1f64), &mut terms);
+
+    let mut constant = real!(0f64);
+    let mut keyed_terms: Vec<(String, Term)> = vec![];
+    for term in terms {
+        if term.factors.is_empty() {
+            constant += term.coefficient;
+        } else {
+            keyed_terms.push((term.key(), term));
+        }
+    }
+    keyed_terms.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+    // Combine like terms, which are now adjacent.
+    let mut combined: Vec<(String, Term)> = vec![];
+    for (key, term) in keyed_terms {
+        match combined.last_mut() {
+            Some((last_key, last)) if *last_key == key => last.coefficient += term.coefficient,
+            _ => combined.push((key, term)),
+        }
+    }
+    let mut terms: Vec<Term> = combined
+        .into_iter()
+        .map(|(_, term)| term)
+        .filter(|term| term.coefficient != real!(0f64))
+        .collect();
+    if constant != real!(0f64) {
+        terms.push(Term {
+            coefficient: constant,
+            factors: vec![],
+        });
+    }
+
+    let mut terms = terms.into_iter();
+    let mut sum = match terms.next() {
+        Some(term) => term.into_expression(),
+        None => return Expression::Number(real!(0f64)),
+    };
+    for mut term in terms {
+        sum = if term.coefficient.im == 0f64 && term.coefficient.re < 0f64 {
+            term.coefficient = -term.coefficient;
+            sum - term.into_expression()
+        } else {
+            sum + term.into_expression()
+        };
+    }
+    sum
+}
+
 impl Expression {
     /// Simplify the expression as much as possible, in-place.
     ///
+    /// Constant subexpressions (including those involving `pi`) are folded into numbers, and
+    /// algebraic identities such as `x + 0`, `x * 1`, `x * 0`, and `--x` are applied, without
+    /// needing any variable or memory values.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -201,6 +1085,11 @@ impl Expression {
     /// expression.simplify();
     ///
     /// assert_eq!(expression, Expression::Number(Complex64::from(3.0)));
+    ///
+    /// let mut expression = Expression::from_str("((0 + %theta) * 1) - 0").unwrap();
+    /// expression.simplify();
+    ///
+    /// assert_eq!(expression, Expression::Variable(String::from("theta")));
     /// ```
     pub fn simplify(&mut self) {
         use Expression::*;
@@ -212,16 +1101,21 @@ impl Expression {
             } => {
                 expression.simplify();
                 if let Number(number) = expression.as_ref() {
-                    *self = Number(calculate_function(function, number));
+                    if let Ok(value) = calculate_function(function, number, &HashMap::new()) {
+                        *self = Number(value);
+                    }
                 }
             }
             Infix {
                 left,
-                operator: _,
+                operator,
                 right,
             } => {
                 left.simplify();
                 right.simplify();
+                if let Some(simplified) = simplify_infix(left, operator, right) {
+                    *self = simplified;
+                }
             }
             Prefix {
                 operator,
@@ -233,15 +1127,32 @@ impl Expression {
                 // Avoid potentially expensive clone
                 // Cannot directly swap `expression` with `self` because that causes
                 // a double mutable borrow.
-                if let Plus = operator {
-                    let mut temp = Expression::PiConstant;
-                    std::mem::swap(expression.as_mut(), &mut temp);
-                    std::mem::swap(self, &mut temp);
+                match operator {
+                    Plus => {
+                        let mut temp = Expression::PiConstant;
+                        std::mem::swap(expression.as_mut(), &mut temp);
+                        std::mem::swap(self, &mut temp);
+                    }
+                    Minus => {
+                        if let Prefix {
+                            operator: Minus,
+                            expression: inner,
+                        } = expression.as_mut()
+                        {
+                            *self = take(inner);
+                        }
+                    }
                 }
             }
             Variable(_) | Address(_) | PiConstant | Number(_) => {}
         };
 
+        // Keep multiples of pi exact, rather than collapsing them into floating-point numbers.
+        if let Some(multiple) = self.to_pi_multiple() {
+            *self = multiple.into();
+            return;
+        }
+
         if let Ok(number) = self.evaluate(&HashMap::new(), &HashMap::new()) {
             *self = Number(number);
         }
@@ -265,9 +1176,47 @@ impl Expression {
         self
     }
 
+    /// Rewrite the expression, in-place, into a canonical form which is stable across equivalent
+    /// ways of writing it, for use in diffing and caching:
+    ///
+    /// - nested sums and products are flattened, and their operands sorted deterministically;
+    /// - subtraction and prefix minus become negative numeric coefficients, and numeric factors
+    ///   and constant terms are combined;
+    /// - terms which differ only in their coefficient are combined.
+    ///
+    /// Division, exponentiation, and function arguments are canonicalized within, but not
+    /// otherwise rearranged.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::Expression;
+    /// use std::str::FromStr;
+    ///
+    /// let mut first = Expression::from_str("-(%b * 2) + %a - 3 + 2").unwrap();
+    /// let mut second = Expression::from_str("%a - 1 - 2*%b").unwrap();
+    /// first.canonicalize();
+    /// second.canonicalize();
+    ///
+    /// assert_eq!(first.to_string(), "%a - 2 * %b - 1");
+    /// assert_eq!(first.to_string(), second.to_string());
+    /// ```
+    pub fn canonicalize(&mut self) {
+        *self = canonical_sum(take(self));
+    }
+
+    /// Consume the expression, returning it in canonical form. See [`Expression::canonicalize`].
+    pub fn into_canonical(mut self) -> Self {
+        self.canonicalize();
+        self
+    }
+
     /// Evaluate an expression, expecting that it may be fully reduced to a single complex number.
     /// If it cannot be reduced to a complex number, return an error.
     ///
+    /// The expression is only borrowed, so the same parametric expression may be evaluated against
+    /// many sets of values without being cloned.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -292,52 +1241,180 @@ impl Expression {
         &self,
         variables: &HashMap<String, num_complex::Complex64>,
         memory_references: &HashMap<&str, Vec<f64>>,
+    ) -> Result<num_complex::Complex64, EvaluationError> {
+        self.evaluate_with_functions(variables, memory_references, &HashMap::new())
+    }
+
+    /// Evaluate an expression as with [`Expression::evaluate`], additionally using `functions` to
+    /// compute any call to a function which is not built into Quil.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::{Expression, FunctionRegistry};
+    /// use std::str::FromStr;
+    /// use std::collections::HashMap;
+    /// use num_complex::Complex64;
+    ///
+    /// let expression = Expression::from_str("double(%beta) + 1").unwrap();
+    ///
+    /// let mut variables = HashMap::with_capacity(1);
+    /// variables.insert(String::from("beta"), Complex64::from(2.0));
+    ///
+    /// let mut functions = FunctionRegistry::new();
+    /// functions.insert(String::from("double"), |value| value * 2.0);
+    ///
+    /// let evaluated = expression
+    ///     .evaluate_with_functions(&variables, &HashMap::new(), &functions)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(evaluated, Complex64::from(5.0))
+    /// ```
+    pub fn evaluate_with_functions(
+        &self,
+        variables: &HashMap<String, num_complex::Complex64>,
+        memory_references: &HashMap<&str, Vec<f64>>,
+        functions: &FunctionRegistry,
+    ) -> Result<num_complex::Complex64, EvaluationError> {
+        self.evaluate_in(&EvaluationContext {
+            variables,
+            memory_references,
+            functions,
+            checked: false,
+        })
+    }
+
+    /// Evaluate an expression as with [`Expression::evaluate_with_functions`], but fail rather
+    /// than produce an infinite or `NaN` value, such as through division by zero or overflow.
+    /// The error identifies the innermost subexpression at fault.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::{EvaluationError, Expression};
+    /// use std::str::FromStr;
+    /// use std::collections::HashMap;
+    ///
+    /// let expression = Expression::from_str("1 + 2 / (3 - 3)").unwrap();
+    ///
+    /// assert!(!expression.evaluate(&HashMap::new(), &HashMap::new()).unwrap().re.is_finite());
+    /// assert_eq!(
+    ///     expression.evaluate_checked(&HashMap::new(), &HashMap::new(), &HashMap::new()),
+    ///     Err(EvaluationError::DivisionByZero {
+    ///         expression: Box::new(Expression::from_str("2 / (3 - 3)").unwrap())
+    ///     })
+    /// );
+    /// ```
+    pub fn evaluate_checked(
+        &self,
+        variables: &HashMap<String, num_complex::Complex64>,
+        memory_references: &HashMap<&str, Vec<f64>>,
+        functions: &FunctionRegistry,
+    ) -> Result<num_complex::Complex64, EvaluationError> {
+        self.evaluate_in(&EvaluationContext {
+            variables,
+            memory_references,
+            functions,
+            checked: true,
+        })
+    }
+
+    /// Evaluate an expression as with [`Expression::evaluate`], taking the values of memory
+    /// references from `memory`, which may be complex. See [`MemoryValues`].
+    pub fn evaluate_with_memory(
+        &self,
+        variables: &HashMap<String, num_complex::Complex64>,
+        memory: &MemoryValues,
+    ) -> Result<num_complex::Complex64, EvaluationError> {
+        self.evaluate_in(&EvaluationContext {
+            variables,
+            memory_references: memory,
+            functions: &HashMap::new(),
+            checked: false,
+        })
+    }
+
+    /// Evaluate an expression as with [`Expression::evaluate`], reading memory references from
+    /// the typed `memory`. Every memory reference must be within a `REAL` region, as with
+    /// [`type_check`](crate::program::type_check::type_check).
+    pub fn evaluate_with_memory_contents(
+        &self,
+        variables: &HashMap<String, num_complex::Complex64>,
+        memory: &MemoryContents,
+    ) -> Result<num_complex::Complex64, EvaluationError> {
+        self.evaluate_in(&EvaluationContext {
+            variables,
+            memory_references: memory,
+            functions: &HashMap::new(),
+            checked: false,
+        })
+    }
+
+    fn evaluate_in(
+        &self,
+        context: &EvaluationContext,
     ) -> Result<num_complex::Complex64, EvaluationError> {
         use Expression::*;
 
-        match self {
+        let value = match self {
             FunctionCall {
                 function,
                 expression,
             } => {
-                let evaluated = expression.evaluate(variables, memory_references)?;
-                Ok(calculate_function(function, &evaluated))
+                let evaluated = expression.evaluate_in(context)?;
+                calculate_function(function, &evaluated, context.functions)
             }
             Infix {
                 left,
                 operator,
                 right,
             } => {
-                let left_evaluated = left.evaluate(variables, memory_references)?;
-                let right_evaluated = right.evaluate(variables, memory_references)?;
-                Ok(calculate_infix(&left_evaluated, operator, &right_evaluated))
+                // Both operands are evaluated even if one fails, to report every unbound value.
+                match (left.evaluate_in(context), right.evaluate_in(context)) {
+                    (Ok(_), Ok(right))
+                        if context.checked
+                            && matches!(operator, InfixOperator::Slash)
+                            && right == real!(0f64) =>
+                    {
+                        Err(EvaluationError::DivisionByZero {
+                            expression: Box::new(self.clone()),
+                        })
+                    }
+                    (Ok(left), Ok(right)) => Ok(calculate_infix(&left, operator, &right)),
+                    (Err(left), Err(right)) => Err(left.combine(right, self)),
+                    (Err(error), Ok(_)) | (Ok(_), Err(error)) => Err(error),
+                }
             }
             Prefix {
                 operator,
                 expression,
             } => {
                 use PrefixOperator::*;
-                let value = expression.evaluate(variables, memory_references)?;
+                let value = expression.evaluate_in(context)?;
                 if matches!(operator, Minus) {
                     Ok(-value)
                 } else {
                     Ok(value)
                 }
             }
-            Variable(identifier) => match variables.get(identifier.as_str()) {
+            Variable(identifier) => match context.variables.get(identifier.as_str()) {
                 Some(value) => Ok(*value),
-                None => Err(EvaluationError::Incomplete),
+                None => Err(EvaluationError::unbound(self)),
             },
-            Address(memory_reference) => memory_references
-                .get(memory_reference.name.as_str())
-                .and_then(|values| {
-                    let value = values.get(memory_reference.index as usize)?;
-                    Some(real!(*value))
-                })
-                .ok_or(EvaluationError::Incomplete),
+            Address(memory_reference) => context
+                .memory_references
+                .value(memory_reference)?
+                .ok_or_else(|| EvaluationError::unbound(self)),
             PiConstant => Ok(real!(PI)),
             Number(number) => Ok(*number),
+        }?;
+
+        if context.checked && !(value.re.is_finite() && value.im.is_finite()) {
+            return Err(EvaluationError::NotFinite {
+                expression: Box::new(self.clone()),
+            });
         }
+        Ok(value)
     }
 
     /// Substitute an expression in the place of each matching variable.
@@ -361,6 +1438,56 @@ impl Expression {
     /// assert_eq!(evaluated, Expression::from_str("1.0 + %y").unwrap())
     /// ```
     pub fn substitute_variables(self, variable_values: &HashMap<String, Expression>) -> Self {
+        self.substitute_leaves(&|leaf| match leaf {
+            Expression::Variable(identifier) => match variable_values.get(identifier.as_str()) {
+                Some(value) => value.clone(),
+                None => Expression::Variable(identifier),
+            },
+            other => other,
+        })
+    }
+
+    /// Substitute an expression in the place of each matching memory reference.
+    /// Consumes the expression and returns a new one.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::Expression;
+    /// use quil_rs::instruction::MemoryReference;
+    /// use std::str::FromStr;
+    /// use std::collections::HashMap;
+    ///
+    /// let expression = Expression::from_str("theta[0] + theta[1]").unwrap();
+    ///
+    /// let mut memory_references = HashMap::with_capacity(1);
+    /// memory_references.insert(
+    ///     MemoryReference { name: String::from("theta"), index: 1 },
+    ///     Expression::from_str("2*alpha[0]").unwrap(),
+    /// );
+    ///
+    /// let substituted = expression.substitute_memory_references(&memory_references);
+    ///
+    /// assert_eq!(substituted, Expression::from_str("theta[0] + 2*alpha[0]").unwrap())
+    /// ```
+    pub fn substitute_memory_references(
+        self,
+        memory_reference_values: &HashMap<MemoryReference, Expression>,
+    ) -> Self {
+        self.substitute_leaves(&|leaf| match leaf {
+            Expression::Address(memory_reference) => {
+                match memory_reference_values.get(&memory_reference) {
+                    Some(value) => value.clone(),
+                    None => Expression::Address(memory_reference),
+                }
+            }
+            other => other,
+        })
+    }
+
+    /// Replace each leaf of the expression (anything other than a function call, infix, or prefix
+    /// expression) with the result of `substitute`.
+    fn substitute_leaves<F: Fn(Expression) -> Expression>(self, substitute: &F) -> Self {
         use Expression::*;
 
         match self {
@@ -369,15 +1496,15 @@ impl Expression {
                 expression,
             } => FunctionCall {
                 function,
-                expression: expression.substitute_variables(variable_values).into(),
+                expression: expression.substitute_leaves(substitute).into(),
             },
             Infix {
                 left,
                 operator,
                 right,
             } => {
-                let left = left.substitute_variables(variable_values).into();
-                let right = right.substitute_variables(variable_values).into();
+                let left = left.substitute_leaves(substitute).into();
+                let right = right.substitute_leaves(substitute).into();
                 Infix {
                     left,
                     operator,
@@ -389,13 +1516,232 @@ impl Expression {
                 expression,
             } => Prefix {
                 operator,
-                expression: expression.substitute_variables(variable_values).into(),
-            },
-            Variable(identifier) => match variable_values.get(identifier.as_str()) {
-                Some(value) => value.clone(),
-                None => Variable(identifier),
+                expression: expression.substitute_leaves(substitute).into(),
             },
-            other => other,
+            leaf => substitute(leaf),
+        }
+    }
+
+    /// Return the names of all variables (such as `%theta`) which must be bound in order to
+    /// evaluate this expression.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::Expression;
+    /// use std::str::FromStr;
+    ///
+    /// let expression = Expression::from_str("cos(%theta) * %phi + ro[0] - %theta").unwrap();
+    /// let mut variables: Vec<_> = expression.variables().into_iter().collect();
+    /// variables.sort_unstable();
+    ///
+    /// assert_eq!(variables, vec!["phi", "theta"]);
+    /// ```
+    pub fn variables(&self) -> HashSet<&str> {
+        match self {
+            Expression::Variable(identifier) => std::iter::once(identifier.as_str()).collect(),
+            Expression::FunctionCall { expression, .. } => expression.variables(),
+            Expression::Infix { left, right, .. } => {
+                let mut result = left.variables();
+                result.extend(right.variables());
+                result
+            }
+            Expression::Prefix { expression, .. } => expression.variables(),
+            Expression::Address(_) | Expression::Number(_) | Expression::PiConstant => {
+                HashSet::new()
+            }
+        }
+    }
+
+    /// Return the memory references which must be provided in order to evaluate this expression,
+    /// in the order in which they appear. A reference appearing more than once is repeated.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::Expression;
+    /// use quil_rs::instruction::MemoryReference;
+    /// use std::str::FromStr;
+    ///
+    /// let expression = Expression::from_str("theta[1] * %phi + beta").unwrap();
+    ///
+    /// assert_eq!(
+    ///     expression.memory_references(),
+    ///     vec![
+    ///         &MemoryReference { name: String::from("theta"), index: 1 },
+    ///         &MemoryReference { name: String::from("beta"), index: 0 },
+    ///     ]
+    /// );
+    /// ```
+    pub fn memory_references(&self) -> Vec<&MemoryReference> {
+        match self {
+            Expression::Address(reference) => vec![reference],
+            Expression::FunctionCall { expression, .. } => expression.memory_references(),
+            Expression::Infix { left, right, .. } => {
+                let mut result = left.memory_references();
+                result.extend(right.memory_references());
+                result
+            }
+            Expression::Prefix { expression, .. } => expression.memory_references(),
+            Expression::Number(_) | Expression::PiConstant | Expression::Variable(_) => vec![],
+        }
+    }
+
+    /// If this is a constant expression equal to a rational multiple of pi, such as `3*pi/4` or
+    /// `pi/4 + pi/2`, return that multiple exactly. Only integer numbers are considered exact.
+    ///
+    /// [`Expression::simplify`] reduces such expressions to the canonical form of the multiple,
+    /// which [`PiMultiple::to_f64`] or [`Expression::evaluate`] convert to a floating-point value.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::{Expression, PiMultiple};
+    /// use std::str::FromStr;
+    ///
+    /// let expression = Expression::from_str("pi/4 + 2*pi/4").unwrap();
+    /// assert_eq!(expression.to_pi_multiple(), PiMultiple::new(3, 4));
+    /// assert_eq!(expression.into_simplified().to_string(), "3 * pi / 4");
+    /// ```
+    pub fn to_pi_multiple(&self) -> Option<PiMultiple> {
+        match self.exact_value()? {
+            (value, true) => Some(PiMultiple(value)),
+            (value, false) if value.is_zero() => Some(PiMultiple(value)),
+            _ => None,
+        }
+    }
+
+    /// Compute the value of a constant expression exactly, as a rational number which is multiplied
+    /// by pi if the returned flag is set. Return `None` if that is not possible.
+    fn exact_value(&self) -> Option<(Rational, bool)> {
+        use Expression::*;
+
+        match self {
+            Number(number) if number.im == 0f64 => Some((Rational::from_f64(number.re)?, false)),
+            PiConstant => Some((Rational::new(1, 1)?, true)),
+            Prefix {
+                operator,
+                expression,
+            } => {
+                let (value, pi) = expression.exact_value()?;
+                match operator {
+                    PrefixOperator::Plus => Some((value, pi)),
+                    PrefixOperator::Minus => Some((value.negate(), pi)),
+                }
+            }
+            Infix {
+                left,
+                operator,
+                right,
+            } => {
+                let (left, left_pi) = left.exact_value()?;
+                let (right, right_pi) = right.exact_value()?;
+                match operator {
+                    InfixOperator::Plus | InfixOperator::Minus => {
+                        let right = match operator {
+                            InfixOperator::Minus => right.negate(),
+                            _ => right,
+                        };
+                        let pi = if left.is_zero() {
+                            right_pi
+                        } else if right.is_zero() || left_pi == right_pi {
+                            left_pi
+                        } else {
+                            return None;
+                        };
+                        Some((left.checked_add(right)?, pi))
+                    }
+                    InfixOperator::Star if !(left_pi && right_pi) => {
+                        Some((left.checked_multiply(right)?, left_pi || right_pi))
+                    }
+                    InfixOperator::Slash if left_pi || !right_pi => Some((
+                        left.checked_multiply(right.reciprocal()?)?,
+                        left_pi && !right_pi,
+                    )),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Compare two expressions structurally, as with `==`, except that numbers (including `pi`)
+    /// need only be equal to within `tolerance`. As with `==`, the operands of commutative
+    /// operators may appear in either order.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::Expression;
+    /// use std::str::FromStr;
+    ///
+    /// let computed = Expression::from_str("%theta * 3.1415926535").unwrap();
+    /// let expected = Expression::from_str("pi * %theta").unwrap();
+    ///
+    /// assert!(computed.approx_eq(&expected, 1e-9));
+    /// assert!(!computed.approx_eq(&expected, 1e-12));
+    /// ```
+    pub fn approx_eq(&self, other: &Self, tolerance: f64) -> bool {
+        use Expression::*;
+
+        match (self, other) {
+            (Number(_) | PiConstant, Number(_) | PiConstant) => {
+                match (self.to_complex(), other.to_complex()) {
+                    (Some(left), Some(right)) => complex_approx_eq(left, right, tolerance),
+                    _ => false,
+                }
+            }
+            (
+                FunctionCall {
+                    function,
+                    expression,
+                },
+                FunctionCall {
+                    function: other_function,
+                    expression: other_expression,
+                },
+            ) => function == other_function && expression.approx_eq(other_expression, tolerance),
+            (
+                Infix {
+                    left,
+                    operator,
+                    right,
+                },
+                Infix {
+                    left: other_left,
+                    operator: other_operator,
+                    right: other_right,
+                },
+            ) => {
+                operator == other_operator
+                    && ((left.approx_eq(other_left, tolerance)
+                        && right.approx_eq(other_right, tolerance))
+                        || (matches!(operator, InfixOperator::Plus | InfixOperator::Star)
+                            && left.approx_eq(other_right, tolerance)
+                            && right.approx_eq(other_left, tolerance)))
+            }
+            (
+                Prefix {
+                    operator,
+                    expression,
+                },
+                Prefix {
+                    operator: other_operator,
+                    expression: other_expression,
+                },
+            ) => operator == other_operator && expression.approx_eq(other_expression, tolerance),
+            (Address(reference), Address(other_reference)) => reference == other_reference,
+            (Variable(name), Variable(other_name)) => name == other_name,
+            _ => false,
+        }
+    }
+
+    /// If this is a number or `pi`, return its value.
+    fn to_complex(&self) -> Option<Complex64> {
+        match self {
+            Expression::Number(number) => Some(*number),
+            Expression::PiConstant => Some(real!(PI)),
+            _ => None,
         }
     }
 
@@ -416,18 +1762,231 @@ impl FromStr for Expression {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let tokens = lex(s)?;
-        disallow_leftover(parse_expression(&tokens))
+        disallow_leftover(parser::parse_expression(&tokens))
+    }
+}
+
+impl From<f64> for Expression {
+    fn from(value: f64) -> Self {
+        Expression::Number(real!(value))
+    }
+}
+
+impl From<i64> for Expression {
+    fn from(value: i64) -> Self {
+        Expression::Number(real!(value as f64))
+    }
+}
+
+impl From<Complex64> for Expression {
+    fn from(value: Complex64) -> Self {
+        Expression::Number(value)
+    }
+}
+
+impl Expression {
+    /// The constant `pi`.
+    pub fn pi() -> Self {
+        Expression::PiConstant
+    }
+
+    /// A variable, written `%name` in Quil.
+    pub fn var<S: Into<String>>(name: S) -> Self {
+        Expression::Variable(name.into())
+    }
+
+    /// A reference to the value at `index` within the memory region `name`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::Expression;
+    ///
+    /// let expression = Expression::address("theta", 1) * Expression::var("scale") + 2i64;
+    /// assert_eq!(expression.to_string(), "theta[1] * %scale + 2");
+    /// assert_eq!((Expression::pi() / 4.0).to_string(), "pi / 4");
+    /// ```
+    pub fn address<S: Into<String>>(name: S, index: u64) -> Self {
+        Expression::Address(MemoryReference {
+            name: name.into(),
+            index,
+        })
+    }
+}
+
+/// Evaluate a constant expression, i.e. one containing no variables or memory references, failing
+/// with [`EvaluationError::Incomplete`] otherwise.
+impl TryFrom<&Expression> for Complex64 {
+    type Error = EvaluationError;
+
+    fn try_from(expression: &Expression) -> Result<Self, Self::Error> {
+        expression.evaluate(&HashMap::new(), &HashMap::new())
+    }
+}
+
+impl TryFrom<Expression> for Complex64 {
+    type Error = EvaluationError;
+
+    fn try_from(expression: Expression) -> Result<Self, Self::Error> {
+        Self::try_from(&expression)
+    }
+}
+
+/// Evaluate a constant expression, as with the conversion to [`Complex64`], additionally failing
+/// with [`EvaluationError::NumberNotReal`] if the result has a non-zero imaginary part.
+impl TryFrom<&Expression> for f64 {
+    type Error = EvaluationError;
+
+    fn try_from(expression: &Expression) -> Result<Self, Self::Error> {
+        let value = Complex64::try_from(expression)?;
+        if is_small(value.im) {
+            Ok(value.re)
+        } else {
+            Err(EvaluationError::NumberNotReal)
+        }
+    }
+}
+
+impl TryFrom<Expression> for f64 {
+    type Error = EvaluationError;
+
+    fn try_from(expression: Expression) -> Result<Self, Self::Error> {
+        Self::try_from(&expression)
+    }
+}
+
+/// Implement an arithmetic operator trait for [`Expression`], building an [`Expression::Infix`]
+/// from the two operands. The right operand may be anything convertible into an expression.
+macro_rules! impl_expression_binary_op {
+    ($trait:ident, $method:ident, $operator:ident) => {
+        impl<T: Into<Expression>> std::ops::$trait<T> for Expression {
+            type Output = Self;
+
+            fn $method(self, other: T) -> Self {
+                Expression::Infix {
+                    left: Box::new(self),
+                    operator: InfixOperator::$operator,
+                    right: Box::new(other.into()),
+                }
+            }
+        }
+    };
+}
+
+impl_expression_binary_op!(Add, add, Plus);
+impl_expression_binary_op!(Sub, sub, Minus);
+impl_expression_binary_op!(Mul, mul, Star);
+impl_expression_binary_op!(Div, div, Slash);
+
+impl std::ops::Neg for Expression {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Expression::Prefix {
+            operator: PrefixOperator::Minus,
+            expression: Box::new(self),
+        }
+    }
+}
+
+/// Parse an expression from the beginning of `input`, returning it along with the range of byte
+/// offsets within `input` which it occupies.
+///
+/// Unlike [`Expression::from_str`], any input following the expression is ignored, which allows
+/// Quil expressions to be embedded within larger documents. Leading whitespace and newlines are
+/// skipped and are not included in the returned range.
+///
+/// # Example
+///
+/// ```rust
+/// use quil_rs::expression::{parse_expression, Expression};
+/// use std::str::FromStr;
+///
+/// let input = "  2*%theta } rest of document";
+/// let (expression, span) = parse_expression(input).unwrap();
+///
+/// assert_eq!(expression, Expression::from_str("2*%theta").unwrap());
+/// assert_eq!(&input[span], "2*%theta");
+/// ```
+pub fn parse_expression(
+    input: &str,
+) -> Result<(Expression, Range<usize>), ProgramError<Expression>> {
+    let tokens = lex_prefix(input)?;
+    let leading_whitespace = tokens
+        .iter()
+        .take_while(|token| matches!(token.as_token(), Token::Indentation | Token::NewLine))
+        .count();
+    let tokens = &tokens[leading_whitespace..];
+    let (remainder, expression) = parser::parse_expression(tokens)
+        .map_err(|err| ProgramError::from(parser::extract_nom_err(err)))?;
+    let consumed = &tokens[..tokens.len() - remainder.len()];
+    let span = match (consumed.first(), consumed.last()) {
+        (Some(first), Some(last)) => first.span().start..last.span().end,
+        _ => 0..0,
+    };
+    Ok((expression, span))
+}
+
+/// How the numbers within an expression are written out; see [`Expression::display_with`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberFormat {
+    /// The shortest representation which parses back into exactly the same `f64`, apart from the
+    /// sign of zero. This is the format used by `Display`.
+    Shortest,
+    /// The shortest representation of the value once rounded to this many significant digits.
+    SignificantDigits(NonZeroUsize),
+    /// Exactly this many digits after the decimal point, and never an exponent. Values which are
+    /// not finite have no such representation, and are written as with `Shortest`.
+    Fixed(usize),
+}
+
+/// An expression formatted with a particular [`NumberFormat`], as returned by
+/// [`Expression::display_with`].
+#[derive(Clone, Copy, Debug)]
+pub struct FormattedExpression<'a> {
+    expression: &'a Expression,
+    number_format: NumberFormat,
+}
+
+impl fmt::Display for FormattedExpression<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.expression.format(f, self.number_format)
     }
 }
 
+/// Format a num_complex::Complex64 value as with [`format_complex_with`], in the shortest form
+/// which reproduces it exactly when parsed.
+#[cfg(test)]
+fn format_complex(value: &Complex64) -> String {
+    format_complex_with(value, NumberFormat::Shortest)
+}
+
 /// Format a num_complex::Complex64 value in a way that omits the real or imaginary part when
 /// reasonable. That is:
 ///
 /// - When imaginary is set but real is 0, show only imaginary
 /// - When imaginary is 0, show real only
 /// - When both are non-zero, show with the correct operator in between
-#[inline(always)]
-fn format_complex(value: &Complex64) -> String {
+fn format_complex_with(value: &Complex64, number_format: NumberFormat) -> String {
+    if value.re == 0f64 && value.im == 0f64 {
+        "0".to_owned()
+    } else if value.im == 0f64 {
+        format_real(value.re, number_format)
+    } else if value.re == 0f64 {
+        format_real(value.im, number_format) + "i"
+    } else {
+        let mut out = format_real(value.re, number_format);
+        if value.im > 0f64 {
+            out.push('+')
+        }
+        out.push_str(&format_real(value.im, number_format));
+        out.push('i');
+        out
+    }
+}
+
+/// Format one part of a complex number.
+fn format_real(value: f64, number_format: NumberFormat) -> String {
     const FORMAT: u128 = format::STANDARD;
     // Safety:
     // This uses `build_unchecked`, which is safe as long as `is_valid` is true, and
@@ -452,57 +2011,187 @@ fn format_complex(value: &Complex64) -> String {
         assert!(options.is_valid());
         options
     };
-    if value.re == 0f64 && value.im == 0f64 {
-        "0".to_owned()
-    } else if value.im == 0f64 {
-        to_string_with_options::<_, FORMAT>(value.re, &OPTIONS)
-    } else if value.re == 0f64 {
-        to_string_with_options::<_, FORMAT>(value.im, &OPTIONS) + "i"
-    } else {
-        let mut out = to_string_with_options::<_, FORMAT>(value.re, &OPTIONS);
-        if value.im > 0f64 {
-            out.push('+')
+    match number_format {
+        NumberFormat::Fixed(precision) if value.is_finite() => format!("{:.*}", precision, value),
+        NumberFormat::Shortest | NumberFormat::Fixed(_) => {
+            to_string_with_options::<_, FORMAT>(value, &OPTIONS)
+        }
+        NumberFormat::SignificantDigits(digits) => {
+            // Rust formats floats with correct rounding, so this rounds to the requested digits.
+            let rounded = format!("{:.*e}", digits.get() - 1, value)
+                .parse::<f64>()
+                .unwrap_or(value);
+            to_string_with_options::<_, FORMAT>(rounded, &OPTIONS)
         }
-        out.push_str(&to_string_with_options::<_, FORMAT>(value.im, &OPTIONS));
-        out.push('i');
-        out
     }
 }
 
-impl fmt::Display for Expression {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+/// How tightly an expression binds when formatted, from loosest to tightest. This mirrors the
+/// parser, and is used to emit only the parentheses needed to re-parse into the same structure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Precedence {
+    Sum,
+    Product,
+    Exponentiation,
+    Prefix,
+    Primary,
+}
+
+impl InfixOperator {
+    fn precedence(&self) -> Precedence {
+        match self {
+            InfixOperator::Plus | InfixOperator::Minus => Precedence::Sum,
+            InfixOperator::Star | InfixOperator::Slash => Precedence::Product,
+            InfixOperator::Caret => Precedence::Exponentiation,
+        }
+    }
+}
+
+impl Expression {
+    fn precedence(&self) -> Precedence {
+        match self {
+            Expression::Infix { operator, .. } => operator.precedence(),
+            Expression::Prefix { .. } => Precedence::Prefix,
+            // Numbers are formatted with a sign or as a sum when either part is negative or both
+            // parts are non-zero, so they must be treated as such.
+            Expression::Number(value) if value.re != 0f64 && value.im != 0f64 => Precedence::Sum,
+            Expression::Number(value) if value.re < 0f64 || value.im < 0f64 => Precedence::Prefix,
+            Expression::Address(_)
+            | Expression::FunctionCall { .. }
+            | Expression::Number(_)
+            | Expression::PiConstant
+            | Expression::Variable(_) => Precedence::Primary,
+        }
+    }
+}
+
+/// Write an operand of an infix or prefix expression, wrapped in parentheses if requested.
+fn format_operand(
+    f: &mut fmt::Formatter,
+    operand: &Expression,
+    parenthesize: bool,
+    number_format: NumberFormat,
+) -> fmt::Result {
+    let operand = operand.display_with(number_format);
+    if parenthesize {
+        write!(f, "({})", operand)
+    } else {
+        write!(f, "{}", operand)
+    }
+}
+
+impl Expression {
+    /// Display this expression, writing its numbers in the given format.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::{Expression, NumberFormat};
+    /// use std::num::NonZeroUsize;
+    /// use std::str::FromStr;
+    ///
+    /// let expression = Expression::from_str("%theta * 0.123456789").unwrap();
+    /// let digits = NumberFormat::SignificantDigits(NonZeroUsize::new(3).unwrap());
+    ///
+    /// assert_eq!(expression.display_with(digits).to_string(), "%theta * 0.123");
+    /// assert_eq!(expression.display_with(NumberFormat::Fixed(2)).to_string(), "%theta * 0.12");
+    /// ```
+    pub fn display_with(&self, number_format: NumberFormat) -> FormattedExpression<'_> {
+        FormattedExpression {
+            expression: self,
+            number_format,
+        }
+    }
+
+    fn format(&self, f: &mut fmt::Formatter, number_format: NumberFormat) -> fmt::Result {
         use Expression::*;
         match self {
             Address(memory_reference) => write!(f, "{}", memory_reference),
             FunctionCall {
                 function,
                 expression,
-            } => write!(f, "{}({})", function, expression),
+            } => write!(
+                f,
+                "{}({})",
+                function,
+                expression.display_with(number_format)
+            ),
             Infix {
                 left,
                 operator,
                 right,
-            } => write!(f, "({}{}{})", left, operator, right),
-            Number(value) => write!(f, "{}", format_complex(value)),
+            } => {
+                let precedence = operator.precedence();
+                let (left_precedence, right_precedence) = (left.precedence(), right.precedence());
+                // `^` is right-associative, and all other operators are left-associative. Signed
+                // right operands are always parenthesized to avoid output such as `1 - -2`.
+                let (parenthesize_left, parenthesize_right) = match operator {
+                    InfixOperator::Caret => (
+                        left_precedence < Precedence::Primary,
+                        right_precedence < precedence || right_precedence == Precedence::Prefix,
+                    ),
+                    _ => (
+                        left_precedence < precedence,
+                        right_precedence <= precedence || right_precedence == Precedence::Prefix,
+                    ),
+                };
+                format_operand(f, left, parenthesize_left, number_format)?;
+                write!(f, " {} ", operator)?;
+                format_operand(f, right, parenthesize_right, number_format)
+            }
+            Number(value) => write!(f, "{}", format_complex_with(value, number_format)),
             PiConstant => write!(f, "pi"),
             Prefix {
                 operator,
                 expression,
-            } => write!(f, "({}{})", operator, expression),
+            } => {
+                write!(f, "{}", operator)?;
+                let parenthesize = expression.precedence() < Precedence::Primary;
+                format_operand(f, expression, parenthesize, number_format)
+            }
             Variable(identifier) => write!(f, "%{}", identifier),
         }
     }
 }
 
-/// A function defined within Quil syntax.
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.format(f, NumberFormat::Shortest)
+    }
+}
+
+/// A function defined within Quil syntax. With the `serde` feature, built-in functions serialize
+/// by their names in Quil.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(test, derive(Arbitrary))]
 pub enum ExpressionFunction {
+    #[cfg_attr(feature = "serde", serde(rename = "abs"))]
+    AbsoluteValue,
+    #[cfg_attr(feature = "serde", serde(rename = "acos"))]
+    ArcCosine,
+    #[cfg_attr(feature = "serde", serde(rename = "asin"))]
+    ArcSine,
+    #[cfg_attr(feature = "serde", serde(rename = "atan"))]
+    ArcTangent,
+    #[cfg_attr(feature = "serde", serde(rename = "cis"))]
     Cis,
+    #[cfg_attr(feature = "serde", serde(rename = "cos"))]
     Cosine,
+    #[cfg_attr(feature = "serde", serde(rename = "exp"))]
     Exponent,
+    #[cfg_attr(feature = "serde", serde(rename = "ln"))]
+    NaturalLogarithm,
+    #[cfg_attr(feature = "serde", serde(rename = "sin"))]
     Sine,
+    #[cfg_attr(feature = "serde", serde(rename = "sqrt"))]
     SquareRoot,
+    #[cfg_attr(feature = "serde", serde(rename = "tan"))]
+    Tangent,
+    /// A function which is not built into Quil, such as one defined by a vendor. It can only be
+    /// evaluated when given an implementation in a [`FunctionRegistry`].
+    #[cfg_attr(feature = "serde", serde(rename = "custom"))]
+    Custom(String),
 }
 
 impl fmt::Display for ExpressionFunction {
@@ -512,61 +2201,339 @@ impl fmt::Display for ExpressionFunction {
             f,
             "{}",
             match self {
+                AbsoluteValue => "abs",
+                ArcCosine => "acos",
+                ArcSine => "asin",
+                ArcTangent => "atan",
                 Cis => "cis",
                 Cosine => "cos",
                 Exponent => "exp",
+                NaturalLogarithm => "ln",
                 Sine => "sin",
                 SquareRoot => "sqrt",
+                Tangent => "tan",
+                Custom(name) => name.as_str(),
+            }
+        )
+    }
+}
+
+/// An operator applied to a single operand. With the `serde` feature, it serializes as its
+/// symbol in Quil.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum PrefixOperator {
+    #[cfg_attr(feature = "serde", serde(rename = "+"))]
+    Plus,
+    #[cfg_attr(feature = "serde", serde(rename = "-"))]
+    Minus,
+}
+
+impl fmt::Display for PrefixOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use PrefixOperator::*;
+        write!(
+            f,
+            "{}",
+            match self {
+                Plus => "+",
+                Minus => "-",
+            }
+        )
+    }
+}
+
+/// An operator applied to two operands. With the `serde` feature, it serializes as its symbol
+/// in Quil.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum InfixOperator {
+    #[cfg_attr(feature = "serde", serde(rename = "^"))]
+    Caret,
+    #[cfg_attr(feature = "serde", serde(rename = "+"))]
+    Plus,
+    #[cfg_attr(feature = "serde", serde(rename = "-"))]
+    Minus,
+    #[cfg_attr(feature = "serde", serde(rename = "/"))]
+    Slash,
+    #[cfg_attr(feature = "serde", serde(rename = "*"))]
+    Star,
+}
+
+impl fmt::Display for InfixOperator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use InfixOperator::*;
+        write!(
+            f,
+            "{}",
+            match self {
+                Caret => "^",
+                Plus => "+",
+                Minus => "-",
+                Slash => "/",
+                Star => "*",
+            }
+        )
+    }
+}
+
+/// The order in which [`Expression::iter`] yields nodes relative to their subexpressions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraversalOrder {
+    /// Yield each node before its subexpressions.
+    PreOrder,
+    /// Yield each node after its subexpressions.
+    PostOrder,
+}
+
+/// An iterator over an expression and all of its subexpressions, created by
+/// [`Expression::iter`].
+#[derive(Clone, Debug)]
+pub struct Iter<'a> {
+    /// Nodes remaining to be yielded, along with whether their subexpressions have already been
+    /// pushed onto the stack.
+    stack: Vec<(&'a Expression, bool)>,
+    order: TraversalOrder,
+}
+
+impl<'a> Iter<'a> {
+    /// Push the subexpressions of `expression` such that they are popped from left to right.
+    fn push_subexpressions(&mut self, expression: &'a Expression) {
+        match expression {
+            Expression::FunctionCall { expression, .. } | Expression::Prefix { expression, .. } => {
+                self.stack.push((expression.as_ref(), false))
+            }
+            Expression::Infix { left, right, .. } => {
+                self.stack.push((right.as_ref(), false));
+                self.stack.push((left.as_ref(), false));
+            }
+            Expression::Address(_)
+            | Expression::Number(_)
+            | Expression::PiConstant
+            | Expression::Variable(_) => {}
+        }
+    }
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Expression;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (expression, expanded) = self.stack.pop()?;
+            match self.order {
+                TraversalOrder::PreOrder => {
+                    self.push_subexpressions(expression);
+                    return Some(expression);
+                }
+                TraversalOrder::PostOrder if expanded => return Some(expression),
+                TraversalOrder::PostOrder => {
+                    self.stack.push((expression, true));
+                    self.push_subexpressions(expression);
+                }
+            }
+        }
+    }
+}
+
+/// A read-only traversal of an expression. Each method is called for the corresponding kind of
+/// node, and by default visits that node's subexpressions from left to right, so implementors
+/// need only override the methods for the nodes they are interested in.
+///
+/// # Example
+///
+/// ```rust
+/// use quil_rs::expression::{Expression, ExpressionVisitor};
+/// use std::str::FromStr;
+///
+/// #[derive(Default)]
+/// struct CountVariables(usize);
+///
+/// impl ExpressionVisitor for CountVariables {
+///     fn visit_variable(&mut self, _name: &str) {
+///         self.0 += 1;
+///     }
+/// }
+///
+/// let mut visitor = CountVariables::default();
+/// Expression::from_str("%a * cos(%b) + %a").unwrap().accept(&mut visitor);
+/// assert_eq!(visitor.0, 3);
+/// ```
+pub trait ExpressionVisitor {
+    /// Visit any expression, dispatching to the method for its kind of node.
+    fn visit_expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Address(memory_reference) => self.visit_address(memory_reference),
+            Expression::FunctionCall {
+                function,
+                expression,
+            } => self.visit_function_call(function, expression),
+            Expression::Infix {
+                left,
+                operator,
+                right,
+            } => self.visit_infix(left, operator, right),
+            Expression::Number(value) => self.visit_number(value),
+            Expression::PiConstant => self.visit_pi_constant(),
+            Expression::Prefix {
+                operator,
+                expression,
+            } => self.visit_prefix(operator, expression),
+            Expression::Variable(name) => self.visit_variable(name),
+        }
+    }
+
+    fn visit_address(&mut self, _memory_reference: &MemoryReference) {}
+
+    fn visit_function_call(&mut self, _function: &ExpressionFunction, argument: &Expression) {
+        self.visit_expression(argument);
+    }
+
+    fn visit_infix(&mut self, left: &Expression, _operator: &InfixOperator, right: &Expression) {
+        self.visit_expression(left);
+        self.visit_expression(right);
+    }
+
+    fn visit_number(&mut self, _value: &Complex64) {}
+
+    fn visit_pi_constant(&mut self) {}
+
+    fn visit_prefix(&mut self, _operator: &PrefixOperator, expression: &Expression) {
+        self.visit_expression(expression);
+    }
+
+    fn visit_variable(&mut self, _name: &str) {}
+}
+
+/// A bottom-up rewrite of an expression, applied with [`Expression::rewrite`]. Each node is passed
+/// to [`ExpressionRewriter::rewrite`] after all of its subexpressions have been rewritten.
+///
+/// # Example
+///
+/// ```rust
+/// use quil_rs::expression::{Expression, ExpressionRewriter};
+/// use std::str::FromStr;
+///
+/// struct RenameVariables;
+///
+/// impl ExpressionRewriter for RenameVariables {
+///     fn rewrite(&mut self, expression: Expression) -> Expression {
+///         match expression {
+///             Expression::Variable(name) => Expression::Variable(format!("{}_0", name)),
+///             other => other,
+///         }
+///     }
+/// }
+///
+/// let rewritten = Expression::from_str("%a * cos(%b)").unwrap().rewrite(&mut RenameVariables);
+/// assert_eq!(rewritten, Expression::from_str("%a_0 * cos(%b_0)").unwrap());
+/// ```
+pub trait ExpressionRewriter {
+    /// Rewrite a single node, whose subexpressions have already been rewritten.
+    fn rewrite(&mut self, expression: Expression) -> Expression;
+}
+
+impl Expression {
+    /// Iterate over this expression and all of its subexpressions in pre-order, i.e. with each
+    /// node yielded before its subexpressions, which are yielded from left to right.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::Expression;
+    /// use std::str::FromStr;
+    ///
+    /// let expression = Expression::from_str("%a + sin(pi)").unwrap();
+    /// let nodes: Vec<String> = expression.iter().map(|node| node.to_string()).collect();
+    ///
+    /// assert_eq!(nodes, vec!["%a + sin(pi)", "%a", "sin(pi)", "pi"]);
+    /// ```
+    pub fn iter(&self) -> Iter<'_> {
+        self.iter_with_order(TraversalOrder::PreOrder)
+    }
+
+    /// Iterate over this expression and all of its subexpressions in the given order.
+    pub fn iter_with_order(&self, order: TraversalOrder) -> Iter<'_> {
+        Iter {
+            stack: vec![(self, false)],
+            order,
+        }
+    }
+
+    /// Traverse this expression with the given visitor.
+    pub fn accept<V: ExpressionVisitor + ?Sized>(&self, visitor: &mut V) {
+        visitor.visit_expression(self)
+    }
+
+    /// Consume this expression, rewriting each of its nodes from the bottom up with the given
+    /// rewriter.
+    pub fn rewrite<R: ExpressionRewriter + ?Sized>(self, rewriter: &mut R) -> Self {
+        use Expression::*;
+
+        let expression = match self {
+            FunctionCall {
+                function,
+                expression,
+            } => FunctionCall {
+                function,
+                expression: expression.rewrite(rewriter).into(),
+            },
+            Infix {
+                left,
+                operator,
+                right,
+            } => {
+                let left = left.rewrite(rewriter).into();
+                let right = right.rewrite(rewriter).into();
+                Infix {
+                    left,
+                    operator,
+                    right,
+                }
             }
-        )
+            Prefix {
+                operator,
+                expression,
+            } => Prefix {
+                operator,
+                expression: expression.rewrite(rewriter).into(),
+            },
+            leaf => leaf,
+        };
+        rewriter.rewrite(expression)
     }
-}
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-#[cfg_attr(test, derive(Arbitrary))]
-pub enum PrefixOperator {
-    Plus,
-    Minus,
-}
+    /// The number of nodes on the longest path from this expression to one of its leaves, where
+    /// a lone leaf such as `%a` or `1.0` has a depth of 1.
+    pub fn depth(&self) -> usize {
+        use Expression::*;
 
-impl fmt::Display for PrefixOperator {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use PrefixOperator::*;
-        write!(
-            f,
-            "{}",
-            match self {
-                Plus => "+",
-                Minus => "-",
-            }
-        )
+        match self {
+            FunctionCall { expression, .. } | Prefix { expression, .. } => 1 + expression.depth(),
+            Infix { left, right, .. } => 1 + left.depth().max(right.depth()),
+            Address(_) | Number(_) | PiConstant | Variable(_) => 1,
+        }
     }
-}
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
-#[cfg_attr(test, derive(Arbitrary))]
-pub enum InfixOperator {
-    Caret,
-    Plus,
-    Minus,
-    Slash,
-    Star,
-}
+    /// The total number of nodes in this expression, including this one.
+    pub fn node_count(&self) -> usize {
+        self.iter().count()
+    }
 
-impl fmt::Display for InfixOperator {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use InfixOperator::*;
-        write!(
-            f,
-            "{}",
-            match self {
-                Caret => "^",
-                Plus => "+",
-                Minus => "-",
-                Slash => "/",
-                Star => "*",
-            }
-        )
+    /// The number of function calls, infix operations, and prefix operations in this expression.
+    pub fn operation_count(&self) -> usize {
+        self.iter()
+            .filter(|node| {
+                matches!(
+                    node,
+                    Expression::FunctionCall { .. }
+                        | Expression::Infix { .. }
+                        | Expression::Prefix { .. }
+                )
+            })
+            .count()
     }
 }
 
@@ -671,47 +2638,788 @@ mod tests {
         }
     }
 
-    /// Generate an arbitrary Expression for a property test.
-    /// See https://docs.rs/proptest/1.0.0/proptest/prelude/trait.Strategy.html#method.prop_recursive
-    fn arb_expr() -> impl Strategy<Value = Expression> {
-        use Expression::*;
-        let leaf = prop_oneof![
-            any::<MemoryReference>().prop_map(Address),
-            (any::<f64>(), any::<f64>())
-                .prop_map(|(re, im)| Number(num_complex::Complex64::new(re, im))),
-            Just(PiConstant),
-            ".*".prop_map(Variable),
+    #[test]
+    fn simplify_identities() {
+        for (input, expected) in [
+            ("(0 + %theta) * 1", "%theta"),
+            ("((0 + %theta) * 1) - 0", "%theta"),
+            ("%theta * 0", "0"),
+            ("0 * %theta", "0"),
+            ("0 - %theta", "-%theta"),
+            ("%theta - %theta", "0"),
+            ("%theta / 1", "%theta"),
+            ("-(-%theta)", "%theta"),
+            ("-(-(-%theta))", "-%theta"),
+            ("(%theta + 1) + 2", "%theta + 3"),
+            ("2 * (3 * %theta)", "%theta * 6"),
+            ("(%theta - 1) + 2", "(%theta - 1) + 2"),
+            ("sin(%theta + 0) * (pi - pi + 1)", "sin(%theta)"),
+            ("pi/2 + pi/2", "pi"),
+            ("2 * pi - pi", "pi"),
+            ("-1 * %theta", "-%theta"),
+            ("%theta * -1", "-%theta"),
+            ("%theta / -1", "-%theta"),
+            ("-1 * -%theta", "%theta"),
+            ("(-%theta) * (-%phi)", "%theta * %phi"),
+            ("(-%theta) / (-%phi)", "%theta / %phi"),
+            ("(-%theta) * %phi", "(-%theta) * %phi"),
+            ("%theta ^ (2 - 1)", "%theta"),
+            ("%theta ^ (1 - 1)", "1"),
+        ] {
+            let simplified = Expression::from_str(input).unwrap().into_simplified();
+            let expected = Expression::from_str(expected).unwrap().into_simplified();
+            assert_eq!(simplified, expected, "{}", input);
+        }
+
+        let power = |exponent: f64| Expression::Infix {
+            left: Box::new(Expression::Variable("theta".to_owned())),
+            operator: InfixOperator::Caret,
+            right: Box::new(Expression::Number(real!(exponent))),
+        };
+        assert_eq!(
+            power(1f64).into_simplified(),
+            Expression::Variable("theta".to_owned())
+        );
+        assert_eq!(
+            power(0f64).into_simplified(),
+            Expression::Number(real!(1f64))
+        );
+
+        let mut variables = HashMap::new();
+        variables.insert("theta".to_owned(), real!(0f64));
+        assert_eq!(
+            power(0f64).evaluate(&variables, &HashMap::new()),
+            Ok(real!(1f64))
+        );
+        assert_eq!(
+            power(1f64).evaluate(&variables, &HashMap::new()),
+            Ok(real!(0f64))
+        );
+    }
+
+    #[test]
+    fn simplify_preserves_value() {
+        let mut variables = HashMap::new();
+        variables.insert("x".to_owned(), real!(3f64));
+        for input in [
+            "(%x / 2) / 4",
+            "4 / (%x / 2)",
+            "(2 / %x) / 4",
+            "2 / (4 / %x)",
+            "(%x / 2) * 4",
+            "(%x * 2) / 4",
+            "(%x - 2) - 4",
+            "4 - (%x - 2)",
+            "(%x + 2) + 4",
+            "(%x * 2) * 4",
+        ] {
+            let expression = Expression::from_str(input).unwrap();
+            let expected = expression.evaluate(&variables, &HashMap::new()).unwrap();
+            let simplified = expression.into_simplified();
+            assert_eq!(
+                simplified.evaluate(&variables, &HashMap::new()),
+                Ok(expected),
+                "{} simplified to {}",
+                input,
+                simplified
+            );
+        }
+    }
+
+    #[test]
+    fn substitute() {
+        let mut variables = HashMap::new();
+        variables.insert(
+            "theta".to_owned(),
+            Expression::from_str("2*alpha[0]").unwrap(),
+        );
+        let substituted = Expression::from_str("cos(%theta) + %theta * %phi")
+            .unwrap()
+            .substitute_variables(&variables);
+        assert_eq!(
+            substituted,
+            Expression::from_str("cos(2*alpha[0]) + (2*alpha[0]) * %phi").unwrap()
+        );
+
+        let mut memory_references = HashMap::new();
+        memory_references.insert(
+            MemoryReference {
+                name: "alpha".to_owned(),
+                index: 0,
+            },
+            Expression::from_str("%beta - 1").unwrap(),
+        );
+        let substituted = substituted.substitute_memory_references(&memory_references);
+        assert_eq!(
+            substituted,
+            Expression::from_str("cos(2*(%beta - 1)) + (2*(%beta - 1)) * %phi").unwrap()
+        );
+    }
+
+    #[test]
+    fn variables_and_memory_references() {
+        let expression =
+            Expression::from_str("exp(%a * theta[0]) / (-%b + theta[0] - beta[2] + pi)").unwrap();
+        assert_eq!(expression.variables(), vec!["a", "b"].into_iter().collect());
+        assert_eq!(
+            expression.memory_references(),
+            vec![
+                &MemoryReference {
+                    name: "theta".to_owned(),
+                    index: 0
+                },
+                &MemoryReference {
+                    name: "theta".to_owned(),
+                    index: 0
+                },
+                &MemoryReference {
+                    name: "beta".to_owned(),
+                    index: 2
+                },
+            ]
+        );
+
+        let constant = Expression::from_str("cos(pi / 2) + 1i").unwrap();
+        assert!(constant.variables().is_empty());
+        assert!(constant.memory_references().is_empty());
+    }
+
+    #[test]
+    fn operator_overloads() {
+        let theta = Expression::Variable("theta".to_owned());
+        let built = -(theta.clone() * 2.0 + Complex64::new(0.0, 1.0)) / theta.clone() - 1.0;
+        assert_eq!(
+            built,
+            Expression::from_str("-(%theta * 2 + 1i) / %theta - 1").unwrap()
+        );
+        assert_eq!(
+            theta.clone() + theta,
+            Expression::from_str("%theta + %theta").unwrap()
+        );
+        assert_eq!(Expression::from(PI), Expression::Number(real!(PI)));
+    }
+
+    #[test]
+    fn display_minimal_parentheses() {
+        let theta = || Expression::Variable("theta".to_owned());
+        for (expression, expected) in [
+            ((theta() + 1.0) + 2.0, "%theta + 1 + 2"),
+            (theta() + (Expression::from(1.0) + 2.0), "%theta + (1 + 2)"),
+            (theta() * 2.0 + 1.0, "%theta * 2 + 1"),
+            ((theta() + 1.0) * 2.0, "(%theta + 1) * 2"),
+            (theta() - (theta() - 1.0), "%theta - (%theta - 1)"),
+            (theta() / (theta() * 2.0), "%theta / (%theta * 2)"),
+            (-(theta() + 1.0), "-(%theta + 1)"),
+            (-theta() * 2.0, "-%theta * 2"),
+            (theta() - -theta(), "%theta - (-%theta)"),
+            (theta() * -1.0, "%theta * (-1)"),
+            (Expression::from(-1.0) * theta(), "-1 * %theta"),
+        ] {
+            assert_eq!(expression.to_string(), expected);
+            assert_eq!(
+                Expression::from_str(expected).unwrap().to_string(),
+                expected
+            );
+        }
+
+        // Complex numbers are written as a single literal, but parse back as a sum.
+        assert_eq!(
+            (theta() * Complex64::new(1.0, 2.0)).to_string(),
+            "%theta * (1+2i)"
+        );
+
+        let power = |left: Expression, right: Expression| Expression::Infix {
+            left: Box::new(left),
+            operator: InfixOperator::Caret,
+            right: Box::new(right),
+        };
+        assert_eq!(
+            power(theta(), power(theta(), Expression::from(2.0))).to_string(),
+            "%theta ^ %theta ^ 2"
+        );
+        assert_eq!(
+            power(power(theta(), theta()), Expression::from(2.0)).to_string(),
+            "(%theta ^ %theta) ^ 2"
+        );
+        assert_eq!(
+            power(-theta(), Expression::from(2.0)).to_string(),
+            "(-%theta) ^ 2"
+        );
+    }
+
+    #[test]
+    fn custom_functions() {
+        let mut expression = Expression::from_str("vendor_fn(%theta * 2) + 1").unwrap();
+        assert_eq!(
+            expression,
+            Expression::FunctionCall {
+                function: ExpressionFunction::Custom("vendor_fn".to_owned()),
+                expression: Box::new(Expression::from_str("%theta * 2").unwrap()),
+            } + 1.0
+        );
+        assert_eq!(expression.to_string(), "vendor_fn(%theta * 2) + 1");
+
+        let mut variables = HashMap::new();
+        variables.insert("theta".to_owned(), real!(1f64));
+        assert_eq!(
+            expression.evaluate(&variables, &HashMap::new()),
+            Err(EvaluationError::UndefinedFunction("vendor_fn".to_owned()))
+        );
+
+        let mut functions = FunctionRegistry::new();
+        functions.insert("vendor_fn".to_owned(), |value| value * value);
+        assert_eq!(
+            expression.evaluate_with_functions(&variables, &HashMap::new(), &functions),
+            Ok(real!(5f64))
+        );
+
+        expression = expression.substitute_variables(
+            &std::iter::once(("theta".to_owned(), Expression::from(1.0))).collect(),
+        );
+        expression.simplify();
+        assert_eq!(
+            expression,
+            Expression::from_str("vendor_fn(2) + 1").unwrap()
+        );
+    }
+
+    #[test]
+    fn builtin_functions() {
+        for (input, expected) in [
+            ("abs(-3 + 4i)", real!(5f64)),
+            ("acos(1)", real!(0f64)),
+            ("asin(1)", real!(PI / 2f64)),
+            ("atan(1)", real!(PI / 4f64)),
+            ("ln(exp(2))", real!(2f64)),
+            ("sqrt(4)", real!(2f64)),
+            ("tan(pi / 4)", real!(1f64)),
+        ] {
+            let expression = Expression::from_str(input).unwrap();
+            assert!(
+                !matches!(
+                    expression,
+                    Expression::FunctionCall {
+                        function: ExpressionFunction::Custom(_),
+                        ..
+                    }
+                ),
+                "{}",
+                input
+            );
+            let evaluated = expression
+                .evaluate(&HashMap::new(), &HashMap::new())
+                .unwrap();
+            assert!((evaluated - expected).norm() < 1e-12, "{}", input);
+        }
+    }
+
+    #[test]
+    fn pi_multiples() {
+        for (input, expected) in [
+            ("pi", Some((1, 1))),
+            ("pi/2", Some((1, 2))),
+            ("3*pi/4", Some((3, 4))),
+            ("pi/4 + pi/2", Some((3, 4))),
+            ("-pi/2", Some((-1, 2))),
+            ("2*pi/-4", Some((-1, 2))),
+            ("(pi/3)*6", Some((2, 1))),
+            ("pi - pi", Some((0, 1))),
+            ("pi/2 + 1", None),
+            ("pi*pi", None),
+            ("pi/0", None),
+            ("0.5*pi", None),
+            ("%theta*pi", None),
+        ] {
+            let expression = Expression::from_str(input).unwrap();
+            let expected = expected
+                .and_then(|(numerator, denominator)| PiMultiple::new(numerator, denominator));
+            assert_eq!(expression.to_pi_multiple(), expected, "{}", input);
+        }
+
+        for (input, expected) in [
+            ("pi", "pi"),
+            ("pi/2", "pi / 2"),
+            ("2*pi/4", "pi / 2"),
+            ("pi/4 + pi/2", "3 * pi / 4"),
+            ("-(pi/2)", "-pi / 2"),
+            ("-3*pi", "-3 * pi"),
+            ("2*(3*pi)", "6 * pi"),
+            ("%theta + pi/2", "%theta + pi / 2"),
+            ("cos(2 * pi) + 2", "3"),
+        ] {
+            let simplified = Expression::from_str(input).unwrap().into_simplified();
+            assert_eq!(simplified.to_string(), expected, "{}", input);
+            assert_eq!(
+                simplified.clone().into_simplified(),
+                simplified,
+                "{}",
+                input
+            );
+        }
+
+        let multiple = PiMultiple::new(3, 4).unwrap();
+        assert_eq!(multiple.to_f64(), 3f64 * PI / 4f64);
+        assert_eq!(
+            Expression::from(multiple).evaluate(&HashMap::new(), &HashMap::new()),
+            Ok(real!(3f64 * PI / 4f64))
+        );
+    }
+
+    #[test]
+    fn approximate_equality() {
+        let tolerance = 1e-9;
+        for (left, right, expected) in [
+            ("0.1 + 0.2", "0.3 + 0.2", false),
+            ("sin(%theta * 1.0000000001)", "sin(%theta)", false),
+            ("sin(%theta * 1.0000000001)", "sin(%theta * 1)", true),
+            ("2 * theta[0] + 1i", "1.0000000001i + theta[0] * 2", true),
+            ("2 - theta[0]", "theta[0] - 2", false),
+            ("-pi", "-3.14159265358979", true),
+            ("%a / 3", "%a / 3.0000001", false),
+            ("%a + 1", "theta[0] + 1", false),
+            ("cos(1)", "sin(1)", false),
+        ] {
+            let left = Expression::from_str(left).unwrap();
+            let right = Expression::from_str(right).unwrap();
+            assert_eq!(
+                left.approx_eq(&right, tolerance),
+                expected,
+                "{} ~ {}",
+                left,
+                right
+            );
+            assert_eq!(
+                right.approx_eq(&left, tolerance),
+                expected,
+                "{} ~ {}",
+                right,
+                left
+            );
+        }
+
+        let computed = Expression::Number(real!(0.1) + real!(0.2));
+        assert_ne!(computed, Expression::Number(real!(0.3)));
+        assert!(computed.approx_eq(&Expression::Number(real!(0.3)), f64::EPSILON));
+    }
+
+    #[test]
+    fn try_from_constant() {
+        let parse = |input| Expression::from_str(input).unwrap();
+
+        assert_eq!(f64::try_from(parse("pi / 2")), Ok(PI / 2f64));
+        assert_eq!(f64::try_from(&parse("1 + 2 * 3")), Ok(7f64));
+        assert_eq!(
+            f64::try_from(parse("1 + 2i")),
+            Err(EvaluationError::NumberNotReal)
+        );
+        assert!(matches!(
+            f64::try_from(parse("%theta + 1")),
+            Err(EvaluationError::Incomplete { .. })
+        ));
+
+        assert_eq!(
+            Complex64::try_from(parse("1 + 2i")),
+            Ok(Complex64::new(1f64, 2f64))
+        );
+        assert!(matches!(
+            Complex64::try_from(&parse("theta[0]")),
+            Err(EvaluationError::Incomplete { .. })
+        ));
+    }
+
+    #[test]
+    fn iterate_and_visit() {
+        let expression = Expression::from_str("-%a * cos(theta[0] + pi)").unwrap();
+        let format = |nodes: Iter| nodes.map(|node| node.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            format(expression.iter()),
+            vec![
+                "-%a * cos(theta[0] + pi)",
+                "-%a",
+                "%a",
+                "cos(theta[0] + pi)",
+                "theta[0] + pi",
+                "theta[0]",
+                "pi"
+            ]
+        );
+        assert_eq!(
+            format(expression.iter_with_order(TraversalOrder::PostOrder)),
+            vec![
+                "%a",
+                "-%a",
+                "theta[0]",
+                "pi",
+                "theta[0] + pi",
+                "cos(theta[0] + pi)",
+                "-%a * cos(theta[0] + pi)"
+            ]
+        );
+
+        #[derive(Default)]
+        struct Leaves(Vec<String>);
+
+        impl ExpressionVisitor for Leaves {
+            fn visit_address(&mut self, memory_reference: &MemoryReference) {
+                self.0.push(memory_reference.to_string());
+            }
+
+            fn visit_pi_constant(&mut self) {
+                self.0.push("pi".to_owned());
+            }
+
+            fn visit_variable(&mut self, name: &str) {
+                self.0.push(name.to_owned());
+            }
+        }
+
+        let mut leaves = Leaves::default();
+        expression.accept(&mut leaves);
+        assert_eq!(leaves.0, vec!["a", "theta[0]", "pi"]);
+
+        struct NegateNumbers;
+
+        impl ExpressionRewriter for NegateNumbers {
+            fn rewrite(&mut self, expression: Expression) -> Expression {
+                match expression {
+                    Expression::Number(value) => Expression::Number(-value),
+                    other => other,
+                }
+            }
+        }
+
+        let rewritten = Expression::from_str("1 + sin(2 * %a)")
+            .unwrap()
+            .rewrite(&mut NegateNumbers);
+        assert_eq!(rewritten.to_string(), "-1 + sin(-2 * %a)");
+    }
+
+    #[test]
+    fn structural_equality() {
+        let parse = |input| Expression::from_str(input).unwrap();
+
+        assert_eq!(parse("(%a + 1) * %b"), parse("%b * (1 + %a)"));
+        assert_ne!(parse("%a - %b"), parse("%b - %a"));
+        assert_ne!(parse("%a / %b"), parse("%b / %a"));
+        assert_ne!(parse("%a + %b"), parse("%a * %b"));
+        assert_ne!(parse("sin(%a)"), parse("cos(%a)"));
+        assert_ne!(parse("theta[0]"), parse("theta[1]"));
+        assert_ne!(parse("pi"), Expression::Number(real!(PI)));
+        assert_eq!(
+            Expression::Number(real!(0f64)),
+            Expression::Number(real!(-0f64))
+        );
+
+        let nan = Expression::Number(real!(f64::NAN));
+        assert_eq!(nan, nan.clone());
+        assert_ne!(nan, Expression::Number(real!(0f64)));
+        assert_eq!(hash_to_u64(&parse("1 + 2i")), hash_to_u64(&parse("2i + 1")));
+    }
+
+    #[test]
+    fn incomplete_evaluation() {
+        let mut variables = HashMap::new();
+        variables.insert("bound".to_owned(), real!(1f64));
+        let mut memory_references = HashMap::new();
+        memory_references.insert("theta", vec![1f64]);
+
+        let expression =
+            Expression::from_str("%bound + cos(%a * theta[1]) * (theta[0] - %b / %a)").unwrap();
+        let error = expression
+            .evaluate(&variables, &memory_references)
+            .unwrap_err();
+        assert_eq!(
+            error,
+            EvaluationError::Incomplete {
+                expression: Box::new(
+                    Expression::from_str("cos(%a * theta[1]) * (theta[0] - %b / %a)").unwrap()
+                ),
+                unbound_variables: vec!["a".to_owned(), "b".to_owned()],
+                unbound_memory_references: vec![MemoryReference {
+                    name: "theta".to_owned(),
+                    index: 1
+                }],
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "could not evaluate cos(%a * theta[1]) * (theta[0] - %b / %a): \
+             no value for variables %a, %b or memory reference theta[1]"
+        );
+
+        let error = Expression::from_str("%bound * sin(%c)")
+            .unwrap()
+            .evaluate(&variables, &memory_references)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "could not evaluate %c: no value for variable %c"
+        );
+    }
+
+    #[test]
+    fn checked_evaluation() {
+        let mut variables = HashMap::new();
+        variables.insert("a".to_owned(), real!(1f64));
+        let mut memory_references = HashMap::new();
+        memory_references.insert("theta", vec![f64::NAN]);
+        let functions = FunctionRegistry::new();
+
+        for (input, expected) in [
+            ("1 / (%a - 1)", Ok("1 / (%a - 1)")),
+            ("2 * exp(1000) + %a", Err("exp(1000)")),
+            ("ln(0) - 1", Err("ln(0)")),
+            ("%a + theta[0]", Err("theta[0]")),
+        ] {
+            let expression = Expression::from_str(input).unwrap();
+            let error = expression
+                .evaluate_checked(&variables, &memory_references, &functions)
+                .unwrap_err();
+            let expected = match expected {
+                Ok(subexpression) => EvaluationError::DivisionByZero {
+                    expression: Box::new(Expression::from_str(subexpression).unwrap()),
+                },
+                Err(subexpression) => EvaluationError::NotFinite {
+                    expression: Box::new(Expression::from_str(subexpression).unwrap()),
+                },
+            };
+            assert_eq!(error, expected, "{}", input);
+
+            let unchecked = expression.evaluate(&variables, &memory_references).unwrap();
+            assert!(!unchecked.is_finite(), "{}", input);
+        }
+
+        assert_eq!(
+            Expression::from_str("%a / 2").unwrap().evaluate_checked(
+                &variables,
+                &memory_references,
+                &functions
+            ),
+            Ok(real!(0.5))
+        );
+    }
+
+    #[test]
+    fn evaluate_repeatedly_without_cloning() {
+        let expression = Expression::from_str("%theta * 2 + theta[0]").unwrap();
+        let results: Vec<_> = (0..4)
+            .map(|value| {
+                let mut variables = HashMap::new();
+                variables.insert("theta".to_owned(), real!(value as f64));
+                let mut memory_references = HashMap::new();
+                memory_references.insert("theta", vec![1f64]);
+                expression.evaluate(&variables, &memory_references)
+            })
+            .collect();
+        assert_eq!(
+            results,
+            vec![
+                Ok(real!(1f64)),
+                Ok(real!(3f64)),
+                Ok(real!(5f64)),
+                Ok(real!(7f64))
+            ]
+        );
+    }
+
+    #[test]
+    fn canonical_form() {
+        for (inputs, expected) in [
+            (vec!["%a + %b", "%b + %a"], "%a + %b"),
+            (vec!["(%c + %a) + %b", "%a + (%c + %b)"], "%a + %b + %c"),
+            (vec!["%a * (%c * %b)", "(%b * %a) * %c"], "%a * %b * %c"),
+            (vec!["-%a", "0 - %a", "%a * -1"], "-1 * %a"),
+            (vec!["-(%a - %b)", "%b - %a"], "-1 * %a + %b"),
+            (
+                vec!["2 * %x * 3 - %y + 1 - 4", "-3 - %y + %x * 6"],
+                "6 * %x - %y - 3",
+            ),
+            (vec!["%x - %x", "0", "1 - 1"], "0"),
+            (vec!["%x + 2 * %x", "3 * %x"], "3 * %x"),
+            (
+                vec!["sin(%b + %a) * 2", "2 * sin(%a + %b)"],
+                "2 * sin(%a + %b)",
+            ),
+            (
+                vec!["(%b + %a) / (2 * %c)", "(%a + %b) / (%c * 2)"],
+                "(%a + %b) / (2 * %c)",
+            ),
+            (vec!["%x + 1i + 1", "(1 + 1i) + %x"], "%x + (1+1i)"),
+        ] {
+            for input in inputs {
+                let canonical = Expression::from_str(input).unwrap().into_canonical();
+                assert_eq!(canonical.to_string(), expected, "{}", input);
+                assert_eq!(canonical.clone().into_canonical(), canonical, "{}", input);
+            }
+        }
+    }
+
+    #[test]
+    fn complexity_metrics() {
+        let cases = vec![
+            ("%a", 1, 1, 0),
+            ("pi", 1, 1, 0),
+            ("-%a", 2, 2, 1),
+            ("%a + theta[1]", 2, 3, 1),
+            ("sin(%a * 2) + 1", 4, 6, 3),
+            ("(%a + %b) * (%c + %d)", 3, 7, 3),
         ];
-        (leaf).prop_recursive(
-            4,  // No more than 4 branch levels deep
-            64, // Target around 64 total nodes
-            2,  // Each "collection" is up to 2 elements
-            |expr| {
-                prop_oneof![
-                    (any::<ExpressionFunction>(), expr.clone()).prop_map(|(function, e)| {
-                        FunctionCall {
-                            function,
-                            expression: Box::new(e),
-                        }
-                    }),
-                    (expr.clone(), any::<InfixOperator>(), expr.clone()).prop_map(
-                        |(l, operator, r)| Infix {
-                            left: Box::new(l),
-                            operator,
-                            right: Box::new(r)
-                        }
-                    ),
-                    (any::<PrefixOperator>(), expr).prop_map(|(operator, e)| Prefix {
-                        operator,
-                        expression: Box::new(e)
-                    })
-                ]
+
+        for (input, depth, node_count, operation_count) in cases {
+            let expression = Expression::from_str(input).unwrap();
+            assert_eq!(expression.depth(), depth, "depth of {}", input);
+            assert_eq!(
+                expression.node_count(),
+                node_count,
+                "node count of {}",
+                input
+            );
+            assert_eq!(
+                expression.operation_count(),
+                operation_count,
+                "operation count of {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate_with_memory() {
+        let mut patch_values = HashMap::new();
+        patch_values.insert("theta", vec![1.0, 2.0]);
+        let mut memory = MemoryValues::from(&patch_values);
+        memory.insert(
+            MemoryReference {
+                name: "iq".to_owned(),
+                index: 2,
             },
-        )
+            Complex64::new(0.5, -0.5),
+        );
+
+        let expression = Expression::from_str("theta[0] + theta[1] * iq[2]").unwrap();
+        assert_eq!(
+            expression.evaluate_with_memory(&HashMap::new(), &memory),
+            Ok(Complex64::new(2.0, -1.0))
+        );
+
+        let expression = Expression::from_str("theta[2] + iq[0]").unwrap();
+        assert_eq!(
+            expression.evaluate_with_memory(&HashMap::new(), &memory),
+            Err(EvaluationError::Incomplete {
+                expression: Box::new(expression.clone()),
+                unbound_variables: vec![],
+                unbound_memory_references: vec![
+                    MemoryReference {
+                        name: "theta".to_owned(),
+                        index: 2,
+                    },
+                    MemoryReference {
+                        name: "iq".to_owned(),
+                        index: 0,
+                    },
+                ],
+            })
+        );
+
+        assert!(memory.remove_region("iq"));
+        assert_eq!(
+            memory.get(&MemoryReference {
+                name: "iq".to_owned(),
+                index: 2,
+            }),
+            None
+        );
+    }
+
+    #[test]
+    fn evaluate_with_memory_contents() {
+        let mut memory = MemoryContents::new();
+        memory.insert("theta", MemoryRegionContents::Real(vec![0.5, 1.5]));
+        let size = Vector {
+            data_type: ScalarType::Integer,
+            length: 1,
+        };
+        memory.insert("count", MemoryRegionContents::zeroed(&size));
+        let reference = |name: &str, index| MemoryReference {
+            name: name.to_owned(),
+            index,
+        };
+
+        let cases = vec![
+            ("theta[1] * 2", Ok(real!(3f64))),
+            (
+                "theta[0] + count[0]",
+                Err(EvaluationError::NotRealMemory {
+                    memory_reference: reference("count", 0),
+                    data_type: ScalarType::Integer,
+                }),
+            ),
+            (
+                "theta[2]",
+                Err(EvaluationError::IndexOutOfBounds {
+                    memory_reference: reference("theta", 2),
+                    length: 2,
+                }),
+            ),
+            (
+                "beta[0]",
+                Err(EvaluationError::Incomplete {
+                    expression: Box::new(Expression::Address(reference("beta", 0))),
+                    unbound_variables: vec![],
+                    unbound_memory_references: vec![reference("beta", 0)],
+                }),
+            ),
+        ];
+
+        for (input, expected) in cases {
+            let expression = Expression::from_str(input).unwrap();
+            let evaluated = expression.evaluate_with_memory_contents(&HashMap::new(), &memory);
+            assert_eq!(evaluated, expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn constructors() {
+        assert_eq!(Expression::from(3i64), Expression::Number(real!(3f64)));
+        assert_eq!(Expression::from(-0.5), Expression::Number(real!(-0.5)));
+        assert_eq!(
+            Expression::from(imag!(2f64)),
+            Expression::from_str("2i").unwrap()
+        );
+        assert_eq!(Expression::pi(), Expression::PiConstant);
+        assert_eq!(
+            Expression::var("theta"),
+            Expression::from_str("%theta").unwrap()
+        );
+        assert_eq!(
+            Expression::address("ro", 0),
+            Expression::from_str("ro[0]").unwrap()
+        );
     }
 
-    fn arb_complex64() -> impl Strategy<Value = Complex64> {
-        any::<(f64, f64)>().prop_map(|(re, im)| Complex64::new(re, im))
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_json_round_trip() {
+        let expression = Expression::from_str("-sin(%theta) * theta[1] ^ (pi + 1.5i)").unwrap();
+        let json = serde_json::to_value(&expression).unwrap();
+        assert_eq!(json["type"], "infix");
+        assert_eq!(json["value"]["operator"], "*");
+        assert_eq!(json["value"]["left"]["value"]["operator"], "-");
+        assert_eq!(
+            json["value"]["left"]["value"]["expression"]["value"]["function"],
+            "sin"
+        );
+
+        let deserialized: Expression = serde_json::from_value(json).unwrap();
+        assert_eq!(deserialized.to_string(), expression.to_string());
+
+        let json = r#"{"type": "function_call", "value": {
+            "function": {"custom": "sinc"},
+            "expression": {"type": "number", "value": [0.5, -1.0]}
+        }}"#;
+        let deserialized: Expression = serde_json::from_str(json).unwrap();
+        assert_eq!(deserialized.to_string(), "sinc(0.5-1i)");
     }
 
     proptest! {
@@ -777,7 +3485,7 @@ mod tests {
         }
 
         #[test]
-        fn eq_iff_hash_eq(x in arb_expr(), y in arb_expr()) {
+        fn eq_iff_hash_eq(x in strategies::expression(), y in strategies::expression()) {
             let h_x = {
                 let mut s = DefaultHasher::new();
                 x.hash(&mut s);
@@ -807,7 +3515,7 @@ mod tests {
         }
 
         #[test]
-        fn no_other_exps_are_real(expr in arb_expr().prop_filter("Not numbers", |e| match e {
+        fn no_other_exps_are_real(expr in strategies::expression().prop_filter("Not numbers", |e| match e {
             Expression::Number(_) | Expression::PiConstant => false,
             _ => true,
         }
@@ -816,12 +3524,50 @@ mod tests {
         }
 
         #[test]
-        fn complexes_are_parseable_as_expressions(value in arb_complex64()) {
+        fn identifiers_parse_as_memory_regions(name in strategies::identifier()) {
+            let expected = Expression::Address(MemoryReference { name: name.clone(), index: 0 });
+            prop_assert_eq!(Expression::from_str(&name), Ok(expected));
+        }
+
+        #[test]
+        fn complexes_are_parseable_as_expressions(value in strategies::complex64()) {
             let parsed = Expression::from_str(&format_complex(&value));
             assert!(parsed.is_ok());
             assert_eq!(Expression::Number(value), parsed.unwrap().into_simplified());
         }
 
+        #[test]
+        fn displayed_numbers_round_trip(value in any::<f64>().prop_filter("finite", |x| x.is_finite())) {
+            let displayed = Expression::Number(real!(value)).to_string();
+            let parsed = Expression::from_str(&displayed).unwrap();
+            let evaluated = parsed.evaluate(&HashMap::new(), &HashMap::new()).unwrap();
+            // Zero is always written unsigned.
+            if value != 0f64 {
+                prop_assert_eq!(evaluated.re.to_bits(), value.to_bits(), "{}", displayed);
+            }
+        }
+
+    }
+
+    #[test]
+    fn parse_expression_with_span() {
+        for (input, expected, consumed) in [
+            ("1 + 2", "1 + 2", "1 + 2"),
+            ("\n\t sin(%theta/2), 0", "sin(%theta/2)", "sin(%theta/2)"),
+            (
+                "theta[1] * 2 ]] not quil {}",
+                "theta[1] * 2",
+                "theta[1] * 2",
+            ),
+            ("pi PULSE", "pi", "pi"),
+        ] {
+            let (expression, span) = parse_expression(input).unwrap();
+            assert_eq!(expression, Expression::from_str(expected).unwrap());
+            assert_eq!(&input[span], consumed);
+        }
+
+        assert!(parse_expression("").is_err());
+        assert!(parse_expression("{ 1 + 2 }").is_err());
     }
 
     #[test]
@@ -861,4 +3607,48 @@ mod tests {
             assert_eq!(format_complex(x), *s);
         }
     }
+
+    #[test]
+    fn number_formats() {
+        let digits = |digits| NumberFormat::SignificantDigits(NonZeroUsize::new(digits).unwrap());
+        for (value, number_format, expected) in [
+            (
+                Complex64::new(0.1 + 0.2, 0.0),
+                NumberFormat::Shortest,
+                "0.30000000000000004",
+            ),
+            (Complex64::new(0.1 + 0.2, 0.0), digits(15), "0.3"),
+            (Complex64::new(1234.5678, 0.0), digits(3), "1230"),
+            (Complex64::new(-1.26e-10, 0.0), digits(2), "-1.3e-10"),
+            (Complex64::new(1.0, -1.0 / 3.0), digits(4), "1-0.3333i"),
+            (
+                Complex64::new(1.0 / 3.0, 0.0),
+                NumberFormat::Fixed(3),
+                "0.333",
+            ),
+            (Complex64::new(0.0, 2.0), NumberFormat::Fixed(1), "2.0i"),
+            (
+                Complex64::new(1e20, 0.0),
+                NumberFormat::Fixed(0),
+                "100000000000000000000",
+            ),
+        ] {
+            let formatted = Expression::Number(value)
+                .display_with(number_format)
+                .to_string();
+            assert_eq!(formatted, expected);
+        }
+
+        // Non-finite parts have no fixed-point form, so fall back to the default format.
+        for (value, expected) in [
+            (Complex64::new(f64::INFINITY, 0.0), "inf"),
+            (Complex64::new(0.0, f64::NEG_INFINITY), "-infi"),
+            (Complex64::new(f64::NAN, 1.0), "NaN+1.000i"),
+        ] {
+            let formatted = Expression::Number(value)
+                .display_with(NumberFormat::Fixed(3))
+                .to_string();
+            assert_eq!(formatted, expected);
+        }
+    }
 }
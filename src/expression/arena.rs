@@ -0,0 +1,368 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Storage of many expressions within a single pool, as an alternative to boxed trees.
+
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use num_complex::Complex64;
+
+use super::{Expression, ExpressionFunction, InfixOperator, PrefixOperator};
+use crate::instruction::MemoryReference;
+
+/// A handle to an expression stored within an [`ExpressionArena`]. It is only meaningful for the
+/// arena which issued it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ExpressionId(usize);
+
+/// A single node of an expression stored within an [`ExpressionArena`], which refers to its
+/// subexpressions by [`ExpressionId`] rather than owning them.
+#[derive(Clone, Debug)]
+pub enum ExpressionNode {
+    Address(MemoryReference),
+    FunctionCall {
+        function: ExpressionFunction,
+        expression: ExpressionId,
+    },
+    Infix {
+        left: ExpressionId,
+        operator: InfixOperator,
+        right: ExpressionId,
+    },
+    Number(Complex64),
+    PiConstant,
+    Prefix {
+        operator: PrefixOperator,
+        expression: ExpressionId,
+    },
+    Variable(String),
+}
+
+// Implemented by hand since we can't derive with f64s hidden inside. Unlike for `Expression`,
+// numbers are compared bitwise so that interning never changes the value of a node.
+impl PartialEq for ExpressionNode {
+    fn eq(&self, other: &Self) -> bool {
+        use ExpressionNode::*;
+        match (self, other) {
+            (Address(left), Address(right)) => left == right,
+            (
+                FunctionCall {
+                    function: left_function,
+                    expression: left_expression,
+                },
+                FunctionCall {
+                    function: right_function,
+                    expression: right_expression,
+                },
+            ) => left_function == right_function && left_expression == right_expression,
+            (
+                Infix {
+                    left: left_left,
+                    operator: left_operator,
+                    right: left_right,
+                },
+                Infix {
+                    left: right_left,
+                    operator: right_operator,
+                    right: right_right,
+                },
+            ) => {
+                left_left == right_left
+                    && left_operator == right_operator
+                    && left_right == right_right
+            }
+            (Number(left), Number(right)) => {
+                left.re.to_bits() == right.re.to_bits() && left.im.to_bits() == right.im.to_bits()
+            }
+            (PiConstant, PiConstant) => true,
+            (
+                Prefix {
+                    operator: left_operator,
+                    expression: left_expression,
+                },
+                Prefix {
+                    operator: right_operator,
+                    expression: right_expression,
+                },
+            ) => left_operator == right_operator && left_expression == right_expression,
+            (Variable(left), Variable(right)) => left == right,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ExpressionNode {}
+
+impl Hash for ExpressionNode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        use ExpressionNode::*;
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Address(memory_reference) => memory_reference.hash(state),
+            FunctionCall {
+                function,
+                expression,
+            } => {
+                function.hash(state);
+                expression.hash(state);
+            }
+            Infix {
+                left,
+                operator,
+                right,
+            } => {
+                left.hash(state);
+                operator.hash(state);
+                right.hash(state);
+            }
+            Number(number) => {
+                number.re.to_bits().hash(state);
+                number.im.to_bits().hash(state);
+            }
+            PiConstant => {}
+            Prefix {
+                operator,
+                expression,
+            } => {
+                operator.hash(state);
+                expression.hash(state);
+            }
+            Variable(name) => name.hash(state),
+        }
+    }
+}
+
+/// A pool of expression nodes, each stored once no matter how many expressions share it.
+///
+/// Building a program's worth of expressions within one arena needs only a single growing
+/// allocation, rather than one per node, and identical subexpressions are stored only once.
+///
+/// # Example
+///
+/// ```rust
+/// use quil_rs::expression::{Expression, ExpressionArena};
+/// use std::str::FromStr;
+///
+/// let mut arena = ExpressionArena::new();
+/// let first = arena.intern(&Expression::from_str("cos(%theta / 2)").unwrap());
+/// let second = arena.intern(&Expression::from_str("sin(%theta / 2)").unwrap());
+///
+/// // `%theta`, `2`, `%theta/2`, and the two function calls.
+/// assert_eq!(arena.len(), 5);
+/// assert_eq!(arena.to_expression(second), Expression::from_str("sin(%theta / 2)").unwrap());
+/// assert_eq!(arena.intern(&Expression::from_str("cos(%theta / 2)").unwrap()), first);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct ExpressionArena {
+    nodes: Vec<ExpressionNode>,
+    ids: HashMap<ExpressionNode, ExpressionId>,
+}
+
+impl ExpressionArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of distinct nodes stored within this arena.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Store a single node, returning the identifier of the equal node already in the arena if
+    /// there is one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the node refers to an [`ExpressionId`] not issued by this arena.
+    pub fn insert(&mut self, node: ExpressionNode) -> ExpressionId {
+        if let Some(id) = self.ids.get(&node) {
+            return *id;
+        }
+
+        let children = match &node {
+            ExpressionNode::FunctionCall { expression, .. }
+            | ExpressionNode::Prefix { expression, .. } => vec![*expression],
+            ExpressionNode::Infix { left, right, .. } => vec![*left, *right],
+            _ => vec![],
+        };
+        for ExpressionId(index) in children {
+            assert!(
+                index < self.nodes.len(),
+                "expression node refers to an id not issued by this arena"
+            );
+        }
+
+        let id = ExpressionId(self.nodes.len());
+        self.nodes.push(node.clone());
+        self.ids.insert(node, id);
+        id
+    }
+
+    /// Store the given expression and all of its subexpressions, returning its identifier.
+    pub fn intern(&mut self, expression: &Expression) -> ExpressionId {
+        let node = match expression {
+            Expression::Address(memory_reference) => {
+                ExpressionNode::Address(memory_reference.clone())
+            }
+            Expression::FunctionCall {
+                function,
+                expression,
+            } => ExpressionNode::FunctionCall {
+                function: function.clone(),
+                expression: self.intern(expression),
+            },
+            Expression::Infix {
+                left,
+                operator,
+                right,
+            } => ExpressionNode::Infix {
+                left: self.intern(left),
+                operator: operator.clone(),
+                right: self.intern(right),
+            },
+            Expression::Number(number) => ExpressionNode::Number(*number),
+            Expression::PiConstant => ExpressionNode::PiConstant,
+            Expression::Prefix {
+                operator,
+                expression,
+            } => ExpressionNode::Prefix {
+                operator: operator.clone(),
+                expression: self.intern(expression),
+            },
+            Expression::Variable(name) => ExpressionNode::Variable(name.clone()),
+        };
+        self.insert(node)
+    }
+
+    /// The node with the given identifier.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the identifier was not issued by this arena.
+    pub fn get(&self, id: ExpressionId) -> &ExpressionNode {
+        &self.nodes[id.0]
+    }
+
+    /// Rebuild the boxed tree of the expression with the given identifier.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the identifier was not issued by this arena.
+    pub fn to_expression(&self, id: ExpressionId) -> Expression {
+        match self.get(id) {
+            ExpressionNode::Address(memory_reference) => {
+                Expression::Address(memory_reference.clone())
+            }
+            ExpressionNode::FunctionCall {
+                function,
+                expression,
+            } => Expression::FunctionCall {
+                function: function.clone(),
+                expression: Box::new(self.to_expression(*expression)),
+            },
+            ExpressionNode::Infix {
+                left,
+                operator,
+                right,
+            } => Expression::Infix {
+                left: Box::new(self.to_expression(*left)),
+                operator: operator.clone(),
+                right: Box::new(self.to_expression(*right)),
+            },
+            ExpressionNode::Number(number) => Expression::Number(*number),
+            ExpressionNode::PiConstant => Expression::PiConstant,
+            ExpressionNode::Prefix {
+                operator,
+                expression,
+            } => Expression::Prefix {
+                operator: operator.clone(),
+                expression: Box::new(self.to_expression(*expression)),
+            },
+            ExpressionNode::Variable(name) => Expression::Variable(name.clone()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use num_complex::Complex64;
+
+    use super::{ExpressionArena, ExpressionNode};
+    use crate::expression::{Expression, InfixOperator};
+
+    #[test]
+    fn round_trip() {
+        let mut arena = ExpressionArena::new();
+        let inputs = [
+            "%a",
+            "sin(%a) + sin(%a)",
+            "theta[1] * -pi",
+            "(1 + 2i) ^ %b",
+            "-0.0",
+        ];
+        let ids: Vec<_> = inputs
+            .iter()
+            .map(|input| arena.intern(&Expression::from_str(input).unwrap()))
+            .collect();
+
+        for (input, id) in inputs.iter().zip(ids) {
+            let expression = arena.to_expression(id);
+            assert_eq!(expression, Expression::from_str(input).unwrap());
+            assert_eq!(
+                expression.to_string(),
+                Expression::from_str(input).unwrap().to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn shared_nodes() {
+        let mut arena = ExpressionArena::new();
+        let sum = arena.intern(&Expression::from_str("sin(%a) + sin(%a)").unwrap());
+        assert_eq!(arena.len(), 3);
+
+        let sine = arena.intern(&Expression::from_str("sin(%a)").unwrap());
+        assert_eq!(arena.len(), 3);
+        assert_eq!(
+            arena.get(sum),
+            &ExpressionNode::Infix {
+                left: sine,
+                operator: InfixOperator::Plus,
+                right: sine,
+            }
+        );
+
+        let zero = arena.insert(ExpressionNode::Number(Complex64::new(0.0, 0.0)));
+        let negative_zero = arena.insert(ExpressionNode::Number(Complex64::new(-0.0, 0.0)));
+        assert_ne!(zero, negative_zero);
+    }
+
+    #[test]
+    #[should_panic]
+    fn foreign_id() {
+        let mut arena = ExpressionArena::new();
+        let id = arena.intern(&Expression::from_str("%a + %b").unwrap());
+        ExpressionArena::new().insert(ExpressionNode::Prefix {
+            operator: crate::expression::PrefixOperator::Minus,
+            expression: id,
+        });
+    }
+}
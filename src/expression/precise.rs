@@ -0,0 +1,251 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Arbitrary-precision evaluation of expressions, backed by [`rug`].
+
+use std::collections::HashMap;
+
+use num_complex::Complex64;
+use rug::float::Constant;
+use rug::ops::Pow;
+use rug::{Complex, Float};
+
+use super::{
+    EvaluationError, Expression, ExpressionFunction, FunctionRegistry, InfixOperator,
+    PrefixOperator,
+};
+
+/// The value of an expression evaluated with arbitrary precision.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PreciseValue(Complex);
+
+impl PreciseValue {
+    /// The underlying arbitrary-precision complex number.
+    pub fn as_complex(&self) -> &Complex {
+        &self.0
+    }
+
+    /// Consume this value, returning the underlying arbitrary-precision complex number.
+    pub fn into_complex(self) -> Complex {
+        self.0
+    }
+
+    /// Round this value to the nearest [`Complex64`].
+    pub fn to_complex64(&self) -> Complex64 {
+        Complex64::new(self.0.real().to_f64(), self.0.imag().to_f64())
+    }
+}
+
+impl From<PreciseValue> for Complex64 {
+    fn from(value: PreciseValue) -> Self {
+        value.to_complex64()
+    }
+}
+
+struct PreciseContext<'a> {
+    variables: &'a HashMap<String, Complex64>,
+    memory_references: &'a HashMap<&'a str, Vec<f64>>,
+    functions: &'a FunctionRegistry,
+    /// The precision, in bits, of both the real and imaginary parts of every intermediate value.
+    precision: u32,
+}
+
+impl Expression {
+    /// Evaluate an expression as with [`Expression::evaluate_with_functions`], but carrying out
+    /// every operation with `precision` bits of precision rather than in `f64`. This avoids the
+    /// accumulation of rounding error over long chains of operations.
+    ///
+    /// Inputs are taken as `f64` values and converted exactly. Calls to functions in `functions`
+    /// are made with the argument rounded to [`Complex64`], and so are no more precise than that.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::expression::Expression;
+    /// use std::str::FromStr;
+    /// use std::collections::HashMap;
+    /// use num_complex::Complex64;
+    ///
+    /// let expression = Expression::from_str("2^60 + 1 - 2^60").unwrap();
+    /// let (variables, memory_references, functions) =
+    ///     (HashMap::new(), HashMap::new(), HashMap::new());
+    ///
+    /// assert_eq!(
+    ///     expression.evaluate(&variables, &memory_references).unwrap(),
+    ///     Complex64::from(0.0)
+    /// );
+    ///
+    /// let evaluated = expression
+    ///     .evaluate_precise(&variables, &memory_references, &functions, 128)
+    ///     .unwrap();
+    ///
+    /// assert_eq!(Complex64::from(evaluated), Complex64::from(1.0));
+    /// ```
+    pub fn evaluate_precise(
+        &self,
+        variables: &HashMap<String, Complex64>,
+        memory_references: &HashMap<&str, Vec<f64>>,
+        functions: &FunctionRegistry,
+        precision: u32,
+    ) -> Result<PreciseValue, EvaluationError> {
+        self.evaluate_precise_in(&PreciseContext {
+            variables,
+            memory_references,
+            functions,
+            precision,
+        })
+        .map(PreciseValue)
+    }
+
+    fn evaluate_precise_in(&self, context: &PreciseContext) -> Result<Complex, EvaluationError> {
+        use Expression::*;
+
+        let precision = context.precision;
+        match self {
+            FunctionCall {
+                function,
+                expression,
+            } => {
+                let argument = expression.evaluate_precise_in(context)?;
+                calculate_function(function, argument, context)
+            }
+            Infix {
+                left,
+                operator,
+                right,
+            } => {
+                // Both operands are evaluated even if one fails, to report every unbound value.
+                match (
+                    left.evaluate_precise_in(context),
+                    right.evaluate_precise_in(context),
+                ) {
+                    (Ok(left), Ok(right)) => Ok(match operator {
+                        InfixOperator::Caret => Complex::with_val(precision, left.pow(&right)),
+                        InfixOperator::Plus => Complex::with_val(precision, left + &right),
+                        InfixOperator::Minus => Complex::with_val(precision, left - &right),
+                        InfixOperator::Slash => Complex::with_val(precision, left / &right),
+                        InfixOperator::Star => Complex::with_val(precision, left * &right),
+                    }),
+                    (Err(left), Err(right)) => Err(left.combine(right, self)),
+                    (Err(error), Ok(_)) | (Ok(_), Err(error)) => Err(error),
+                }
+            }
+            Prefix {
+                operator,
+                expression,
+            } => {
+                let value = expression.evaluate_precise_in(context)?;
+                match operator {
+                    PrefixOperator::Minus => Ok(-value),
+                    PrefixOperator::Plus => Ok(value),
+                }
+            }
+            Variable(identifier) => match context.variables.get(identifier.as_str()) {
+                Some(value) => Ok(Complex::with_val(precision, (value.re, value.im))),
+                None => Err(EvaluationError::unbound(self)),
+            },
+            Address(memory_reference) => context
+                .memory_references
+                .get(memory_reference.name.as_str())
+                .and_then(|values| values.get(memory_reference.index as usize))
+                .map(|value| Complex::with_val(precision, *value))
+                .ok_or_else(|| EvaluationError::unbound(self)),
+            PiConstant => Ok(Complex::with_val(
+                precision,
+                Float::with_val(precision, Constant::Pi),
+            )),
+            Number(number) => Ok(Complex::with_val(precision, (number.re, number.im))),
+        }
+    }
+}
+
+fn calculate_function(
+    function: &ExpressionFunction,
+    argument: Complex,
+    context: &PreciseContext,
+) -> Result<Complex, EvaluationError> {
+    use ExpressionFunction::*;
+    Ok(match function {
+        AbsoluteValue => argument.abs(),
+        ArcCosine => argument.acos(),
+        ArcSine => argument.asin(),
+        ArcTangent => argument.atan(),
+        Sine => argument.sin(),
+        Cis => {
+            let (sine, cosine) = argument.sin_cos(Complex::new(context.precision));
+            let i = Complex::with_val(context.precision, (0, 1));
+            Complex::with_val(context.precision, cosine + i * sine)
+        }
+        Cosine => argument.cos(),
+        Exponent => argument.exp(),
+        NaturalLogarithm => argument.ln(),
+        SquareRoot => argument.sqrt(),
+        Tangent => argument.tan(),
+        Custom(name) => match context.functions.get(name) {
+            Some(implementation) => {
+                let rounded = PreciseValue(argument).to_complex64();
+                let value = implementation(rounded);
+                Complex::with_val(context.precision, (value.re, value.im))
+            }
+            None => return Err(EvaluationError::UndefinedFunction(name.clone())),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use num_complex::Complex64;
+
+    use crate::expression::{EvaluationError, Expression};
+
+    #[test]
+    fn evaluate_precise() {
+        let mut variables = HashMap::new();
+        variables.insert(String::from("phase"), Complex64::from(0.1));
+        let mut memory_references = HashMap::new();
+        memory_references.insert("theta", vec![0.2]);
+
+        let cases = vec![
+            ("%phase + theta[0]", Complex64::from(0.1 + 0.2)),
+            ("cis(pi)", Complex64::from(-1.0)),
+            ("(2^60 + 1) - 2^60", Complex64::from(1.0)),
+            ("sqrt(-4)", Complex64::new(0.0, 2.0)),
+        ];
+
+        for (input, expected) in cases {
+            let evaluated = Expression::from_str(input)
+                .unwrap()
+                .evaluate_precise(&variables, &memory_references, &HashMap::new(), 256)
+                .unwrap()
+                .to_complex64();
+            assert!(
+                (evaluated - expected).norm() < 1e-15,
+                "{}: {}",
+                input,
+                evaluated
+            );
+        }
+    }
+
+    #[test]
+    fn evaluate_precise_incomplete() {
+        let expression = Expression::from_str("%a + theta[1]").unwrap();
+        let result =
+            expression.evaluate_precise(&HashMap::new(), &HashMap::new(), &HashMap::new(), 64);
+        assert!(matches!(result, Err(EvaluationError::Incomplete { .. })));
+    }
+}
@@ -0,0 +1,211 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [`proptest`] strategies for generating arbitrary expressions, available with the `proptest`
+//! feature.
+//!
+//! # Example
+//!
+//! ```rust
+//! use proptest::prelude::*;
+//! use quil_rs::expression::strategies::{expression_with, ExpressionStrategyConfig, Leaves};
+//!
+//! let config = ExpressionStrategyConfig {
+//!     depth: 3,
+//!     leaves: Leaves {
+//!         addresses: false,
+//!         ..Leaves::default()
+//!     },
+//!     ..ExpressionStrategyConfig::default()
+//! };
+//!
+//! proptest!(|(expression in expression_with(&config))| {
+//!     prop_assert!(expression.depth() <= 4);
+//!     prop_assert!(expression.memory_references().is_empty());
+//! });
+//! ```
+
+use num_complex::Complex64;
+use proptest::prelude::*;
+
+use super::{Expression, ExpressionFunction, InfixOperator, PrefixOperator};
+use crate::instruction::MemoryReference;
+use crate::parser::{lex, Token};
+
+/// Names which expressions read as constants or functions rather than as memory regions.
+const EXPRESSION_KEYWORDS: &[&str] = &[
+    "abs", "acos", "asin", "atan", "cis", "cos", "exp", "i", "ln", "pi", "sin", "sqrt", "tan",
+];
+
+/// Whether the name reads back as itself, rather than as a keyword such as `MEASURE` or `pi`, or
+/// as a number such as `inf`.
+fn is_unreserved(name: &str) -> bool {
+    !EXPRESSION_KEYWORDS.contains(&name)
+        && matches!(
+            lex(name).as_deref(),
+            Ok([token]) if matches!(token.as_token(), Token::Identifier(lexed) if lexed == name)
+        )
+}
+
+/// Which kinds of leaf may appear within generated expressions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Leaves {
+    /// Memory references, such as `theta[0]`.
+    pub addresses: bool,
+    /// Complex numbers, with arbitrary real and imaginary parts including `NaN` and infinities.
+    pub numbers: bool,
+    /// The constant `pi`.
+    pub pi: bool,
+    /// Variables, such as `%theta`.
+    pub variables: bool,
+}
+
+impl Default for Leaves {
+    fn default() -> Self {
+        Self {
+            addresses: true,
+            numbers: true,
+            pi: true,
+            variables: true,
+        }
+    }
+}
+
+/// The shape of generated expressions. See [`proptest::strategy::Strategy::prop_recursive`] for
+/// the meaning of `depth` and `desired_size`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExpressionStrategyConfig {
+    /// The greatest number of levels of operations above the leaves.
+    pub depth: u32,
+    /// The total number of nodes to aim for.
+    pub desired_size: u32,
+    pub leaves: Leaves,
+}
+
+impl Default for ExpressionStrategyConfig {
+    fn default() -> Self {
+        Self {
+            depth: 4,
+            desired_size: 64,
+            leaves: Leaves::default(),
+        }
+    }
+}
+
+/// Generate an arbitrary identifier, as used for variable and memory region names. Reserved
+/// words, such as `DEFCAL`, `MEASURE`, and `pi`, are never generated.
+pub fn identifier() -> impl Strategy<Value = String> {
+    "[a-zA-Z_][a-zA-Z0-9_]{0,7}".prop_filter("reserved word", |name| is_unreserved(name))
+}
+
+/// Generate an arbitrary complex number.
+pub fn complex64() -> impl Strategy<Value = Complex64> {
+    any::<(f64, f64)>().prop_map(|(re, im)| Complex64::new(re, im))
+}
+
+/// Generate an arbitrary memory reference.
+pub fn memory_reference() -> impl Strategy<Value = MemoryReference> {
+    (identifier(), any::<u64>()).prop_map(|(name, index)| MemoryReference { name, index })
+}
+
+/// Generate one of the functions built into Quil.
+pub fn expression_function() -> impl Strategy<Value = ExpressionFunction> {
+    use ExpressionFunction::*;
+    prop_oneof![
+        Just(AbsoluteValue),
+        Just(ArcCosine),
+        Just(ArcSine),
+        Just(ArcTangent),
+        Just(Cis),
+        Just(Cosine),
+        Just(Exponent),
+        Just(NaturalLogarithm),
+        Just(Sine),
+        Just(SquareRoot),
+        Just(Tangent),
+    ]
+}
+
+/// Generate an arbitrary infix operator.
+pub fn infix_operator() -> impl Strategy<Value = InfixOperator> {
+    use InfixOperator::*;
+    prop_oneof![
+        Just(Caret),
+        Just(Plus),
+        Just(Minus),
+        Just(Slash),
+        Just(Star)
+    ]
+}
+
+/// Generate an arbitrary prefix operator.
+pub fn prefix_operator() -> impl Strategy<Value = PrefixOperator> {
+    prop_oneof![Just(PrefixOperator::Plus), Just(PrefixOperator::Minus)]
+}
+
+/// Generate an arbitrary expression using the default [`ExpressionStrategyConfig`].
+pub fn expression() -> BoxedStrategy<Expression> {
+    expression_with(&ExpressionStrategyConfig::default())
+}
+
+/// Generate an arbitrary expression of the given shape.
+///
+/// # Panics
+///
+/// Panics if `config` allows no kind of leaf.
+pub fn expression_with(config: &ExpressionStrategyConfig) -> BoxedStrategy<Expression> {
+    use Expression::*;
+
+    let mut leaves = vec![];
+    if config.leaves.addresses {
+        leaves.push(memory_reference().prop_map(Address).boxed());
+    }
+    if config.leaves.numbers {
+        leaves.push(complex64().prop_map(Number).boxed());
+    }
+    if config.leaves.pi {
+        leaves.push(Just(PiConstant).boxed());
+    }
+    if config.leaves.variables {
+        leaves.push(identifier().prop_map(Variable).boxed());
+    }
+    assert!(
+        !leaves.is_empty(),
+        "expressions must allow at least one kind of leaf"
+    );
+
+    proptest::strategy::Union::new(leaves)
+        .prop_recursive(config.depth, config.desired_size, 2, |expression| {
+            prop_oneof![
+                (expression_function(), expression.clone()).prop_map(|(function, e)| {
+                    FunctionCall {
+                        function,
+                        expression: Box::new(e),
+                    }
+                }),
+                (expression.clone(), infix_operator(), expression.clone()).prop_map(
+                    |(left, operator, right)| Infix {
+                        left: Box::new(left),
+                        operator,
+                        right: Box::new(right),
+                    }
+                ),
+                (prefix_operator(), expression).prop_map(|(operator, e)| Prefix {
+                    operator,
+                    expression: Box::new(e),
+                }),
+            ]
+        })
+        .boxed()
+}
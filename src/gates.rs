@@ -0,0 +1,825 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The standard Quil gates, as defined by the Quil specification's `stdgates.quil`, and the
+//! numeric evaluation of gate matrices.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use num_complex::Complex64;
+use thiserror::Error;
+
+use crate::expression::{EvaluationError, Expression};
+use crate::instruction::{Gate, GateDefinition, GateModifier, GateType};
+
+/// A numeric matrix, as a vector of rows.
+pub type Matrix = Vec<Vec<Complex64>>;
+
+/// A problem in computing the numeric matrix of a gate.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum GateError {
+    #[error("gate {gate} takes {expected} parameters but was given {found}")]
+    ParameterCount {
+        gate: String,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("could not evaluate the matrix of gate {gate}: {source}")]
+    Evaluation {
+        gate: String,
+        source: EvaluationError,
+    },
+
+    #[error(
+        "the matrix of gate {gate} is {rows}x{columns}, which is not a square power of two in size"
+    )]
+    InvalidSize {
+        gate: String,
+        rows: usize,
+        columns: usize,
+    },
+
+    #[error("gate {0} is defined as a matrix, not a permutation")]
+    NotAPermutation(String),
+
+    #[error("the permutation of gate {gate} is invalid: {reason}")]
+    InvalidPermutation { gate: String, reason: String },
+
+    #[error("gate {0} is not defined")]
+    UndefinedGate(String),
+
+    #[error("gate {gate} acts on {expected} qubits but was applied to {found}")]
+    QubitCount {
+        gate: String,
+        expected: usize,
+        found: usize,
+    },
+}
+
+impl GateDefinition {
+    /// Evaluate the gate's matrix for the given parameter values, which are bound to the gate's
+    /// parameters in order. The matrix of a gate defined `AS PERMUTATION` is that described by
+    /// [`GateDefinition::to_permutation`].
+    ///
+    /// Returns an error if the number of values does not match the number of parameters, if an
+    /// entry of the matrix refers to anything other than the gate's parameters, or if the matrix
+    /// is not square with a size of a power of two.
+    ///
+    /// ```rust
+    /// use num_complex::Complex64;
+    /// use quil_rs::gates::standard_gate;
+    ///
+    /// let rx = standard_gate("RX").unwrap();
+    /// let matrix = rx.to_matrix(&[Complex64::from(std::f64::consts::PI)]).unwrap();
+    /// assert!((matrix[0][1] - Complex64::new(0.0, -1.0)).norm() < 1e-12);
+    /// ```
+    pub fn to_matrix(&self, parameters: &[Complex64]) -> Result<Matrix, GateError> {
+        if parameters.len() != self.parameters.len() {
+            return Err(GateError::ParameterCount {
+                gate: self.name.clone(),
+                expected: self.parameters.len(),
+                found: parameters.len(),
+            });
+        }
+
+        if self.r#type == GateType::Permutation {
+            let permutation = self.to_permutation()?;
+            let mut matrix =
+                vec![vec![Complex64::new(0.0, 0.0); permutation.len()]; permutation.len()];
+            for (column, row) in permutation.into_iter().enumerate() {
+                matrix[row][column] = Complex64::new(1.0, 0.0);
+            }
+            return Ok(matrix);
+        }
+
+        let rows = self.matrix.len();
+        if let Some(row) = self.matrix.iter().find(|row| row.len() != rows) {
+            return Err(GateError::InvalidSize {
+                gate: self.name.clone(),
+                rows,
+                columns: row.len(),
+            });
+        }
+        if rows < 2 || !rows.is_power_of_two() {
+            return Err(GateError::InvalidSize {
+                gate: self.name.clone(),
+                rows,
+                columns: rows,
+            });
+        }
+
+        let variables: HashMap<String, Complex64> = self
+            .parameters
+            .iter()
+            .cloned()
+            .zip(parameters.iter().copied())
+            .collect();
+        self.matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|entry| {
+                        entry
+                            .evaluate(&variables, &HashMap::new())
+                            .map_err(|source| GateError::Evaluation {
+                                gate: self.name.clone(),
+                                source,
+                            })
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Return the permutation of a gate defined `AS PERMUTATION`: for each basis state, in
+    /// order, the index of the basis state to which the gate maps it.
+    ///
+    /// Returns an error unless the definition is a single row of distinct integers, numbering a
+    /// power of two (of at least two), each a valid index of that many basis states.
+    ///
+    /// ```rust
+    /// use quil_rs::expression::Expression;
+    /// use quil_rs::instruction::{GateDefinition, GateType};
+    ///
+    /// let cnot = GateDefinition {
+    ///     name: "CNOT".to_owned(),
+    ///     parameters: vec![],
+    ///     matrix: vec![vec![0.0, 1.0, 3.0, 2.0].into_iter().map(Expression::from).collect()],
+    ///     r#type: GateType::Permutation,
+    /// };
+    /// assert_eq!(cnot.to_permutation().unwrap(), vec![0, 1, 3, 2]);
+    /// assert_eq!(cnot.to_matrix(&[]).unwrap()[3][2], 1.0.into());
+    /// ```
+    pub fn to_permutation(&self) -> Result<Vec<usize>, GateError> {
+        if self.r#type != GateType::Permutation {
+            return Err(GateError::NotAPermutation(self.name.clone()));
+        }
+        let invalid = |reason: String| GateError::InvalidPermutation {
+            gate: self.name.clone(),
+            reason,
+        };
+
+        let entries = match self.matrix.as_slice() {
+            [entries] => entries,
+            rows => return Err(invalid(format!("it has {} rows, not one", rows.len()))),
+        };
+        let size = entries.len();
+        if size < 2 || !size.is_power_of_two() {
+            return Err(invalid(format!(
+                "it has {} entries, which is not a power of two",
+                size
+            )));
+        }
+
+        let mut seen = vec![false; size];
+        entries
+            .iter()
+            .map(|entry| {
+                let index = entry
+                    .evaluate(&HashMap::new(), &HashMap::new())
+                    .ok()
+                    .filter(|value| {
+                        value.im == 0.0
+                            && value.re >= 0.0
+                            && value.re.fract() == 0.0
+                            && value.re < size as f64
+                    })
+                    .map(|value| value.re as usize)
+                    .ok_or_else(|| invalid(format!("{} is not an index below {}", entry, size)))?;
+                if std::mem::replace(&mut seen[index], true) {
+                    return Err(invalid(format!("{} appears more than once", index)));
+                }
+                Ok(index)
+            })
+            .collect()
+    }
+}
+
+/// Return the identity matrix of the given size.
+pub fn identity(size: usize) -> Matrix {
+    (0..size)
+        .map(|row| {
+            (0..size)
+                .map(|column| {
+                    if row == column {
+                        Complex64::new(1.0, 0.0)
+                    } else {
+                        Complex64::new(0.0, 0.0)
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Return the conjugate transpose of a square matrix.
+pub fn conjugate_transpose(matrix: &Matrix) -> Matrix {
+    (0..matrix.len())
+        .map(|row| matrix.iter().map(|other| other[row].conj()).collect())
+        .collect()
+}
+
+/// Return the square matrix with `upper` and `lower` along its diagonal, and zeros elsewhere.
+/// This applies `upper` to the remaining qubits when a new, most significant qubit is 0, and
+/// `lower` when it is 1.
+fn block_diagonal(upper: &Matrix, lower: &Matrix) -> Matrix {
+    let size = upper.len();
+    let zeros = vec![Complex64::new(0.0, 0.0); size];
+    upper
+        .iter()
+        .map(|row| row.iter().chain(&zeros).copied().collect())
+        .chain(
+            lower
+                .iter()
+                .map(|row| zeros.iter().chain(row).copied().collect()),
+        )
+        .collect()
+}
+
+impl Gate {
+    /// Compute the unitary matrix of the gate, with its modifiers applied, over its qubits in the
+    /// order given: the first qubit is the most significant in indexing the matrix's rows and
+    /// columns, as for the matrix of a `DEFGATE`.
+    ///
+    /// The gate is looked up by name among `definitions`, and then among the
+    /// [standard gates](standard_gates). Its parameters must evaluate to numbers.
+    ///
+    /// Modifiers apply from the innermost (the last written) outward. `CONTROLLED` adds a control
+    /// qubit, and `FORKED` a qubit which selects between the first and second halves of the
+    /// parameters, each ahead of the qubits of the gate it modifies. `DAGGER` takes the inverse.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::{gates::standard_gate, instruction::Instruction, Program};
+    ///
+    /// let program = Program::from_str("CONTROLLED X 0 1").unwrap();
+    /// let gate = match &program.instructions[0] {
+    ///     Instruction::Gate(gate) => gate,
+    ///     _ => unreachable!(),
+    /// };
+    /// let cnot = standard_gate("CNOT").unwrap().to_matrix(&[]).unwrap();
+    /// assert_eq!(gate.to_unitary(&[]).unwrap(), cnot);
+    /// ```
+    pub fn to_unitary(&self, definitions: &[GateDefinition]) -> Result<Matrix, GateError> {
+        let definition = match definitions
+            .iter()
+            .find(|definition| definition.name == self.name)
+        {
+            Some(definition) => definition.clone(),
+            None => standard_gate(&self.name)
+                .ok_or_else(|| GateError::UndefinedGate(self.name.clone()))?,
+        };
+
+        let parameters = self
+            .parameters
+            .iter()
+            .map(|parameter| {
+                parameter
+                    .evaluate(&HashMap::new(), &HashMap::new())
+                    .map_err(|source| GateError::Evaluation {
+                        gate: self.name.clone(),
+                        source,
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let matrix = self.apply_modifiers(&self.modifiers, &parameters, &definition)?;
+        let expected = matrix.len().trailing_zeros() as usize;
+        if self.qubits.len() != expected {
+            return Err(GateError::QubitCount {
+                gate: self.name.clone(),
+                expected,
+                found: self.qubits.len(),
+            });
+        }
+        Ok(matrix)
+    }
+
+    /// Compute the matrix of the definition with the given modifiers, outermost first, applied.
+    fn apply_modifiers(
+        &self,
+        modifiers: &[GateModifier],
+        parameters: &[Complex64],
+        definition: &GateDefinition,
+    ) -> Result<Matrix, GateError> {
+        let (modifier, inner) = match modifiers.split_first() {
+            Some(split) => split,
+            None => return definition.to_matrix(parameters),
+        };
+
+        match modifier {
+            GateModifier::Dagger => Ok(conjugate_transpose(
+                &self.apply_modifiers(inner, parameters, definition)?,
+            )),
+            GateModifier::Controlled => {
+                let target = self.apply_modifiers(inner, parameters, definition)?;
+                Ok(block_diagonal(&identity(target.len()), &target))
+            }
+            GateModifier::Forked => {
+                // Each `FORKED`, this one included, doubles the parameters of the gate it wraps.
+                let forks = modifiers
+                    .iter()
+                    .filter(|modifier| **modifier == GateModifier::Forked)
+                    .count();
+                let expected = (0..forks)
+                    .try_fold(definition.parameters.len(), |count, _| count.checked_mul(2))
+                    .unwrap_or(usize::MAX);
+                if parameters.len() != expected {
+                    return Err(GateError::ParameterCount {
+                        gate: self.name.clone(),
+                        expected,
+                        found: parameters.len(),
+                    });
+                }
+                let (first, second) = parameters.split_at(parameters.len() / 2);
+                Ok(block_diagonal(
+                    &self.apply_modifiers(inner, first, definition)?,
+                    &self.apply_modifiers(inner, second, definition)?,
+                ))
+            }
+        }
+    }
+}
+
+/// The name, parameters, and matrix of a standard gate. Each matrix entry is written as a Quil
+/// expression, in which each parameter is a variable.
+type StandardGate = (
+    &'static str,
+    &'static [&'static str],
+    &'static [&'static [&'static str]],
+);
+
+/// Each of the standard gates.
+const STANDARD_GATES: &[StandardGate] = &[
+    ("I", &[], &[&["1", "0"], &["0", "1"]]),
+    ("X", &[], &[&["0", "1"], &["1", "0"]]),
+    ("Y", &[], &[&["0", "-1i"], &["1i", "0"]]),
+    ("Z", &[], &[&["1", "0"], &["0", "-1"]]),
+    (
+        "H",
+        &[],
+        &[&["1/sqrt(2)", "1/sqrt(2)"], &["1/sqrt(2)", "-1/sqrt(2)"]],
+    ),
+    ("S", &[], &[&["1", "0"], &["0", "1i"]]),
+    ("T", &[], &[&["1", "0"], &["0", "(1+1i)/sqrt(2)"]]),
+    ("PHASE", &["theta"], &[&["1", "0"], &["0", "cis(%theta)"]]),
+    (
+        "RX",
+        &["theta"],
+        &[
+            &["cos(%theta/2)", "-1i*sin(%theta/2)"],
+            &["-1i*sin(%theta/2)", "cos(%theta/2)"],
+        ],
+    ),
+    (
+        "RY",
+        &["theta"],
+        &[
+            &["cos(%theta/2)", "-sin(%theta/2)"],
+            &["sin(%theta/2)", "cos(%theta/2)"],
+        ],
+    ),
+    (
+        "RZ",
+        &["theta"],
+        &[&["cis(-%theta/2)", "0"], &["0", "cis(%theta/2)"]],
+    ),
+    (
+        "CZ",
+        &[],
+        &[
+            &["1", "0", "0", "0"],
+            &["0", "1", "0", "0"],
+            &["0", "0", "1", "0"],
+            &["0", "0", "0", "-1"],
+        ],
+    ),
+    (
+        "CNOT",
+        &[],
+        &[
+            &["1", "0", "0", "0"],
+            &["0", "1", "0", "0"],
+            &["0", "0", "0", "1"],
+            &["0", "0", "1", "0"],
+        ],
+    ),
+    (
+        "CCNOT",
+        &[],
+        &[
+            &["1", "0", "0", "0", "0", "0", "0", "0"],
+            &["0", "1", "0", "0", "0", "0", "0", "0"],
+            &["0", "0", "1", "0", "0", "0", "0", "0"],
+            &["0", "0", "0", "1", "0", "0", "0", "0"],
+            &["0", "0", "0", "0", "1", "0", "0", "0"],
+            &["0", "0", "0", "0", "0", "1", "0", "0"],
+            &["0", "0", "0", "0", "0", "0", "0", "1"],
+            &["0", "0", "0", "0", "0", "0", "1", "0"],
+        ],
+    ),
+    (
+        "CPHASE00",
+        &["theta"],
+        &[
+            &["cis(%theta)", "0", "0", "0"],
+            &["0", "1", "0", "0"],
+            &["0", "0", "1", "0"],
+            &["0", "0", "0", "1"],
+        ],
+    ),
+    (
+        "CPHASE01",
+        &["theta"],
+        &[
+            &["1", "0", "0", "0"],
+            &["0", "cis(%theta)", "0", "0"],
+            &["0", "0", "1", "0"],
+            &["0", "0", "0", "1"],
+        ],
+    ),
+    (
+        "CPHASE10",
+        &["theta"],
+        &[
+            &["1", "0", "0", "0"],
+            &["0", "1", "0", "0"],
+            &["0", "0", "cis(%theta)", "0"],
+            &["0", "0", "0", "1"],
+        ],
+    ),
+    (
+        "CPHASE",
+        &["theta"],
+        &[
+            &["1", "0", "0", "0"],
+            &["0", "1", "0", "0"],
+            &["0", "0", "1", "0"],
+            &["0", "0", "0", "cis(%theta)"],
+        ],
+    ),
+    (
+        "SWAP",
+        &[],
+        &[
+            &["1", "0", "0", "0"],
+            &["0", "0", "1", "0"],
+            &["0", "1", "0", "0"],
+            &["0", "0", "0", "1"],
+        ],
+    ),
+    (
+        "CSWAP",
+        &[],
+        &[
+            &["1", "0", "0", "0", "0", "0", "0", "0"],
+            &["0", "1", "0", "0", "0", "0", "0", "0"],
+            &["0", "0", "1", "0", "0", "0", "0", "0"],
+            &["0", "0", "0", "1", "0", "0", "0", "0"],
+            &["0", "0", "0", "0", "1", "0", "0", "0"],
+            &["0", "0", "0", "0", "0", "0", "1", "0"],
+            &["0", "0", "0", "0", "0", "1", "0", "0"],
+            &["0", "0", "0", "0", "0", "0", "0", "1"],
+        ],
+    ),
+    (
+        "ISWAP",
+        &[],
+        &[
+            &["1", "0", "0", "0"],
+            &["0", "0", "1i", "0"],
+            &["0", "1i", "0", "0"],
+            &["0", "0", "0", "1"],
+        ],
+    ),
+    (
+        "PSWAP",
+        &["theta"],
+        &[
+            &["1", "0", "0", "0"],
+            &["0", "0", "cis(%theta)", "0"],
+            &["0", "cis(%theta)", "0", "0"],
+            &["0", "0", "0", "1"],
+        ],
+    ),
+    (
+        "XY",
+        &["theta"],
+        &[
+            &["1", "0", "0", "0"],
+            &["0", "cos(%theta/2)", "1i*sin(%theta/2)", "0"],
+            &["0", "1i*sin(%theta/2)", "cos(%theta/2)", "0"],
+            &["0", "0", "0", "1"],
+        ],
+    ),
+];
+
+fn build(name: &str, parameters: &[&str], matrix: &[&[&str]]) -> GateDefinition {
+    GateDefinition {
+        name: name.to_owned(),
+        parameters: parameters
+            .iter()
+            .map(|parameter| (*parameter).to_owned())
+            .collect(),
+        matrix: matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|entry| {
+                        Expression::from_str(entry)
+                            .expect("each standard gate matrix entry is a valid expression")
+                            .into_simplified()
+                    })
+                    .collect()
+            })
+            .collect(),
+        r#type: GateType::Matrix,
+    }
+}
+
+/// Return the definitions of the standard Quil gates: `I`, `X`, `Y`, `Z`, `H`, `S`, `T`,
+/// `PHASE`, `RX`, `RY`, `RZ`, `CZ`, `CNOT`, `CCNOT`, `CPHASE00`, `CPHASE01`, `CPHASE10`,
+/// `CPHASE`, `SWAP`, `CSWAP`, `ISWAP`, `PSWAP`, and `XY`.
+///
+/// Each is defined by its matrix, in which each gate parameter appears as a variable of the same
+/// name, as in `cos(%theta/2)`.
+pub fn standard_gates() -> Vec<GateDefinition> {
+    STANDARD_GATES
+        .iter()
+        .map(|(name, parameters, matrix)| build(name, parameters, matrix))
+        .collect()
+}
+
+/// Return the definition of the standard gate with the given name, if there is one.
+///
+/// ```rust
+/// use quil_rs::gates::standard_gate;
+///
+/// let rx = standard_gate("RX").unwrap();
+/// assert_eq!(rx.parameters, vec!["theta"]);
+/// assert_eq!(rx.matrix.len(), 2);
+/// assert!(standard_gate("FOO").is_none());
+/// ```
+pub fn standard_gate(name: &str) -> Option<GateDefinition> {
+    STANDARD_GATES
+        .iter()
+        .find(|(gate_name, _, _)| *gate_name == name)
+        .map(|(name, parameters, matrix)| build(name, parameters, matrix))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::expression::{EvaluationError, Expression};
+    use crate::instruction::{Gate, GateDefinition, GateType, Instruction};
+    use crate::{imag, real};
+
+    use super::{identity, standard_gate, standard_gates, GateError, Matrix};
+
+    fn assert_close(actual: &Matrix, expected: &Matrix) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual_row, expected_row) in actual.iter().zip(expected) {
+            for (actual, expected) in actual_row.iter().zip(expected_row) {
+                assert!(
+                    (actual - expected).norm() < 1e-12,
+                    "{:?} != {:?}",
+                    actual_row,
+                    expected_row
+                );
+            }
+        }
+    }
+
+    /// Check that each standard gate's matrix evaluates to a unitary for a sample parameter value.
+    #[test]
+    fn standard_gates_are_unitary() {
+        for gate in standard_gates() {
+            let parameters = vec![real!(0.7); gate.parameters.len()];
+            let matrix = gate.to_matrix(&parameters).unwrap();
+            let size = matrix.len();
+
+            let product: Matrix = (0..size)
+                .map(|i| {
+                    (0..size)
+                        .map(|j| (0..size).map(|k| matrix[k][i].conj() * matrix[k][j]).sum())
+                        .collect()
+                })
+                .collect();
+            let identity: Matrix = (0..size)
+                .map(|i| {
+                    (0..size)
+                        .map(|j| if i == j { real!(1.0) } else { real!(0.0) })
+                        .collect()
+                })
+                .collect();
+            assert_close(&product, &identity);
+        }
+    }
+
+    #[test]
+    fn to_matrix() {
+        let phase = standard_gate("PHASE").unwrap();
+        assert_close(
+            &phase
+                .to_matrix(&[real!(std::f64::consts::FRAC_PI_2)])
+                .unwrap(),
+            &vec![vec![real!(1.0), real!(0.0)], vec![real!(0.0), imag!(1.0)]],
+        );
+        assert_eq!(
+            phase.to_matrix(&[]),
+            Err(GateError::ParameterCount {
+                gate: "PHASE".to_owned(),
+                expected: 1,
+                found: 0
+            })
+        );
+
+        let unbound = GateDefinition {
+            name: "U".to_owned(),
+            parameters: vec!["theta".to_owned()],
+            matrix: vec![
+                vec![
+                    Expression::from_str("%theta").unwrap(),
+                    Expression::from_str("%phi").unwrap(),
+                ],
+                vec![
+                    Expression::from_str("0").unwrap(),
+                    Expression::from_str("1").unwrap(),
+                ],
+            ],
+            r#type: GateType::Matrix,
+        };
+        assert!(matches!(
+            unbound.to_matrix(&[real!(1.0)]),
+            Err(GateError::Evaluation {
+                source: EvaluationError::Incomplete { .. },
+                ..
+            })
+        ));
+
+        let mut wrong_size = standard_gate("X").unwrap();
+        wrong_size.matrix.pop();
+        assert!(matches!(
+            wrong_size.to_matrix(&[]),
+            Err(GateError::InvalidSize {
+                rows: 1,
+                columns: 2,
+                ..
+            })
+        ));
+    }
+
+    fn gate(text: &str) -> Gate {
+        match Instruction::parse(text).unwrap() {
+            Instruction::Gate(gate) => gate,
+            other => panic!("expected a gate, found {}", other),
+        }
+    }
+
+    #[test]
+    fn to_unitary() {
+        let x = standard_gate("X").unwrap().to_matrix(&[]).unwrap();
+        let cnot = standard_gate("CNOT").unwrap().to_matrix(&[]).unwrap();
+        let ccnot = standard_gate("CCNOT").unwrap().to_matrix(&[]).unwrap();
+        assert_close(&gate("X 0").to_unitary(&[]).unwrap(), &x);
+        assert_close(&gate("CONTROLLED X 0 1").to_unitary(&[]).unwrap(), &cnot);
+        assert_close(
+            &gate("CONTROLLED CONTROLLED X 0 1 2")
+                .to_unitary(&[])
+                .unwrap(),
+            &ccnot,
+        );
+
+        let s = standard_gate("S").unwrap().to_matrix(&[]).unwrap();
+        let s_dagger = vec![vec![real!(1.0), real!(0.0)], vec![real!(0.0), imag!(-1.0)]];
+        assert_close(&gate("DAGGER S 0").to_unitary(&[]).unwrap(), &s_dagger);
+        assert_close(&gate("DAGGER DAGGER S 0").to_unitary(&[]).unwrap(), &s);
+
+        // FORKED PHASE(0, pi) applies PHASE(0), the identity, when its first qubit is 0, and
+        // PHASE(pi), which is Z, when it is 1: that is, CZ.
+        let cz = standard_gate("CZ").unwrap().to_matrix(&[]).unwrap();
+        assert_close(
+            &gate("FORKED PHASE(0, pi) 0 1").to_unitary(&[]).unwrap(),
+            &cz,
+        );
+        assert_close(&gate("PHASE(0) 0").to_unitary(&[]).unwrap(), &identity(2));
+
+        let definitions = vec![GateDefinition {
+            name: "MYX".to_owned(),
+            parameters: vec![],
+            matrix: standard_gate("X").unwrap().matrix,
+            r#type: GateType::Matrix,
+        }];
+        assert_close(&gate("MYX 0").to_unitary(&definitions).unwrap(), &x);
+
+        assert_eq!(
+            gate("FOO 0").to_unitary(&[]),
+            Err(GateError::UndefinedGate("FOO".to_owned()))
+        );
+        assert_eq!(
+            gate("CONTROLLED X 0").to_unitary(&[]),
+            Err(GateError::QubitCount {
+                gate: "X".to_owned(),
+                expected: 2,
+                found: 1
+            })
+        );
+        assert_eq!(
+            gate("FORKED RX(pi) 0 1").to_unitary(&[]),
+            Err(GateError::ParameterCount {
+                gate: "RX".to_owned(),
+                expected: 2,
+                found: 1
+            })
+        );
+        let forked = format!(
+            "{}RX(pi) {}",
+            "FORKED ".repeat(64),
+            (0..65)
+                .map(|qubit| qubit.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        assert!(matches!(
+            gate(&forked).to_unitary(&[]),
+            Err(GateError::ParameterCount { .. })
+        ));
+        assert_eq!(
+            gate("FORKED FORKED RX(0, pi) 0 1 2").to_unitary(&[]),
+            Err(GateError::ParameterCount {
+                gate: "RX".to_owned(),
+                expected: 4,
+                found: 2
+            })
+        );
+        assert!(matches!(
+            gate("RX(theta[0]) 0").to_unitary(&[]),
+            Err(GateError::Evaluation { .. })
+        ));
+    }
+
+    fn permutation(name: &str, entries: &[f64]) -> GateDefinition {
+        GateDefinition {
+            name: name.to_owned(),
+            parameters: vec![],
+            matrix: vec![entries.iter().copied().map(Expression::from).collect()],
+            r#type: GateType::Permutation,
+        }
+    }
+
+    #[test]
+    fn permutation_gates() {
+        let ccnot = permutation("CCNOT", &[0.0, 1.0, 2.0, 3.0, 4.0, 5.0, 7.0, 6.0]);
+        assert_eq!(
+            ccnot.to_permutation().unwrap(),
+            vec![0, 1, 2, 3, 4, 5, 7, 6]
+        );
+        assert_close(
+            &ccnot.to_matrix(&[]).unwrap(),
+            &standard_gate("CCNOT").unwrap().to_matrix(&[]).unwrap(),
+        );
+
+        // A cyclic shift, which is not its own inverse, maps each basis state j to p[j].
+        let shift = permutation("SHIFT", &[1.0, 2.0, 3.0, 0.0]);
+        let matrix = shift.to_matrix(&[]).unwrap();
+        assert_eq!(matrix[1][0], real!(1.0));
+        assert_eq!(matrix[0][3], real!(1.0));
+
+        let invalid = [
+            permutation("SHORT", &[0.0]),
+            permutation("ODD", &[0.0, 1.0, 2.0]),
+            permutation("REPEATED", &[0.0, 1.0, 1.0, 2.0]),
+            permutation("OUT_OF_RANGE", &[0.0, 1.0, 2.0, 4.0]),
+            permutation("FRACTIONAL", &[0.0, 1.5]),
+        ];
+        for gate in invalid.iter() {
+            assert!(
+                matches!(
+                    gate.to_permutation(),
+                    Err(GateError::InvalidPermutation { .. })
+                ),
+                "{}",
+                gate.name
+            );
+        }
+
+        assert_eq!(
+            standard_gate("X").unwrap().to_permutation(),
+            Err(GateError::NotAPermutation("X".to_owned()))
+        );
+    }
+}
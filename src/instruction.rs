@@ -12,16 +12,25 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::{collections::HashMap, fmt};
+use thiserror::Error;
 
-use crate::expression::Expression;
+use crate::expression::{EvaluationError, Expression};
 use crate::program::frame::FrameMatchCondition;
 
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum ArithmeticOperand {
     LiteralInteger(i64),
     LiteralReal(f64),
@@ -39,10 +48,15 @@ impl fmt::Display for ArithmeticOperand {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ArithmeticOperator {
+    #[cfg_attr(feature = "serde", serde(rename = "ADD"))]
     Add,
+    #[cfg_attr(feature = "serde", serde(rename = "SUB"))]
     Subtract,
+    #[cfg_attr(feature = "serde", serde(rename = "DIV"))]
     Divide,
+    #[cfg_attr(feature = "serde", serde(rename = "MUL"))]
     Multiply,
 }
 
@@ -58,6 +72,11 @@ impl fmt::Display for ArithmeticOperator {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum BinaryOperand {
     LiteralInteger(i64),
     MemoryReference(MemoryReference),
@@ -73,9 +92,13 @@ impl fmt::Display for BinaryOperand {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum BinaryOperator {
+    #[cfg_attr(feature = "serde", serde(rename = "AND"))]
     And,
+    #[cfg_attr(feature = "serde", serde(rename = "IOR"))]
     Ior,
+    #[cfg_attr(feature = "serde", serde(rename = "XOR"))]
     Xor,
 }
 impl fmt::Display for BinaryOperator {
@@ -89,8 +112,11 @@ impl fmt::Display for BinaryOperator {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum UnaryOperator {
+    #[cfg_attr(feature = "serde", serde(rename = "NEG"))]
     Neg,
+    #[cfg_attr(feature = "serde", serde(rename = "NOT"))]
     Not,
 }
 
@@ -104,6 +130,11 @@ impl fmt::Display for UnaryOperator {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum ComparisonOperand {
     LiteralInteger(i64),
     LiteralReal(f64),
@@ -121,11 +152,17 @@ impl fmt::Display for ComparisonOperand {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ComparisonOperator {
+    #[cfg_attr(feature = "serde", serde(rename = "EQ"))]
     Equal,
+    #[cfg_attr(feature = "serde", serde(rename = "GE"))]
     GreaterThanOrEqual,
+    #[cfg_attr(feature = "serde", serde(rename = "GT"))]
     GreaterThan,
+    #[cfg_attr(feature = "serde", serde(rename = "LE"))]
     LessThanOrEqual,
+    #[cfg_attr(feature = "serde", serde(rename = "LT"))]
     LessThan,
 }
 
@@ -142,6 +179,11 @@ impl fmt::Display for ComparisonOperator {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum AttributeValue {
     String(String),
     Expression(Expression),
@@ -151,15 +193,258 @@ impl fmt::Display for AttributeValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use AttributeValue::*;
         match self {
-            String(value) => write!(f, "\"{}\"", value),
+            String(value) => write!(f, "{}", format_string(value)),
             Expression(value) => write!(f, "{}", value),
         }
     }
 }
 
-pub type FrameAttributes = HashMap<String, AttributeValue>;
+/// The attributes of a frame, as written in its `DEFFRAME`. See [`FrameAttributes`] for their
+/// typed values.
+pub type RawFrameAttributes = HashMap<String, AttributeValue>;
+
+/// A problem with the attributes of a frame, as found by [`FrameAttributes::from_raw`].
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum FrameAttributeError {
+    #[error("{0} is not a known frame attribute")]
+    UnknownAttribute(String),
+
+    #[error("frame attribute {0} must be a string")]
+    ExpectedString(String),
+
+    #[error("frame attribute {attribute} must be a real number: {source}")]
+    ExpectedNumber {
+        attribute: String,
+        source: EvaluationError,
+    },
+
+    #[error("frame attribute {attribute} is invalid: {reason}")]
+    Invalid { attribute: String, reason: String },
+}
+
+/// Whether a frame transmits or receives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrameDirection {
+    Transmit,
+    Receive,
+}
+
+impl fmt::Display for FrameDirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameDirection::Transmit => write!(f, "tx"),
+            FrameDirection::Receive => write!(f, "rx"),
+        }
+    }
+}
+
+/// The standard attributes of a frame, with their values as numbers in Hertz or seconds, or as
+/// strings. Each is `None` when not given.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameAttributes {
+    /// `SAMPLE-RATE`, in Hertz.
+    pub sample_rate: Option<f64>,
+    /// `INITIAL-FREQUENCY`, in Hertz.
+    pub initial_frequency: Option<f64>,
+    /// `CENTER-FREQUENCY`, in Hertz.
+    pub center_frequency: Option<f64>,
+    /// `BANDWIDTH`, in Hertz: the width of the band, around the center frequency, within which
+    /// the frame's frequency may be set.
+    pub bandwidth: Option<f64>,
+    /// `DIRECTION`, either `"tx"` or `"rx"`.
+    pub direction: Option<FrameDirection>,
+    /// `HARDWARE-OBJECT`, which names the hardware on which the frame is played.
+    pub hardware_object: Option<String>,
+    /// `CHANNEL-DELAY`, in seconds.
+    pub channel_delay: Option<f64>,
+}
+
+impl FrameAttributes {
+    pub const SAMPLE_RATE: &'static str = "SAMPLE-RATE";
+    pub const INITIAL_FREQUENCY: &'static str = "INITIAL-FREQUENCY";
+    pub const CENTER_FREQUENCY: &'static str = "CENTER-FREQUENCY";
+    pub const BANDWIDTH: &'static str = "BANDWIDTH";
+    pub const DIRECTION: &'static str = "DIRECTION";
+    pub const HARDWARE_OBJECT: &'static str = "HARDWARE-OBJECT";
+    pub const CHANNEL_DELAY: &'static str = "CHANNEL-DELAY";
+
+    /// Read the typed values of the given attributes. Fails if any attribute is not one of the
+    /// standard attributes, or has a value of the wrong type: rates, frequencies, and bandwidths
+    /// must be positive real numbers, the delay a non-negative one, and the direction and hardware object
+    /// strings.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::instruction::{FrameAttributes, FrameDirection};
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DEFFRAME 0 \"rf\":\n    SAMPLE-RATE: 1e9\n    DIRECTION: \"tx\"",
+    /// )
+    /// .unwrap();
+    /// let (_, attributes) = program.frames.iter().next().unwrap();
+    /// let attributes = FrameAttributes::from_raw(attributes).unwrap();
+    /// assert_eq!(attributes.sample_rate, Some(1e9));
+    /// assert_eq!(attributes.direction, Some(FrameDirection::Transmit));
+    /// assert_eq!(attributes.center_frequency, None);
+    /// ```
+    pub fn from_raw(raw: &RawFrameAttributes) -> Result<Self, FrameAttributeError> {
+        let mut attributes = Self::default();
+        let mut keys: Vec<&String> = raw.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            let value = &raw[key];
+            match key.as_str() {
+                Self::SAMPLE_RATE => attributes.sample_rate = Some(positive(key, value)?),
+                Self::INITIAL_FREQUENCY => {
+                    attributes.initial_frequency = Some(positive(key, value)?)
+                }
+                Self::CENTER_FREQUENCY => attributes.center_frequency = Some(positive(key, value)?),
+                Self::BANDWIDTH => attributes.bandwidth = Some(positive(key, value)?),
+                Self::CHANNEL_DELAY => {
+                    let delay = real(key, value)?;
+                    if delay < 0.0 {
+                        return Err(FrameAttributeError::Invalid {
+                            attribute: key.clone(),
+                            reason: "must not be negative".to_owned(),
+                        });
+                    }
+                    attributes.channel_delay = Some(delay);
+                }
+                Self::DIRECTION => {
+                    attributes.direction = Some(match string(key, value)? {
+                        "tx" => FrameDirection::Transmit,
+                        "rx" => FrameDirection::Receive,
+                        other => {
+                            return Err(FrameAttributeError::Invalid {
+                                attribute: key.clone(),
+                                reason: format!("expected \"tx\" or \"rx\", found \"{}\"", other),
+                            })
+                        }
+                    })
+                }
+                Self::HARDWARE_OBJECT => {
+                    attributes.hardware_object = Some(string(key, value)?.to_owned())
+                }
+                _ => return Err(FrameAttributeError::UnknownAttribute(key.clone())),
+            }
+        }
+
+        Ok(attributes)
+    }
+
+    /// Parse `HARDWARE-OBJECT` as JSON, into [`serde_json::Value`] or any other type which may be
+    /// deserialized from it. Returns `None` if there is no hardware object.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::instruction::FrameAttributes;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     r#"DEFFRAME 0 "rf":
+    ///     HARDWARE-OBJECT: "{\"instrument\": \"tsunami\", \"channel\": 2}""#,
+    /// )
+    /// .unwrap();
+    /// let (_, attributes) = program.frames.iter().next().unwrap();
+    /// let hardware_object: serde_json::Value = FrameAttributes::from_raw(attributes)
+    ///     .unwrap()
+    ///     .parse_hardware_object()
+    ///     .unwrap()
+    ///     .unwrap();
+    /// assert_eq!(hardware_object["channel"], 2);
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn parse_hardware_object<T: DeserializeOwned>(
+        &self,
+    ) -> Result<Option<T>, FrameAttributeError> {
+        self.hardware_object
+            .as_deref()
+            .map(|json| {
+                serde_json::from_str(json).map_err(|error| FrameAttributeError::Invalid {
+                    attribute: Self::HARDWARE_OBJECT.to_owned(),
+                    reason: error.to_string(),
+                })
+            })
+            .transpose()
+    }
+
+    /// Return the attributes as they would be written in a `DEFFRAME`, omitting those which are
+    /// `None`.
+    pub fn to_raw(&self) -> RawFrameAttributes {
+        let numbers = vec![
+            (Self::SAMPLE_RATE, self.sample_rate),
+            (Self::INITIAL_FREQUENCY, self.initial_frequency),
+            (Self::CENTER_FREQUENCY, self.center_frequency),
+            (Self::BANDWIDTH, self.bandwidth),
+            (Self::CHANNEL_DELAY, self.channel_delay),
+        ];
+        let strings = vec![
+            (
+                Self::DIRECTION,
+                self.direction.map(|direction| direction.to_string()),
+            ),
+            (Self::HARDWARE_OBJECT, self.hardware_object.clone()),
+        ];
+
+        numbers
+            .into_iter()
+            .filter_map(|(key, value)| {
+                value.map(|value| {
+                    (
+                        key.to_owned(),
+                        AttributeValue::Expression(Expression::from(value)),
+                    )
+                })
+            })
+            .chain(strings.into_iter().filter_map(|(key, value)| {
+                value.map(|value| (key.to_owned(), AttributeValue::String(value)))
+            }))
+            .collect()
+    }
+}
+
+/// Read a frame attribute which must be a real number.
+fn real(key: &str, value: &AttributeValue) -> Result<f64, FrameAttributeError> {
+    match value {
+        AttributeValue::Expression(expression) => expression
+            .evaluate(&HashMap::new(), &HashMap::new())
+            .and_then(|value| Expression::from(value).to_real())
+            .map_err(|source| FrameAttributeError::ExpectedNumber {
+                attribute: key.to_owned(),
+                source,
+            }),
+        AttributeValue::String(_) => Err(FrameAttributeError::ExpectedNumber {
+            attribute: key.to_owned(),
+            source: EvaluationError::NotANumber,
+        }),
+    }
+}
+
+/// Read a frame attribute which must be a positive real number.
+fn positive(key: &str, value: &AttributeValue) -> Result<f64, FrameAttributeError> {
+    let value = real(key, value)?;
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err(FrameAttributeError::Invalid {
+            attribute: key.to_owned(),
+            reason: "must be positive".to_owned(),
+        })
+    }
+}
+
+/// Read a frame attribute which must be a string.
+fn string<'a>(key: &str, value: &'a AttributeValue) -> Result<&'a str, FrameAttributeError> {
+    match value {
+        AttributeValue::String(value) => Ok(value),
+        AttributeValue::Expression(_) => Err(FrameAttributeError::ExpectedString(key.to_owned())),
+    }
+}
 
 #[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Calibration {
     pub instructions: Vec<Instruction>,
     pub modifiers: Vec<GateModifier>,
@@ -176,11 +461,18 @@ pub struct FrameIdentifier {
 
 impl fmt::Display for FrameIdentifier {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} \"{}\"", format_qubits(&self.qubits), self.name)
+        write!(
+            f,
+            "{} {}",
+            format_qubits(&self.qubits),
+            format_string(&self.name)
+        )
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "UPPERCASE"))]
 pub enum GateModifier {
     Controlled,
     Dagger,
@@ -203,6 +495,8 @@ impl fmt::Display for GateModifier {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "UPPERCASE"))]
 pub enum GateType {
     Matrix,
     Permutation,
@@ -223,6 +517,8 @@ impl fmt::Display for GateType {
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "UPPERCASE"))]
 pub enum ScalarType {
     Bit,
     Integer,
@@ -247,6 +543,7 @@ impl fmt::Display for ScalarType {
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Vector {
     pub data_type: ScalarType,
     pub length: u64,
@@ -259,6 +556,7 @@ impl fmt::Display for Vector {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WaveformInvocation {
     pub name: String,
     pub parameters: HashMap<String, Expression>,
@@ -287,12 +585,22 @@ impl fmt::Display for WaveformInvocation {
 }
 
 #[derive(Clone, Debug, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct MemoryReference {
     pub name: String,
     pub index: u64,
 }
 
+impl MemoryReference {
+    pub fn new(name: impl Into<String>, index: u64) -> Self {
+        Self {
+            name: name.into(),
+            index,
+        }
+    }
+}
+
 impl Eq for MemoryReference {}
 
 impl fmt::Display for MemoryReference {
@@ -302,6 +610,7 @@ impl fmt::Display for MemoryReference {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Gate {
     pub name: String,
     pub parameters: Vec<Expression>,
@@ -309,7 +618,112 @@ pub struct Gate {
     pub modifiers: Vec<GateModifier>,
 }
 
+impl Gate {
+    /// Create a gate with the given name and no parameters, qubits, or modifiers, to be completed
+    /// using the builder methods [`Gate::param`], [`Gate::qubit`], [`Gate::modifier`], and
+    /// [`Gate::controlled`].
+    ///
+    /// ```rust
+    /// use quil_rs::{expression::Expression, instruction::{GateModifier, Instruction}};
+    ///
+    /// let gate = Instruction::gate("RX")
+    ///     .param(Expression::PiConstant)
+    ///     .qubit(1)
+    ///     .modifier(GateModifier::Dagger)
+    ///     .controlled(0);
+    /// assert_eq!(Instruction::from(gate).to_string(), "CONTROLLED DAGGER RX(pi) 0 1");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `name` is the keyword of a command, such as `MEASURE` or `NOT`, as the gate would
+    /// then be read back as that command.
+    pub fn new(name: impl Into<String>) -> Self {
+        let name = name.into();
+        assert!(
+            !crate::parser::instruction::is_reserved_gate_name(&name),
+            "gate name {} is reserved for the command of the same name",
+            name
+        );
+        Self {
+            name,
+            parameters: vec![],
+            qubits: vec![],
+            modifiers: vec![],
+        }
+    }
+
+    /// Append a parameter to the gate.
+    pub fn param(mut self, parameter: impl Into<Expression>) -> Self {
+        self.parameters.push(parameter.into());
+        self
+    }
+
+    /// Append a qubit to the gate.
+    pub fn qubit(mut self, qubit: impl Into<Qubit>) -> Self {
+        self.qubits.push(qubit.into());
+        self
+    }
+
+    /// Prepend a modifier to the gate, making it the outermost. A `CONTROLLED` or `FORKED`
+    /// modifier also requires its own leading qubit(s); see [`Gate::controlled`].
+    pub fn modifier(mut self, modifier: GateModifier) -> Self {
+        self.modifiers.insert(0, modifier);
+        self
+    }
+
+    /// Prepend a `CONTROLLED` modifier to the gate along with its control qubit.
+    pub fn controlled(mut self, control: impl Into<Qubit>) -> Self {
+        self.qubits.insert(0, control.into());
+        self.modifier(GateModifier::Controlled)
+    }
+
+    /// Return the inverse of this gate. A leading `DAGGER` modifier is removed; self-inverse
+    /// standard gates are returned unchanged; standard rotation and phase gates have their
+    /// parameters negated; any other gate is given a leading `DAGGER` modifier.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::{instruction::Instruction, Program};
+    ///
+    /// let program = Program::from_str("RX(pi/2) 0\nCONTROLLED RZ(%theta) 0 1\nS 0").unwrap();
+    /// let inverses: Vec<String> = program
+    ///     .instructions
+    ///     .into_iter()
+    ///     .map(|instruction| match instruction {
+    ///         Instruction::Gate(gate) => Instruction::Gate(gate.dagger()).to_string(),
+    ///         _ => unreachable!(),
+    ///     })
+    ///     .collect();
+    /// assert_eq!(
+    ///     inverses,
+    ///     vec!["RX(-pi / 2) 0", "CONTROLLED RZ(-%theta) 0 1", "DAGGER S 0"]
+    /// );
+    /// ```
+    pub fn dagger(mut self) -> Self {
+        if self.modifiers.first() == Some(&GateModifier::Dagger) {
+            self.modifiers.remove(0);
+            return self;
+        }
+
+        match self.name.as_str() {
+            "I" | "X" | "Y" | "Z" | "H" | "CNOT" | "CZ" | "SWAP" | "CCNOT" | "CSWAP" => {}
+            "RX" | "RY" | "RZ" | "PHASE" | "CPHASE" | "CPHASE00" | "CPHASE01" | "CPHASE10"
+            | "PSWAP" | "XY" => {
+                for parameter in &mut self.parameters {
+                    let inverted = -std::mem::replace(parameter, Expression::PiConstant);
+                    *parameter = inverted.into_simplified();
+                }
+            }
+            _ => self.modifiers.insert(0, GateModifier::Dagger),
+        }
+
+        self
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CircuitDefinition {
     pub name: String,
     pub parameters: Vec<String>,
@@ -319,6 +733,7 @@ pub struct CircuitDefinition {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct GateDefinition {
     pub name: String,
     pub parameters: Vec<String>,
@@ -327,6 +742,7 @@ pub struct GateDefinition {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Declaration {
     pub name: String,
     pub size: Vector,
@@ -334,17 +750,20 @@ pub struct Declaration {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Measurement {
     pub qubit: Qubit,
     pub target: Option<MemoryReference>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Reset {
     pub qubit: Option<Qubit>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Capture {
     pub blocking: bool,
     pub frame: FrameIdentifier,
@@ -353,6 +772,7 @@ pub struct Capture {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Delay {
     pub duration: Expression,
     pub frame_names: Vec<String>,
@@ -360,17 +780,27 @@ pub struct Delay {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Fence {
     pub qubits: Vec<Qubit>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FrameDefinition {
     pub identifier: FrameIdentifier,
-    pub attributes: HashMap<String, AttributeValue>,
+    pub attributes: RawFrameAttributes,
+}
+
+impl FrameDefinition {
+    /// Return the typed values of the frame's attributes, as by [`FrameAttributes::from_raw`].
+    pub fn typed_attributes(&self) -> Result<FrameAttributes, FrameAttributeError> {
+        FrameAttributes::from_raw(&self.attributes)
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct MeasureCalibrationDefinition {
     pub qubit: Option<Qubit>,
     pub parameter: String,
@@ -378,6 +808,7 @@ pub struct MeasureCalibrationDefinition {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Pragma {
     pub name: String,
     pub arguments: Vec<String>,
@@ -385,6 +816,7 @@ pub struct Pragma {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Pulse {
     pub blocking: bool,
     pub frame: FrameIdentifier,
@@ -392,6 +824,7 @@ pub struct Pulse {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct RawCapture {
     pub blocking: bool,
     pub frame: FrameIdentifier,
@@ -400,48 +833,56 @@ pub struct RawCapture {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SetFrequency {
     pub frame: FrameIdentifier,
     pub frequency: Expression,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SetPhase {
     pub frame: FrameIdentifier,
     pub phase: Expression,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SetScale {
     pub frame: FrameIdentifier,
     pub scale: Expression,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ShiftFrequency {
     pub frame: FrameIdentifier,
     pub frequency: Expression,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ShiftPhase {
     pub frame: FrameIdentifier,
     pub phase: Expression,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct SwapPhases {
     pub frame_1: FrameIdentifier,
     pub frame_2: FrameIdentifier,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct WaveformDefinition {
     pub name: String,
     pub definition: Waveform,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Arithmetic {
     pub operator: ArithmeticOperator,
     pub destination: ArithmeticOperand,
@@ -449,39 +890,141 @@ pub struct Arithmetic {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Comparison {
     pub operator: ComparisonOperator,
     pub operands: (MemoryReference, MemoryReference, ComparisonOperand),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct BinaryLogic {
     pub operator: BinaryOperator,
     pub operands: (MemoryReference, BinaryOperand),
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct UnaryLogic {
     pub operator: UnaryOperator,
     pub operand: MemoryReference,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct Label(pub String);
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Label(pub Target);
+
+/// The label defined by a `LABEL` or targeted by a jump.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
+pub enum Target {
+    Fixed(String),
+    /// A label yet to be named, to be given a unique name with
+    /// [`Program::resolve_label_placeholders`](crate::Program::resolve_label_placeholders)
+    /// before the program is printed or run.
+    Placeholder(LabelPlaceholder),
+}
+
+impl Target {
+    /// Return the name of the label, if it is fixed.
+    pub fn as_fixed(&self) -> Option<&str> {
+        match self {
+            Target::Fixed(name) => Some(name),
+            Target::Placeholder(_) => None,
+        }
+    }
+}
+
+impl From<&str> for Target {
+    fn from(name: &str) -> Self {
+        Target::Fixed(name.to_owned())
+    }
+}
+
+impl From<String> for Target {
+    fn from(name: String) -> Self {
+        Target::Fixed(name)
+    }
+}
+
+impl From<LabelPlaceholder> for Target {
+    fn from(placeholder: LabelPlaceholder) -> Self {
+        Target::Placeholder(placeholder)
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Fixed(name) => write!(f, "{}", name),
+            Target::Placeholder(placeholder) => write!(f, "{}", placeholder),
+        }
+    }
+}
+
+/// An opaque identifier for a label not yet named, so that control flow may be built from
+/// fragments without knowledge of the labels used elsewhere. Each placeholder created with
+/// [`LabelPlaceholder::new`] is distinct from every other, even those with the same base name.
+///
+/// ```rust
+/// use quil_rs::instruction::{LabelPlaceholder, Target};
+///
+/// let (first, second) = (LabelPlaceholder::new("loop"), LabelPlaceholder::new("loop"));
+/// assert_ne!(first, second);
+/// assert_eq!(first.base(), "loop");
+/// assert_eq!(Target::from(first.clone()), Target::Placeholder(first));
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LabelPlaceholder {
+    id: u64,
+    base: String,
+}
+
+impl LabelPlaceholder {
+    /// Create a new placeholder, distinct from all others. It will be named after `base` when
+    /// resolved.
+    pub fn new(base: impl Into<String>) -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            base: base.into(),
+        }
+    }
+
+    /// Return the name on which the label's eventual name will be based.
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+}
+
+/// Like qubit placeholders, label placeholders are printed in a form which is not valid Quil.
+impl fmt::Display for LabelPlaceholder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<placeholder {} {}>", self.id, self.base)
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Move {
     pub destination: ArithmeticOperand,
     pub source: ArithmeticOperand,
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Exchange {
     pub left: ArithmeticOperand,
     pub right: ArithmeticOperand,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Load {
     pub destination: MemoryReference,
     pub source: String,
@@ -489,6 +1032,7 @@ pub struct Load {
 }
 
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Store {
     pub destination: String,
     pub offset: MemoryReference,
@@ -496,23 +1040,57 @@ pub struct Store {
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Jump {
-    pub target: String,
+    pub target: Target,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct JumpWhen {
-    pub target: String,
+    pub target: Target,
     pub condition: MemoryReference,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct JumpUnless {
-    pub target: String,
+    pub target: Target,
     pub condition: MemoryReference,
 }
 
+/// A single Quil instruction.
+///
+/// With the `serde` feature, instructions, and the types within them, serialize in a stable,
+/// tagged form:
+///
+/// * an enum whose variants hold data, such as this one, is an object with the name of the
+///   variant in `snake_case` under `type`, and its contents, if any, under `value`, as for
+///   [`Expression`];
+/// * an operator, gate modifier, or other enum without data is its name in Quil, such as `"ADD"`
+///   or `"DAGGER"`;
+/// * a struct is an object with a field for each of its own.
+///
+/// ```rust
+/// use std::str::FromStr;
+/// use quil_rs::Program;
+///
+/// # #[cfg(feature = "serde")]
+/// # {
+/// let program = Program::from_str("DAGGER RX(pi) 0\nHALT").unwrap();
+/// let json = serde_json::to_value(&program.instructions[1]).unwrap();
+/// assert_eq!(json, serde_json::json!({ "type": "halt" }));
+/// let json = serde_json::to_value(&program.instructions[0]).unwrap();
+/// assert_eq!(json["type"], "gate");
+/// assert_eq!(json["value"]["modifiers"], serde_json::json!(["DAGGER"]));
+/// # }
+/// ```
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "type", content = "value", rename_all = "snake_case")
+)]
 pub enum Instruction {
     Gate(Gate),
     CircuitDefinition(CircuitDefinition),
@@ -551,6 +1129,55 @@ pub enum Instruction {
     JumpUnless(JumpUnless),
 }
 
+macro_rules! impl_from_for_instruction {
+    ($($variant:ident($type:ty)),* $(,)?) => {
+        $(
+            impl From<$type> for Instruction {
+                fn from(inner: $type) -> Self {
+                    Instruction::$variant(inner)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_for_instruction!(
+    Gate(Gate),
+    CircuitDefinition(CircuitDefinition),
+    GateDefinition(GateDefinition),
+    Declaration(Declaration),
+    Measurement(Measurement),
+    Reset(Reset),
+    CalibrationDefinition(Calibration),
+    Capture(Capture),
+    Delay(Delay),
+    Fence(Fence),
+    FrameDefinition(FrameDefinition),
+    MeasureCalibrationDefinition(MeasureCalibrationDefinition),
+    Pragma(Pragma),
+    Pulse(Pulse),
+    RawCapture(RawCapture),
+    SetFrequency(SetFrequency),
+    SetPhase(SetPhase),
+    SetScale(SetScale),
+    ShiftFrequency(ShiftFrequency),
+    ShiftPhase(ShiftPhase),
+    SwapPhases(SwapPhases),
+    WaveformDefinition(WaveformDefinition),
+    Arithmetic(Arithmetic),
+    Comparison(Comparison),
+    BinaryLogic(BinaryLogic),
+    UnaryLogic(UnaryLogic),
+    Label(Label),
+    Move(Move),
+    Exchange(Exchange),
+    Load(Load),
+    Store(Store),
+    Jump(Jump),
+    JumpWhen(JumpWhen),
+    JumpUnless(JumpUnless),
+);
+
 #[derive(Clone, Debug)]
 pub enum InstructionRole {
     ClassicalCompute,
@@ -630,6 +1257,12 @@ pub fn format_matrix(matrix: &[Vec<Expression>]) -> String {
         .join("\n\t")
 }
 
+/// Write a string as a Quil string literal, within double quotes and with each double quote or
+/// backslash escaped by a backslash.
+pub fn format_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
 pub fn format_qubits(qubits: &[Qubit]) -> String {
     qubits
         .iter()
@@ -732,7 +1365,7 @@ impl fmt::Display for Instruction {
             }) => {
                 write!(f, "DELAY {}", format_qubits(qubits))?;
                 for frame_name in frame_names {
-                    write!(f, " \"{}\"", frame_name)?;
+                    write!(f, " {}", format_string(frame_name))?;
                 }
                 write!(f, " {}", duration)
             }
@@ -746,15 +1379,19 @@ impl fmt::Display for Instruction {
             Instruction::FrameDefinition(FrameDefinition {
                 identifier,
                 attributes,
-            }) => write!(
-                f,
-                "DEFFRAME {}:{}",
-                identifier,
-                attributes
-                    .iter()
-                    .map(|(k, v)| format!("\n\t{}: {}", k, v))
-                    .collect::<String>()
-            ),
+            }) => {
+                let mut attributes: Vec<_> = attributes.iter().collect();
+                attributes.sort_by_key(|(key, _)| *key);
+                write!(
+                    f,
+                    "DEFFRAME {}:{}",
+                    identifier,
+                    attributes
+                        .iter()
+                        .map(|(k, v)| format!("\n\t{}: {}", k, v))
+                        .collect::<String>()
+                )
+            }
             Instruction::Gate(Gate {
                 name,
                 parameters,
@@ -856,7 +1493,7 @@ impl fmt::Display for Instruction {
                     write!(f, " {}", arguments.join(" "))?;
                 }
                 if let Some(data) = data {
-                    write!(f, " \"{}\"", data)?;
+                    write!(f, " {}", format_string(data))?;
                 }
                 Ok(())
             }
@@ -971,6 +1608,69 @@ mod test_instruction_display {
 pub enum Qubit {
     Fixed(u64),
     Variable(String),
+    /// A qubit yet to be allocated, to be replaced by a fixed qubit with
+    /// [`Program::resolve_placeholders`](crate::Program::resolve_placeholders) before the program
+    /// is printed or run.
+    Placeholder(QubitPlaceholder),
+}
+
+/// An opaque identifier for a qubit not yet allocated. Each placeholder created with
+/// [`QubitPlaceholder::new`] is distinct from every other.
+///
+/// ```rust
+/// use quil_rs::instruction::{Qubit, QubitPlaceholder};
+///
+/// let (first, second) = (QubitPlaceholder::new(), QubitPlaceholder::new());
+/// assert_ne!(first, second);
+/// assert_eq!(Qubit::Placeholder(first), Qubit::Placeholder(first));
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize, Deserialize)]
+pub struct QubitPlaceholder(u64);
+
+impl QubitPlaceholder {
+    /// Create a new placeholder, distinct from all others.
+    pub fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Default for QubitPlaceholder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Placeholders are printed in a form which is not valid Quil, so that a program in which they
+/// remain cannot be mistaken for one which is ready to run.
+impl fmt::Display for QubitPlaceholder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<placeholder {}>", self.0)
+    }
+}
+
+impl From<u64> for Qubit {
+    fn from(index: u64) -> Self {
+        Qubit::Fixed(index)
+    }
+}
+
+impl From<&str> for Qubit {
+    fn from(name: &str) -> Self {
+        Qubit::Variable(name.to_owned())
+    }
+}
+
+impl From<QubitPlaceholder> for Qubit {
+    fn from(placeholder: QubitPlaceholder) -> Self {
+        Qubit::Placeholder(placeholder)
+    }
+}
+
+impl From<String> for Qubit {
+    fn from(name: String) -> Self {
+        Qubit::Variable(name)
+    }
 }
 
 impl fmt::Display for Qubit {
@@ -979,17 +1679,120 @@ impl fmt::Display for Qubit {
         match self {
             Fixed(value) => write!(f, "{}", value),
             Variable(value) => write!(f, "{}", value),
+            Placeholder(placeholder) => write!(f, "{}", placeholder),
         }
     }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Waveform {
     pub matrix: Vec<Expression>,
     pub parameters: Vec<String>,
 }
 
 impl Instruction {
+    /// Start building a gate; see [`Gate::new`].
+    pub fn gate(name: impl Into<String>) -> Gate {
+        Gate::new(name)
+    }
+
+    /// Create a `MEASURE` of the qubit into the given element of a memory region.
+    ///
+    /// ```rust
+    /// use quil_rs::instruction::Instruction;
+    ///
+    /// assert_eq!(Instruction::measure(0, "ro", 1).to_string(), "MEASURE 0 ro[1]");
+    /// ```
+    pub fn measure(qubit: impl Into<Qubit>, region: impl Into<String>, index: u64) -> Self {
+        Instruction::Measurement(Measurement {
+            qubit: qubit.into(),
+            target: Some(MemoryReference::new(region, index)),
+        })
+    }
+
+    /// Create a `MEASURE` of the qubit which discards its result.
+    pub fn measure_discard(qubit: impl Into<Qubit>) -> Self {
+        Instruction::Measurement(Measurement {
+            qubit: qubit.into(),
+            target: None,
+        })
+    }
+
+    /// Create a `RESET` of a single qubit.
+    pub fn reset(qubit: impl Into<Qubit>) -> Self {
+        Instruction::Reset(Reset {
+            qubit: Some(qubit.into()),
+        })
+    }
+
+    /// Create a `RESET` of all qubits.
+    pub fn reset_all() -> Self {
+        Instruction::Reset(Reset { qubit: None })
+    }
+
+    /// Create the instructions which actively reset each of the qubits, in turn, to the zero
+    /// state: the qubit is measured into the next element of the memory region, starting from
+    /// the first, and flipped by an `X` gate if it was found in the one state. The region must be
+    /// declared with at least as many elements as there are qubits.
+    ///
+    /// Each qubit's `JUMP-UNLESS` targets a [`LabelPlaceholder`] based on `reset_` and the qubit,
+    /// which is named by [`Program::resolve_label_placeholders`](crate::Program::resolve_label_placeholders).
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::instruction::Instruction;
+    /// use quil_rs::Program;
+    ///
+    /// let mut program = Program::from_str("DECLARE ro BIT[2]").unwrap();
+    /// for instruction in Instruction::active_reset(vec![0, 3], "ro") {
+    ///     program.add_instruction(instruction);
+    /// }
+    /// assert_eq!(
+    ///     program.resolve_label_placeholders().to_string(false),
+    ///     "MEASURE 0 ro[0]
+    /// JUMP-UNLESS @reset_0 ro[0]
+    /// X 0
+    /// LABEL @reset_0
+    /// MEASURE 3 ro[1]
+    /// JUMP-UNLESS @reset_3 ro[1]
+    /// X 3
+    /// LABEL @reset_3
+    /// "
+    /// );
+    /// ```
+    pub fn active_reset<Q: Into<Qubit>>(
+        qubits: impl IntoIterator<Item = Q>,
+        region: &str,
+    ) -> Vec<Self> {
+        qubits
+            .into_iter()
+            .enumerate()
+            .flat_map(|(index, qubit)| {
+                let qubit = qubit.into();
+                let condition = MemoryReference::new(region, index as u64);
+                let base = match &qubit {
+                    Qubit::Fixed(index) => format!("reset_{}", index),
+                    Qubit::Variable(name) => format!("reset_{}", name),
+                    Qubit::Placeholder(_) => "reset".to_owned(),
+                };
+                let target = Target::Placeholder(LabelPlaceholder::new(base));
+                vec![
+                    Instruction::Measurement(Measurement {
+                        qubit: qubit.clone(),
+                        target: Some(condition.clone()),
+                    }),
+                    Instruction::JumpUnless(JumpUnless {
+                        target: target.clone(),
+                        condition,
+                    }),
+                    Instruction::from(Instruction::gate("X").qubit(qubit)),
+                    Instruction::Label(Label(target)),
+                ]
+            })
+            .collect()
+    }
+
     /// Apply the provided closure to this instruction, mutating any `Expression`s within.
     /// Does not affect instructions without `Expression`s within.
     /// Does not traverse or mutate instructions nested within blocks (such as
@@ -1064,6 +1867,124 @@ impl Instruction {
         }
     }
 
+    /// Return true if this instruction declares or defines something for use elsewhere in a
+    /// program (`DECLARE`, `DEFCAL`, `DEFCIRCUIT`, `DEFFRAME`, `DEFGATE`, or `DEFWAVEFORM`),
+    /// rather than being executed itself.
+    pub fn is_definition(&self) -> bool {
+        matches!(
+            self,
+            Instruction::CalibrationDefinition(_)
+                | Instruction::CircuitDefinition(_)
+                | Instruction::Declaration(_)
+                | Instruction::FrameDefinition(_)
+                | Instruction::GateDefinition(_)
+                | Instruction::MeasureCalibrationDefinition(_)
+                | Instruction::WaveformDefinition(_)
+        )
+    }
+
+    /// Return true if this instruction is part of Quil-T, the pulse-level extension of Quil: a
+    /// definition of a calibration, frame, or waveform, or an instruction which acts on frames
+    /// (`CAPTURE`, `DELAY`, `FENCE`, `PULSE`, `RAW-CAPTURE`, and those which set, shift, or swap
+    /// frequencies, phases, and scales).
+    pub fn is_quil_t(&self) -> bool {
+        matches!(
+            self,
+            Instruction::CalibrationDefinition(_)
+                | Instruction::Capture(_)
+                | Instruction::Delay(_)
+                | Instruction::Fence(_)
+                | Instruction::FrameDefinition(_)
+                | Instruction::MeasureCalibrationDefinition(_)
+                | Instruction::Pulse(_)
+                | Instruction::RawCapture(_)
+                | Instruction::SetFrequency(_)
+                | Instruction::SetPhase(_)
+                | Instruction::SetScale(_)
+                | Instruction::ShiftFrequency(_)
+                | Instruction::ShiftPhase(_)
+                | Instruction::SwapPhases(_)
+                | Instruction::WaveformDefinition(_)
+        )
+    }
+
+    /// Return all of the qubits directly used by this instruction, including those of the frames
+    /// it plays on, in the order in which they appear. Does not traverse instructions nested
+    /// within blocks (such as within `DEFCAL`).
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::{instruction::Qubit, Program};
+    ///
+    /// let program = Program::from_str("PULSE 0 1 \"cz\" flat(duration: 1e-6, iq: 1)").unwrap();
+    /// assert_eq!(
+    ///     program.instructions[0].qubits(),
+    ///     vec![&Qubit::Fixed(0), &Qubit::Fixed(1)]
+    /// );
+    /// ```
+    pub fn qubits(&self) -> Vec<&Qubit> {
+        match self {
+            Instruction::Gate(Gate { qubits, .. })
+            | Instruction::Delay(Delay { qubits, .. })
+            | Instruction::Fence(Fence { qubits }) => qubits.iter().collect(),
+            Instruction::Measurement(Measurement { qubit, .. }) => vec![qubit],
+            Instruction::Reset(Reset { qubit }) => qubit.iter().collect(),
+            Instruction::Capture(Capture { frame, .. })
+            | Instruction::Pulse(Pulse { frame, .. })
+            | Instruction::RawCapture(RawCapture { frame, .. })
+            | Instruction::SetFrequency(SetFrequency { frame, .. })
+            | Instruction::SetPhase(SetPhase { frame, .. })
+            | Instruction::SetScale(SetScale { frame, .. })
+            | Instruction::ShiftFrequency(ShiftFrequency { frame, .. })
+            | Instruction::ShiftPhase(ShiftPhase { frame, .. }) => frame.qubits.iter().collect(),
+            Instruction::SwapPhases(SwapPhases { frame_1, frame_2 }) => {
+                frame_1.qubits.iter().chain(frame_2.qubits.iter()).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Return mutable references to all of the qubits directly used by this instruction,
+    /// including those of the frames it plays on. Does not traverse instructions nested within
+    /// blocks (such as within `DEFCAL`).
+    pub fn get_qubits_mut(&mut self) -> Vec<&mut Qubit> {
+        match self {
+            Instruction::Gate(Gate { qubits, .. })
+            | Instruction::Delay(Delay { qubits, .. })
+            | Instruction::Fence(Fence { qubits }) => qubits.iter_mut().collect(),
+            Instruction::Measurement(Measurement { qubit, .. }) => vec![qubit],
+            Instruction::Reset(Reset { qubit }) => qubit.iter_mut().collect(),
+            Instruction::Capture(Capture { frame, .. })
+            | Instruction::Pulse(Pulse { frame, .. })
+            | Instruction::RawCapture(RawCapture { frame, .. })
+            | Instruction::SetFrequency(SetFrequency { frame, .. })
+            | Instruction::SetPhase(SetPhase { frame, .. })
+            | Instruction::SetScale(SetScale { frame, .. })
+            | Instruction::ShiftFrequency(ShiftFrequency { frame, .. })
+            | Instruction::ShiftPhase(ShiftPhase { frame, .. }) => {
+                frame.qubits.iter_mut().collect()
+            }
+            Instruction::SwapPhases(SwapPhases { frame_1, frame_2 }) => frame_1
+                .qubits
+                .iter_mut()
+                .chain(frame_2.qubits.iter_mut())
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Return a mutable reference to the label defined by this instruction, if it is a `LABEL`,
+    /// or targeted by it, if it is a jump.
+    pub fn get_target_mut(&mut self) -> Option<&mut Target> {
+        match self {
+            Instruction::Label(Label(target))
+            | Instruction::Jump(Jump { target })
+            | Instruction::JumpWhen(JumpWhen { target, .. })
+            | Instruction::JumpUnless(JumpUnless { target, .. }) => Some(target),
+            _ => None,
+        }
+    }
+
     pub(crate) fn get_frame_match_condition(
         &self,
         include_blocked: bool,
@@ -1107,7 +2028,7 @@ impl Instruction {
                 Some(FrameMatchCondition::Specific(frame))
             }
             Instruction::SwapPhases(SwapPhases { frame_1, frame_2 }) => {
-                Some(FrameMatchCondition::And(vec![
+                Some(FrameMatchCondition::AnyOf(vec![
                     FrameMatchCondition::Specific(frame_1),
                     FrameMatchCondition::Specific(frame_2),
                 ]))
@@ -1158,6 +2079,58 @@ mod tests {
 
     use crate::{expression::Expression, Program};
 
+    use super::{
+        AttributeValue, FrameAttributeError, FrameAttributes, FrameDirection, Gate, GateModifier,
+        Instruction, Qubit,
+    };
+
+    #[test]
+    fn constructors() {
+        let built = Instruction::from(
+            Instruction::gate("CPHASE")
+                .param(0.5)
+                .qubit(0)
+                .qubit("q")
+                .controlled(2),
+        );
+        assert_eq!(
+            built,
+            Instruction::parse("CONTROLLED CPHASE(0.5) 2 0 q").unwrap()
+        );
+        assert_eq!(
+            built,
+            Instruction::Gate(Gate {
+                name: "CPHASE".to_owned(),
+                parameters: vec![Expression::from(0.5)],
+                qubits: vec![
+                    Qubit::Fixed(2),
+                    Qubit::Fixed(0),
+                    Qubit::Variable("q".to_owned())
+                ],
+                modifiers: vec![GateModifier::Controlled],
+            })
+        );
+
+        for (instruction, expected) in [
+            (Instruction::measure(1, "ro", 2), "MEASURE 1 ro[2]"),
+            (Instruction::measure_discard(1), "MEASURE 1"),
+            (Instruction::reset(3), "RESET 3"),
+            (Instruction::reset_all(), "RESET"),
+        ] {
+            assert_eq!(instruction, Instruction::parse(expected).unwrap());
+        }
+
+        // Keywords without a command of their own may still name gates.
+        let wait = Instruction::from(Instruction::gate("WAIT").qubit(0));
+        assert_eq!(wait, Instruction::parse(&wait.to_string()).unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "gate name NOT is reserved")]
+    fn reserved_gate_name() {
+        Instruction::gate("NOT").qubit(0);
+    }
+
     #[test]
     fn apply_to_expressions() {
         let mut program = Program::from_str(
@@ -1180,4 +2153,113 @@ RX(%a) 0",
 
         assert_eq!(expected_program, program);
     }
+
+    #[test]
+    fn typed_frame_attributes() {
+        let program = Program::from_str(
+            "DEFFRAME 0 \"ro_rx\":
+    SAMPLE-RATE: 2e9
+    CENTER-FREQUENCY: 7e9
+    DIRECTION: \"rx\"
+    HARDWARE-OBJECT: \"q0_ro_rx\"
+    CHANNEL-DELAY: 0
+",
+        )
+        .unwrap();
+        let (_, raw) = program.frames.iter().next().unwrap();
+        let attributes = FrameAttributes::from_raw(raw).unwrap();
+        assert_eq!(
+            attributes,
+            FrameAttributes {
+                sample_rate: Some(2e9),
+                initial_frequency: None,
+                center_frequency: Some(7e9),
+                bandwidth: None,
+                direction: Some(FrameDirection::Receive),
+                hardware_object: Some("q0_ro_rx".to_owned()),
+                channel_delay: Some(0.0),
+            }
+        );
+        assert_eq!(
+            FrameAttributes::from_raw(&attributes.to_raw()),
+            Ok(attributes)
+        );
+
+        let mut invalid = raw.clone();
+        invalid.insert(
+            "DIRECTION".to_owned(),
+            AttributeValue::String("up".to_owned()),
+        );
+        assert!(matches!(
+            FrameAttributes::from_raw(&invalid),
+            Err(FrameAttributeError::Invalid { attribute, .. }) if attribute == "DIRECTION"
+        ));
+        invalid.remove("DIRECTION");
+        invalid.insert(
+            "SAMPLE-RATE".to_owned(),
+            AttributeValue::String("fast".to_owned()),
+        );
+        invalid.insert(
+            "GAIN".to_owned(),
+            AttributeValue::Expression(Expression::from(1.0)),
+        );
+        // Attributes are checked in order of their names.
+        assert_eq!(
+            FrameAttributes::from_raw(&invalid),
+            Err(FrameAttributeError::UnknownAttribute("GAIN".to_owned()))
+        );
+        invalid.remove("GAIN");
+        assert!(matches!(
+            FrameAttributes::from_raw(&invalid),
+            Err(FrameAttributeError::ExpectedNumber { attribute, .. }) if attribute == "SAMPLE-RATE"
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn hardware_object() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct HardwareObject {
+            instrument: String,
+            channel: u32,
+        }
+
+        let json = r#"{"instrument": "tsunami", "channel": 2}"#;
+        let program = Program::from_str(&format!(
+            "DEFFRAME 0 \"rf\":\n    HARDWARE-OBJECT: {}",
+            super::format_string(json)
+        ))
+        .unwrap();
+        let (identifier, raw) = program.frames.iter().next().unwrap();
+        let attributes = FrameAttributes::from_raw(raw).unwrap();
+        assert_eq!(attributes.hardware_object.as_deref(), Some(json));
+        assert_eq!(
+            program.frames.get_hardware_object(identifier),
+            attributes.parse_hardware_object::<HardwareObject>()
+        );
+        assert_eq!(
+            attributes.parse_hardware_object(),
+            Ok(Some(HardwareObject {
+                instrument: "tsunami".to_owned(),
+                channel: 2
+            }))
+        );
+        assert!(Program::from_str(&program.to_string(true))
+            .unwrap()
+            .frames
+            .eq(&program.frames));
+
+        let named = FrameAttributes {
+            hardware_object: Some("q0_rf".to_owned()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            named.parse_hardware_object::<serde_json::Value>(),
+            Err(FrameAttributeError::Invalid { attribute, .. }) if attribute == "HARDWARE-OBJECT"
+        ));
+        assert_eq!(
+            FrameAttributes::default().parse_hardware_object::<serde_json::Value>(),
+            Ok(None)
+        );
+    }
 }
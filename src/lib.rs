@@ -19,9 +19,52 @@
 //!
 //! * Builder utilities for Quil [programs], [instructions], and [expressions]
 //! * A [parser] and [serializer] for converting Quil to and from text strings
+//! * A `quil!` macro, in the companion `quil-macros` crate, for writing Quil programs inline
+//!   within Rust and checking them at compile time
+//! * Definitions of the [standard gates]
+//! * Typed representations of [noise pragmas]
+//! * Sampling of the standard Quil-T [waveform templates]
 //! * A [constructor for timing graphs], for understanding and debugging Quil-T
 //!   pulse control programs
 //!
+//! And with optional features (linked here when they are enabled):
+//!
+#![cfg_attr(
+    feature = "npy",
+    doc = "* With `npy`, export of waveforms and matrices to [NumPy arrays](crate::npy)"
+)]
+#![cfg_attr(
+    not(feature = "npy"),
+    doc = "* With `npy`, export of waveforms and matrices to NumPy arrays"
+)]
+#![cfg_attr(
+    feature = "serde",
+    doc = "* With `serde`, serialization of programs, instructions, and expressions with [serde],
+  and a [stable JSON format](crate::Program::to_json) for programs"
+)]
+#![cfg_attr(
+    not(feature = "serde"),
+    doc = "* With `serde`, serialization of programs, instructions, and expressions with [serde],
+  and a stable JSON format for programs"
+)]
+#![cfg_attr(
+    feature = "binary",
+    doc = "* With `binary`, a [compact binary encoding](crate::Program::to_binary) of programs,
+  for caching them"
+)]
+#![cfg_attr(
+    not(feature = "binary"),
+    doc = "* With `binary`, a compact binary encoding of programs, for caching them"
+)]
+#![cfg_attr(
+    feature = "python",
+    doc = "* With `python`, [Python bindings](crate::python) for parsing and analyzing programs"
+)]
+#![cfg_attr(
+    not(feature = "python"),
+    doc = "* With `python`, Python bindings for parsing and analyzing programs"
+)]
+//!
 //! This crate is still early in its development and does not fully support all
 //! Quil features, nor claim a stable API. Prior to `v1.0`, minor-version changes
 //! are considered breaking changes. Please pin your versions when needed, and
@@ -30,15 +73,29 @@
 //!
 //! [constructor for timing graphs]: crate::program::graph::ScheduledProgram#method.get_dot_format
 //! [expressions]: crate::expression::Expression
+//! [standard gates]: crate::gates::standard_gates
 //! [instructions]: crate::instruction::Instruction
+//! [noise pragmas]: crate::pragma::NoisePragma
 //! [parser]: crate::program::Program#method.from_str
 //! [programs]: crate::program::Program
 //! [serializer]: crate::program::Program#method.to_string
+//! [waveform templates]: crate::waveform
+
+// `ProgramError` carries the instructions it reports on, which makes it larger than Clippy would
+// like an error to be. It is returned by parsing and program analysis alike, so accept its size.
+#![allow(clippy::result_large_err)]
 
 pub mod expression;
+pub mod gates;
 pub mod instruction;
 mod macros;
+#[cfg(feature = "npy")]
+pub mod npy;
 pub(crate) mod parser;
+pub mod pragma;
 pub mod program;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod waveform;
 
 pub use program::Program;
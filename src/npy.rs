@@ -0,0 +1,283 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Export of sampled waveforms and gate matrices in the NumPy `.npy` and `.npz` formats, for
+//! analysis in Python without bindings: the bytes written may be loaded with `numpy.load`.
+//!
+//! Arrays are written as little-endian `complex128` in C order. Archives are written without
+//! compression.
+
+use std::convert::TryFrom;
+
+use num_complex::Complex64;
+use thiserror::Error;
+
+use crate::gates::Matrix;
+
+/// A problem in writing an array or archive.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum NpyError {
+    #[error("row {row} of the matrix has {found} entries, but row 0 has {expected}")]
+    RaggedMatrix {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+
+    #[error("the archive is too large to be written without ZIP64 extensions")]
+    ArchiveTooLarge,
+}
+
+/// The magic string which begins every `.npy` file, followed by format version 1.0.
+const NPY_MAGIC: &[u8] = b"\x93NUMPY\x01\x00";
+
+/// The alignment, in bytes, of the data following the header of a `.npy` file.
+const NPY_ALIGNMENT: usize = 64;
+
+/// Write an array of the given shape, whose entries are given in C order.
+fn write_npy(shape: &[usize], entries: impl Iterator<Item = Complex64>) -> Vec<u8> {
+    let shape = match shape {
+        [length] => format!("({},)", length),
+        _ => format!(
+            "({})",
+            shape
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '<c16', 'fortran_order': False, 'shape': {}, }}",
+        shape
+    );
+    // The header is padded with spaces and ended with a newline to align the data.
+    let unpadded = NPY_MAGIC.len() + 2 + header.len() + 1;
+    let padding = (NPY_ALIGNMENT - unpadded % NPY_ALIGNMENT) % NPY_ALIGNMENT;
+    header.extend(std::iter::repeat(' ').take(padding));
+    header.push('\n');
+
+    let mut bytes = Vec::with_capacity(NPY_MAGIC.len() + 2 + header.len());
+    bytes.extend_from_slice(NPY_MAGIC);
+    // The header of a version 1.0 file is short enough for its length to fit in two bytes.
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    for entry in entries {
+        bytes.extend_from_slice(&entry.re.to_le_bytes());
+        bytes.extend_from_slice(&entry.im.to_le_bytes());
+    }
+    bytes
+}
+
+/// Write sampled waveform as a one-dimensional `.npy` array.
+///
+/// ```rust
+/// use quil_rs::npy::waveform_to_npy;
+/// use quil_rs::waveform::{Gaussian, WaveformTemplate};
+///
+/// let samples = Gaussian::new(1e-7, 2e-8, 5e-8).unwrap().sample(1e9);
+/// let bytes = waveform_to_npy(&samples);
+/// assert_eq!(bytes.len(), 128 + 16 * 100);
+/// ```
+pub fn waveform_to_npy(samples: &[Complex64]) -> Vec<u8> {
+    write_npy(&[samples.len()], samples.iter().copied())
+}
+
+/// Write a matrix, such as that of a gate, as a two-dimensional `.npy` array. Fails if its rows
+/// differ in length.
+pub fn matrix_to_npy(matrix: &Matrix) -> Result<Vec<u8>, NpyError> {
+    let columns = matrix.first().map_or(0, Vec::len);
+    if let Some((row, entries)) = matrix
+        .iter()
+        .enumerate()
+        .find(|(_, entries)| entries.len() != columns)
+    {
+        return Err(NpyError::RaggedMatrix {
+            row,
+            expected: columns,
+            found: entries.len(),
+        });
+    }
+    Ok(write_npy(
+        &[matrix.len(), columns],
+        matrix.iter().flatten().copied(),
+    ))
+}
+
+/// Compute the CRC-32 checksum of the given bytes, as used by ZIP archives.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// Convert a size or offset into the four bytes allowed for it in a ZIP archive.
+fn zip_u32(value: usize) -> Result<[u8; 4], NpyError> {
+    u32::try_from(value)
+        .map(u32::to_le_bytes)
+        .map_err(|_| NpyError::ArchiveTooLarge)
+}
+
+/// Write the given named `.npy` arrays into an `.npz` archive, in which each is stored as
+/// `<name>.npy` so that `numpy.load` makes it available as `<name>`.
+///
+/// ```rust
+/// use quil_rs::gates::standard_gate;
+/// use quil_rs::npy::{matrix_to_npy, to_npz};
+///
+/// let matrix = standard_gate("CNOT").unwrap().to_matrix(&[]).unwrap();
+/// let archive = to_npz(vec![("cnot", matrix_to_npy(&matrix).unwrap())]).unwrap();
+/// assert!(archive.starts_with(b"PK\x03\x04"));
+/// ```
+pub fn to_npz<N: AsRef<str>>(
+    arrays: impl IntoIterator<Item = (N, Vec<u8>)>,
+) -> Result<Vec<u8>, NpyError> {
+    // The modification date of each file: 1980-01-01, the earliest which may be represented.
+    const DATE: [u8; 2] = 0x21u16.to_le_bytes();
+    const VERSION: [u8; 2] = 20u16.to_le_bytes();
+
+    let mut archive = vec![];
+    let mut directory = vec![];
+    let mut count = 0u16;
+
+    for (name, data) in arrays {
+        let name = format!("{}.npy", name.as_ref());
+        let name_length = u16::try_from(name.len()).map_err(|_| NpyError::ArchiveTooLarge)?;
+        let offset = zip_u32(archive.len())?;
+        let size = zip_u32(data.len())?;
+        let crc = crc32(&data).to_le_bytes();
+        count = count.checked_add(1).ok_or(NpyError::ArchiveTooLarge)?;
+
+        // The fields common to the local header and the central directory: the version needed,
+        // flags, compression method (none), time, date, checksum, sizes, and name length.
+        let mut common = vec![];
+        common.extend_from_slice(&VERSION);
+        common.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+        common.extend_from_slice(&DATE);
+        common.extend_from_slice(&crc);
+        common.extend_from_slice(&size);
+        common.extend_from_slice(&size);
+        common.extend_from_slice(&name_length.to_le_bytes());
+
+        archive.extend_from_slice(b"PK\x03\x04");
+        archive.extend_from_slice(&common);
+        archive.extend_from_slice(&[0, 0]);
+        archive.extend_from_slice(name.as_bytes());
+        archive.extend_from_slice(&data);
+
+        directory.extend_from_slice(b"PK\x01\x02");
+        directory.extend_from_slice(&VERSION);
+        directory.extend_from_slice(&common);
+        // The lengths of the extra field and comment, the disk number, and the attributes.
+        directory.extend_from_slice(&[0; 12]);
+        directory.extend_from_slice(&offset);
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let directory_offset = zip_u32(archive.len())?;
+    let directory_size = zip_u32(directory.len())?;
+    archive.extend_from_slice(&directory);
+    archive.extend_from_slice(b"PK\x05\x06");
+    archive.extend_from_slice(&[0, 0, 0, 0]);
+    archive.extend_from_slice(&count.to_le_bytes());
+    archive.extend_from_slice(&count.to_le_bytes());
+    archive.extend_from_slice(&directory_size);
+    archive.extend_from_slice(&directory_offset);
+    archive.extend_from_slice(&[0, 0]);
+    Ok(archive)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{imag, real};
+
+    use super::{crc32, matrix_to_npy, to_npz, waveform_to_npy, NpyError};
+
+    fn header(bytes: &[u8]) -> &str {
+        let length = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        std::str::from_utf8(&bytes[10..10 + length]).unwrap()
+    }
+
+    #[test]
+    fn waveform() {
+        let bytes = waveform_to_npy(&[real!(1.0), imag!(-2.0)]);
+        assert!(bytes.starts_with(b"\x93NUMPY\x01\x00"));
+        assert_eq!(
+            header(&bytes).trim_end(),
+            "{'descr': '<c16', 'fortran_order': False, 'shape': (2,), }"
+        );
+        assert!(header(&bytes).ends_with('\n'));
+        assert_eq!(bytes.len(), 128 + 32);
+
+        let data = &bytes[128..];
+        assert_eq!(&data[0..8], &1.0f64.to_le_bytes());
+        assert_eq!(&data[8..16], &0.0f64.to_le_bytes());
+        assert_eq!(&data[24..32], &(-2.0f64).to_le_bytes());
+    }
+
+    #[test]
+    fn matrix() {
+        let bytes = matrix_to_npy(&vec![
+            vec![real!(1.0), real!(2.0), real!(3.0)],
+            vec![real!(4.0), real!(5.0), real!(6.0)],
+        ])
+        .unwrap();
+        assert!(header(&bytes).contains("'shape': (2, 3)"));
+        // Entries are in C order, so the second is that of the first row and second column.
+        assert_eq!(&bytes[128 + 16..128 + 24], &2.0f64.to_le_bytes());
+
+        assert_eq!(
+            matrix_to_npy(&vec![vec![real!(1.0)], vec![]]),
+            Err(NpyError::RaggedMatrix {
+                row: 1,
+                expected: 1,
+                found: 0
+            })
+        );
+    }
+
+    #[test]
+    fn archive() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let array = waveform_to_npy(&[real!(1.0)]);
+        let archive = to_npz(vec![("first", array.clone()), ("second", array.clone())]).unwrap();
+
+        let local_header = 30 + "first.npy".len();
+        assert_eq!(&archive[..4], b"PK\x03\x04");
+        assert_eq!(&archive[30..local_header], b"first.npy");
+        assert_eq!(
+            &archive[local_header..local_header + array.len()],
+            &array[..]
+        );
+
+        let end = &archive[archive.len() - 22..];
+        assert_eq!(&end[..4], b"PK\x05\x06");
+        assert_eq!(u16::from_le_bytes([end[10], end[11]]), 2);
+        let directory_offset = u32::from_le_bytes([end[16], end[17], end[18], end[19]]) as usize;
+        assert_eq!(
+            &archive[directory_offset..directory_offset + 4],
+            b"PK\x01\x02"
+        );
+    }
+}
@@ -12,8 +12,11 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+
 use nom::{
-    combinator::opt,
+    branch::alt,
+    combinator::{map, opt},
     multi::{many0, many1, separated_list0, separated_list1},
     sequence::{delimited, tuple},
 };
@@ -22,9 +25,9 @@ use crate::instruction::{
     Arithmetic, ArithmeticOperand, ArithmeticOperator, BinaryLogic, BinaryOperator, Calibration,
     Capture, CircuitDefinition, Comparison, ComparisonOperator, Declaration, Delay, Exchange,
     Fence, FrameDefinition, Instruction, Jump, JumpUnless, JumpWhen, Label, Load,
-    MeasureCalibrationDefinition, Measurement, Move, Pragma, Pulse, Qubit, RawCapture, Reset,
-    SetFrequency, SetPhase, SetScale, ShiftFrequency, ShiftPhase, Store, UnaryLogic, UnaryOperator,
-    Waveform, WaveformDefinition,
+    MeasureCalibrationDefinition, Measurement, Move, Pragma, Pulse, RawCapture, Reset,
+    SetFrequency, SetPhase, SetScale, ShiftFrequency, ShiftPhase, Store, Target, UnaryLogic,
+    UnaryOperator, Waveform, WaveformDefinition,
 };
 use crate::parser::common::parse_variable_qubit;
 use crate::parser::instruction::parse_block;
@@ -35,6 +38,7 @@ use super::{
         self, parse_frame_attribute, parse_frame_identifier, parse_gate_modifier,
         parse_memory_reference, parse_qubit, parse_waveform_invocation,
     },
+    error::{ParseError, ParserErrorKind},
     expression::parse_expression,
     instruction, ParserInput, ParserResult,
 };
@@ -158,7 +162,7 @@ pub fn parse_defcal<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruction>
 /// following the `DEFCAL` token.
 pub fn parse_defcal_gate<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruction> {
     let (input, modifiers) = many0(parse_gate_modifier)(input)?;
-    let (input, name) = token!(Identifier(v))(input)?;
+    let (input, name) = common::parse_contextual_identifier(input)?;
     let (input, parameters) = opt(delimited(
         token!(LParenthesis),
         separated_list0(token!(Comma), parse_expression),
@@ -182,9 +186,14 @@ pub fn parse_defcal_gate<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruc
 
 /// Parse the contents of a `DEFCAL MEASURE` instruction, following the `MEASURE` token.
 pub fn parse_defcal_measure<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruction> {
-    let (input, qubit_index) = opt(token!(Integer(v)))(input)?;
-    let qubit = qubit_index.map(Qubit::Fixed);
-    let (input, destination) = token!(Identifier(v))(input)?;
+    // The qubit is optional, so a lone identifier names the destination rather than a qubit.
+    let (input, (qubit, destination)) = alt((
+        map(
+            tuple((parse_qubit, token!(Identifier(v)))),
+            |(qubit, destination)| (Some(qubit), destination),
+        ),
+        map(token!(Identifier(v)), |destination| (None, destination)),
+    ))(input)?;
     let (input, _) = token!(Colon)(input)?;
     let (input, instructions) = instruction::parse_block(input)?;
     Ok((
@@ -199,10 +208,20 @@ pub fn parse_defcal_measure<'a>(input: ParserInput<'a>) -> ParserResult<'a, Inst
 
 /// Parse the contents of a `DEFFRAME` instruction.
 pub fn parse_defframe<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruction> {
+    let start = input;
     let (input, identifier) = parse_frame_identifier(input)?;
     let (input, _) = token!(Colon)(input)?;
     let (input, attribute_pairs) = many1(parse_frame_attribute)(input)?;
-    let attributes = attribute_pairs.iter().cloned().collect();
+    let mut attributes = HashMap::new();
+    for (key, value) in attribute_pairs {
+        if attributes.contains_key(&key) {
+            return Err(nom::Err::Failure(ParseError::from_kind(
+                start,
+                ParserErrorKind::DuplicateFrameAttribute(key),
+            )));
+        }
+        attributes.insert(key, value);
+    }
 
     Ok((
         input,
@@ -236,7 +255,7 @@ pub fn parse_defwaveform<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruc
 }
 
 pub fn parse_defcircuit<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruction> {
-    let (input, name) = token!(Identifier(v))(input)?;
+    let (input, name) = common::parse_contextual_identifier(input)?;
     let (input, parameters) = opt(delimited(
         token!(LParenthesis),
         separated_list0(token!(Comma), token!(Variable(v))),
@@ -298,14 +317,25 @@ pub fn parse_fence(input: ParserInput) -> ParserResult<Instruction> {
 /// Parse the contents of a `JUMP` instruction.
 pub fn parse_jump<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruction> {
     let (input, target) = token!(Label(v))(input)?;
-    Ok((input, Instruction::Jump(Jump { target })))
+    Ok((
+        input,
+        Instruction::Jump(Jump {
+            target: Target::Fixed(target),
+        }),
+    ))
 }
 
 /// Parse the contents of a `JUMP-WHEN` instruction.
 pub fn parse_jump_when<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruction> {
     let (input, target) = token!(Label(v))(input)?;
     let (input, condition) = common::parse_memory_reference(input)?;
-    Ok((input, Instruction::JumpWhen(JumpWhen { target, condition })))
+    Ok((
+        input,
+        Instruction::JumpWhen(JumpWhen {
+            target: Target::Fixed(target),
+            condition,
+        }),
+    ))
 }
 
 /// Parse the contents of a `JUMP-UNLESS` instruction.
@@ -314,14 +344,17 @@ pub fn parse_jump_unless<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruc
     let (input, condition) = common::parse_memory_reference(input)?;
     Ok((
         input,
-        Instruction::JumpUnless(JumpUnless { target, condition }),
+        Instruction::JumpUnless(JumpUnless {
+            target: Target::Fixed(target),
+            condition,
+        }),
     ))
 }
 
 /// Parse the contents of a `DECLARE` instruction.
 pub fn parse_label<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruction> {
     let (input, name) = token!(Label(v))(input)?;
-    Ok((input, Instruction::Label(Label(name))))
+    Ok((input, Instruction::Label(Label(Target::Fixed(name)))))
 }
 
 /// Parse the contents of a `MOVE` instruction.
@@ -371,9 +404,11 @@ pub fn parse_store<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruction>
 
 /// Parse the contents of a `PRAGMA` instruction.
 pub fn parse_pragma<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruction> {
-    let (input, pragma_type) = token!(Identifier(v))(input)?;
-    // FIXME: Also allow Int (not just Identifier)
-    let (input, arguments) = many0(token!(Identifier(v)))(input)?;
+    let (input, pragma_type) = common::parse_contextual_identifier(input)?;
+    let (input, arguments) = many0(alt((
+        common::parse_contextual_identifier,
+        map(token!(Integer(v)), |index| index.to_string()),
+    )))(input)?;
     let (input, data) = opt(token!(String(v)))(input)?;
     Ok((
         input,
@@ -496,7 +531,7 @@ mod tests {
         make_test,
     };
 
-    use super::{parse_declare, parse_defcircuit, parse_measurement, parse_pragma};
+    use super::{parse_declare, parse_defcircuit, parse_defframe, parse_measurement, parse_pragma};
 
     make_test!(
         declare_instruction_length_1,
@@ -583,6 +618,17 @@ mod tests {
         })
     );
 
+    make_test!(
+        pragma_integer_arguments,
+        parse_pragma,
+        "ADD-KRAUS X 0 \"(0.0 1.0 1.0 0.0)\"",
+        Instruction::Pragma(Pragma {
+            name: "ADD-KRAUS".to_owned(),
+            arguments: vec!["X".to_owned(), "0".to_owned()],
+            data: Some("(0.0 1.0 1.0 0.0)".to_owned())
+        })
+    );
+
     make_test!(
         defcircuit_no_params,
         parse_defcircuit,
@@ -656,4 +702,11 @@ mod tests {
             ]
         })
     );
+
+    #[test]
+    fn defframe_duplicate_attribute() {
+        let tokens = lex("0 \"rf\":\n    SAMPLE-RATE: 1e9\n    SAMPLE-RATE: 2e9").unwrap();
+        let error = parse_defframe(&tokens).unwrap_err();
+        assert!(format!("{:?}", error).contains("DuplicateFrameAttribute"));
+    }
 }
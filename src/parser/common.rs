@@ -236,6 +236,20 @@ pub fn parse_variable_qubit(input: ParserInput) -> ParserResult<String> {
     }
 }
 
+/// Parse an identifier, also accepting command keywords, which are only reserved at the head of
+/// an instruction. This allows user-defined names such as a gate named `NOT`.
+pub fn parse_contextual_identifier(input: ParserInput) -> ParserResult<String> {
+    match super::split_first_token(input) {
+        None => Err(nom::Err::Error(ParseError::from_kind(
+            input,
+            ParserErrorKind::UnexpectedEOF("an identifier"),
+        ))),
+        Some((Token::Identifier(name), remainder)) => Ok((remainder, name.clone())),
+        Some((Token::Command(command), remainder)) => Ok((remainder, command.to_string())),
+        Some((other_token, _)) => expected_token!(input, other_token, "Identifier".to_owned()),
+    }
+}
+
 /// Parse a "vector" which is an integer index, such as `[0]`
 pub fn parse_vector<'a>(input: ParserInput<'a>) -> ParserResult<'a, Vector> {
     let (input, data_type_token) = token!(DataType(v))(input)?;
@@ -261,9 +275,11 @@ pub fn parse_vector<'a>(input: ParserInput<'a>) -> ParserResult<'a, Vector> {
 pub fn parse_waveform_name<'a>(input: ParserInput<'a>) -> ParserResult<'a, String> {
     use crate::parser::lexer::Operator::Slash;
 
-    let (input, mut name) = token!(Identifier(v))(input)?;
-    let (input, name_extension) =
-        opt(tuple((token!(Operator(Slash)), token!(Identifier(v)))))(input)?;
+    let (input, mut name) = parse_contextual_identifier(input)?;
+    let (input, name_extension) = opt(tuple((
+        token!(Operator(Slash)),
+        parse_contextual_identifier,
+    )))(input)?;
     if let Some((_, extension)) = name_extension {
         name = format!("{}/{}", name, extension);
     }
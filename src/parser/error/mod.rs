@@ -53,10 +53,118 @@ pub enum ParserErrorKind {
     EndOfInput,
 
     /// An instruction was encountered which is not yet supported for parsing by this library
-    #[error("instruction {0} is not yet supported by this parser")]
-    UnsupportedInstruction(Command),
+    #[error(
+        "instruction {command} is not yet supported by this parser{}; supported commands are: {}",
+        format_suggestion(.suggestion),
+        format_commands(.supported)
+    )]
+    UnsupportedInstruction {
+        command: Command,
+        /// The commands which this parser does support.
+        supported: &'static [Command],
+        /// The supported command whose name is closest to that of `command`, if any is close enough
+        /// to be a plausible typo.
+        suggestion: Option<Command>,
+    },
+
+    /// An attribute was given more than once in a frame definition
+    #[error("frame attribute {0} is given more than once")]
+    DuplicateFrameAttribute(String),
 
     /// Literals specified in the input cannot be supported without loss of precision
     #[error("using this literal will result in loss of precision")]
     UnsupportedPrecision,
 }
+
+impl ParserErrorKind {
+    /// Build an [`ParserErrorKind::UnsupportedInstruction`] for the given command, suggesting the
+    /// closest of the `supported` commands by edit distance.
+    pub(crate) fn unsupported_instruction(command: Command, supported: &'static [Command]) -> Self {
+        let name = command.to_string();
+        // Allow roughly one edit for every three characters before a suggestion becomes noise.
+        let max_distance = std::cmp::max(1, name.len() / 3);
+        let suggestion = supported
+            .iter()
+            .map(|candidate| (edit_distance(&name, &candidate.to_string()), candidate))
+            .filter(|(distance, _)| *distance <= max_distance)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, candidate)| *candidate);
+
+        Self::UnsupportedInstruction {
+            command,
+            supported,
+            suggestion,
+        }
+    }
+}
+
+fn format_suggestion(suggestion: &Option<Command>) -> String {
+    suggestion
+        .map(|command| format!(" (did you mean {}?)", command))
+        .unwrap_or_default()
+}
+
+fn format_commands(commands: &[Command]) -> String {
+    commands
+        .iter()
+        .map(Command::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The Levenshtein distance between two strings: the number of single-character insertions,
+/// deletions, and substitutions needed to turn one into the other.
+fn edit_distance(left: &str, right: &str) -> usize {
+    let right: Vec<char> = right.chars().collect();
+    let mut previous_row: Vec<usize> = (0..=right.len()).collect();
+
+    for (i, left_char) in left.chars().enumerate() {
+        let mut current_row = vec![i + 1];
+        for (j, right_char) in right.iter().enumerate() {
+            let substitution_cost = if left_char == *right_char { 0 } else { 1 };
+            current_row.push(
+                (previous_row[j] + substitution_cost)
+                    .min(previous_row[j + 1] + 1)
+                    .min(current_row[j] + 1),
+            );
+        }
+        previous_row = current_row;
+    }
+
+    previous_row[right.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_distance, Command, ParserErrorKind};
+
+    #[test]
+    fn edit_distances() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("NOP", "NOT"), 1);
+        assert_eq!(edit_distance("JUMP", "JUMP-WHEN"), 5);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn unsupported_instruction_suggestion() {
+        const SUPPORTED: &[Command] = &[Command::Halt, Command::Not, Command::Pulse];
+
+        assert_eq!(
+            ParserErrorKind::unsupported_instruction(Command::Nop, SUPPORTED),
+            ParserErrorKind::UnsupportedInstruction {
+                command: Command::Nop,
+                supported: SUPPORTED,
+                suggestion: Some(Command::Not),
+            }
+        );
+        assert_eq!(
+            ParserErrorKind::unsupported_instruction(Command::Include, SUPPORTED),
+            ParserErrorKind::UnsupportedInstruction {
+                command: Command::Include,
+                supported: SUPPORTED,
+                suggestion: None,
+            }
+        );
+    }
+}
@@ -31,6 +31,7 @@ enum Precedence {
     Lowest,
     Sum,
     Product,
+    Exponentiation,
     Call,
 }
 
@@ -41,6 +42,7 @@ impl From<&Token> for Precedence {
             Token::Operator(Operator::Star) | Token::Operator(Operator::Slash) => {
                 Precedence::Product
             }
+            Token::Operator(Operator::Caret) => Precedence::Exponentiation,
             // TODO: Is this used?
             Token::LParenthesis => Precedence::Call,
             _ => Precedence::Lowest,
@@ -144,7 +146,8 @@ fn parse_function_call<'a>(
 ///
 /// 1. Memory references with brackets
 /// 2. Special function and constant identifiers
-/// 3. Anything else is considered to be a memory reference without index brackets
+/// 3. Any other identifier followed by parentheses is considered to be a custom function call
+/// 4. Anything else is considered to be a memory reference without index brackets
 fn parse_expression_identifier(input: ParserInput) -> ParserResult<Expression> {
     let (input, memory_reference) = opt(parse_memory_reference_with_brackets)(input)?;
     if let Some(memory_reference) = memory_reference {
@@ -154,19 +157,32 @@ fn parse_expression_identifier(input: ParserInput) -> ParserResult<Expression> {
     match super::split_first_token(input) {
         None => unexpected_eof!(input),
         Some((Token::Identifier(ident), remainder)) => match ident.as_str() {
+            "abs" => parse_function_call(remainder, ExpressionFunction::AbsoluteValue),
+            "acos" => parse_function_call(remainder, ExpressionFunction::ArcCosine),
+            "asin" => parse_function_call(remainder, ExpressionFunction::ArcSine),
+            "atan" => parse_function_call(remainder, ExpressionFunction::ArcTangent),
             "cis" => parse_function_call(remainder, ExpressionFunction::Cis),
             "cos" => parse_function_call(remainder, ExpressionFunction::Cosine),
             "exp" => parse_function_call(remainder, ExpressionFunction::Exponent),
             "i" => Ok((remainder, Expression::Number(imag!(1f64)))),
+            "ln" => parse_function_call(remainder, ExpressionFunction::NaturalLogarithm),
             "pi" => Ok((remainder, Expression::PiConstant)),
             "sin" => parse_function_call(remainder, ExpressionFunction::Sine),
-            name => Ok((
-                remainder,
-                Expression::Address(MemoryReference {
-                    name: name.to_owned(),
-                    index: 0,
-                }),
-            )),
+            "sqrt" => parse_function_call(remainder, ExpressionFunction::SquareRoot),
+            "tan" => parse_function_call(remainder, ExpressionFunction::Tangent),
+            name => match super::first_token(remainder) {
+                // Functions not built into Quil are retained by name, to be supplied at evaluation.
+                Some(Token::LParenthesis) => {
+                    parse_function_call(remainder, ExpressionFunction::Custom(name.to_owned()))
+                }
+                _ => Ok((
+                    remainder,
+                    Expression::Address(MemoryReference {
+                        name: name.to_owned(),
+                        index: 0,
+                    }),
+                )),
+            },
         },
         Some((other_token, _)) => expected_token!(input, other_token, "identifier".to_owned()),
     }
@@ -198,7 +214,13 @@ fn parse_infix(input: ParserInput, left: Expression) -> ParserResult<Expression>
                 Operator::Slash => InfixOperator::Slash,
                 Operator::Star => InfixOperator::Star,
             };
-            let precedence = get_precedence(remainder);
+            // The right operand extends only across operators which bind more tightly than this one,
+            // making operators left-associative. `^` is instead right-associative, so its right
+            // operand also takes any further `^`.
+            let precedence = match expression_operator {
+                InfixOperator::Caret => Precedence::Product,
+                _ => get_precedence(input),
+            };
             let (remainder, right) = parse(remainder, precedence)?;
             let infix_expression = Expression::Infix {
                 left: Box::new(left),
@@ -261,11 +283,20 @@ mod tests {
         let cases = vec![
             "pi",
             "sin(pi)",
-            "(1+(2*3))",
-            "((1+2)*3)",
+            "1 + 2 * 3",
+            "(1 + 2) * 3",
+            "1 - 2 - 3",
+            "1 - (2 - 3)",
+            "1 / (2 * 3)",
+            "2 ^ 3 ^ 4",
+            "(2 ^ 3) ^ 4",
+            "-(%a + 1) * 2",
+            "%a * (-1)",
             "%theta",
             "cis(%theta)",
-            "(%a+%b)",
+            "abs(ln(%theta)) - atan(tan(%theta))",
+            "%a + %b",
+            "-sin(%theta / 2) * 2",
         ];
 
         for case in cases {
@@ -346,6 +377,70 @@ mod tests {
         }
     );
 
+    #[test]
+    fn precedence_and_associativity() {
+        let number = |value: f64| Box::new(Expression::Number(real!(value)));
+        let infix = |left, operator, right| {
+            Box::new(Expression::Infix {
+                left,
+                operator,
+                right,
+            })
+        };
+        let cases = vec![
+            (
+                "1+2*3",
+                *infix(
+                    number(1f64),
+                    InfixOperator::Plus,
+                    infix(number(2f64), InfixOperator::Star, number(3f64)),
+                ),
+            ),
+            (
+                "1*2+3",
+                *infix(
+                    infix(number(1f64), InfixOperator::Star, number(2f64)),
+                    InfixOperator::Plus,
+                    number(3f64),
+                ),
+            ),
+            (
+                "1-2-3",
+                *infix(
+                    infix(number(1f64), InfixOperator::Minus, number(2f64)),
+                    InfixOperator::Minus,
+                    number(3f64),
+                ),
+            ),
+            (
+                "1/2*3",
+                *infix(
+                    infix(number(1f64), InfixOperator::Slash, number(2f64)),
+                    InfixOperator::Star,
+                    number(3f64),
+                ),
+            ),
+            (
+                "2*3^4",
+                *infix(
+                    number(2f64),
+                    InfixOperator::Star,
+                    infix(number(3f64), InfixOperator::Caret, number(4f64)),
+                ),
+            ),
+            (
+                "2^3^4",
+                *infix(
+                    number(2f64),
+                    InfixOperator::Caret,
+                    infix(number(3f64), InfixOperator::Caret, number(4f64)),
+                ),
+            ),
+        ];
+
+        compare(cases);
+    }
+
     #[test]
     fn parenthetical() {
         let cases = vec![
@@ -26,7 +26,7 @@ use crate::instruction::Gate;
 /// Parse a gate instruction.
 pub fn parse_gate<'a>(input: ParserInput<'a>) -> ParserResult<'a, Instruction> {
     let (input, modifiers) = many0(parse_gate_modifier)(input)?;
-    let (input, name) = token!(Identifier(v))(input)?;
+    let (input, name) = common::parse_contextual_identifier(input)?;
     let (input, parameters) = opt(delimited(
         token!(LParenthesis),
         separated_list0(token!(Comma), parse_expression),
@@ -15,7 +15,7 @@
 use nom::{
     combinator::all_consuming,
     multi::{many0, many1},
-    sequence::{delimited, preceded},
+    sequence::{delimited, preceded, terminated},
 };
 
 use crate::parser::extract_nom_err;
@@ -30,10 +30,64 @@ use super::{
     command, common,
     error::{ParseError, ParserErrorKind},
     gate,
-    lexer::{Command, Token},
+    lexer::{self, Command, Token},
     ParserInput, ParserResult,
 };
 
+/// The commands which [`parse_instruction`] knows how to parse.
+pub(crate) const SUPPORTED_COMMANDS: &[Command] = &[
+    Command::Add,
+    Command::And,
+    Command::Capture,
+    Command::Declare,
+    Command::DefCal,
+    Command::DefCircuit,
+    Command::DefFrame,
+    Command::DefWaveform,
+    Command::Delay,
+    Command::Div,
+    Command::Eq,
+    Command::Exchange,
+    Command::Fence,
+    Command::GE,
+    Command::GT,
+    Command::Halt,
+    Command::Ior,
+    Command::Jump,
+    Command::JumpUnless,
+    Command::JumpWhen,
+    Command::Label,
+    Command::LE,
+    Command::Load,
+    Command::LT,
+    Command::Measure,
+    Command::Move,
+    Command::Mul,
+    Command::Neg,
+    Command::Not,
+    Command::Pragma,
+    Command::Pulse,
+    Command::RawCapture,
+    Command::Reset,
+    Command::SetFrequency,
+    Command::SetPhase,
+    Command::SetScale,
+    Command::ShiftFrequency,
+    Command::ShiftPhase,
+    Command::Store,
+    Command::Sub,
+    Command::Xor,
+];
+
+/// Whether a gate with this name would be read back as the command of the same name, as for
+/// `NOT 0`, rather than as a gate.
+pub(crate) fn is_reserved_gate_name(name: &str) -> bool {
+    matches!(
+        lexer::recognize_command_or_identifier(name.to_owned()),
+        Token::Command(command) if SUPPORTED_COMMANDS.contains(&command)
+    )
+}
+
 /// Parse the next instructon from the input, skipping past leading newlines, comments, and semicolons.
 pub fn parse_instruction(input: ParserInput) -> ParserResult<Instruction> {
     let (input, _) = common::skip_newlines_and_comments(input)?;
@@ -43,72 +97,18 @@ pub fn parse_instruction(input: ParserInput) -> ParserResult<Instruction> {
             ParserErrorKind::EndOfInput,
         ))),
         Some((Token::Command(command), remainder)) => {
-            match command {
-                Command::Add => command::parse_arithmetic(ArithmeticOperator::Add, remainder),
-                Command::And => command::parse_logical_binary(BinaryOperator::And, remainder),
-                Command::Capture => command::parse_capture(remainder, true),
-                // Command::Convert => {}
-                Command::Declare => command::parse_declare(remainder),
-                Command::DefCal => command::parse_defcal(remainder),
-                Command::DefCircuit => command::parse_defcircuit(remainder),
-                Command::DefFrame => command::parse_defframe(remainder),
-                // Command::DefGate => Ok((remainder, cut(parse_command_defgate))),
-                Command::DefWaveform => command::parse_defwaveform(remainder),
-                Command::Delay => command::parse_delay(remainder),
-                Command::Div => command::parse_arithmetic(ArithmeticOperator::Divide, remainder),
-                Command::Eq => command::parse_comparison(ComparisonOperator::Equal, remainder),
-                Command::GE => {
-                    command::parse_comparison(ComparisonOperator::GreaterThanOrEqual, remainder)
-                }
-                Command::GT => {
-                    command::parse_comparison(ComparisonOperator::GreaterThan, remainder)
+            parse_command(input, *command, remainder).or_else(|err| {
+                // A supported command always owns the head of its instruction, so its own errors
+                // stand. Any other keyword may instead be the name of a gate.
+                if SUPPORTED_COMMANDS.contains(command) {
+                    return Err(err);
                 }
-                Command::LE => {
-                    command::parse_comparison(ComparisonOperator::LessThanOrEqual, remainder)
+                match gate::parse_gate(input) {
+                    Ok((remainder, Instruction::Gate(gate))) if !gate.qubits.is_empty() => {
+                        Ok((remainder, Instruction::Gate(gate)))
+                    }
+                    _ => Err(err),
                 }
-                Command::LT => command::parse_comparison(ComparisonOperator::LessThan, remainder),
-                Command::Fence => command::parse_fence(remainder),
-                Command::Halt => Ok((remainder, Instruction::Halt)),
-                // Command::Include => {}
-                Command::Ior => command::parse_logical_binary(BinaryOperator::Ior, remainder),
-                Command::Jump => command::parse_jump(remainder),
-                Command::JumpUnless => command::parse_jump_unless(remainder),
-                Command::JumpWhen => command::parse_jump_when(remainder),
-                Command::Label => command::parse_label(remainder),
-                Command::Load => command::parse_load(remainder),
-                Command::Measure => command::parse_measurement(remainder),
-                Command::Move => command::parse_move(remainder),
-                Command::Exchange => command::parse_exchange(remainder),
-                Command::Mul => command::parse_arithmetic(ArithmeticOperator::Multiply, remainder),
-                Command::Neg => command::parse_logical_unary(UnaryOperator::Neg, remainder),
-                // Command::Nop => {}
-                Command::Not => command::parse_logical_unary(UnaryOperator::Not, remainder),
-                Command::Pragma => command::parse_pragma(remainder),
-                Command::Pulse => command::parse_pulse(remainder, true),
-                Command::RawCapture => command::parse_raw_capture(remainder, true),
-                Command::Reset => command::parse_reset(remainder),
-                Command::SetFrequency => command::parse_set_frequency(remainder),
-                Command::SetPhase => command::parse_set_phase(remainder),
-                Command::SetScale => command::parse_set_scale(remainder),
-                Command::ShiftFrequency => command::parse_shift_frequency(remainder),
-                Command::ShiftPhase => command::parse_shift_phase(remainder),
-                Command::Store => command::parse_store(remainder),
-                Command::Sub => command::parse_arithmetic(ArithmeticOperator::Subtract, remainder),
-                // Command::Wait => {}
-                Command::Xor => command::parse_logical_binary(BinaryOperator::Xor, remainder),
-                other => Err(nom::Err::Failure(ParseError::from_kind(
-                    &input[..1],
-                    ParserErrorKind::UnsupportedInstruction(*other),
-                ))),
-            }
-            .map_err(|err| {
-                nom::Err::Failure(
-                    ParseError::from_kind(
-                        &input[..1],
-                        ParserErrorKind::InvalidCommand { command: *command },
-                    )
-                    .with_previous(extract_nom_err(err)),
-                )
             })
         }
         Some((Token::NonBlocking, remainder)) => match super::split_first_token(remainder) {
@@ -128,6 +128,77 @@ pub fn parse_instruction(input: ParserInput) -> ParserResult<Instruction> {
     }
 }
 
+/// Parse the arguments of the given `command`, which was the first token of `input` and is
+/// followed by `remainder`.
+fn parse_command<'a>(
+    input: ParserInput<'a>,
+    command: Command,
+    remainder: ParserInput<'a>,
+) -> ParserResult<'a, Instruction> {
+    match command {
+        Command::Add => command::parse_arithmetic(ArithmeticOperator::Add, remainder),
+        Command::And => command::parse_logical_binary(BinaryOperator::And, remainder),
+        Command::Capture => command::parse_capture(remainder, true),
+        // Command::Convert => {}
+        Command::Declare => command::parse_declare(remainder),
+        Command::DefCal => command::parse_defcal(remainder),
+        Command::DefCircuit => command::parse_defcircuit(remainder),
+        Command::DefFrame => command::parse_defframe(remainder),
+        // Command::DefGate => Ok((remainder, cut(parse_command_defgate))),
+        Command::DefWaveform => command::parse_defwaveform(remainder),
+        Command::Delay => command::parse_delay(remainder),
+        Command::Div => command::parse_arithmetic(ArithmeticOperator::Divide, remainder),
+        Command::Eq => command::parse_comparison(ComparisonOperator::Equal, remainder),
+        Command::GE => command::parse_comparison(ComparisonOperator::GreaterThanOrEqual, remainder),
+        Command::GT => command::parse_comparison(ComparisonOperator::GreaterThan, remainder),
+        Command::LE => command::parse_comparison(ComparisonOperator::LessThanOrEqual, remainder),
+        Command::LT => command::parse_comparison(ComparisonOperator::LessThan, remainder),
+        Command::Fence => command::parse_fence(remainder),
+        Command::Halt => Ok((remainder, Instruction::Halt)),
+        // Command::Include => {}
+        Command::Ior => command::parse_logical_binary(BinaryOperator::Ior, remainder),
+        Command::Jump => command::parse_jump(remainder),
+        Command::JumpUnless => command::parse_jump_unless(remainder),
+        Command::JumpWhen => command::parse_jump_when(remainder),
+        Command::Label => command::parse_label(remainder),
+        Command::Load => command::parse_load(remainder),
+        Command::Measure => command::parse_measurement(remainder),
+        Command::Move => command::parse_move(remainder),
+        Command::Exchange => command::parse_exchange(remainder),
+        Command::Mul => command::parse_arithmetic(ArithmeticOperator::Multiply, remainder),
+        Command::Neg => command::parse_logical_unary(UnaryOperator::Neg, remainder),
+        // Command::Nop => {}
+        Command::Not => command::parse_logical_unary(UnaryOperator::Not, remainder),
+        Command::Pragma => command::parse_pragma(remainder),
+        Command::Pulse => command::parse_pulse(remainder, true),
+        Command::RawCapture => command::parse_raw_capture(remainder, true),
+        Command::Reset => command::parse_reset(remainder),
+        Command::SetFrequency => command::parse_set_frequency(remainder),
+        Command::SetPhase => command::parse_set_phase(remainder),
+        Command::SetScale => command::parse_set_scale(remainder),
+        Command::ShiftFrequency => command::parse_shift_frequency(remainder),
+        Command::ShiftPhase => command::parse_shift_phase(remainder),
+        Command::Store => command::parse_store(remainder),
+        Command::Sub => command::parse_arithmetic(ArithmeticOperator::Subtract, remainder),
+        // Command::Wait => {}
+        Command::Xor => command::parse_logical_binary(BinaryOperator::Xor, remainder),
+        // Returned directly so that this error is not obscured as a failure to parse
+        // the arguments of a supported command.
+        other => {
+            return Err(nom::Err::Failure(ParseError::from_kind(
+                &input[..1],
+                ParserErrorKind::unsupported_instruction(other, SUPPORTED_COMMANDS),
+            )))
+        }
+    }
+    .map_err(|err| {
+        nom::Err::Failure(
+            ParseError::from_kind(&input[..1], ParserErrorKind::InvalidCommand { command })
+                .with_previous(extract_nom_err(err)),
+        )
+    })
+}
+
 /// Parse all instructions from the input, trimming leading and trailing newlines and comments.
 /// Returns an error if it does not reach the end of input.
 pub fn parse_instructions(input: ParserInput) -> ParserResult<Vec<Instruction>> {
@@ -138,6 +209,28 @@ pub fn parse_instructions(input: ParserInput) -> ParserResult<Vec<Instruction>>
     ))(input)
 }
 
+/// Parse all instructions from the input as with [`parse_instructions`], along with the tokens
+/// from which each was parsed.
+pub fn parse_instructions_with_tokens<'a>(
+    input: ParserInput<'a>,
+) -> ParserResult<'a, Vec<(Instruction, ParserInput<'a>)>> {
+    all_consuming(terminated(
+        many0(parse_instruction_with_tokens),
+        common::skip_newlines_and_comments,
+    ))(input)
+}
+
+/// Parse the next instruction as with [`parse_instruction`], along with the tokens from which it
+/// was parsed, excluding any leading newlines, comments, and semicolons.
+fn parse_instruction_with_tokens<'a>(
+    input: ParserInput<'a>,
+) -> ParserResult<'a, (Instruction, ParserInput<'a>)> {
+    let (input, _) = common::skip_newlines_and_comments(input)?;
+    let (remainder, instruction) = parse_instruction(input)?;
+    let consumed = input.len() - remainder.len();
+    Ok((remainder, (instruction, &input[..consumed])))
+}
+
 /// Parse a block of indented "block instructions."
 pub fn parse_block(input: ParserInput) -> ParserResult<Vec<Instruction>> {
     many1(parse_block_instruction)(input)
@@ -160,11 +253,12 @@ mod tests {
     use crate::instruction::{
         Arithmetic, ArithmeticOperand, ArithmeticOperator, AttributeValue, BinaryLogic,
         BinaryOperand, BinaryOperator, Calibration, Capture, Comparison, ComparisonOperand,
-        ComparisonOperator, FrameDefinition, FrameIdentifier, Gate, Instruction, Jump, JumpWhen,
-        Label, MemoryReference, Move, Pulse, Qubit, RawCapture, Reset, SetFrequency, SetPhase,
-        SetScale, ShiftFrequency, ShiftPhase, UnaryLogic, UnaryOperator, Waveform,
-        WaveformDefinition, WaveformInvocation,
+        ComparisonOperator, FrameDefinition, FrameIdentifier, Gate, GateModifier, Instruction,
+        Jump, JumpWhen, Label, MeasureCalibrationDefinition, MemoryReference, Move, Pulse, Qubit,
+        RawCapture, Reset, SetFrequency, SetPhase, SetScale, ShiftFrequency, ShiftPhase, Target,
+        UnaryLogic, UnaryOperator, Waveform, WaveformDefinition, WaveformInvocation,
     };
+    use crate::parser::extract_nom_err;
     use crate::parser::lexer::lex;
     use crate::{make_test, real, Program};
 
@@ -398,6 +492,21 @@ mod tests {
         ]
     );
 
+    #[test]
+    fn unsupported_instruction() {
+        let tokens = lex("NOP").unwrap();
+        let err = extract_nom_err(parse_instructions(&tokens).unwrap_err());
+        let message = err.to_string();
+        assert!(
+            message.contains(
+                "instruction NOP is not yet supported by this parser (did you mean NOT?)"
+            ),
+            "{}",
+            message
+        );
+        assert!(message.contains("supported commands are: ADD, AND, CAPTURE"));
+    }
+
     #[test]
     fn test_binary_logic_error() {
         ["AND ro", "XOR 1 1", "IOR 1"].iter().for_each(|input| {
@@ -452,6 +561,55 @@ mod tests {
             })
     }
 
+    make_test!(
+        keyword_named_gates,
+        parse_instructions,
+        "WAIT 0 1\nDAGGER NOT 0",
+        vec![
+            Instruction::Gate(Gate {
+                name: "WAIT".to_owned(),
+                parameters: vec![],
+                qubits: vec![Qubit::Fixed(0), Qubit::Fixed(1)],
+                modifiers: vec![],
+            }),
+            Instruction::Gate(Gate {
+                name: "NOT".to_owned(),
+                parameters: vec![],
+                qubits: vec![Qubit::Fixed(0)],
+                modifiers: vec![GateModifier::Dagger],
+            }),
+        ]
+    );
+
+    make_test!(
+        keyword_named_waveforms,
+        parse_instructions,
+        "DEFWAVEFORM WAIT:\n\t1.0, 2.0\nPULSE 0 \"xy\" WAIT",
+        vec![
+            Instruction::WaveformDefinition(WaveformDefinition {
+                name: "WAIT".to_owned(),
+                definition: Waveform {
+                    matrix: vec![
+                        Expression::Number(real!(1.0)),
+                        Expression::Number(real!(2.0))
+                    ],
+                    parameters: vec![],
+                }
+            }),
+            Instruction::Pulse(Pulse {
+                blocking: true,
+                frame: FrameIdentifier {
+                    name: "xy".to_owned(),
+                    qubits: vec![Qubit::Fixed(0)]
+                },
+                waveform: WaveformInvocation {
+                    name: "WAIT".to_owned(),
+                    parameters: HashMap::new(),
+                }
+            }),
+        ]
+    );
+
     make_test!(
         capture_instructions,
         parse_instructions,
@@ -587,6 +745,26 @@ mod tests {
         })]
     );
 
+    make_test!(
+        measure_calibration,
+        parse_instructions,
+        "DEFCAL MEASURE q addr:\n\tRESET q\nDEFCAL MEASURE addr:\n\tRESET",
+        vec![
+            Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+                qubit: Some(Qubit::Variable("q".to_owned())),
+                parameter: "addr".to_owned(),
+                instructions: vec![Instruction::Reset(Reset {
+                    qubit: Some(Qubit::Variable("q".to_owned()))
+                })]
+            }),
+            Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+                qubit: None,
+                parameter: "addr".to_owned(),
+                instructions: vec![Instruction::Reset(Reset { qubit: None })]
+            })
+        ]
+    );
+
     make_test!(
         frame_definition,
         parse_instructions,
@@ -611,12 +789,12 @@ mod tests {
         parse_instructions,
         "LABEL @hello\nJUMP @hello\nJUMP-WHEN @hello ro",
         vec![
-            Instruction::Label(Label("hello".to_owned())),
+            Instruction::Label(Label(Target::Fixed("hello".to_owned()))),
             Instruction::Jump(Jump {
-                target: "hello".to_owned()
+                target: Target::Fixed("hello".to_owned())
             }),
             Instruction::JumpWhen(JumpWhen {
-                target: "hello".to_owned(),
+                target: Target::Fixed("hello".to_owned()),
                 condition: MemoryReference {
                     name: "ro".to_owned(),
                     index: 0
@@ -854,6 +1032,7 @@ mod tests {
             r#"DEFCAL MEASURE 0 dest:
 	DECLARE iq REAL[2]
 	CAPTURE 0 "out" flat(duration: 1.0, iqs: (2.0+3.0i)) iq[0]"#,
+            "DEFWAVEFORM WAIT:\n\t1.0, 2.0\nWAIT 0\nPULSE 0 \"xy\" WAIT",
         ];
 
         for input in inputs {
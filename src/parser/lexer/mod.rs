@@ -16,9 +16,9 @@ mod error;
 mod wrapped_parsers;
 
 use nom::{
-    bytes::complete::{is_a, is_not, take_until, take_while, take_while1},
+    bytes::complete::{escaped, is_a, is_not, take_while, take_while1},
     character::complete::{digit1, one_of},
-    combinator::{all_consuming, map, recognize, value},
+    combinator::{all_consuming, map, opt, recognize, value},
     multi::many0,
     number::complete::double,
     sequence::{delimited, preceded, terminated, tuple},
@@ -122,18 +122,64 @@ pub enum Operator {
     Star,
 }
 
+/// The set of characters which the lexer accepts within identifiers (including those used for
+/// labels and variables).
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum IdentifierPolicy {
+    /// Accept only identifiers conforming to the Quil specification: an ASCII letter or underscore,
+    /// followed by any number of ASCII letters, digits, underscores, and dashes.
+    #[default]
+    Strict,
+    /// Additionally accept any Unicode alphabetic character where a letter is allowed, and any
+    /// Unicode alphanumeric character where a letter or digit is allowed. This is useful for
+    /// ingesting nonconforming programs, such as those with Unicode `PRAGMA` arguments.
+    Permissive,
+}
+
+impl IdentifierPolicy {
+    fn is_valid_identifier_character(self, chr: char) -> bool {
+        self.is_valid_identifier_leading_character(chr)
+            || chr.is_ascii_digit()
+            || chr == '\\'
+            || chr == '-'
+            || (self == Self::Permissive && chr.is_alphanumeric())
+    }
+
+    fn is_valid_identifier_leading_character(self, chr: char) -> bool {
+        match self {
+            Self::Strict => chr.is_ascii_alphabetic() || chr == '_',
+            Self::Permissive => chr.is_alphabetic() || chr == '_',
+        }
+    }
+}
+
 type InternalLexError<'a> = nom::error::Error<LexInput<'a>>;
-pub type LexInput<'a> = LocatedSpan<&'a str>;
+pub type LexInput<'a> = LocatedSpan<&'a str, IdentifierPolicy>;
 pub type LexResult<'a, T = Token, E = LexError> = IResult<LexInput<'a>, T, E>;
 
 /// Completely lex a string, returning the tokens within. Panics if the string cannot be completely read.
 pub(crate) fn lex(input: &str) -> Result<Vec<TokenWithLocation>, LexError> {
-    let input = LocatedSpan::new(input);
+    lex_with_policy(input, IdentifierPolicy::default())
+}
+
+/// Completely lex a string, accepting identifiers according to the given [`IdentifierPolicy`].
+pub(crate) fn lex_with_policy(
+    input: &str,
+    policy: IdentifierPolicy,
+) -> Result<Vec<TokenWithLocation>, LexError> {
+    let input = LocatedSpan::new_extra(input, policy);
     all_consuming(_lex)(input)
         .finish()
         .map(|(_, tokens)| tokens)
 }
 
+/// Lex as much of a string as possible, returning the tokens within and ignoring any remaining
+/// input which cannot be lexed.
+pub(crate) fn lex_prefix(input: &str) -> Result<Vec<TokenWithLocation>, LexError> {
+    let input = LocatedSpan::new_extra(input, IdentifierPolicy::default());
+    _lex(input).finish().map(|(_, tokens)| tokens)
+}
+
 fn _lex(input: LexInput) -> LexResult<Vec<TokenWithLocation>> {
     terminated(
         many0(alt(
@@ -188,7 +234,7 @@ fn lex_comment(input: LexInput) -> LexResult {
 
 /// If the given identifier string matches a command keyword, return the keyword;
 /// otherwise, return the original identifier as a token.
-fn recognize_command_or_identifier(identifier: String) -> Token {
+pub(crate) fn recognize_command_or_identifier(identifier: String) -> Token {
     use Command::*;
 
     match identifier.as_str() {
@@ -243,21 +289,14 @@ fn recognize_command_or_identifier(identifier: String) -> Token {
     }
 }
 
-fn is_valid_identifier_character(chr: char) -> bool {
-    is_valid_identifier_leading_character(chr) || chr.is_ascii_digit() || chr == '\\' || chr == '-'
-}
-
-fn is_valid_identifier_leading_character(chr: char) -> bool {
-    chr.is_ascii_alphabetic() || chr == '_'
-}
-
 fn lex_identifier_raw(input: LexInput) -> LexResult<String> {
+    let policy = input.extra;
     expecting(
         "a valid identifier",
         map(
             tuple::<_, _, InternalLexError, _>((
-                take_while1(is_valid_identifier_leading_character),
-                take_while(is_valid_identifier_character),
+                take_while1(move |chr| policy.is_valid_identifier_leading_character(chr)),
+                take_while(move |chr| policy.is_valid_identifier_character(chr)),
             )),
             |(left, right)| format!("{}{}", left, right),
         ),
@@ -355,13 +394,33 @@ fn lex_punctuation(input: LexInput) -> LexResult {
     )(input)
 }
 
+/// Lex a string within double quotes, in which a double quote or backslash is escaped by a
+/// preceding backslash.
 fn lex_string(input: LexInput) -> LexResult {
     map(
-        delimited(tag("\""), take_until("\""), tag("\"")),
-        |v: LexInput| Token::String(v.to_string()),
+        delimited(
+            tag("\""),
+            opt(escaped(is_not("\\\""), '\\', one_of("\\\""))),
+            tag("\""),
+        ),
+        |v: Option<LexInput>| Token::String(v.map_or_else(String::new, |v| unescape(v.fragment()))),
     )(input)
 }
 
+/// Replace each escaped character within the contents of a string with the character itself.
+fn unescape(escaped: &str) -> String {
+    let mut unescaped = String::with_capacity(escaped.len());
+    let mut chars = escaped.chars();
+    while let Some(chr) = chars.next() {
+        if chr == '\\' {
+            unescaped.extend(chars.next());
+        } else {
+            unescaped.push(chr);
+        }
+    }
+    unescaped
+}
+
 fn lex_variable(input: LexInput) -> LexResult {
     map(preceded(tag("%"), lex_identifier_raw), |ident| {
         Token::Variable(ident)
@@ -370,7 +429,7 @@ fn lex_variable(input: LexInput) -> LexResult {
 
 #[cfg(test)]
 mod tests {
-    use super::{lex, Command, Operator, Token};
+    use super::{lex, lex_with_policy, Command, IdentifierPolicy, Operator, Token};
 
     #[test]
     fn comment() {
@@ -404,6 +463,22 @@ mod tests {
         )
     }
 
+    #[test]
+    fn identifier_policy() {
+        let input = "PRAGMA ÉCHO_q0 théta";
+        assert!(lex(input).is_err());
+
+        let tokens = lex_with_policy(input, IdentifierPolicy::Permissive).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Command(Command::Pragma),
+                Token::Identifier(String::from("ÉCHO_q0")),
+                Token::Identifier(String::from("théta")),
+            ]
+        )
+    }
+
     #[test]
     fn number() {
         let input = "2 2i 2.0 2e3 2.0e3 (1+2i)";
@@ -441,6 +516,20 @@ mod tests {
         )
     }
 
+    #[test]
+    fn escaped_string() {
+        let input = r#""" "say \"hi\"" "a\\b""#;
+        let tokens = lex(input).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::String(String::new()),
+                Token::String("say \"hi\"".to_owned()),
+                Token::String("a\\b".to_owned()),
+            ]
+        )
+    }
+
     #[test]
     fn gate_operation() {
         let input = "I 0; RX 1\nCZ 0 1";
@@ -15,8 +15,8 @@
 use nom::IResult;
 
 pub(crate) use expression::parse_expression;
-pub(crate) use instruction::parse_instructions;
-pub(crate) use lexer::lex;
+pub(crate) use instruction::{parse_instructions, parse_instructions_with_tokens};
+pub(crate) use lexer::{lex, lex_prefix, lex_with_policy};
 
 mod command;
 mod gate;
@@ -31,7 +31,7 @@ mod token;
 
 pub(crate) use error::ErrorInput;
 pub use error::{InternalParseError, ParseError, ParserErrorKind};
-pub use lexer::{LexError, LexErrorKind};
+pub use lexer::{IdentifierPolicy, LexError, LexErrorKind};
 pub use token::{Token, TokenWithLocation};
 
 type ParserInput<'a> = &'a [TokenWithLocation];
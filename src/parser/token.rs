@@ -1,6 +1,7 @@
 use crate::parser::lexer::{Command, DataType, LexInput, LexResult, Modifier, Operator};
 use std::fmt;
 use std::fmt::Formatter;
+use std::ops::Range;
 
 /// Wrapper for [`Token`] that includes file location information.
 #[derive(Debug, Clone, PartialEq)]
@@ -8,6 +9,7 @@ pub struct TokenWithLocation {
     token: Token,
     line: u32,
     column: usize,
+    span: Range<usize>,
 }
 
 impl PartialEq<Token> for TokenWithLocation {
@@ -36,6 +38,11 @@ impl TokenWithLocation {
     pub fn column(&self) -> usize {
         self.column
     }
+
+    /// The range of byte offsets within the lexed input which this token occupies.
+    pub fn span(&self) -> Range<usize> {
+        self.span.clone()
+    }
 }
 
 impl nom::InputLength for TokenWithLocation {
@@ -58,14 +65,17 @@ where
         // TODO: naive_get_utf8_column might be faster for shorter lines
         // See: https://github.com/rigetti/quil-rs/issues/93
         let column = input.get_utf8_column();
+        let start = input.location_offset();
         // Using this syntax because map(parser, || ...)(input) has lifetime issues for parser.
         parser.parse(input).map(|(leftover, token)| {
+            let end = leftover.location_offset();
             (
                 leftover,
                 TokenWithLocation {
                     token,
                     line,
                     column,
+                    span: start..end,
                 },
             )
         })
@@ -0,0 +1,547 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed representations of pragmas with well-known meanings, which are otherwise parsed as
+//! opaque [`Pragma`]s.
+
+use std::any::Any;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use num_complex::Complex64;
+use thiserror::Error;
+
+use crate::expression::Expression;
+use crate::gates::Matrix;
+use crate::instruction::{Instruction, Pragma, Qubit};
+
+/// A problem in interpreting a pragma as a typed pragma.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum PragmaError {
+    #[error("PRAGMA {0} is not of the expected kind")]
+    UnexpectedName(String),
+
+    #[error("PRAGMA {name} is invalid: {reason}")]
+    Invalid { name: String, reason: String },
+}
+
+fn invalid(name: &str, reason: impl Into<String>) -> PragmaError {
+    PragmaError::Invalid {
+        name: name.to_owned(),
+        reason: reason.into(),
+    }
+}
+
+fn parse_qubit(argument: &str) -> Qubit {
+    match argument.parse() {
+        Ok(index) => Qubit::Fixed(index),
+        Err(_) => Qubit::Variable(argument.to_owned()),
+    }
+}
+
+/// Parse the entries of a matrix written in row-major order within parentheses and separated by
+/// spaces, as in `"(1.0 0.0 0.0 1.0)"`, into a square matrix.
+fn parse_matrix(name: &str, data: Option<&str>) -> Result<Matrix, PragmaError> {
+    let data = data
+        .ok_or_else(|| invalid(name, "it has no matrix"))?
+        .trim();
+    let entries = data
+        .strip_prefix('(')
+        .and_then(|data| data.strip_suffix(')'))
+        .ok_or_else(|| invalid(name, "its matrix is not within parentheses"))?
+        .split_whitespace()
+        .map(|entry| {
+            Expression::from_str(entry)
+                .ok()
+                .and_then(|expression| expression.evaluate(&HashMap::new(), &HashMap::new()).ok())
+                .ok_or_else(|| invalid(name, format!("{} is not a number", entry)))
+        })
+        .collect::<Result<Vec<Complex64>, _>>()?;
+
+    let size = (entries.len() as f64).sqrt() as usize;
+    if size == 0 || size * size != entries.len() {
+        return Err(invalid(
+            name,
+            format!("its {} matrix entries do not form a square", entries.len()),
+        ));
+    }
+    Ok(entries.chunks(size).map(<[Complex64]>::to_vec).collect())
+}
+
+/// Format a number as a single entry of a pragma's matrix.
+fn format_entry(value: &Complex64) -> String {
+    match (value.re, value.im) {
+        (re, 0.0) => format!("{:?}", re),
+        (0.0, im) => format!("{:?}i", im),
+        (re, im) if im < 0.0 => format!("{:?}-{:?}i", re, -im),
+        (re, im) => format!("{:?}+{:?}i", re, im),
+    }
+}
+
+fn format_matrix(matrix: &[Vec<Complex64>]) -> String {
+    let entries: Vec<String> = matrix.iter().flatten().map(format_entry).collect();
+    format!("({})", entries.join(" "))
+}
+
+/// `PRAGMA ADD-KRAUS`: one Kraus operator of the noise channel which follows each application of
+/// a gate to the given qubits. A channel of several operators is described by several pragmas.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AddKraus {
+    pub gate: String,
+    pub qubits: Vec<Qubit>,
+    pub matrix: Matrix,
+}
+
+/// `PRAGMA READOUT-POVM`: the confusion matrix of the readout of a qubit, in which the entry at
+/// row `i` and column `j` is the probability of reading out `i` given that the qubit is in state
+/// `j`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReadoutPovm {
+    pub qubit: Qubit,
+    pub matrix: Vec<Vec<f64>>,
+}
+
+/// A pragma which describes or suppresses noise, for use by simulators.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NoisePragma {
+    AddKraus(AddKraus),
+    ReadoutPovm(ReadoutPovm),
+    /// `PRAGMA NO-NOISE`: simulate the program without noise.
+    NoNoise,
+}
+
+impl NoisePragma {
+    /// Return the name of the pragma, as written after `PRAGMA`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NoisePragma::AddKraus(_) => "ADD-KRAUS",
+            NoisePragma::ReadoutPovm(_) => "READOUT-POVM",
+            NoisePragma::NoNoise => "NO-NOISE",
+        }
+    }
+}
+
+impl TryFrom<&Pragma> for NoisePragma {
+    type Error = PragmaError;
+
+    fn try_from(pragma: &Pragma) -> Result<Self, Self::Error> {
+        let name = pragma.name.as_str();
+        match name {
+            "ADD-KRAUS" => {
+                let (gate, qubits) = pragma
+                    .arguments
+                    .split_first()
+                    .ok_or_else(|| invalid(name, "it names no gate"))?;
+                let qubits: Vec<Qubit> = qubits.iter().map(|qubit| parse_qubit(qubit)).collect();
+                let matrix = parse_matrix(name, pragma.data.as_deref())?;
+                // The matrix must have 2^n rows for n qubits, compared without computing 2^n,
+                // which would overflow for many qubits.
+                if qubits.is_empty()
+                    || !matrix.len().is_power_of_two()
+                    || matrix.len().trailing_zeros() as usize != qubits.len()
+                {
+                    return Err(invalid(
+                        name,
+                        format!(
+                            "a {}x{} matrix cannot act on {} qubits",
+                            matrix.len(),
+                            matrix.len(),
+                            qubits.len()
+                        ),
+                    ));
+                }
+                Ok(NoisePragma::AddKraus(AddKraus {
+                    gate: gate.clone(),
+                    qubits,
+                    matrix,
+                }))
+            }
+            "READOUT-POVM" => {
+                let qubit = match pragma.arguments.as_slice() {
+                    [qubit] => parse_qubit(qubit),
+                    _ => return Err(invalid(name, "it must name exactly one qubit")),
+                };
+                let matrix = parse_matrix(name, pragma.data.as_deref())?;
+                if matrix.len() != 2 {
+                    return Err(invalid(name, "its matrix must be 2x2"));
+                }
+                let matrix = matrix
+                    .into_iter()
+                    .map(|row| {
+                        row.into_iter()
+                            .map(|entry| {
+                                if entry.im == 0.0 {
+                                    Ok(entry.re)
+                                } else {
+                                    Err(invalid(name, "its matrix must be real"))
+                                }
+                            })
+                            .collect()
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(NoisePragma::ReadoutPovm(ReadoutPovm { qubit, matrix }))
+            }
+            "NO-NOISE" => {
+                if pragma.arguments.is_empty() && pragma.data.is_none() {
+                    Ok(NoisePragma::NoNoise)
+                } else {
+                    Err(invalid(name, "it takes no arguments"))
+                }
+            }
+            _ => Err(PragmaError::UnexpectedName(pragma.name.clone())),
+        }
+    }
+}
+
+impl TryFrom<&Instruction> for NoisePragma {
+    type Error = PragmaError;
+
+    fn try_from(instruction: &Instruction) -> Result<Self, Self::Error> {
+        match instruction {
+            Instruction::Pragma(pragma) => Self::try_from(pragma),
+            other => Err(PragmaError::UnexpectedName(other.to_string())),
+        }
+    }
+}
+
+impl From<&NoisePragma> for Pragma {
+    fn from(noise: &NoisePragma) -> Self {
+        let (arguments, data) = match noise {
+            NoisePragma::AddKraus(AddKraus {
+                gate,
+                qubits,
+                matrix,
+            }) => (
+                std::iter::once(gate.clone())
+                    .chain(qubits.iter().map(ToString::to_string))
+                    .collect(),
+                Some(format_matrix(matrix)),
+            ),
+            NoisePragma::ReadoutPovm(ReadoutPovm { qubit, matrix }) => {
+                let matrix: Matrix = matrix
+                    .iter()
+                    .map(|row| row.iter().map(|&entry| Complex64::from(entry)).collect())
+                    .collect();
+                (vec![qubit.to_string()], Some(format_matrix(&matrix)))
+            }
+            NoisePragma::NoNoise => (vec![], None),
+        };
+        Pragma {
+            name: noise.name().to_owned(),
+            arguments,
+            data,
+        }
+    }
+}
+
+impl From<NoisePragma> for Instruction {
+    fn from(noise: NoisePragma) -> Self {
+        Instruction::Pragma(Pragma::from(&noise))
+    }
+}
+
+impl fmt::Display for NoisePragma {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Instruction::Pragma(Pragma::from(self)))
+    }
+}
+
+/// A structured representation of a pragma, as produced by the parsers of a [`PragmaRegistry`].
+///
+/// Use [`TypedPragma::as_any`] to recover the concrete type of a parsed pragma.
+pub trait TypedPragma: Any + fmt::Debug {
+    /// Return the pragma in its opaque form, from which it may be printed or parsed again.
+    fn to_pragma(&self) -> Pragma;
+
+    /// Return the pragma as [`Any`], so that it may be downcast to its concrete type.
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// An unrecognized pragma is its own, opaque, typed representation.
+impl TypedPragma for Pragma {
+    fn to_pragma(&self) -> Pragma {
+        self.clone()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl TypedPragma for NoisePragma {
+    fn to_pragma(&self) -> Pragma {
+        Pragma::from(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl fmt::Display for dyn TypedPragma {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Instruction::Pragma(self.to_pragma()))
+    }
+}
+
+/// A function which interprets a pragma of a particular name as a [`TypedPragma`].
+pub type PragmaParser = fn(&Pragma) -> Result<Box<dyn TypedPragma>, PragmaError>;
+
+fn parse_noise_pragma(pragma: &Pragma) -> Result<Box<dyn TypedPragma>, PragmaError> {
+    Ok(Box::new(NoisePragma::try_from(pragma)?))
+}
+
+/// A set of parsers for pragmas, by pragma name, used to give structure to pragmas with
+/// domain-specific meanings. Pragmas without a registered parser are left opaque.
+///
+/// ```rust
+/// use quil_rs::instruction::Pragma;
+/// use quil_rs::pragma::{NoisePragma, PragmaRegistry};
+///
+/// let registry = PragmaRegistry::new().with_noise_pragmas();
+/// let pragma = Pragma {
+///     name: "NO-NOISE".to_owned(),
+///     arguments: vec![],
+///     data: None,
+/// };
+/// let typed = registry.parse(&pragma).unwrap();
+/// assert_eq!(typed.as_any().downcast_ref::<NoisePragma>(), Some(&NoisePragma::NoNoise));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PragmaRegistry {
+    parsers: HashMap<String, PragmaParser>,
+}
+
+impl PragmaRegistry {
+    /// Create a registry with no parsers, in which every pragma is opaque.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the parsers of the [`NoisePragma`]s.
+    pub fn with_noise_pragmas(mut self) -> Self {
+        for name in &["ADD-KRAUS", "READOUT-POVM", "NO-NOISE"] {
+            self.register(name, parse_noise_pragma);
+        }
+        self
+    }
+
+    /// Register the parser for pragmas of the given name, replacing any parser already
+    /// registered for that name.
+    pub fn register(&mut self, name: &str, parser: PragmaParser) {
+        self.parsers.insert(name.to_owned(), parser);
+    }
+
+    /// Return whether a parser is registered for pragmas of the given name.
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.parsers.contains_key(name)
+    }
+
+    /// Interpret a pragma with the parser registered for its name. A pragma with no registered
+    /// parser is returned as it is, in its opaque form.
+    pub fn parse(&self, pragma: &Pragma) -> Result<Box<dyn TypedPragma>, PragmaError> {
+        match self.parsers.get(&pragma.name) {
+            Some(parser) => parser(pragma),
+            None => Ok(Box::new(pragma.clone())),
+        }
+    }
+
+    /// Interpret each of the pragmas among the given instructions, in order.
+    pub fn parse_all<'a>(
+        &self,
+        instructions: impl IntoIterator<Item = &'a Instruction>,
+    ) -> Result<Vec<Box<dyn TypedPragma>>, PragmaError> {
+        instructions
+            .into_iter()
+            .filter_map(|instruction| match instruction {
+                Instruction::Pragma(pragma) => Some(self.parse(pragma)),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+    use std::convert::TryFrom;
+
+    use crate::instruction::{Instruction, Pragma, Qubit};
+    use crate::{imag, real};
+
+    use super::{AddKraus, NoisePragma, PragmaError, PragmaRegistry, ReadoutPovm, TypedPragma};
+
+    fn parse(text: &str) -> Result<NoisePragma, PragmaError> {
+        NoisePragma::try_from(&Instruction::parse(text).unwrap())
+    }
+
+    #[test]
+    fn noise_pragmas() {
+        let kraus = parse("PRAGMA ADD-KRAUS X 0 \"(0.0 0.5i 0.5-0.5i 1e-3)\"").unwrap();
+        assert_eq!(
+            kraus,
+            NoisePragma::AddKraus(AddKraus {
+                gate: "X".to_owned(),
+                qubits: vec![Qubit::Fixed(0)],
+                matrix: vec![
+                    vec![real!(0.0), imag!(0.5)],
+                    vec![real!(0.5) - imag!(0.5), real!(0.001)]
+                ],
+            })
+        );
+        assert_eq!(
+            kraus.to_string(),
+            "PRAGMA ADD-KRAUS X 0 \"(0.0 0.5i 0.5-0.5i 0.001)\""
+        );
+
+        let povm = parse("PRAGMA READOUT-POVM 1 \"(0.9 0.2 0.1 0.8)\"").unwrap();
+        assert_eq!(
+            povm,
+            NoisePragma::ReadoutPovm(ReadoutPovm {
+                qubit: Qubit::Fixed(1),
+                matrix: vec![vec![0.9, 0.2], vec![0.1, 0.8]],
+            })
+        );
+        assert_eq!(
+            povm.to_string(),
+            "PRAGMA READOUT-POVM 1 \"(0.9 0.2 0.1 0.8)\""
+        );
+
+        let no_noise = parse("PRAGMA NO-NOISE").unwrap();
+        assert_eq!(no_noise, NoisePragma::NoNoise);
+        assert_eq!(no_noise.to_string(), "PRAGMA NO-NOISE");
+
+        for pragma in &[kraus, povm, no_noise] {
+            assert_eq!(&parse(&pragma.to_string()).unwrap(), pragma);
+        }
+    }
+
+    #[test]
+    fn invalid_noise_pragmas() {
+        let invalid = [
+            "PRAGMA ADD-KRAUS X 0 1 \"(1.0 0.0 0.0 1.0)\"",
+            "PRAGMA ADD-KRAUS X 0 \"(1.0 0.0 0.0)\"",
+            "PRAGMA ADD-KRAUS X 0 \"1.0 0.0 0.0 1.0\"",
+            "PRAGMA ADD-KRAUS X 0",
+            "PRAGMA READOUT-POVM 0 \"(0.9 0.2i 0.1 0.8)\"",
+            "PRAGMA READOUT-POVM 0 1 \"(0.9 0.2 0.1 0.8)\"",
+            "PRAGMA NO-NOISE 0",
+        ];
+        for text in invalid.iter() {
+            assert!(
+                matches!(parse(text), Err(PragmaError::Invalid { .. })),
+                "{}",
+                text
+            );
+        }
+        for count in [63, 64, 65, 128] {
+            let qubits: Vec<String> = (0..count).map(|qubit| qubit.to_string()).collect();
+            let text = format!(
+                "PRAGMA ADD-KRAUS X {} \"(1.0 0.0 0.0 1.0)\"",
+                qubits.join(" ")
+            );
+            assert!(
+                matches!(parse(&text), Err(PragmaError::Invalid { .. })),
+                "{} qubits",
+                count
+            );
+        }
+        assert_eq!(
+            parse("PRAGMA INITIAL_REWIRING \"NAIVE\""),
+            Err(PragmaError::UnexpectedName("INITIAL_REWIRING".to_owned()))
+        );
+    }
+
+    /// A pragma which names the qubits to be reset before the program is run.
+    #[derive(Debug, PartialEq)]
+    struct Reset(Vec<u64>);
+
+    impl TypedPragma for Reset {
+        fn to_pragma(&self) -> Pragma {
+            Pragma {
+                name: "RESET-QUBITS".to_owned(),
+                arguments: self.0.iter().map(ToString::to_string).collect(),
+                data: None,
+            }
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    fn parse_reset(pragma: &Pragma) -> Result<Box<dyn TypedPragma>, PragmaError> {
+        let qubits = pragma
+            .arguments
+            .iter()
+            .map(|argument| {
+                argument.parse().map_err(|_| PragmaError::Invalid {
+                    name: pragma.name.clone(),
+                    reason: format!("{} is not a qubit", argument),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Box::new(Reset(qubits)))
+    }
+
+    #[test]
+    fn registry() {
+        let mut registry = PragmaRegistry::new().with_noise_pragmas();
+        registry.register("RESET-QUBITS", parse_reset);
+        assert!(registry.is_registered("RESET-QUBITS"));
+        assert!(!registry.is_registered("INITIAL_REWIRING"));
+
+        let instructions: Vec<Instruction> = vec![
+            "PRAGMA RESET-QUBITS 0 1",
+            "H 0",
+            "PRAGMA NO-NOISE",
+            "PRAGMA INITIAL_REWIRING \"NAIVE\"",
+        ]
+        .into_iter()
+        .map(|text| Instruction::parse(text).unwrap())
+        .collect();
+        let pragmas = registry.parse_all(&instructions).unwrap();
+
+        assert_eq!(pragmas.len(), 3);
+        assert_eq!(
+            pragmas[0].as_any().downcast_ref::<Reset>(),
+            Some(&Reset(vec![0, 1]))
+        );
+        assert_eq!(
+            pragmas[1].as_any().downcast_ref::<NoisePragma>(),
+            Some(&NoisePragma::NoNoise)
+        );
+        let opaque: Option<&Pragma> = pragmas[2].as_any().downcast_ref();
+        assert_eq!(
+            opaque.map(|pragma| pragma.name.as_str()),
+            Some("INITIAL_REWIRING")
+        );
+        for (pragma, instruction) in
+            pragmas
+                .iter()
+                .zip(vec![&instructions[0], &instructions[2], &instructions[3]])
+        {
+            assert_eq!(pragma.to_string(), instruction.to_string());
+        }
+
+        let invalid = Instruction::parse("PRAGMA RESET-QUBITS q").unwrap();
+        assert!(matches!(
+            registry.parse_all(&[invalid]),
+            Err(PragmaError::Invalid { .. })
+        ));
+        assert!(PragmaRegistry::new().parse_all(&instructions[..3]).is_ok());
+    }
+}
@@ -0,0 +1,166 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::instruction::{
+    Capture, Delay, Fence, Gate, Instruction, Label, Measurement, Pragma, Pulse, RawCapture, Reset,
+};
+
+use super::Program;
+
+/// Generate a method on [`Program`] which iterates, in order, over those of its instructions
+/// which are of a single variant, yielding the contents of each.
+macro_rules! typed_accessor {
+    ($(#[$meta:meta])* $name:ident, $variant:ident, $type:ty) => {
+        $(#[$meta])*
+        pub fn $name(&self) -> impl Iterator<Item = &$type> {
+            self.instructions
+                .iter()
+                .filter_map(|instruction| match instruction {
+                    Instruction::$variant(inner) => Some(inner),
+                    _ => None,
+                })
+        }
+    };
+}
+
+// Typed views over the program's instructions, which spare callers from matching on
+// `Instruction` at every call site. Like `Program::instructions`, these exclude the program's
+// header definitions and any instructions nested within blocks (such as within `DEFCAL`).
+impl Program {
+    typed_accessor!(
+        /// Iterate over the program's gates, in order.
+        gates,
+        Gate,
+        Gate
+    );
+
+    typed_accessor!(
+        /// Iterate over the program's measurements, in order.
+        measurements,
+        Measurement,
+        Measurement
+    );
+
+    typed_accessor!(
+        /// Iterate over the program's resets, in order.
+        resets,
+        Reset,
+        Reset
+    );
+
+    typed_accessor!(
+        /// Iterate over the program's pulses, in order.
+        pulses,
+        Pulse,
+        Pulse
+    );
+
+    typed_accessor!(
+        /// Iterate over the program's captures, in order.
+        captures,
+        Capture,
+        Capture
+    );
+
+    typed_accessor!(
+        /// Iterate over the program's raw captures, in order.
+        raw_captures,
+        RawCapture,
+        RawCapture
+    );
+
+    typed_accessor!(
+        /// Iterate over the program's delays, in order.
+        delays,
+        Delay,
+        Delay
+    );
+
+    typed_accessor!(
+        /// Iterate over the program's fences, in order.
+        fences,
+        Fence,
+        Fence
+    );
+
+    typed_accessor!(
+        /// Iterate over the program's pragmas, in order.
+        pragmas,
+        Pragma,
+        Pragma
+    );
+
+    typed_accessor!(
+        /// Iterate over the program's labels, in order.
+        labels,
+        Label,
+        Label
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::instruction::{Qubit, Reset};
+    use crate::Program;
+
+    #[test]
+    fn typed_accessors() {
+        let program = Program::from_str(
+            "DECLARE ro BIT[2]
+DEFCAL X 0:
+    PULSE 0 \"rf\" flat(duration: 1.0, iq: 1)
+PRAGMA INITIAL_REWIRING \"NAIVE\"
+RESET
+H 0
+LABEL @loop
+CNOT 0 1
+PULSE 1 \"rf\" flat(duration: 1.0, iq: 1)
+MEASURE 0 ro[0]
+MEASURE 1 ro[1]
+RESET 1
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            program
+                .gates()
+                .map(|gate| gate.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["H", "CNOT"]
+        );
+        assert_eq!(
+            program
+                .measurements()
+                .map(|measurement| &measurement.qubit)
+                .collect::<Vec<_>>(),
+            vec![&Qubit::Fixed(0), &Qubit::Fixed(1)]
+        );
+        assert_eq!(
+            program.resets().collect::<Vec<_>>(),
+            vec![
+                &Reset { qubit: None },
+                &Reset {
+                    qubit: Some(Qubit::Fixed(1))
+                }
+            ]
+        );
+        assert_eq!(program.pulses().count(), 1);
+        assert_eq!(program.pragmas().count(), 1);
+        assert_eq!(program.labels().count(), 1);
+        assert_eq!(program.captures().count(), 0);
+    }
+}
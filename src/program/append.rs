@@ -0,0 +1,262 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::instruction::{
+    Declaration, FrameDefinition, Instruction, Jump, JumpUnless, JumpWhen, Label, Target,
+    WaveformDefinition,
+};
+
+use super::{LabelGenerator, MemoryRegion, Program, ProgramError, Result};
+
+/// Return all fixed labels defined or targeted by the instructions.
+fn get_labels<'a>(instructions: impl IntoIterator<Item = &'a Instruction>) -> HashSet<String> {
+    instructions
+        .into_iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Label(Label(Target::Fixed(label)))
+            | Instruction::Jump(Jump {
+                target: Target::Fixed(label),
+            })
+            | Instruction::JumpWhen(JumpWhen {
+                target: Target::Fixed(label),
+                ..
+            })
+            | Instruction::JumpUnless(JumpUnless {
+                target: Target::Fixed(label),
+                ..
+            }) => Some(label.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn declaration(name: &str, region: &MemoryRegion) -> Instruction {
+    Instruction::Declaration(Declaration {
+        name: name.to_owned(),
+        size: region.size.clone(),
+        sharing: region.sharing.clone(),
+    })
+}
+
+impl Program {
+    /// Append the instructions of `other` to this program, merging its declarations and
+    /// definitions into this program's own.
+    ///
+    /// Any label defined or targeted within `other` which is also used within this program is
+    /// renamed throughout `other`, by appending the smallest numeric suffix (such as `@loop_1`)
+    /// which makes it unique. `DECLARE`, `DEFFRAME`, `DEFWAVEFORM`, and `DEFCAL` definitions in
+    /// `other` which are identical to those in this program are merged into one.
+    ///
+    /// Return an error, leaving this program unchanged, if `other` redefines a memory region,
+    /// frame, waveform, or calibration of this program differently.
+    pub fn append(&mut self, other: Program) -> Result<()> {
+        let conflict = |existing, new| ProgramError::ConflictingDefinition { existing, new };
+
+        for (name, region) in &other.memory_regions {
+            match self.memory_regions.get(name) {
+                Some(existing) if existing != region => {
+                    return Err(conflict(
+                        declaration(name, existing),
+                        declaration(name, region),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        for (identifier, attributes) in other.frames.iter() {
+            match self.frames.get(identifier) {
+                Some(existing) if existing != attributes => {
+                    return Err(conflict(
+                        Instruction::FrameDefinition(FrameDefinition {
+                            identifier: identifier.clone(),
+                            attributes: existing.clone(),
+                        }),
+                        Instruction::FrameDefinition(FrameDefinition {
+                            identifier: identifier.clone(),
+                            attributes: attributes.clone(),
+                        }),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        for (name, definition) in other.waveforms.iter() {
+            match self.waveforms.get(name) {
+                Some(existing) if existing != definition => {
+                    return Err(conflict(
+                        Instruction::WaveformDefinition(WaveformDefinition {
+                            name: name.clone(),
+                            definition: existing.clone(),
+                        }),
+                        Instruction::WaveformDefinition(WaveformDefinition {
+                            name: name.clone(),
+                            definition: definition.clone(),
+                        }),
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        // Calibrations are merged last among the checks, as merging also adds them.
+        self.calibrations
+            .merge(other.calibrations)
+            .map_err(|(existing, new)| conflict(existing, new))?;
+
+        self.memory_regions.extend(other.memory_regions);
+        for (identifier, attributes) in other.frames.iter() {
+            self.frames.insert(identifier.clone(), attributes.clone());
+        }
+        self.waveforms.extend(other.waveforms.into_inner());
+
+        let used_labels = get_labels(&self.instructions);
+        let other_labels = get_labels(&other.instructions);
+        let mut generator = LabelGenerator::new(used_labels.union(&other_labels).cloned());
+        let mut renames: HashMap<String, String> = HashMap::new();
+        for label in other_labels.intersection(&used_labels) {
+            renames.insert(label.clone(), generator.fresh(label));
+        }
+
+        for mut instruction in other.instructions {
+            if let Some(Target::Fixed(label)) = instruction.get_target_mut() {
+                if let Some(renamed) = renames.get(label.as_str()) {
+                    *label = renamed.clone();
+                }
+            }
+            self.instructions.push(instruction);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::instruction::Instruction;
+    use crate::program::ProgramError;
+    use crate::Program;
+
+    #[test]
+    fn append_renames_labels_and_merges_definitions() {
+        let mut program = Program::from_str(
+            "DECLARE ro BIT
+DEFFRAME 0 \"rf\":
+    SAMPLE-RATE: 1.0
+DEFCAL X 0:
+    PULSE 0 \"rf\" flat(duration: 1.0, iq: 1)
+LABEL @loop
+X 0
+JUMP @loop
+",
+        )
+        .unwrap();
+        let other = Program::from_str(
+            "DECLARE ro BIT
+DECLARE theta REAL
+DEFFRAME 0 \"rf\":
+    SAMPLE-RATE: 1.0
+DEFCAL X 0:
+    PULSE 0 \"rf\" flat(duration: 1.0, iq: 1)
+LABEL @loop
+LABEL @loop_1
+JUMP-WHEN @loop ro[0]
+JUMP @loop_1
+",
+        )
+        .unwrap();
+        program.append(other).unwrap();
+
+        let expected = Program::from_str(
+            "DECLARE ro BIT
+DECLARE theta REAL
+DEFFRAME 0 \"rf\":
+    SAMPLE-RATE: 1.0
+DEFCAL X 0:
+    PULSE 0 \"rf\" flat(duration: 1.0, iq: 1)
+LABEL @loop
+X 0
+JUMP @loop
+LABEL @loop_2
+LABEL @loop_1
+JUMP-WHEN @loop_2 ro[0]
+JUMP @loop_1
+",
+        )
+        .unwrap();
+        assert_eq!(program, expected);
+    }
+
+    #[test]
+    fn append_rejects_conflicting_definitions() {
+        let original = Program::from_str("DECLARE ro BIT[2]\nDEFWAVEFORM w:\n    1, 2\n").unwrap();
+
+        for (input, existing, new) in [
+            ("DECLARE ro REAL", "DECLARE ro BIT[2]", "DECLARE ro REAL[1]"),
+            (
+                "DEFWAVEFORM w:\n    2, 1",
+                "DEFWAVEFORM w:\n\t1, 2",
+                "DEFWAVEFORM w:\n\t2, 1",
+            ),
+        ] {
+            let mut program = original.clone();
+            let error = program
+                .append(Program::from_str(input).unwrap())
+                .unwrap_err();
+            match error {
+                ProgramError::ConflictingDefinition {
+                    existing: actual_existing,
+                    new: actual_new,
+                } => {
+                    assert_eq!(actual_existing.to_string(), existing);
+                    assert_eq!(actual_new.to_string(), new);
+                }
+                other => panic!("unexpected error {:?}", other),
+            }
+            assert_eq!(program, original);
+        }
+
+        let mut program = Program::from_str("DEFCAL X 0:\n    PRAGMA FIRST\n").unwrap();
+        let error = program
+            .append(Program::from_str("DEFCAL X 0:\n    PRAGMA SECOND\n").unwrap())
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ProgramError::ConflictingDefinition {
+                new: Instruction::CalibrationDefinition(_),
+                ..
+            }
+        ));
+
+        // Measurement calibrations are selected by their qubit alone, so one which differs only
+        // in its target parameter still redefines the other.
+        let mut program =
+            Program::from_str("DEFCAL MEASURE 0 addr:\n    PRAGMA FIRST addr\n").unwrap();
+        let error = program
+            .append(Program::from_str("DEFCAL MEASURE 0 dest:\n    PRAGMA SECOND dest\n").unwrap())
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            ProgramError::ConflictingDefinition {
+                new: Instruction::MeasureCalibrationDefinition(_),
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,144 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::convert::TryFrom;
+
+use thiserror::Error;
+
+use super::Program;
+
+/// The bytes which begin every program encoded by [`Program::to_binary`].
+const MAGIC: &[u8; 4] = b"QUIL";
+
+/// The version of this crate, which must have encoded a program for it to be decoded. The
+/// encoding follows the layout of the instruction types, and so may change with any release.
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// A problem in decoding a program.
+#[derive(Debug, Error)]
+pub enum ProgramBinaryError {
+    #[error("the bytes do not begin with an encoded program header")]
+    InvalidHeader,
+
+    #[error("the program was encoded by quil-rs {encoded}, but can only be decoded by the same version, not {current}")]
+    VersionMismatch {
+        encoded: String,
+        current: &'static str,
+    },
+
+    #[error("invalid program encoding: {0}")]
+    Encoding(#[from] rmp_serde::decode::Error),
+}
+
+impl Program {
+    /// Encode the program in a compact binary form, for caching a parsed program to be loaded
+    /// again with [`Program::from_binary`] much more quickly than it could be parsed.
+    ///
+    /// The encoding is specific to the version of this crate which wrote it, and is not suitable
+    /// for exchange or long-term storage; use [`Program::to_json`] for those. The version is
+    /// recorded in a short header, so that a stale cache is detected rather than misread.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str("DECLARE ro BIT\nMEASURE 0 ro").unwrap();
+    /// let bytes = program.to_binary();
+    /// assert_eq!(Program::from_binary(&bytes).unwrap(), program);
+    /// ```
+    pub fn to_binary(&self) -> Vec<u8> {
+        let version = CRATE_VERSION.as_bytes();
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(u8::try_from(version.len()).expect("the crate version is short"));
+        bytes.extend_from_slice(version);
+        rmp_serde::encode::write(&mut bytes, self).expect("a program is always encodable");
+        bytes
+    }
+
+    /// Decode a program encoded by [`Program::to_binary`] with the same version of this crate.
+    pub fn from_binary(bytes: &[u8]) -> Result<Self, ProgramBinaryError> {
+        let rest = bytes
+            .strip_prefix(MAGIC)
+            .ok_or(ProgramBinaryError::InvalidHeader)?;
+        let (&length, rest) = rest
+            .split_first()
+            .ok_or(ProgramBinaryError::InvalidHeader)?;
+        let length = usize::from(length);
+        if rest.len() < length {
+            return Err(ProgramBinaryError::InvalidHeader);
+        }
+        let (version, encoded) = rest.split_at(length);
+        if version != CRATE_VERSION.as_bytes() {
+            return Err(ProgramBinaryError::VersionMismatch {
+                encoded: String::from_utf8_lossy(version).into_owned(),
+                current: CRATE_VERSION,
+            });
+        }
+
+        Ok(rmp_serde::from_slice(encoded)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::Program;
+
+    use super::{ProgramBinaryError, CRATE_VERSION, MAGIC};
+
+    #[test]
+    fn binary() {
+        let program = Program::from_str(
+            r#"DECLARE theta REAL[2]
+DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+DEFWAVEFORM ramp:
+    0, 0.5, 1
+DEFCAL RX(%theta) q:
+    SHIFT-PHASE q "rf" %theta
+    PULSE q "rf" ramp
+LABEL @start
+RX(theta[1]) 0
+CONTROLLED H 1 0
+JUMP-WHEN @start theta[0]
+"#,
+        )
+        .unwrap();
+
+        let bytes = program.to_binary();
+        assert_eq!(&bytes[..4], MAGIC);
+        assert_eq!(Program::from_binary(&bytes).unwrap(), program);
+
+        assert!(matches!(
+            Program::from_binary(b"QUI"),
+            Err(ProgramBinaryError::InvalidHeader)
+        ));
+
+        let mut stale = MAGIC.to_vec();
+        stale.push(5);
+        stale.extend_from_slice(b"0.0.1");
+        stale.extend_from_slice(&bytes[5 + CRATE_VERSION.len()..]);
+        assert!(matches!(
+            Program::from_binary(&stale),
+            Err(ProgramBinaryError::VersionMismatch { encoded, .. }) if encoded == "0.0.1"
+        ));
+
+        let truncated = &bytes[..bytes.len() - 1];
+        assert!(matches!(
+            Program::from_binary(truncated),
+            Err(ProgramBinaryError::Encoding(_))
+        ));
+    }
+}
@@ -17,52 +17,334 @@ use std::collections::HashMap;
 use crate::{
     expression::Expression,
     instruction::{
-        Calibration, Gate, GateModifier, Instruction, MeasureCalibrationDefinition, Measurement,
-        Qubit,
+        Arithmetic, ArithmeticOperand, BinaryLogic, BinaryOperand, Calibration, Capture,
+        Comparison, ComparisonOperand, Exchange, Gate, GateModifier, Instruction, JumpUnless,
+        JumpWhen, Load, MeasureCalibrationDefinition, Measurement, MemoryReference, Move, Qubit,
+        RawCapture, Store, UnaryLogic,
     },
 };
 
 use super::error::ProgramError;
+use super::Shared;
+
+/// The greatest number of calibrations which may be nested within one another when expanding an
+/// instruction, unless otherwise specified.
+pub const DEFAULT_MAX_CALIBRATION_DEPTH: usize = 64;
 
 /// A collection of Quil calibrations (`DEFCAL` instructions) with utility methods.
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct CalibrationSet {
-    calibrations: Vec<Calibration>,
-    measure_calibrations: Vec<MeasureCalibrationDefinition>,
+    calibrations: Shared<Vec<Calibration>>,
+    measure_calibrations: Shared<Vec<MeasureCalibrationDefinition>>,
 }
 
-struct MatchedCalibration<'a> {
+/// A calibration which matches a gate, as selected by [`CalibrationSet::get_match_for_gate`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CalibrationMatch<'a> {
+    /// The position of the calibration among those in the set, in order of definition.
+    pub index: usize,
     pub calibration: &'a Calibration,
+    /// The number of the calibration's qubits which are fixed, rather than variable.
     pub fixed_qubit_count: usize,
+    /// The number of the calibration's parameters which are fixed, rather than variable.
+    pub fixed_parameter_count: usize,
 }
 
-impl<'a> MatchedCalibration<'a> {
-    pub fn new(calibration: &'a Calibration) -> Self {
+impl<'a> CalibrationMatch<'a> {
+    fn new(index: usize, calibration: &'a Calibration) -> Self {
         Self {
+            index,
             calibration,
             fixed_qubit_count: calibration
                 .qubits
                 .iter()
                 .filter(|q| match q {
-                    Qubit::Fixed(_) => true,
+                    Qubit::Fixed(_) | Qubit::Placeholder(_) => true,
                     Qubit::Variable(_) => false,
                 })
                 .count(),
+            fixed_parameter_count: calibration
+                .parameters
+                .iter()
+                .filter(|p| !matches!(p, Expression::Variable(_)))
+                .count(),
+        }
+    }
+
+    /// How specific the calibration is, where more specific calibrations take precedence: first
+    /// by the number of fixed qubits, and then by the number of fixed parameters.
+    pub fn precedence(&self) -> (usize, usize) {
+        (self.fixed_qubit_count, self.fixed_parameter_count)
+    }
+}
+
+/// Whether the two calibrations apply to exactly the same gates, such that the later one is a
+/// redefinition of the earlier one. Variable qubits and parameters match any gate's, so their
+/// names do not matter: `DEFCAL RX(%a) q` and `DEFCAL RX(%b) r` have the same signature.
+fn same_signature(left: &Calibration, right: &Calibration) -> bool {
+    left.name == right.name
+        && left.modifiers == right.modifiers
+        && left.qubits.len() == right.qubits.len()
+        && left
+            .qubits
+            .iter()
+            .zip(right.qubits.iter())
+            .all(|pair| match pair {
+                (Qubit::Variable(_), Qubit::Variable(_)) => true,
+                (left, right) => left == right,
+            })
+        && left.parameters.len() == right.parameters.len()
+        && left
+            .parameters
+            .iter()
+            .zip(right.parameters.iter())
+            .all(|(left, right)| {
+                match (
+                    left.clone().into_simplified(),
+                    right.clone().into_simplified(),
+                ) {
+                    (Expression::Variable(_), Expression::Variable(_)) => true,
+                    (left, right) => left == right,
+                }
+            })
+}
+
+/// Whether the two measurement calibrations apply to exactly the same measurements, such that the
+/// later one is a redefinition of the earlier one. A measurement calibration is selected by its
+/// qubit alone, if fixed, so those with the same fixed qubit, or with none, have the same
+/// signature whatever their target parameters.
+fn same_measurement_signature(
+    left: &MeasureCalibrationDefinition,
+    right: &MeasureCalibrationDefinition,
+) -> bool {
+    fn fixed_qubit(qubit: &Option<Qubit>) -> Option<&Qubit> {
+        match qubit {
+            Some(qubit @ Qubit::Fixed(_)) | Some(qubit @ Qubit::Placeholder(_)) => Some(qubit),
+            _ => None,
+        }
+    }
+
+    fixed_qubit(&left.qubit) == fixed_qubit(&right.qubit)
+}
+
+/// Swap each variable qubit used directly by the instruction for its concrete expansion, if any.
+pub(super) fn substitute_qubits(
+    instruction: &mut Instruction,
+    qubit_expansions: &HashMap<&String, Qubit>,
+) {
+    for qubit in instruction.get_qubits_mut() {
+        if let Qubit::Variable(name) = qubit {
+            if let Some(expansion) = qubit_expansions.get(name) {
+                *qubit = expansion.clone();
+            }
+        }
+    }
+}
+
+/// Swap each memory reference named by a measurement calibration's parameter for the target of
+/// the measurement being expanded, wherever the instruction uses memory: as the destination of a
+/// `CAPTURE`, `RAW-CAPTURE`, or nested `MEASURE`, as a classical operand (such as of `MOVE`,
+/// `ADD`, `EQ`, `LOAD`, or `JUMP-WHEN`), within an expression, or as the data of a
+/// `PRAGMA LOAD-MEMORY`.
+fn substitute_measurement_target(
+    instruction: &mut Instruction,
+    parameter: &str,
+    target: &MemoryReference,
+) {
+    fn operand_reference(operand: &mut ArithmeticOperand) -> Option<&mut MemoryReference> {
+        match operand {
+            ArithmeticOperand::MemoryReference(reference) => Some(reference),
+            _ => None,
+        }
+    }
+
+    let references: Vec<&mut MemoryReference> = match instruction {
+        Instruction::Pragma(pragma) => {
+            if pragma.name == "LOAD-MEMORY" && pragma.data.as_deref() == Some(parameter) {
+                pragma.data = Some(target.to_string())
+            }
+            vec![]
+        }
+        Instruction::Capture(Capture {
+            memory_reference, ..
+        })
+        | Instruction::RawCapture(RawCapture {
+            memory_reference, ..
+        })
+        | Instruction::Measurement(Measurement {
+            target: Some(memory_reference),
+            ..
+        })
+        | Instruction::UnaryLogic(UnaryLogic {
+            operand: memory_reference,
+            ..
+        })
+        | Instruction::JumpWhen(JumpWhen {
+            condition: memory_reference,
+            ..
+        })
+        | Instruction::JumpUnless(JumpUnless {
+            condition: memory_reference,
+            ..
+        }) => vec![memory_reference],
+        Instruction::Arithmetic(Arithmetic {
+            destination: left,
+            source: right,
+            ..
+        })
+        | Instruction::Move(Move {
+            destination: left,
+            source: right,
+        })
+        | Instruction::Exchange(Exchange { left, right }) => operand_reference(left)
+            .into_iter()
+            .chain(operand_reference(right))
+            .collect(),
+        Instruction::BinaryLogic(BinaryLogic {
+            operands: (left, right),
+            ..
+        }) => {
+            let mut references = vec![left];
+            if let BinaryOperand::MemoryReference(reference) = right {
+                references.push(reference);
+            }
+            references
+        }
+        Instruction::Comparison(Comparison {
+            operands: (destination, left, right),
+            ..
+        }) => {
+            let mut references = vec![destination, left];
+            if let ComparisonOperand::MemoryReference(reference) = right {
+                references.push(reference);
+            }
+            references
+        }
+        Instruction::Load(Load {
+            destination,
+            source,
+            offset,
+        }) => {
+            if source == parameter {
+                *source = target.name.clone();
+            }
+            vec![destination, offset]
+        }
+        Instruction::Store(Store {
+            destination,
+            offset,
+            source,
+        }) => {
+            if destination == parameter {
+                *destination = target.name.clone();
+            }
+            std::iter::once(offset)
+                .chain(operand_reference(source))
+                .collect()
+        }
+        _ => vec![],
+    };
+    for reference in references {
+        if reference.name == parameter {
+            *reference = target.clone();
         }
     }
+
+    instruction.apply_to_expressions(|expression| {
+        let substitutions: HashMap<MemoryReference, Expression> = expression
+            .get_memory_references()
+            .into_iter()
+            .filter(|reference| reference.name == parameter)
+            .map(|reference| (reference.clone(), Expression::Address(target.clone())))
+            .collect();
+        if !substitutions.is_empty() {
+            *expression = expression
+                .clone()
+                .substitute_memory_references(&substitutions);
+        }
+    });
 }
 
 impl CalibrationSet {
+    /// Build a set from the calibrations (`DEFCAL` and `DEFCAL MEASURE` instructions) among
+    /// the given instructions, ignoring any other instructions.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::{instruction::Instruction, program::CalibrationSet, Program};
+    /// use std::str::FromStr;
+    ///
+    /// let definitions = Program::from_str(
+    ///     "DEFCAL RX(%theta) q:\n    SHIFT-PHASE q \"xy\" %theta\n",
+    /// )
+    /// .unwrap()
+    /// .to_instructions(true);
+    /// let calibrations = CalibrationSet::from_instructions(definitions);
+    ///
+    /// let gate = Program::from_str("RX(pi/2) 3").unwrap().instructions.remove(0);
+    /// let expanded = calibrations.expand(&gate, &[]).unwrap().unwrap();
+    /// assert_eq!(expanded[0].to_string(), "SHIFT-PHASE 3 \"xy\" pi / 2");
+    /// ```
+    pub fn from_instructions<I: IntoIterator<Item = Instruction>>(instructions: I) -> Self {
+        let mut calibrations = Self::default();
+        for instruction in instructions {
+            match instruction {
+                Instruction::CalibrationDefinition(calibration) => {
+                    calibrations.push_calibration(calibration)
+                }
+                Instruction::MeasureCalibrationDefinition(calibration) => {
+                    calibrations.push_measurement_calibration(calibration)
+                }
+                _ => {}
+            }
+        }
+        calibrations
+    }
+
     /// Given an instruction, return the instructions to which it is expanded if there is a match.
-    /// Recursively calibrate instructions, returning an error if a calibration directly or indirectly
-    /// expands into itself.
+    /// The gate's parameters and qubits are substituted for the calibration's variables throughout
+    /// its body. Recursively calibrate instructions, returning an error if a calibration directly
+    /// or indirectly expands into itself, or if expansion nests more than
+    /// [`DEFAULT_MAX_CALIBRATION_DEPTH`] calibrations deep.
+    ///
+    /// `previous_calibrations` are the instructions already being expanded, most recent first,
+    /// within which `instruction` appears; it is empty when expanding from the top level.
     pub fn expand(
         &self,
         instruction: &Instruction,
         previous_calibrations: &[Instruction],
     ) -> Result<Option<Vec<Instruction>>, ProgramError<super::Program>> {
-        if previous_calibrations.contains(instruction) {
-            return Err(ProgramError::RecursiveCalibration(instruction.clone()));
+        self.expand_with_max_depth(
+            instruction,
+            previous_calibrations,
+            DEFAULT_MAX_CALIBRATION_DEPTH,
+        )
+    }
+
+    /// Expand an instruction as with [`CalibrationSet::expand`], but nesting no more than
+    /// `max_depth` calibrations deep. This guards against calibrations which recurse without ever
+    /// repeating an instruction exactly, such as `DEFCAL RX(%theta) 0` containing `RX(%theta/2) 0`.
+    pub fn expand_with_max_depth(
+        &self,
+        instruction: &Instruction,
+        previous_calibrations: &[Instruction],
+        max_depth: usize,
+    ) -> Result<Option<Vec<Instruction>>, ProgramError<super::Program>> {
+        if let Some(position) = previous_calibrations
+            .iter()
+            .position(|previous| previous == instruction)
+        {
+            let mut cycle: Vec<Instruction> = previous_calibrations[..=position]
+                .iter()
+                .rev()
+                .cloned()
+                .collect();
+            cycle.push(instruction.clone());
+            return Err(ProgramError::RecursiveCalibration {
+                instruction: instruction.clone(),
+                cycle,
+            });
         }
         let expanded_once_instructions = match instruction {
             Instruction::Gate(Gate {
@@ -72,10 +354,10 @@ impl CalibrationSet {
                 qubits,
             }) => {
                 let matching_calibration =
-                    self.get_match_for_gate(modifiers, name, parameters, qubits);
+                    self.get_match_for_gate(modifiers, name, parameters, qubits)?;
 
                 match matching_calibration {
-                    Some(calibration) => {
+                    Some(CalibrationMatch { calibration, .. }) => {
                         let mut qubit_expansions: HashMap<&String, Qubit> = HashMap::new();
                         for (index, calibration_qubit) in calibration.qubits.iter().enumerate() {
                             if let Qubit::Variable(identifier) = calibration_qubit {
@@ -102,19 +384,7 @@ impl CalibrationSet {
                         let mut instructions = calibration.instructions.clone();
 
                         for instruction in instructions.iter_mut() {
-                            if let Instruction::Gate(Gate { qubits, .. }) = instruction {
-                                // Swap all qubits for their concrete implementations
-                                for qubit in qubits {
-                                    match qubit {
-                                        Qubit::Variable(name) => {
-                                            if let Some(expansion) = qubit_expansions.get(name) {
-                                                *qubit = expansion.clone();
-                                            }
-                                        }
-                                        Qubit::Fixed(_) => {}
-                                    }
-                                }
-                            }
+                            substitute_qubits(instruction, &qubit_expansions);
 
                             instruction.apply_to_expressions(|expr| {
                                 let previous = std::mem::replace(expr, Expression::PiConstant);
@@ -129,41 +399,43 @@ impl CalibrationSet {
             }
             Instruction::Measurement(Measurement { qubit, target }) => {
                 // The matching calibration is the last-specified one that matched the target qubit (if any),
-                // or otherwise the last-specified one that specified no qubit.
+                // or otherwise the last-specified one that specified no qubit or a variable qubit.
                 let mut matching_calibration = None;
                 let mut found_matching_calibration_without_qubit = false;
                 for cal in self.measure_calibrations.iter().rev() {
-                    if let Some(cal_qubit) = &cal.qubit {
-                        if cal_qubit == qubit {
-                            matching_calibration = Some(cal);
-                            break;
+                    match &cal.qubit {
+                        Some(cal_qubit @ Qubit::Fixed(_))
+                        | Some(cal_qubit @ Qubit::Placeholder(_)) => {
+                            if cal_qubit == qubit {
+                                matching_calibration = Some(cal);
+                                break;
+                            }
+                        }
+                        Some(Qubit::Variable(_)) | None => {
+                            if !found_matching_calibration_without_qubit {
+                                matching_calibration = Some(cal);
+                                found_matching_calibration_without_qubit = true;
+                            }
                         }
-                    } else if !found_matching_calibration_without_qubit {
-                        matching_calibration = Some(cal);
-                        found_matching_calibration_without_qubit = true;
                     }
                 }
 
                 match matching_calibration {
                     Some(calibration) => {
+                        let mut qubit_expansions: HashMap<&String, Qubit> = HashMap::new();
+                        if let Some(Qubit::Variable(identifier)) = &calibration.qubit {
+                            qubit_expansions.insert(identifier, qubit.clone());
+                        }
+
                         let mut instructions = calibration.instructions.clone();
                         for instruction in instructions.iter_mut() {
-                            match instruction {
-                                Instruction::Pragma(pragma) => {
-                                    if pragma.name == "LOAD-MEMORY"
-                                        && pragma.data.as_ref() == Some(&calibration.parameter)
-                                    {
-                                        if let Some(target) = target {
-                                            pragma.data = Some(target.to_string())
-                                        }
-                                    }
-                                }
-                                Instruction::Capture(capture) => {
-                                    if let Some(target) = target {
-                                        capture.memory_reference = target.clone()
-                                    }
-                                }
-                                _ => {}
+                            substitute_qubits(instruction, &qubit_expansions);
+                            if let Some(target) = target {
+                                substitute_measurement_target(
+                                    instruction,
+                                    &calibration.parameter,
+                                    target,
+                                );
                             }
                         }
                         Some(instructions)
@@ -179,12 +451,21 @@ impl CalibrationSet {
         downstream_previous_calibrations.extend_from_slice(previous_calibrations);
 
         Ok(match expanded_once_instructions {
+            Some(_) if previous_calibrations.len() >= max_depth => {
+                return Err(ProgramError::CalibrationDepthExceeded {
+                    instruction: instruction.clone(),
+                    max_depth,
+                });
+            }
             Some(instructions) => {
                 let mut recursively_expanded_instructions = vec![];
 
                 for instruction in instructions {
-                    let expanded_instructions =
-                        self.expand(&instruction, &downstream_previous_calibrations)?;
+                    let expanded_instructions = self.expand_with_max_depth(
+                        &instruction,
+                        &downstream_previous_calibrations,
+                        max_depth,
+                    )?;
                     match expanded_instructions {
                         Some(instructions) => {
                             recursively_expanded_instructions.extend(instructions)
@@ -198,7 +479,7 @@ impl CalibrationSet {
         })
     }
 
-    /// Return the final calibration which matches the gate per the QuilT specification:
+    /// Return the calibration which matches the gate per the QuilT specification:
     ///
     /// A calibration matches a gate if:
     /// 1. It has the same name
@@ -207,16 +488,49 @@ impl CalibrationSet {
     /// 4. It has the same parameter count (both specified and unspecified)
     /// 5. All fixed qubits in the calibration definition match those in the gate
     /// 6. All specified parameters in the calibration definition match those in the gate
+    ///
+    /// Of the matching calibrations, the most specific is selected, as given by
+    /// [`CalibrationMatch::precedence`]. A calibration redefined with the same qubits and
+    /// parameters, up to the names of its variables, is replaced by its later definition. If more
+    /// than one distinct calibration is equally specific, the match is ambiguous and an error is
+    /// returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::{expression::Expression, instruction::Qubit, Program};
+    /// use std::str::FromStr;
+    ///
+    /// let program = Program::from_str(concat!(
+    ///     "DEFCAL RX(%theta) 0:\n    PRAGMA A\n",
+    ///     "DEFCAL RX(pi) q:\n    PRAGMA B\n",
+    ///     "DEFCAL CZ 0 q:\n    PRAGMA C\n",
+    ///     "DEFCAL CZ q 1:\n    PRAGMA D\n",
+    /// ))
+    /// .unwrap();
+    ///
+    /// let selected = program
+    ///     .calibrations
+    ///     .get_match_for_gate(&[], "RX", &[Expression::PiConstant], &[Qubit::Fixed(0)])
+    ///     .unwrap()
+    ///     .unwrap();
+    /// assert_eq!(selected.index, 0);
+    ///
+    /// let qubits = [Qubit::Fixed(0), Qubit::Fixed(1)];
+    /// let ambiguous = program.calibrations.get_match_for_gate(&[], "CZ", &[], &qubits);
+    /// assert!(ambiguous.is_err());
+    /// ```
     pub fn get_match_for_gate(
         &self,
         gate_modifiers: &[GateModifier],
         gate_name: &str,
         gate_parameters: &[Expression],
         gate_qubits: &[Qubit],
-    ) -> Option<&Calibration> {
-        let mut matched_calibration: Option<MatchedCalibration> = None;
+    ) -> Result<Option<CalibrationMatch<'_>>, ProgramError<super::Program>> {
+        // All of the matching calibrations which are the most specific seen so far
+        let mut matched_calibrations: Vec<CalibrationMatch> = vec![];
 
-        for calibration in &self.calibrations {
+        for (index, calibration) in self.calibrations.iter().enumerate() {
             // Filter out non-matching calibrations: check rules 1-4
             if calibration.name != gate_name
                 || calibration.modifiers != gate_modifiers
@@ -236,15 +550,11 @@ impl CalibrationSet {
                             &calibration.qubits[calibration_index],
                             &gate_qubits[calibration_index],
                         ) {
-                            // If they're both fixed, test if they're fixed to the same qubit
-                            (
-                                Qubit::Fixed(calibration_fixed_qubit),
-                                Qubit::Fixed(gate_fixed_qubit),
-                            ) => calibration_fixed_qubit == gate_fixed_qubit,
                             // If the calibration is variable, it matches any fixed qubit
                             (Qubit::Variable(_), _) => true,
-                            // If the calibration is fixed, but the gate's qubit is variable, it's not a match
-                            (Qubit::Fixed(_), _) => false,
+                            // Otherwise, the calibration's qubit must be exactly the gate's qubit,
+                            // whether fixed or a placeholder
+                            (calibration_qubit, gate_qubit) => calibration_qubit == gate_qubit,
                         }
                     });
             if !fixed_qubits_match {
@@ -273,20 +583,36 @@ impl CalibrationSet {
                 continue;
             }
 
-            matched_calibration = match matched_calibration {
-                None => Some(MatchedCalibration::new(calibration)),
-                Some(previous_match) => {
-                    let potential_match = MatchedCalibration::new(calibration);
-                    if potential_match.fixed_qubit_count >= previous_match.fixed_qubit_count {
-                        Some(potential_match)
-                    } else {
-                        Some(previous_match)
-                    }
+            let potential_match = CalibrationMatch::new(index, calibration);
+            match matched_calibrations
+                .first()
+                .map(|previous_match| previous_match.precedence())
+            {
+                Some(precedence) if precedence > potential_match.precedence() => {}
+                Some(precedence) if precedence == potential_match.precedence() => {
+                    matched_calibrations
+                        .retain(|previous| !same_signature(previous.calibration, calibration));
+                    matched_calibrations.push(potential_match);
                 }
+                _ => matched_calibrations = vec![potential_match],
             }
         }
 
-        matched_calibration.map(|m| m.calibration)
+        match matched_calibrations.len() {
+            0 | 1 => Ok(matched_calibrations.pop()),
+            _ => Err(ProgramError::AmbiguousCalibration {
+                instruction: Instruction::Gate(Gate {
+                    name: gate_name.to_owned(),
+                    parameters: gate_parameters.to_vec(),
+                    qubits: gate_qubits.to_vec(),
+                    modifiers: gate_modifiers.to_vec(),
+                }),
+                calibrations: matched_calibrations
+                    .into_iter()
+                    .map(|matched| matched.calibration.clone())
+                    .collect(),
+            }),
+        }
     }
 
     /// Return the count of contained calibrations.
@@ -316,6 +642,115 @@ impl CalibrationSet {
         self.measure_calibrations.push(calibration)
     }
 
+    /// Add the calibrations of `other` to this set, skipping any which are identical to the
+    /// calibration already in effect for the same gate or measurement signature.
+    ///
+    /// If any calibration in `other` redefines one in this set with a different body, the set is
+    /// left unchanged and the two conflicting definitions are returned, existing one first.
+    pub(crate) fn merge(
+        &mut self,
+        other: CalibrationSet,
+    ) -> Result<(), (Instruction, Instruction)> {
+        let mut calibrations = vec![];
+        for calibration in other.calibrations.into_inner() {
+            match self
+                .calibrations
+                .iter()
+                .rev()
+                .find(|existing| same_signature(existing, &calibration))
+            {
+                Some(existing) if existing == &calibration => {}
+                Some(existing) => {
+                    return Err((
+                        Instruction::CalibrationDefinition(existing.clone()),
+                        Instruction::CalibrationDefinition(calibration),
+                    ))
+                }
+                None => calibrations.push(calibration),
+            }
+        }
+
+        let mut measure_calibrations = vec![];
+        for calibration in other.measure_calibrations.into_inner() {
+            match self
+                .measure_calibrations
+                .iter()
+                .rev()
+                .find(|existing| same_measurement_signature(existing, &calibration))
+            {
+                Some(existing) if existing == &calibration => {}
+                Some(existing) => {
+                    return Err((
+                        Instruction::MeasureCalibrationDefinition(existing.clone()),
+                        Instruction::MeasureCalibrationDefinition(calibration),
+                    ))
+                }
+                None => measure_calibrations.push(calibration),
+            }
+        }
+
+        self.calibrations.extend(calibrations);
+        self.measure_calibrations.extend(measure_calibrations);
+        Ok(())
+    }
+
+    /// Add the calibrations of `other` to this set, each replacing any calibration already in the
+    /// set with the same gate or measurement signature.
+    pub(crate) fn extend_replacing(&mut self, other: &CalibrationSet) {
+        for calibration in other.calibrations.iter() {
+            self.calibrations
+                .retain(|existing| !same_signature(existing, calibration));
+            self.calibrations.push(calibration.clone());
+        }
+        for calibration in other.measure_calibrations.iter() {
+            self.measure_calibrations
+                .retain(|existing| !same_measurement_signature(existing, calibration));
+            self.measure_calibrations.push(calibration.clone());
+        }
+    }
+
+    /// Drop each calibration which is superseded by a later one in the set, since it can never be
+    /// selected, and sort the rest by their Quil text. Since a calibration's precedence does not depend on
+    /// its position among calibrations of distinct signatures, this does not change which
+    /// calibration is selected for any gate or measurement.
+    pub(crate) fn normalize(&mut self) {
+        let mut calibrations: Vec<Calibration> = vec![];
+        for calibration in std::mem::take(&mut self.calibrations)
+            .into_inner()
+            .into_iter()
+            .rev()
+        {
+            if !calibrations
+                .iter()
+                .any(|later| same_signature(later, &calibration))
+            {
+                calibrations.push(calibration);
+            }
+        }
+        calibrations.sort_by_cached_key(|calibration| {
+            Instruction::CalibrationDefinition(calibration.clone()).to_string()
+        });
+        self.calibrations = calibrations.into();
+
+        let mut measure_calibrations: Vec<MeasureCalibrationDefinition> = vec![];
+        for calibration in std::mem::take(&mut self.measure_calibrations)
+            .into_inner()
+            .into_iter()
+            .rev()
+        {
+            if !measure_calibrations
+                .iter()
+                .any(|later| same_measurement_signature(later, &calibration))
+            {
+                measure_calibrations.push(calibration);
+            }
+        }
+        measure_calibrations.sort_by_cached_key(|calibration| {
+            Instruction::MeasureCalibrationDefinition(calibration.clone()).to_string()
+        });
+        self.measure_calibrations = measure_calibrations.into();
+    }
+
     /// Return the Quil instructions which describe the contained calibrations.
     pub fn to_instructions(&self) -> Vec<Instruction> {
         self.calibrations
@@ -334,7 +769,8 @@ impl CalibrationSet {
 mod tests {
     use std::str::FromStr;
 
-    use crate::program::Program;
+    use crate::instruction::Instruction;
+    use crate::program::{Program, ProgramError};
 
     #[test]
     fn expansion() {
@@ -395,6 +831,69 @@ mod tests {
                 ),
                 expected: "PRAGMA CORRECT\n",
             },
+            // Qubits are substituted into frames, as well as gates
+            TestCase {
+                input: concat!(
+                    "DEFCAL RX(%theta) q:\n",
+                    "    FENCE q\n",
+                    "    SHIFT-PHASE q \"xy\" %theta\n",
+                    "    PULSE q \"xy\" gaussian(duration: 1, fwhm: 2, t0: 3)\n",
+                    "RX(pi/2) 3\n"
+                ),
+                expected: concat!(
+                    "FENCE 3\n",
+                    "SHIFT-PHASE 3 \"xy\" pi / 2\n",
+                    "PULSE 3 \"xy\" gaussian(duration: 1, fwhm: 2, t0: 3)\n"
+                ),
+            },
+            TestCase {
+                input: concat!(
+                    "DEFCAL MEASURE q addr:\n",
+                    "    CAPTURE q \"ro_rx\" flat(duration: 1, iq: 1) addr\n",
+                    "DEFCAL MEASURE 1 addr:\n",
+                    "    PRAGMA INCORRECT_QUBIT\n",
+                    "MEASURE 0 ro[1]\n"
+                ),
+                expected: "CAPTURE 0 \"ro_rx\" flat(duration: 1, iq: 1) ro[1]\n",
+            },
+            // The measurement's target replaces only the calibration's parameter
+            TestCase {
+                input: concat!(
+                    "DEFCAL MEASURE q addr:\n",
+                    "    PRAGMA LOAD-MEMORY \"addr\"\n",
+                    "    RAW-CAPTURE q \"ro_rx\" 0.5 addr\n",
+                    "    RAW-CAPTURE q \"ro_rx\" 0.5 scratch\n",
+                    "MEASURE 1 ro[2]\n"
+                ),
+                expected: concat!(
+                    "PRAGMA LOAD-MEMORY \"ro[2]\"\n",
+                    "RAW-CAPTURE 1 \"ro_rx\" 0.5 ro[2]\n",
+                    "RAW-CAPTURE 1 \"ro_rx\" 0.5 scratch[0]\n"
+                ),
+            },
+            // ...wherever the parameter is used as memory, including within expressions
+            TestCase {
+                input: concat!(
+                    "DEFCAL MEASURE q addr:\n",
+                    "    RAW-CAPTURE q \"ro_rx\" 0.5 addr\n",
+                    "    MOVE scratch addr\n",
+                    "    ADD addr 1\n",
+                    "    EQ flag addr scratch\n",
+                    "    LOAD scratch addr flag\n",
+                    "    JUMP-WHEN @end addr\n",
+                    "    SHIFT-PHASE q \"ro_rx\" addr*pi\n",
+                    "MEASURE 0 ro[1]\n"
+                ),
+                expected: concat!(
+                    "RAW-CAPTURE 0 \"ro_rx\" 0.5 ro[1]\n",
+                    "MOVE scratch[0] ro[1]\n",
+                    "ADD ro[1] 1\n",
+                    "EQ flag[0] ro[1] scratch[0]\n",
+                    "LOAD scratch[0] ro flag[0]\n",
+                    "JUMP-WHEN @end ro[1]\n",
+                    "SHIFT-PHASE 0 \"ro_rx\" ro[1] * pi\n"
+                ),
+            },
         ];
 
         for case in &cases {
@@ -404,6 +903,129 @@ mod tests {
         }
     }
 
+    #[test]
+    fn match_precedence() {
+        let input = concat!(
+            "DEFCAL RX(%theta) q:\n",
+            "    PRAGMA VARIABLE_QUBIT_VARIABLE_PARAMETER\n",
+            "DEFCAL RX(pi) q:\n",
+            "    PRAGMA VARIABLE_QUBIT_FIXED_PARAMETER\n",
+            "DEFCAL RX(%theta) 0:\n",
+            "    PRAGMA FIXED_QUBIT_VARIABLE_PARAMETER\n",
+            "DEFCAL RX(%theta) 0:\n",
+            "    PRAGMA FIXED_QUBIT_VARIABLE_PARAMETER_REDEFINED\n",
+            "DEFCAL CZ 0 q:\n",
+            "    PRAGMA FIRST\n",
+            "DEFCAL CZ q 1:\n",
+            "    PRAGMA SECOND\n",
+        );
+        let program = Program::from_str(input).unwrap();
+
+        for (gate, expected_index) in [
+            ("RX(pi/2) 1", 0),
+            ("RX(pi) 1", 1),
+            ("RX(pi) 0", 3),
+            ("CZ 0 2", 4),
+            ("CZ 2 1", 5),
+        ] {
+            let gate = match Instruction::parse(gate).unwrap() {
+                Instruction::Gate(gate) => gate,
+                other => panic!("expected a gate, got {}", other),
+            };
+            let selected = program
+                .calibrations
+                .get_match_for_gate(&gate.modifiers, &gate.name, &gate.parameters, &gate.qubits)
+                .unwrap()
+                .unwrap();
+            assert_eq!(selected.index, expected_index, "{}", gate.name);
+        }
+
+        let ambiguous = Program::from_str(&format!("{}CZ 0 1\n", input))
+            .unwrap()
+            .expand_calibrations();
+        match ambiguous {
+            Err(ProgramError::AmbiguousCalibration { calibrations, .. }) => {
+                assert_eq!(calibrations.len(), 2)
+            }
+            other => panic!("expected an ambiguous calibration, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn renamed_redefinition() {
+        let program = Program::from_str(concat!(
+            "DEFCAL X q:\n",
+            "    PRAGMA FIRST\n",
+            "DEFCAL X r:\n",
+            "    PRAGMA SECOND\n",
+            "DEFCAL RX(%a) q:\n",
+            "    PRAGMA THIRD\n",
+            "DEFCAL RX(%b) q:\n",
+            "    PRAGMA FOURTH\n",
+            "X 0\n",
+            "RX(pi) 0\n",
+        ))
+        .unwrap();
+
+        let expanded = program.expand_calibrations().unwrap();
+        assert_eq!(expanded.to_string(false), "PRAGMA SECOND\nPRAGMA FOURTH\n");
+
+        let canonical = program.to_canonical();
+        assert_eq!(
+            canonical.calibrations.to_instructions(),
+            Program::from_str(
+                "DEFCAL RX(%b) q:\n    PRAGMA FOURTH\nDEFCAL X r:\n    PRAGMA SECOND\n"
+            )
+            .unwrap()
+            .calibrations
+            .to_instructions()
+        );
+
+        let mut merged = Program::from_str("DEFCAL X q:\n    PRAGMA FIRST\n").unwrap();
+        let conflict =
+            merged.append(Program::from_str("DEFCAL X r:\n    PRAGMA SECOND\n").unwrap());
+        assert!(matches!(
+            conflict,
+            Err(ProgramError::ConflictingDefinition { .. })
+        ));
+    }
+
+    #[test]
+    fn recursive_expansion() {
+        let program = Program::from_str(concat!(
+            "DEFCAL X 0:\n",
+            "    Y 0\n",
+            "DEFCAL Y 0:\n",
+            "    Z 0\n",
+            "DEFCAL Z 0:\n",
+            "    X 0\n",
+            "X 0\n",
+        ))
+        .unwrap();
+        match program.expand_calibrations() {
+            Err(error @ ProgramError::RecursiveCalibration { .. }) => {
+                assert_eq!(
+                    error.to_string(),
+                    "instruction X 0 expands into itself: X 0 -> Y 0 -> Z 0 -> X 0"
+                )
+            }
+            other => panic!("expected a recursive calibration, got {:?}", other),
+        }
+
+        let program = Program::from_str(concat!(
+            "DEFCAL RX(%theta) 0:\n",
+            "    RX(%theta/2) 0\n",
+            "RX(pi) 0\n",
+        ))
+        .unwrap();
+        match program.expand_calibrations_with_max_depth(4) {
+            Err(ProgramError::CalibrationDepthExceeded { max_depth, .. }) => {
+                assert_eq!(max_depth, 4)
+            }
+            other => panic!("expected the depth to be exceeded, got {:?}", other),
+        }
+    }
+
     #[test]
     fn test_eq() {
         let input = "DEFCAL X 0:
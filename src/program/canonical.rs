@@ -0,0 +1,203 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use crate::instruction::{Instruction, Jump, JumpUnless, JumpWhen, Label, Target};
+
+use super::diff::canonicalize_expressions;
+use super::Program;
+
+/// Rename each label defined or targeted within the instructions to `L0`, `L1`, and so on, in
+/// order of first appearance. Label placeholders are named in the same way.
+fn rename_labels(instructions: &mut [Instruction]) {
+    let mut names: HashMap<Target, Target> = HashMap::new();
+    for instruction in instructions {
+        let label = match instruction {
+            Instruction::Label(Label(label))
+            | Instruction::Jump(Jump { target: label })
+            | Instruction::JumpWhen(JumpWhen { target: label, .. })
+            | Instruction::JumpUnless(JumpUnless { target: label, .. }) => label,
+            _ => continue,
+        };
+        let count = names.len();
+        *label = names
+            .entry(label.clone())
+            .or_insert_with(|| Target::Fixed(format!("L{}", count)))
+            .clone();
+    }
+}
+
+impl Program {
+    /// Return an equivalent program in a canonical form, so that programs which differ only in
+    /// ways that do not affect their meaning compare equal and print identically:
+    ///
+    /// - every expression is canonicalized, as by [`Expression::canonicalize`], which also
+    ///   standardizes the formatting of numbers;
+    /// - calibrations superseded by later ones are dropped, and the remaining calibrations and
+    ///   other header definitions are sorted;
+    /// - `DEFGATE` and `DEFCIRCUIT` definitions are moved ahead of all other instructions, and
+    ///   sorted;
+    /// - labels are renamed to `L0`, `L1`, and so on, in order of first appearance.
+    ///
+    /// Labels within the bodies of `DEFCIRCUIT`s are left as they are.
+    ///
+    /// [`Expression::canonicalize`]: crate::expression::Expression::canonicalize
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let first = Program::from_str("LABEL @start\nRX(2*%a + 1.0) 0\nJUMP @start").unwrap();
+    /// let second = Program::from_str("LABEL @loop\nRX(1 + %a*2) 0\nJUMP @loop").unwrap();
+    /// assert_ne!(first, second);
+    /// assert_eq!(first.to_canonical(), second.to_canonical());
+    /// ```
+    pub fn to_canonical(&self) -> Program {
+        let mut program = Program::new();
+        let mut definitions = vec![];
+        let mut body = vec![];
+
+        for instruction in self.to_instructions(true) {
+            let instruction = canonicalize_expressions(instruction);
+            match instruction {
+                Instruction::CircuitDefinition(_) | Instruction::GateDefinition(_) => {
+                    definitions.push(instruction)
+                }
+                instruction if instruction.is_definition() => program.add_instruction(instruction),
+                instruction => body.push(instruction),
+            }
+        }
+        program.calibrations.normalize();
+
+        definitions.sort_by_cached_key(ToString::to_string);
+        rename_labels(&mut body);
+        program.instructions = definitions.into();
+        program.instructions.extend(body);
+
+        program
+    }
+
+    /// Return a hash of the program's meaning, which is the same for programs with the same
+    /// [canonical form](Program::to_canonical): it does not depend on formatting, the names of
+    /// labels, the way numbers are written, or the order of header definitions. It is suitable as
+    /// a key for caching results computed from a program, such as those of compilation.
+    ///
+    /// The hash is computed with 64-bit FNV-1a, and so is stable across platforms, processes,
+    /// and versions of Rust. It may change with the canonical form in a new version of this
+    /// crate.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let first = Program::from_str("LABEL @a\nRX(0.50) 0\nJUMP @a").unwrap();
+    /// let second = Program::from_str("LABEL @b\nRX(5e-1)   0\nJUMP @b").unwrap();
+    /// let third = Program::from_str("LABEL @a\nRX(0.25) 0\nJUMP @a").unwrap();
+    /// assert_eq!(first.semantic_hash(), second.semantic_hash());
+    /// assert_ne!(first.semantic_hash(), third.semantic_hash());
+    /// ```
+    pub fn semantic_hash(&self) -> u64 {
+        const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const PRIME: u64 = 0x0100_0000_01b3;
+
+        self.to_canonical()
+            .to_string(true)
+            .bytes()
+            .fold(OFFSET_BASIS, |hash, byte| {
+                (hash ^ u64::from(byte)).wrapping_mul(PRIME)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::Program;
+
+    #[test]
+    fn to_canonical() {
+        let first = Program::from_str(
+            r#"DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1.0
+    INITIAL-FREQUENCY: 5e9
+DEFFRAME 1 "rf":
+    SAMPLE-RATE: 1.0
+DEFCAL RX(%theta) q:
+    SHIFT-PHASE q "rf" -%theta
+DEFCAL MEASURE 0 addr:
+    PRAGMA A
+DEFCAL RX(%theta) 0:
+    PRAGMA B
+DEFCAL MEASURE 0 addr:
+    PRAGMA C
+DECLARE ro BIT
+LABEL @loop
+RX(pi/2.0) 0
+JUMP-UNLESS @loop ro
+DEFCIRCUIT BELL a b:
+    H a
+    CNOT a b
+"#,
+        )
+        .unwrap();
+        let second = Program::from_str(
+            r#"DECLARE ro BIT[1]
+DEFCIRCUIT BELL a b:
+    H a
+    CNOT a b
+DEFFRAME 1 "rf":
+    SAMPLE-RATE: 1
+DEFFRAME 0 "rf":
+    INITIAL-FREQUENCY: 5000000000
+    SAMPLE-RATE: 1
+DEFCAL RX(%theta) 0:
+    PRAGMA B
+DEFCAL MEASURE 0 addr:
+    PRAGMA C
+DEFCAL RX(%theta) q:
+    SHIFT-PHASE q "rf" -1*%theta
+LABEL @start
+RX(pi/2) 0
+JUMP-UNLESS @start ro
+"#,
+        )
+        .unwrap();
+
+        let canonical = first.to_canonical();
+        assert_eq!(canonical, second.to_canonical());
+        assert_eq!(
+            canonical.to_string(true),
+            second.to_canonical().to_string(true)
+        );
+        assert_eq!(canonical.calibrations.len(), 2);
+        assert_eq!(canonical.instructions[1].to_string(), "LABEL @L0");
+        assert_eq!(canonical.to_canonical(), canonical);
+        assert_eq!(first.semantic_hash(), second.semantic_hash());
+    }
+
+    #[test]
+    fn semantic_hash() {
+        let program = Program::from_str("DECLARE ro BIT\nH 0\nMEASURE 0 ro").unwrap();
+        assert_eq!(program.semantic_hash(), program.clone().semantic_hash());
+        assert_ne!(
+            program.semantic_hash(),
+            Program::from_str("DECLARE ro BIT\nH 1\nMEASURE 0 ro")
+                .unwrap()
+                .semantic_hash()
+        );
+        assert_ne!(program.semantic_hash(), Program::new().semantic_hash());
+    }
+}
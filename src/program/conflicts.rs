@@ -0,0 +1,224 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use crate::instruction::{Capture, FrameIdentifier, Instruction, Pulse, RawCapture};
+
+use super::graph::{ScheduleResult, ScheduledProgram};
+use super::Program;
+
+/// The kinds of instructions which conflict in a [`FrameConflict`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FrameConflictKind {
+    /// Two pulses play at once.
+    Pulses,
+    /// A capture happens while a pulse plays.
+    CaptureDuringPulse,
+    /// Two captures happen at once.
+    Captures,
+}
+
+/// A pair of instructions within one block of a [`ScheduledProgram`] which use a frame at the same
+/// time: either both play on the same frame, or one blocks the frame on which the other plays.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FrameConflict {
+    /// The label of the block.
+    pub block: String,
+    /// The index within the block of the instruction which starts first, or appears first if
+    /// both start at once.
+    pub first: usize,
+    /// The index within the block of the other instruction.
+    pub second: usize,
+    pub kind: FrameConflictKind,
+}
+
+impl fmt::Display for FrameConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let kind = match self.kind {
+            FrameConflictKind::Pulses => "pulses overlap",
+            FrameConflictKind::CaptureDuringPulse => "capture overlaps pulse",
+            FrameConflictKind::Captures => "captures overlap",
+        };
+        write!(
+            f,
+            "{} in block {}: instructions {} and {}",
+            kind, self.block, self.first, self.second
+        )
+    }
+}
+
+/// An instruction which plays on or captures from a frame for some time.
+struct FrameUse<'a> {
+    index: usize,
+    frame: &'a FrameIdentifier,
+    blocking: bool,
+    capture: bool,
+}
+
+impl<'a> FrameUse<'a> {
+    fn of(index: usize, instruction: &'a Instruction) -> Option<Self> {
+        let (frame, blocking, capture) = match instruction {
+            Instruction::Pulse(Pulse {
+                frame, blocking, ..
+            }) => (frame, *blocking, false),
+            Instruction::Capture(Capture {
+                frame, blocking, ..
+            })
+            | Instruction::RawCapture(RawCapture {
+                frame, blocking, ..
+            }) => (frame, *blocking, true),
+            _ => return None,
+        };
+        Some(Self {
+            index,
+            frame,
+            blocking,
+            capture,
+        })
+    }
+
+    /// Return true if the two may not happen at once: if they use the same frame, or if either is
+    /// blocking and they share a qubit.
+    fn conflicts_with(&self, other: &FrameUse) -> bool {
+        self.frame == other.frame
+            || ((self.blocking || other.blocking)
+                && self
+                    .frame
+                    .qubits
+                    .iter()
+                    .any(|qubit| other.frame.qubits.contains(qubit)))
+    }
+}
+
+impl ScheduledProgram {
+    /// Find every pair of pulses and captures which overlap in time, as scheduled by
+    /// [`InstructionBlock::get_time_windows`](super::graph::InstructionBlock::get_time_windows),
+    /// while using the same frame, or while one blocks the frame of the other. Instructions which
+    /// merely meet, with one ending as the other starts, do not conflict.
+    ///
+    /// The frames compared are those named by the instructions themselves, so that conflicts are
+    /// found even on frames which the program does not define, and which therefore do not order
+    /// its instructions.
+    pub fn get_frame_conflicts(&self, program: &Program) -> ScheduleResult<Vec<FrameConflict>> {
+        let mut conflicts = vec![];
+
+        for (label, block) in &self.blocks {
+            let windows = block.get_time_windows(program)?;
+            let uses: Vec<FrameUse> = block
+                .instructions
+                .iter()
+                .enumerate()
+                .filter_map(|(index, instruction)| FrameUse::of(index, instruction))
+                .collect();
+
+            for (position, first) in uses.iter().enumerate() {
+                for second in &uses[position + 1..] {
+                    let (first_window, second_window) =
+                        (windows[first.index], windows[second.index]);
+                    let overlap = first_window.end.min(second_window.end)
+                        - first_window.start.max(second_window.start);
+                    if overlap <= 0.0 || !first.conflicts_with(second) {
+                        continue;
+                    }
+
+                    let kind = match (first.capture, second.capture) {
+                        (false, false) => FrameConflictKind::Pulses,
+                        (true, true) => FrameConflictKind::Captures,
+                        _ => FrameConflictKind::CaptureDuringPulse,
+                    };
+                    let (first, second) = if second_window.start < first_window.start {
+                        (second.index, first.index)
+                    } else {
+                        (first.index, second.index)
+                    };
+                    conflicts.push(FrameConflict {
+                        block: label.clone(),
+                        first,
+                        second,
+                        kind,
+                    });
+                }
+            }
+        }
+
+        Ok(conflicts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::program::graph::ScheduledProgram;
+    use crate::Program;
+
+    use super::{FrameConflict, FrameConflictKind};
+
+    #[test]
+    fn frame_conflicts() {
+        // None of these frames is defined, so the scheduler does not order the instructions.
+        let program = Program::from_str(
+            "DECLARE iq REAL[2]
+PULSE 0 \"xy\" flat(duration: 1.0, iq: 1)
+PULSE 0 \"xy\" flat(duration: 1.0, iq: 1)
+NONBLOCKING PULSE 1 \"xy\" flat(duration: 2.0, iq: 1)
+CAPTURE 1 \"ro\" boxcar_kernel(duration: 1.0) iq
+NONBLOCKING PULSE 2 \"xy\" flat(duration: 1.0, iq: 1)
+NONBLOCKING PULSE 2 \"cz\" flat(duration: 1.0, iq: 1)
+",
+        )
+        .unwrap();
+        let scheduled_program = ScheduledProgram::from_program(&program).unwrap();
+        let conflicts = scheduled_program.get_frame_conflicts(&program).unwrap();
+        assert_eq!(
+            conflicts,
+            vec![
+                FrameConflict {
+                    block: "block_0".to_owned(),
+                    first: 0,
+                    second: 1,
+                    kind: FrameConflictKind::Pulses,
+                },
+                FrameConflict {
+                    block: "block_0".to_owned(),
+                    first: 2,
+                    second: 3,
+                    kind: FrameConflictKind::CaptureDuringPulse,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn defined_frames_do_not_conflict() {
+        let program = Program::from_str(
+            "DECLARE iq REAL[2]
+DEFFRAME 0 \"xy\":
+    SAMPLE-RATE: 1.0
+DEFFRAME 0 \"ro\":
+    SAMPLE-RATE: 1.0
+PULSE 0 \"xy\" flat(duration: 1.0, iq: 1)
+PULSE 0 \"xy\" flat(duration: 1.0, iq: 1)
+CAPTURE 0 \"ro\" boxcar_kernel(duration: 1.0) iq
+",
+        )
+        .unwrap();
+        let scheduled_program = ScheduledProgram::from_program(&program).unwrap();
+        assert_eq!(
+            scheduled_program.get_frame_conflicts(&program).unwrap(),
+            vec![]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::instruction::{Instruction, Qubit};
+
+use super::Program;
+
+/// The interactions between the qubits of a program: an undirected graph with a node for each
+/// qubit used and an edge between each pair of qubits which are operated on together, whether
+/// by a multi-qubit gate or by a pulse-level instruction on a frame which they share.
+///
+/// Only fixed qubits are included, since variable qubits cannot yet be placed on hardware.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InteractionGraph {
+    adjacency: BTreeMap<u64, BTreeSet<u64>>,
+}
+
+impl InteractionGraph {
+    /// Add a qubit to the graph, without any interactions.
+    pub fn add_qubit(&mut self, qubit: u64) {
+        self.adjacency.entry(qubit).or_default();
+    }
+
+    /// Record an interaction between two distinct qubits, adding them to the graph if needed.
+    pub fn add_edge(&mut self, first: u64, second: u64) {
+        if first != second {
+            self.adjacency.entry(first).or_default().insert(second);
+            self.adjacency.entry(second).or_default().insert(first);
+        }
+    }
+
+    /// Return the adjacency list of the graph: each qubit, in order, with the set of qubits with
+    /// which it interacts.
+    pub fn adjacency(&self) -> &BTreeMap<u64, BTreeSet<u64>> {
+        &self.adjacency
+    }
+
+    /// Return the qubits with which the given qubit interacts, or `None` if it is not in the graph.
+    pub fn neighbors(&self, qubit: u64) -> Option<&BTreeSet<u64>> {
+        self.adjacency.get(&qubit)
+    }
+
+    /// Return true if the two qubits interact.
+    pub fn contains_edge(&self, first: u64, second: u64) -> bool {
+        self.adjacency
+            .get(&first)
+            .map_or(false, |neighbors| neighbors.contains(&second))
+    }
+
+    /// Iterate through the qubits of the graph, in order.
+    pub fn qubits(&self) -> impl Iterator<Item = u64> + '_ {
+        self.adjacency.keys().copied()
+    }
+
+    /// Iterate through each edge of the graph once, in order, with its lesser qubit first.
+    pub fn edges(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.adjacency.iter().flat_map(|(&qubit, neighbors)| {
+            neighbors
+                .range(qubit..)
+                .map(move |&neighbor| (qubit, neighbor))
+        })
+    }
+
+    /// Return the number of qubits in the graph.
+    pub fn len(&self) -> usize {
+        self.adjacency.len()
+    }
+
+    /// Return true if the graph has no qubits.
+    pub fn is_empty(&self) -> bool {
+        self.adjacency.is_empty()
+    }
+}
+
+impl Program {
+    /// Compute the interaction graph of the program's instructions, for use in mapping its qubits
+    /// onto those of a device.
+    ///
+    /// Every fixed qubit used by an instruction, as by [`Instruction::qubits`], is a node. The
+    /// qubits of each gate, and those of the frames on which each pulse-level instruction plays,
+    /// are connected pairwise. `DELAY` and `FENCE` synchronize qubits rather than operate on them
+    /// together, and so add no edges.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str("H 0\nCNOT 0 1\nCZ 1 2\nX 3").unwrap();
+    /// let graph = program.interaction_graph();
+    /// assert_eq!(graph.edges().collect::<Vec<_>>(), vec![(0, 1), (1, 2)]);
+    /// assert!(graph.neighbors(3).unwrap().is_empty());
+    /// ```
+    pub fn interaction_graph(&self) -> InteractionGraph {
+        let mut graph = InteractionGraph::default();
+
+        for instruction in &self.instructions {
+            let qubits: Vec<u64> = instruction
+                .qubits()
+                .into_iter()
+                .filter_map(|qubit| match qubit {
+                    Qubit::Fixed(index) => Some(*index),
+                    Qubit::Variable(_) | Qubit::Placeholder(_) => None,
+                })
+                .collect();
+            for &qubit in &qubits {
+                graph.add_qubit(qubit);
+            }
+
+            if let Instruction::Delay(_) | Instruction::Fence(_) = instruction {
+                continue;
+            }
+            for (index, &first) in qubits.iter().enumerate() {
+                for &second in &qubits[index + 1..] {
+                    graph.add_edge(first, second);
+                }
+            }
+        }
+
+        graph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::Program;
+
+    #[test]
+    fn interaction_graph() {
+        let program = Program::from_str(
+            r#"DEFFRAME 2 3 "cz":
+    SAMPLE-RATE: 1.0
+CCNOT 0 1 4
+PULSE 2 3 "cz" flat(duration: 1e-6, iq: 1)
+FENCE 0 5
+DELAY 5 6 1e-6
+CNOT q 0
+"#,
+        )
+        .unwrap();
+        let graph = program.interaction_graph();
+
+        assert_eq!(
+            graph.qubits().collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4, 5, 6]
+        );
+        assert_eq!(
+            graph.edges().collect::<Vec<_>>(),
+            vec![(0, 1), (0, 4), (1, 4), (2, 3)]
+        );
+        assert!(graph.contains_edge(4, 1));
+        assert!(!graph.contains_edge(5, 6));
+        assert_eq!(graph.neighbors(0).unwrap().len(), 2);
+        assert_eq!(graph.neighbors(7), None);
+        assert_eq!(graph.len(), 7);
+    }
+}
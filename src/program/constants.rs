@@ -0,0 +1,371 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::expression::Expression;
+use crate::instruction::{
+    Arithmetic, ArithmeticOperand, ArithmeticOperator, Instruction, MemoryReference, Move,
+    ScalarType,
+};
+
+use super::Program;
+
+/// Compute the result of applying the operator to two literal operands of the same type, if it
+/// can be computed exactly and, for reals, is finite.
+fn fold(
+    operator: &ArithmeticOperator,
+    left: &ArithmeticOperand,
+    right: &ArithmeticOperand,
+) -> Option<ArithmeticOperand> {
+    use ArithmeticOperand::{LiteralInteger, LiteralReal};
+
+    match (left, right) {
+        (LiteralInteger(left), LiteralInteger(right)) => match operator {
+            ArithmeticOperator::Add => left.checked_add(*right),
+            ArithmeticOperator::Subtract => left.checked_sub(*right),
+            ArithmeticOperator::Multiply => left.checked_mul(*right),
+            ArithmeticOperator::Divide => left
+                .checked_rem(*right)
+                .filter(|remainder| *remainder == 0)
+                .and_then(|_| left.checked_div(*right)),
+        }
+        .map(LiteralInteger),
+        (LiteralReal(left), LiteralReal(right)) => match operator {
+            ArithmeticOperator::Add => Some(left + right),
+            ArithmeticOperator::Subtract => Some(left - right),
+            ArithmeticOperator::Multiply => Some(left * right),
+            ArithmeticOperator::Divide if *right != 0.0 => Some(left / right),
+            ArithmeticOperator::Divide => None,
+        }
+        .filter(|result| result.is_finite())
+        .map(LiteralReal),
+        _ => None,
+    }
+}
+
+/// Whether every number within the expression is finite.
+fn is_finite(expression: &Expression) -> bool {
+    expression.iter().all(|node| match node {
+        Expression::Number(number) => number.re.is_finite() && number.im.is_finite(),
+        _ => true,
+    })
+}
+
+/// The literal values known to be held by memory at a point within a program.
+struct KnownValues {
+    values: HashMap<MemoryReference, ArithmeticOperand>,
+    /// The memory regions which share storage with another, and so whose values are never
+    /// tracked: a write to one may change the other.
+    aliased: HashSet<String>,
+}
+
+impl KnownValues {
+    fn new(program: &Program) -> Self {
+        let aliased = program
+            .memory_regions
+            .iter()
+            .filter_map(|(name, region)| Some([name.clone(), region.sharing.clone()?]))
+            .flatten()
+            .collect();
+        Self {
+            values: HashMap::new(),
+            aliased,
+        }
+    }
+
+    /// Return the literal value of the operand, if known.
+    fn resolve(&self, operand: &ArithmeticOperand) -> Option<ArithmeticOperand> {
+        match operand {
+            ArithmeticOperand::MemoryReference(reference) => self.values.get(reference).cloned(),
+            literal => Some(literal.clone()),
+        }
+    }
+
+    /// Record the value held by the memory reference, converting integers written to `REAL`
+    /// memory into reals so that later arithmetic may be folded.
+    fn set(&mut self, program: &Program, reference: &MemoryReference, value: ArithmeticOperand) {
+        if self.aliased.contains(&reference.name) {
+            return;
+        }
+        let is_real = program
+            .memory_regions
+            .get(&reference.name)
+            .map_or(false, |region| region.size.data_type == ScalarType::Real);
+        let value = match value {
+            ArithmeticOperand::LiteralInteger(value) if is_real => {
+                ArithmeticOperand::LiteralReal(value as f64)
+            }
+            value => value,
+        };
+        self.values.insert(reference.clone(), value);
+    }
+
+    /// Forget the value of every element of the named memory region.
+    fn forget_region(&mut self, name: &str) {
+        self.values.retain(|reference, _| reference.name != name);
+    }
+
+    /// Substitute the known values for the memory references within the expression, simplifying
+    /// it if any were substituted and the result remains finite.
+    fn substitute(&self, expression: &mut Expression) {
+        let values: HashMap<MemoryReference, Expression> = expression
+            .memory_references()
+            .into_iter()
+            .filter_map(|reference| {
+                let value = match self.values.get(reference)? {
+                    ArithmeticOperand::LiteralInteger(value) => *value as f64,
+                    ArithmeticOperand::LiteralReal(value) => *value,
+                    ArithmeticOperand::MemoryReference(_) => return None,
+                };
+                Some((reference.clone(), Expression::Number(crate::real!(value))))
+            })
+            .collect();
+
+        if !values.is_empty() {
+            let substituted = expression
+                .clone()
+                .substitute_memory_references(&values)
+                .into_simplified();
+            if is_finite(&substituted) {
+                *expression = substituted;
+            }
+        }
+    }
+}
+
+impl Program {
+    /// Return a copy of this program in which literal values are propagated through classical
+    /// memory.
+    ///
+    /// Values written to memory by `MOVE` are tracked through subsequent `MOVE`, `ADD`, `SUB`,
+    /// `MUL`, and `DIV` instructions: operands of known value are replaced by literals, and
+    /// arithmetic on memory of known value is folded into a `MOVE` of the result. Known values
+    /// are also substituted into the expressions of later instructions, such as gate parameters,
+    /// which are then simplified.
+    ///
+    /// A value is forgotten once its memory is written by any other instruction (such as
+    /// `MEASURE` or `LOAD`), and all values are forgotten at each `LABEL`, where control may
+    /// arrive from elsewhere. Integer division is folded only when exact, and real arithmetic
+    /// only when its result is finite. Memory regions declared with `SHARING`, and those they
+    /// share, are never tracked.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DECLARE theta REAL\nMOVE theta[0] 0.5\nMUL theta[0] 2.0\nRX(pi*theta[0]) 0\n",
+    /// )
+    /// .unwrap();
+    /// let propagated = program.propagate_constants();
+    /// assert_eq!(
+    ///     propagated.to_string(false),
+    ///     "MOVE theta[0] 0.5\nMOVE theta[0] 1\nRX(pi) 0\n"
+    /// );
+    /// ```
+    pub fn propagate_constants(&self) -> Self {
+        let mut known = KnownValues::new(self);
+        let mut instructions = Vec::with_capacity(self.instructions.len());
+
+        for instruction in &self.instructions {
+            let mut instruction = instruction.clone();
+
+            match &mut instruction {
+                Instruction::Label(_) => known.values.clear(),
+                Instruction::Move(Move {
+                    destination: ArithmeticOperand::MemoryReference(destination),
+                    source,
+                }) => match known.resolve(source) {
+                    Some(value) => {
+                        *source = value.clone();
+                        known.set(self, destination, value);
+                    }
+                    None => {
+                        known.values.remove(&*destination);
+                    }
+                },
+                Instruction::Arithmetic(Arithmetic {
+                    operator,
+                    destination: ArithmeticOperand::MemoryReference(destination),
+                    source,
+                }) => {
+                    let source_value = known.resolve(source);
+                    if let Some(value) = &source_value {
+                        *source = value.clone();
+                    }
+
+                    let result = known
+                        .values
+                        .get(&*destination)
+                        .zip(source_value.as_ref())
+                        .and_then(|(left, right)| fold(operator, left, right));
+                    match result {
+                        Some(result) => {
+                            let destination = destination.clone();
+                            known.set(self, &destination, result.clone());
+                            instruction = Instruction::Move(Move {
+                                destination: ArithmeticOperand::MemoryReference(destination),
+                                source: result,
+                            });
+                        }
+                        None => {
+                            known.values.remove(&*destination);
+                        }
+                    }
+                }
+                _ => {
+                    instruction.apply_to_expressions(|expression| known.substitute(expression));
+
+                    let accesses = instruction.get_memory_accesses();
+                    for region in accesses.writes.iter().chain(accesses.captures.iter()) {
+                        known.forget_region(region);
+                    }
+                }
+            }
+
+            instructions.push(instruction);
+        }
+
+        Program {
+            instructions: instructions.into(),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::instruction::{
+        ArithmeticOperand, Declaration, Instruction, MemoryReference, Move, ScalarType, Vector,
+    };
+    use crate::Program;
+
+    #[test]
+    fn propagate_constants() {
+        let program = Program::from_str(
+            "DECLARE a INTEGER[2]
+DECLARE theta REAL
+DECLARE ro BIT
+MOVE a[0] 7
+ADD a[0] 3
+MOVE a[1] a[0]
+DIV a[1] 4
+SUB a[1] 1
+MOVE theta[0] 1
+MUL theta[0] 0.5
+RX(theta[0]*2) 0
+MOVE ro[0] 1
+MEASURE 0 ro[0]
+MOVE a[0] ro[0]
+RZ(theta[0]) 0
+LABEL @loop
+RX(theta[0]) 0
+JUMP @loop
+",
+        )
+        .unwrap();
+
+        let expected = "MOVE a[0] 7
+MOVE a[0] 10
+MOVE a[1] 10
+DIV a[1] 4
+SUB a[1] 1
+MOVE theta[0] 1
+MOVE theta[0] 0.5
+RX(1) 0
+MOVE ro[0] 1
+MEASURE 0 ro[0]
+MOVE a[0] ro[0]
+RZ(0.5) 0
+LABEL @loop
+RX(theta[0]) 0
+JUMP @loop
+";
+        assert_eq!(program.propagate_constants().to_string(false), expected);
+    }
+    #[test]
+    fn propagate_constants_skips_non_finite_results() {
+        // Overflow is left for the program to perform when run.
+        let program = Program::from_str("DECLARE theta REAL\nMUL theta[0] 10.0\n").unwrap();
+        let mut overflowing = program.clone();
+        overflowing.instructions.insert(
+            0,
+            Instruction::Move(Move {
+                destination: ArithmeticOperand::MemoryReference(MemoryReference {
+                    name: String::from("theta"),
+                    index: 0,
+                }),
+                source: ArithmeticOperand::LiteralReal(f64::MAX),
+            }),
+        );
+        assert_eq!(overflowing.propagate_constants(), overflowing);
+
+        let program = Program::from_str(
+            "DECLARE theta REAL
+MOVE theta[0] 0.0
+RX(1/theta[0]) 0
+RX(theta[0]/theta[0]) 0
+RX(theta[0]/2) 0
+",
+        )
+        .unwrap();
+
+        let expected = "MOVE theta[0] 0
+RX(1 / theta[0]) 0
+RX(theta[0] / theta[0]) 0
+RX(0) 0
+";
+        assert_eq!(program.propagate_constants().to_string(false), expected);
+    }
+
+    #[test]
+    fn propagate_constants_skips_shared_memory() {
+        let mut program = Program::new();
+        for (name, sharing) in [("a", None), ("b", Some("a")), ("c", None)] {
+            program.add_instruction(Instruction::Declaration(Declaration {
+                name: String::from(name),
+                size: Vector {
+                    data_type: ScalarType::Integer,
+                    length: 1,
+                },
+                sharing: sharing.map(String::from),
+            }));
+        }
+        let body = Program::from_str(
+            "MOVE a[0] 1
+MOVE b[0] 2
+ADD a[0] 1
+MOVE c[0] 3
+RX(a[0]) 0
+RX(b[0]) 0
+RX(c[0]) 0
+",
+        )
+        .unwrap();
+        program.instructions.extend(body.instructions);
+
+        let expected = "MOVE a[0] 1
+MOVE b[0] 2
+ADD a[0] 1
+MOVE c[0] 3
+RX(a[0]) 0
+RX(b[0]) 0
+RX(3) 0
+";
+        assert_eq!(program.propagate_constants().to_string(false), expected);
+    }
+}
@@ -0,0 +1,393 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::instruction::{Fence, Instruction, Qubit, Reset};
+
+use super::Program;
+
+/// A resource through which one instruction may affect another.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum DataResource {
+    /// A region of classical memory, by name.
+    Memory(String),
+
+    /// The state of a qubit.
+    Qubit(Qubit),
+
+    /// The flow of control, which `LABEL`, jump, and `HALT` instructions change: no instruction
+    /// may be moved across them.
+    ControlFlow,
+}
+
+impl fmt::Display for DataResource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DataResource::Memory(region) => write!(f, "memory {}", region),
+            DataResource::Qubit(qubit) => write!(f, "qubit {}", qubit),
+            DataResource::ControlFlow => write!(f, "control flow"),
+        }
+    }
+}
+
+/// The way in which a later instruction depends on an earlier one through a shared resource.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DataDependencyKind {
+    /// The later instruction reads what the earlier one wrote.
+    ReadAfterWrite,
+
+    /// The later instruction overwrites what the earlier one wrote.
+    WriteAfterWrite,
+
+    /// The later instruction overwrites what the earlier one read.
+    WriteAfterRead,
+}
+
+/// An edge of a [`DataFlowGraph`]: the instruction at `to` must follow the one at `from`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct DataDependency {
+    pub from: usize,
+    pub to: usize,
+    pub resource: DataResource,
+    pub kind: DataDependencyKind,
+}
+
+/// The resources read and written by a single instruction.
+struct Accesses {
+    reads: Vec<DataResource>,
+    writes: Vec<DataResource>,
+}
+
+impl Accesses {
+    fn of(instruction: &Instruction, all_qubits: &[Qubit]) -> Self {
+        let memory = instruction.get_memory_accesses();
+        let mut reads: Vec<DataResource> =
+            memory.reads.into_iter().map(DataResource::Memory).collect();
+        let mut writes: Vec<DataResource> = memory
+            .writes
+            .into_iter()
+            .chain(memory.captures)
+            .map(DataResource::Memory)
+            .collect();
+
+        // Any operation on a qubit both depends on and changes its state.
+        let qubits = match instruction {
+            Instruction::Fence(Fence { qubits }) if qubits.is_empty() => {
+                all_qubits.iter().collect()
+            }
+            Instruction::Reset(Reset { qubit: None }) => all_qubits.iter().collect(),
+            _ => instruction.qubits(),
+        };
+        writes.extend(qubits.into_iter().cloned().map(DataResource::Qubit));
+
+        match instruction {
+            Instruction::Label(_)
+            | Instruction::Jump(_)
+            | Instruction::JumpWhen(_)
+            | Instruction::JumpUnless(_)
+            | Instruction::Halt => writes.push(DataResource::ControlFlow),
+            _ => reads.push(DataResource::ControlFlow),
+        }
+
+        // Sort for a deterministic order of edges.
+        reads.sort_by_cached_key(ToString::to_string);
+        writes.sort_by_cached_key(ToString::to_string);
+        Self { reads, writes }
+    }
+}
+
+/// The most recent accesses to a resource, while building a [`DataFlowGraph`].
+#[derive(Default)]
+struct ResourceState {
+    last_write: Option<usize>,
+    reads_since_write: Vec<usize>,
+}
+
+/// A graph of the dependencies among a sequence of instructions through classical memory, qubit
+/// state, and control flow. Two instructions which are not connected by a path through the graph
+/// may be reordered or executed in parallel without changing the program's results.
+///
+/// Memory is tracked by region, so writes to different elements of one region are ordered. Each
+/// instruction depends only on the most recent instructions to access each of its resources;
+/// dependencies on earlier ones are implied by transitivity. Only the instructions themselves are
+/// considered: the pulse-level timing among frames is described instead by a
+/// [`ScheduledProgram`](crate::program::graph::ScheduledProgram).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DataFlowGraph {
+    dependencies: Vec<DataDependency>,
+    /// For each instruction, the indices within `dependencies` of the edges into it.
+    incoming: Vec<Vec<usize>>,
+    /// For each instruction, the indices within `dependencies` of the edges out of it.
+    outgoing: Vec<Vec<usize>>,
+}
+
+impl DataFlowGraph {
+    /// Build the graph of the dependencies among the given instructions, which are identified by
+    /// their indices in order. A `FENCE` or `RESET` without qubits acts on all qubits used by the
+    /// instructions.
+    pub fn new<'a>(instructions: impl IntoIterator<Item = &'a Instruction>) -> Self {
+        let instructions: Vec<&Instruction> = instructions.into_iter().collect();
+        let mut all_qubits: Vec<Qubit> = vec![];
+        for qubit in instructions
+            .iter()
+            .flat_map(|instruction| instruction.qubits())
+        {
+            if !all_qubits.contains(qubit) {
+                all_qubits.push(qubit.clone());
+            }
+        }
+
+        let mut graph = Self {
+            dependencies: vec![],
+            incoming: vec![vec![]; instructions.len()],
+            outgoing: vec![vec![]; instructions.len()],
+        };
+        let mut states: HashMap<DataResource, ResourceState> = HashMap::new();
+
+        for (index, instruction) in instructions.into_iter().enumerate() {
+            let accesses = Accesses::of(instruction, &all_qubits);
+
+            for resource in accesses.reads {
+                let state = states.entry(resource.clone()).or_default();
+                if let Some(writer) = state.last_write {
+                    graph.add(writer, index, resource, DataDependencyKind::ReadAfterWrite);
+                }
+                state.reads_since_write.push(index);
+            }
+
+            for resource in accesses.writes {
+                let state = states.entry(resource.clone()).or_default();
+                let readers = std::mem::take(&mut state.reads_since_write);
+                let previous_writer = state.last_write.replace(index);
+                for reader in readers.into_iter().filter(|&reader| reader != index) {
+                    graph.add(
+                        reader,
+                        index,
+                        resource.clone(),
+                        DataDependencyKind::WriteAfterRead,
+                    );
+                }
+                if let Some(writer) = previous_writer {
+                    graph.add(writer, index, resource, DataDependencyKind::WriteAfterWrite);
+                }
+            }
+        }
+
+        graph
+    }
+
+    fn add(&mut self, from: usize, to: usize, resource: DataResource, kind: DataDependencyKind) {
+        let edge = self.dependencies.len();
+        self.dependencies.push(DataDependency {
+            from,
+            to,
+            resource,
+            kind,
+        });
+        self.outgoing[from].push(edge);
+        self.incoming[to].push(edge);
+    }
+
+    /// Return the number of instructions in the graph.
+    pub fn len(&self) -> usize {
+        self.incoming.len()
+    }
+
+    /// Return true if the graph has no instructions.
+    pub fn is_empty(&self) -> bool {
+        self.incoming.is_empty()
+    }
+
+    /// Return all of the dependencies, in the order in which they were found.
+    pub fn dependencies(&self) -> &[DataDependency] {
+        &self.dependencies
+    }
+
+    /// Iterate through the dependencies of the instruction at the given index on earlier ones.
+    pub fn dependencies_of(&self, index: usize) -> impl Iterator<Item = &DataDependency> {
+        self.incoming
+            .get(index)
+            .into_iter()
+            .flatten()
+            .map(move |&edge| &self.dependencies[edge])
+    }
+
+    /// Iterate through the dependencies of later instructions on the one at the given index.
+    pub fn dependents_of(&self, index: usize) -> impl Iterator<Item = &DataDependency> {
+        self.outgoing
+            .get(index)
+            .into_iter()
+            .flatten()
+            .map(move |&edge| &self.dependencies[edge])
+    }
+
+    /// Return true if the instruction at `to` depends, directly or transitively, on the one at
+    /// `from`.
+    pub fn depends_on(&self, to: usize, from: usize) -> bool {
+        if from >= to {
+            return false;
+        }
+        let mut visited = vec![false; self.len()];
+        let mut stack = vec![to];
+        while let Some(index) = stack.pop() {
+            for dependency in self.dependencies_of(index) {
+                if dependency.from == from {
+                    return true;
+                }
+                if dependency.from > from && !visited[dependency.from] {
+                    visited[dependency.from] = true;
+                    stack.push(dependency.from);
+                }
+            }
+        }
+        false
+    }
+
+    /// Group the instructions into layers, in order, such that each instruction depends only on
+    /// instructions in earlier layers. The instructions within a layer may be executed in any
+    /// order, or in parallel. Each instruction is placed in the earliest layer possible.
+    pub fn layers(&self) -> Vec<Vec<usize>> {
+        let mut depths = vec![0; self.len()];
+        let mut layers: Vec<Vec<usize>> = vec![];
+        for index in 0..self.len() {
+            // Dependencies always point backwards, so those of each instruction are settled.
+            let depth = self
+                .dependencies_of(index)
+                .map(|dependency| depths[dependency.from] + 1)
+                .max()
+                .unwrap_or(0);
+            depths[index] = depth;
+            if layers.len() <= depth {
+                layers.resize_with(depth + 1, Vec::new);
+            }
+            layers[depth].push(index);
+        }
+        layers
+    }
+}
+
+impl Program {
+    /// Build the [`DataFlowGraph`] of the program's instructions, which are identified by their
+    /// indices within [`Program::instructions`].
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DECLARE ro BIT\nH 0\nX 1\nCNOT 0 1\nH 2\nMEASURE 2 ro",
+    /// )
+    /// .unwrap();
+    /// let graph = program.data_flow_graph();
+    /// assert_eq!(graph.layers(), vec![vec![0, 1, 3], vec![2, 4]]);
+    /// ```
+    pub fn data_flow_graph(&self) -> DataFlowGraph {
+        DataFlowGraph::new(&self.instructions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::instruction::Qubit;
+    use crate::Program;
+
+    use super::{DataDependency, DataDependencyKind, DataResource};
+
+    fn edges(source: &str, index: usize) -> Vec<(usize, String, DataDependencyKind)> {
+        Program::from_str(source)
+            .unwrap()
+            .data_flow_graph()
+            .dependencies_of(index)
+            .map(|dependency| {
+                (
+                    dependency.from,
+                    dependency.resource.to_string(),
+                    dependency.kind,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn memory_dependencies() {
+        use DataDependencyKind::*;
+
+        let source = "DECLARE a INTEGER
+DECLARE b INTEGER
+MOVE a 1
+ADD b a
+MOVE a 2
+MOVE b 3
+";
+        assert_eq!(
+            edges(source, 1),
+            vec![(0, "memory a".to_owned(), ReadAfterWrite)]
+        );
+        assert_eq!(
+            edges(source, 2),
+            vec![
+                (1, "memory a".to_owned(), WriteAfterRead),
+                (0, "memory a".to_owned(), WriteAfterWrite)
+            ]
+        );
+        assert_eq!(
+            edges(source, 3),
+            vec![(1, "memory b".to_owned(), WriteAfterWrite)]
+        );
+    }
+
+    #[test]
+    fn qubit_and_control_dependencies() {
+        let program = Program::from_str(
+            "DECLARE ro BIT
+H 0
+H 1
+MEASURE 0 ro
+JUMP-WHEN @end ro
+X 1
+FENCE
+LABEL @end
+",
+        )
+        .unwrap();
+        let graph = program.data_flow_graph();
+
+        assert_eq!(
+            graph.dependencies_of(2).collect::<Vec<_>>(),
+            vec![&DataDependency {
+                from: 0,
+                to: 2,
+                resource: DataResource::Qubit(Qubit::Fixed(0)),
+                kind: DataDependencyKind::WriteAfterWrite,
+            }]
+        );
+        // The jump reads the measurement's result, and waits for every earlier instruction.
+        assert!(graph.depends_on(3, 2));
+        assert!(graph.depends_on(3, 1));
+        // The instruction after the jump may not be moved before it, despite acting on an
+        // independent qubit.
+        assert!(graph.depends_on(4, 3));
+        assert!(graph.depends_on(5, 4));
+        assert!(graph.depends_on(5, 2));
+        assert!(!graph.depends_on(1, 0));
+        assert_eq!(
+            graph.layers(),
+            vec![vec![0, 1], vec![2], vec![3], vec![4], vec![5], vec![6]]
+        );
+    }
+}
@@ -0,0 +1,111 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::expression::Expression;
+use crate::instruction::{Delay, Instruction};
+
+use super::Program;
+
+/// Whether the delay is known to last for no time at all, and so has no effect.
+fn is_zero(delay: &Delay) -> bool {
+    matches!(
+        delay.duration.clone().into_simplified(),
+        Expression::Number(value) if value.re == 0.0 && value.im == 0.0
+    )
+}
+
+impl Program {
+    /// Return a copy of this program in which each run of adjacent `DELAY` instructions on the
+    /// same qubits and frames is coalesced into one, lasting for the sum of their durations, and
+    /// each `DELAY` which lasts for no time is removed. Every frame is delayed for the same time
+    /// as before, so the schedule of the program is unchanged.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DECLARE wait REAL
+    /// DELAY 0 \"rf\" 1.5
+    /// DELAY 1 0.0
+    /// DELAY 0 \"rf\" 0.5
+    /// DELAY 0 1 \"rf\" wait
+    /// ",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(
+    ///     program.merge_delays().to_string(false),
+    ///     "DELAY 0 \"rf\" 2\nDELAY 0 1 \"rf\" wait[0]\n"
+    /// );
+    /// ```
+    pub fn merge_delays(&self) -> Self {
+        let mut instructions: Vec<Instruction> = Vec::with_capacity(self.instructions.len());
+
+        for instruction in &self.instructions {
+            if let Instruction::Delay(delay) = instruction {
+                if is_zero(delay) {
+                    continue;
+                }
+                if let Some(Instruction::Delay(previous)) = instructions.last_mut() {
+                    if previous.qubits == delay.qubits && previous.frame_names == delay.frame_names
+                    {
+                        previous.duration =
+                            (previous.duration.clone() + delay.duration.clone()).into_simplified();
+                        continue;
+                    }
+                }
+            }
+            instructions.push(instruction.clone());
+        }
+
+        Program {
+            instructions: instructions.into(),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::Program;
+
+    #[test]
+    fn merge_delays() {
+        let program = Program::from_str(
+            "DELAY 0 \"rf\" 1
+DELAY 0 \"rf\" 2
+DELAY 0 \"ro\" 2
+DELAY 0 \"rf\" 0
+DELAY 0 \"rf\" 3
+PULSE 0 \"rf\" flat(duration: 1, iq: 1)
+DELAY 0 \"rf\" 1-1
+DELAY 0 \"rf\" 4
+DELAY 0 \"rf\" \"ro\" 1
+",
+        )
+        .unwrap();
+        assert_eq!(
+            program.merge_delays().to_string(false),
+            "DELAY 0 \"rf\" 3
+DELAY 0 \"ro\" 2
+DELAY 0 \"rf\" 3
+PULSE 0 \"rf\" flat(duration: 1, iq: 1)
+DELAY 0 \"rf\" 4
+DELAY 0 \"rf\" \"ro\" 1
+"
+        );
+    }
+}
@@ -0,0 +1,288 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+
+use crate::expression::Expression;
+use crate::instruction::Instruction;
+
+use super::transform::rewrite_instruction;
+use super::Program;
+
+/// Return the instruction with every expression within it, including within its body, in
+/// canonical form.
+pub(crate) fn canonicalize_expressions(instruction: Instruction) -> Instruction {
+    let mut pass = |mut instruction: Instruction| {
+        instruction.apply_to_expressions(Expression::canonicalize);
+        vec![instruction]
+    };
+    rewrite_instruction(&mut pass, instruction)
+        .pop()
+        .expect("the pass returns exactly one instruction")
+}
+
+/// A difference between two programs, as found by [`Program::diff`]. Each index is that of an
+/// instruction within [`Program::to_instructions`] (including headers) of the respective program.
+#[derive(Clone, Debug, PartialEq)]
+pub enum InstructionChange {
+    /// An instruction present only in the new program.
+    Inserted {
+        new_index: usize,
+        instruction: Instruction,
+    },
+
+    /// An instruction present only in the old program.
+    Removed {
+        old_index: usize,
+        instruction: Instruction,
+    },
+
+    /// An instruction of the old program replaced, in the same place, by one of the new program.
+    Changed {
+        old_index: usize,
+        new_index: usize,
+        old: Instruction,
+        new: Instruction,
+    },
+}
+
+impl fmt::Display for InstructionChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InstructionChange::Inserted { instruction, .. } => write!(f, "+ {}", instruction),
+            InstructionChange::Removed { instruction, .. } => write!(f, "- {}", instruction),
+            InstructionChange::Changed { old, new, .. } => write!(f, "- {}\n+ {}", old, new),
+        }
+    }
+}
+
+/// The differences between two programs, in order, as found by [`Program::diff`].
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProgramDiff {
+    pub changes: Vec<InstructionChange>,
+}
+
+impl ProgramDiff {
+    /// Return true if the programs are semantically the same.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl fmt::Display for ProgramDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for change in &self.changes {
+            writeln!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// An edit within the alignment of two sequences of instructions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Edit {
+    Keep,
+    Insert,
+    Remove,
+}
+
+/// Align two sequences of instructions by their longest common subsequence, returning the edits
+/// which turn the old into the new.
+fn align(old: &[Instruction], new: &[Instruction]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_middle = &old[prefix..old.len() - suffix];
+    let new_middle = &new[prefix..new.len() - suffix];
+
+    // lengths[i][j] is the length of the longest common subsequence of old_middle[i..] and
+    // new_middle[j..].
+    let (rows, columns) = (old_middle.len(), new_middle.len());
+    let mut lengths = vec![vec![0usize; columns + 1]; rows + 1];
+    for i in (0..rows).rev() {
+        for j in (0..columns).rev() {
+            lengths[i][j] = if old_middle[i] == new_middle[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits = vec![Edit::Keep; prefix];
+    let (mut i, mut j) = (0, 0);
+    while i < rows || j < columns {
+        if i < rows && j < columns && old_middle[i] == new_middle[j] {
+            edits.push(Edit::Keep);
+            i += 1;
+            j += 1;
+        } else if j < columns && (i == rows || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            edits.push(Edit::Insert);
+            j += 1;
+        } else {
+            edits.push(Edit::Remove);
+            i += 1;
+        }
+    }
+    edits.extend(vec![Edit::Keep; suffix]);
+    edits
+}
+
+impl Program {
+    /// Compare this program (the old) to another (the new), ignoring differences which do not
+    /// affect their meaning, such as the formatting of numbers, the way in which expressions are
+    /// written (see [`Expression::canonicalize`]), and the order of header definitions.
+    ///
+    /// Instructions are aligned so that as many as possible are unchanged. Within each run of
+    /// differing instructions, removed instructions are paired in order with inserted ones and
+    /// reported as changed; any left over are reported as removed or inserted.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let old = Program::from_str("RX(pi/2) 0\nH 1\nCNOT 0 1\nMEASURE 0").unwrap();
+    /// let new = Program::from_str("RX(pi / 2.0) 0\nCZ 0 1\nMEASURE 0\nMEASURE 1").unwrap();
+    /// assert_eq!(
+    ///     old.diff(&new).to_string(),
+    ///     "- H 1\n+ CZ 0 1\n- CNOT 0 1\n+ MEASURE 1\n"
+    /// );
+    /// ```
+    pub fn diff(&self, other: &Program) -> ProgramDiff {
+        let normalize = |program: &Program| -> Vec<Instruction> {
+            program
+                .to_instructions(true)
+                .into_iter()
+                .map(canonicalize_expressions)
+                .collect()
+        };
+        let old = normalize(self);
+        let new = normalize(other);
+
+        let mut changes = vec![];
+        let (mut old_index, mut new_index) = (0, 0);
+        let mut removed: Vec<usize> = vec![];
+        let mut inserted: Vec<usize> = vec![];
+
+        let mut flush = |removed: &mut Vec<usize>, inserted: &mut Vec<usize>| {
+            let paired = removed.len().min(inserted.len());
+            for (&old_index, &new_index) in removed.iter().zip(inserted.iter()) {
+                changes.push(InstructionChange::Changed {
+                    old_index,
+                    new_index,
+                    old: old[old_index].clone(),
+                    new: new[new_index].clone(),
+                });
+            }
+            for &old_index in &removed[paired..] {
+                changes.push(InstructionChange::Removed {
+                    old_index,
+                    instruction: old[old_index].clone(),
+                });
+            }
+            for &new_index in &inserted[paired..] {
+                changes.push(InstructionChange::Inserted {
+                    new_index,
+                    instruction: new[new_index].clone(),
+                });
+            }
+            removed.clear();
+            inserted.clear();
+        };
+
+        for edit in align(&old, &new) {
+            match edit {
+                Edit::Keep => {
+                    flush(&mut removed, &mut inserted);
+                    old_index += 1;
+                    new_index += 1;
+                }
+                Edit::Remove => {
+                    removed.push(old_index);
+                    old_index += 1;
+                }
+                Edit::Insert => {
+                    inserted.push(new_index);
+                    new_index += 1;
+                }
+            }
+        }
+        flush(&mut removed, &mut inserted);
+
+        ProgramDiff { changes }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::Program;
+
+    use super::InstructionChange;
+
+    #[test]
+    fn formatting_differences_are_ignored() {
+        let old = Program::from_str(
+            "DECLARE theta REAL[2]
+DECLARE ro BIT
+RX(theta[0] + 2*theta[1]) 0
+SHIFT-PHASE 0 \"rf\" -1.50
+",
+        )
+        .unwrap();
+        let new = Program::from_str(
+            "DECLARE ro BIT
+DECLARE theta REAL[2]
+RX(theta[1]*2 + theta[0]) 0
+SHIFT-PHASE 0 \"rf\" -(1.5)
+",
+        )
+        .unwrap();
+        assert!(old.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn diff() {
+        let old = Program::from_str("DECLARE ro BIT\nH 0\nX 0\nY 0\nMEASURE 0 ro").unwrap();
+        let new = Program::from_str("DECLARE ro BIT[2]\nH 0\nY 0\nZ 0\nMEASURE 0 ro").unwrap();
+        let changes = old.diff(&new).changes;
+
+        assert_eq!(changes.len(), 3);
+        assert!(matches!(
+            &changes[0],
+            InstructionChange::Changed {
+                old_index: 0,
+                new_index: 0,
+                ..
+            }
+        ));
+        assert!(matches!(
+            &changes[1],
+            InstructionChange::Removed { old_index: 2, .. }
+        ));
+        assert!(matches!(
+            &changes[2],
+            InstructionChange::Inserted { new_index: 3, .. }
+        ));
+        assert_eq!(
+            changes.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            vec!["- DECLARE ro BIT[1]\n+ DECLARE ro BIT[2]", "- X 0", "+ Z 0"]
+        );
+    }
+}
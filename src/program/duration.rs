@@ -0,0 +1,761 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use num_complex::Complex64;
+use thiserror::Error;
+
+use crate::expression::{EvaluationError, Expression};
+use crate::instruction::{
+    AttributeValue, Capture, Delay, FrameAttributes, FrameIdentifier, Instruction, MemoryReference,
+    Pulse, RawCapture,
+};
+
+use crate::waveform::{WaveformError, WaveformTemplate};
+
+use super::timing::evaluate_real_constant;
+use super::Program;
+
+/// The relative tolerance within which a number of samples is considered to be an integer.
+const SAMPLE_TOLERANCE: f64 = 1e-9;
+
+/// How a duration which is not a whole number of samples is converted into one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Rounding {
+    /// Round to the nearest number of samples, with halves rounded away from zero.
+    Nearest,
+    /// Round up to the next whole number of samples.
+    Up,
+    /// Round down to the previous whole number of samples.
+    Down,
+    /// Do not round: fail unless the duration is a whole number of samples.
+    Exact,
+}
+
+/// A problem in resolving a duration.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum DurationError {
+    #[error("could not evaluate duration {expression}: {source}")]
+    Evaluation {
+        expression: Expression,
+        source: EvaluationError,
+    },
+
+    #[error("duration {0} is not a finite, non-negative number of seconds")]
+    InvalidDuration(f64),
+
+    #[error("sample rate {0} is not a finite, positive number of Hertz")]
+    InvalidSampleRate(f64),
+
+    #[error("duration {duration} is not a whole number of samples at {sample_rate} Hz")]
+    InexactSampleCount { duration: f64, sample_rate: f64 },
+
+    #[error("duration {duration} has too many samples at {sample_rate} Hz to count")]
+    TooManySamples { duration: f64, sample_rate: f64 },
+
+    #[error("frame {0} has no constant SAMPLE-RATE")]
+    NoSampleRate(FrameIdentifier),
+}
+
+/// An instruction within a program whose length in samples does not agree with the frame on
+/// which it is played or captured.
+#[derive(Clone, Debug, Error, PartialEq)]
+#[error("instruction {location} ({instruction}) on frame {frame}: {kind}")]
+pub struct SampleLengthViolation {
+    /// The index of the instruction within [`Program::instructions`].
+    pub location: usize,
+    pub instruction: Instruction,
+    pub frame: FrameIdentifier,
+    pub kind: SampleLengthViolationKind,
+}
+
+/// The ways in which the length of a waveform or capture may disagree with its frame.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum SampleLengthViolationKind {
+    #[error("waveform {0} has no samples")]
+    EmptyWaveform(String),
+
+    #[error("RAW-CAPTURE duration: {0}")]
+    RawCaptureDuration(DurationError),
+
+    #[error("kernel {waveform} has {samples} samples, but RAW-CAPTURE on the same frame captures {expected}")]
+    KernelLength {
+        waveform: String,
+        samples: u64,
+        expected: u64,
+    },
+
+    #[error("kernel is neither a DEFWAVEFORM nor a standard template: {0}")]
+    InvalidKernel(WaveformError),
+
+    #[error("kernel duration: {0}")]
+    KernelDuration(DurationError),
+
+    #[error("RAW-CAPTURE writes to undeclared memory region {0}")]
+    UndeclaredRegion(String),
+
+    #[error("RAW-CAPTURE needs {required} values of memory region {region}, which has {declared}")]
+    InsufficientMemory {
+        region: String,
+        required: u64,
+        declared: u64,
+    },
+}
+
+/// The number of `REAL` values in memory filled by each IQ sample of a `RAW-CAPTURE`: its
+/// in-phase and quadrature components.
+const RAW_CAPTURE_VALUES_PER_SAMPLE: u64 = 2;
+
+/// The size of the data captured by a `RAW-CAPTURE`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RawCaptureSize {
+    /// The number of IQ samples captured.
+    pub samples: u64,
+    /// The length the destination memory region must have to hold the samples, which are
+    /// written as pairs of `REAL` values starting at the index of the memory reference.
+    pub memory_length: u64,
+}
+
+/// Convert a duration, in seconds, into a number of samples at the given rate in Hertz. A
+/// duration within floating-point error of a whole number of samples is that number, whatever
+/// the rounding.
+///
+/// ```rust
+/// use quil_rs::program::{seconds_to_samples, DurationError, Rounding};
+///
+/// assert_eq!(seconds_to_samples(1e-6, 1e9, Rounding::Exact), Ok(1000));
+/// assert_eq!(seconds_to_samples(2.5e-9, 1e9, Rounding::Down), Ok(2));
+/// assert_eq!(seconds_to_samples(2.5e-9, 1e9, Rounding::Nearest), Ok(3));
+/// assert!(matches!(
+///     seconds_to_samples(2.5e-9, 1e9, Rounding::Exact),
+///     Err(DurationError::InexactSampleCount { .. })
+/// ));
+/// ```
+pub fn seconds_to_samples(
+    duration: f64,
+    sample_rate: f64,
+    rounding: Rounding,
+) -> Result<u64, DurationError> {
+    if !(duration.is_finite() && duration >= 0.0) {
+        return Err(DurationError::InvalidDuration(duration));
+    }
+    if !(sample_rate.is_finite() && sample_rate > 0.0) {
+        return Err(DurationError::InvalidSampleRate(sample_rate));
+    }
+
+    let exact = duration * sample_rate;
+    let nearest = exact.round();
+    let samples = if (exact - nearest).abs() <= SAMPLE_TOLERANCE * nearest.max(1.0) {
+        nearest
+    } else {
+        match rounding {
+            Rounding::Nearest => nearest,
+            Rounding::Up => exact.ceil(),
+            Rounding::Down => exact.floor(),
+            Rounding::Exact => {
+                return Err(DurationError::InexactSampleCount {
+                    duration,
+                    sample_rate,
+                })
+            }
+        }
+    };
+
+    // Every integer up to 2^53 is represented exactly, and so converts without loss.
+    if samples > (1u64 << f64::MANTISSA_DIGITS) as f64 {
+        return Err(DurationError::TooManySamples {
+            duration,
+            sample_rate,
+        });
+    }
+    Ok(samples as u64)
+}
+
+/// Evaluate a duration, in seconds, which must be a finite, non-negative real number.
+fn resolve_seconds(
+    duration: &Expression,
+    variables: &HashMap<String, Complex64>,
+    memory_references: &HashMap<&str, Vec<f64>>,
+) -> Result<f64, DurationError> {
+    let error = |source| DurationError::Evaluation {
+        expression: duration.clone(),
+        source,
+    };
+    let value = duration
+        .evaluate(variables, memory_references)
+        .map_err(error)?;
+    let seconds = Expression::from(value).to_real().map_err(error)?;
+    if seconds.is_finite() && seconds >= 0.0 {
+        Ok(seconds)
+    } else {
+        Err(DurationError::InvalidDuration(seconds))
+    }
+}
+
+impl Delay {
+    /// Evaluate the duration of the delay, in seconds, using the given values of variables and
+    /// memory, as for [`Expression::evaluate`]. Fails if it does not evaluate to a finite,
+    /// non-negative real number.
+    pub fn resolve_duration(
+        &self,
+        variables: &HashMap<String, Complex64>,
+        memory_references: &HashMap<&str, Vec<f64>>,
+    ) -> Result<f64, DurationError> {
+        resolve_seconds(&self.duration, variables, memory_references)
+    }
+
+    /// Evaluate the duration of the delay as with [`Delay::resolve_duration`], and convert it into
+    /// a number of samples at the given rate as with [`seconds_to_samples`].
+    pub fn resolve_sample_count(
+        &self,
+        sample_rate: f64,
+        rounding: Rounding,
+        variables: &HashMap<String, Complex64>,
+        memory_references: &HashMap<&str, Vec<f64>>,
+    ) -> Result<u64, DurationError> {
+        let seconds = self.resolve_duration(variables, memory_references)?;
+        seconds_to_samples(seconds, sample_rate, rounding)
+    }
+}
+
+impl RawCapture {
+    /// Evaluate the duration of the capture, in seconds, as with [`Delay::resolve_duration`].
+    pub fn resolve_duration(
+        &self,
+        variables: &HashMap<String, Complex64>,
+        memory_references: &HashMap<&str, Vec<f64>>,
+    ) -> Result<f64, DurationError> {
+        resolve_seconds(&self.duration, variables, memory_references)
+    }
+
+    /// Evaluate the duration of the capture, and convert it into a number of IQ samples at the
+    /// given rate, as with [`Delay::resolve_sample_count`].
+    pub fn resolve_sample_count(
+        &self,
+        sample_rate: f64,
+        rounding: Rounding,
+        variables: &HashMap<String, Complex64>,
+        memory_references: &HashMap<&str, Vec<f64>>,
+    ) -> Result<u64, DurationError> {
+        let seconds = self.resolve_duration(variables, memory_references)?;
+        seconds_to_samples(seconds, sample_rate, rounding)
+    }
+}
+
+impl Program {
+    /// Return the constant `SAMPLE-RATE` of the given frame, in Hertz, if it is defined within
+    /// the program and has one.
+    pub fn get_sample_rate(&self, frame: &FrameIdentifier) -> Option<f64> {
+        match self.frames.get(frame)?.get(FrameAttributes::SAMPLE_RATE)? {
+            AttributeValue::Expression(expression) => evaluate_real_constant(expression),
+            AttributeValue::String(_) => None,
+        }
+    }
+
+    /// Resolve the duration of a delay into a number of samples at the `SAMPLE-RATE` of the
+    /// given frame, as with [`Delay::resolve_sample_count`].
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use std::str::FromStr;
+    /// use quil_rs::instruction::{FrameIdentifier, Instruction, Qubit};
+    /// use quil_rs::program::Rounding;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DECLARE wait REAL\nDEFFRAME 0 \"rf\":\n    SAMPLE-RATE: 1e9\nDELAY 0 \"rf\" 2*wait",
+    /// )
+    /// .unwrap();
+    /// let delay = match &program.instructions[0] {
+    ///     Instruction::Delay(delay) => delay,
+    ///     _ => unreachable!(),
+    /// };
+    /// let frame = FrameIdentifier {
+    ///     name: "rf".to_owned(),
+    ///     qubits: vec![Qubit::Fixed(0)],
+    /// };
+    ///
+    /// let mut memory = HashMap::new();
+    /// memory.insert("wait", vec![5e-7]);
+    /// let samples = program
+    ///     .resolve_delay_sample_count(delay, &frame, Rounding::Exact, &HashMap::new(), &memory)
+    ///     .unwrap();
+    /// assert_eq!(samples, 1000);
+    /// ```
+    pub fn resolve_delay_sample_count(
+        &self,
+        delay: &Delay,
+        frame: &FrameIdentifier,
+        rounding: Rounding,
+        variables: &HashMap<String, Complex64>,
+        memory_references: &HashMap<&str, Vec<f64>>,
+    ) -> Result<u64, DurationError> {
+        let sample_rate = self
+            .get_sample_rate(frame)
+            .ok_or_else(|| DurationError::NoSampleRate(frame.clone()))?;
+        delay.resolve_sample_count(sample_rate, rounding, variables, memory_references)
+    }
+
+    /// Return the length of memory needed to hold the given number of IQ samples captured by a
+    /// `RAW-CAPTURE` into the given memory reference, checking that its region is declared with
+    /// at least that length.
+    fn check_raw_capture_memory(
+        &self,
+        memory_reference: &MemoryReference,
+        samples: u64,
+    ) -> Result<u64, SampleLengthViolationKind> {
+        let region = self
+            .memory_regions
+            .get(&memory_reference.name)
+            .ok_or_else(|| {
+                SampleLengthViolationKind::UndeclaredRegion(memory_reference.name.clone())
+            })?;
+        let required = samples
+            .checked_mul(RAW_CAPTURE_VALUES_PER_SAMPLE)
+            .and_then(|length| length.checked_add(memory_reference.index))
+            .unwrap_or(u64::MAX);
+        if required > region.size.length {
+            return Err(SampleLengthViolationKind::InsufficientMemory {
+                region: memory_reference.name.clone(),
+                required,
+                declared: region.size.length,
+            });
+        }
+        Ok(required)
+    }
+
+    /// Resolve the duration of a `RAW-CAPTURE` into the number of IQ samples it captures at the
+    /// `SAMPLE-RATE` of its frame, as with [`RawCapture::resolve_sample_count`], along with the
+    /// length of memory they fill, and check that the destination memory region is declared
+    /// with at least that length.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use std::str::FromStr;
+    /// use quil_rs::instruction::Instruction;
+    /// use quil_rs::program::{RawCaptureSize, Rounding, SampleLengthViolationKind};
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DEFFRAME 0 \"ro\":
+    ///     SAMPLE-RATE: 2
+    /// DECLARE raw REAL[10]
+    /// RAW-CAPTURE 0 \"ro\" 2 raw[1]
+    /// RAW-CAPTURE 0 \"ro\" 3 raw
+    /// ",
+    /// )
+    /// .unwrap();
+    /// let sizes: Vec<_> = program
+    ///     .instructions
+    ///     .iter()
+    ///     .map(|instruction| match instruction {
+    ///         Instruction::RawCapture(raw_capture) => program.resolve_raw_capture_size(
+    ///             raw_capture,
+    ///             Rounding::Exact,
+    ///             &HashMap::new(),
+    ///             &HashMap::new(),
+    ///         ),
+    ///         _ => unreachable!(),
+    ///     })
+    ///     .collect();
+    /// assert_eq!(sizes[0], Ok(RawCaptureSize { samples: 4, memory_length: 9 }));
+    /// assert_eq!(
+    ///     sizes[1],
+    ///     Err(SampleLengthViolationKind::InsufficientMemory {
+    ///         region: "raw".to_owned(),
+    ///         required: 12,
+    ///         declared: 10,
+    ///     })
+    /// );
+    /// ```
+    pub fn resolve_raw_capture_size(
+        &self,
+        raw_capture: &RawCapture,
+        rounding: Rounding,
+        variables: &HashMap<String, Complex64>,
+        memory_references: &HashMap<&str, Vec<f64>>,
+    ) -> Result<RawCaptureSize, SampleLengthViolationKind> {
+        let sample_rate = self.get_sample_rate(&raw_capture.frame).ok_or_else(|| {
+            SampleLengthViolationKind::RawCaptureDuration(DurationError::NoSampleRate(
+                raw_capture.frame.clone(),
+            ))
+        })?;
+        let samples = raw_capture
+            .resolve_sample_count(sample_rate, rounding, variables, memory_references)
+            .map_err(SampleLengthViolationKind::RawCaptureDuration)?;
+        let memory_length =
+            self.check_raw_capture_memory(&raw_capture.memory_reference, samples)?;
+        Ok(RawCaptureSize {
+            samples,
+            memory_length,
+        })
+    }
+
+    /// Check that the integration kernel of a `CAPTURE` fits the `SAMPLE-RATE` of its frame, and
+    /// return its length in samples. The kernel may be a `DEFWAVEFORM`, which must have samples,
+    /// or a standard template, such as one built by
+    /// [`BoxcarKernel::with_sample_rate`](crate::waveform::BoxcarKernel::with_sample_rate), whose
+    /// duration must be a whole number of samples.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::instruction::Instruction;
+    /// use quil_rs::program::SampleLengthViolationKind;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DEFFRAME 0 \"ro_rx\":
+    ///     SAMPLE-RATE: 1e9
+    /// DECLARE iq REAL[2]
+    /// CAPTURE 0 \"ro_rx\" boxcar_kernel(duration: 2e-6) iq
+    /// CAPTURE 0 \"ro_rx\" boxcar_kernel(duration: 2.5e-9) iq
+    /// ",
+    /// )
+    /// .unwrap();
+    /// let kernels: Vec<_> = program
+    ///     .instructions
+    ///     .iter()
+    ///     .map(|instruction| match instruction {
+    ///         Instruction::Capture(capture) => program.validate_capture_kernel(capture),
+    ///         _ => unreachable!(),
+    ///     })
+    ///     .collect();
+    /// assert_eq!(kernels[0], Ok(2000));
+    /// assert!(matches!(
+    ///     kernels[1],
+    ///     Err(SampleLengthViolationKind::KernelDuration(_))
+    /// ));
+    /// ```
+    pub fn validate_capture_kernel(
+        &self,
+        capture: &Capture,
+    ) -> Result<u64, SampleLengthViolationKind> {
+        let waveform = &capture.waveform;
+        let sample_rate = self.get_sample_rate(&capture.frame).ok_or_else(|| {
+            SampleLengthViolationKind::KernelDuration(DurationError::NoSampleRate(
+                capture.frame.clone(),
+            ))
+        })?;
+
+        match self.waveforms.get(&waveform.name) {
+            Some(definition) if definition.matrix.is_empty() => Err(
+                SampleLengthViolationKind::EmptyWaveform(waveform.name.clone()),
+            ),
+            Some(definition) => Ok(definition.matrix.len() as u64),
+            None => {
+                let template = waveform
+                    .to_template()
+                    .map_err(SampleLengthViolationKind::InvalidKernel)?;
+                seconds_to_samples(template.duration(), sample_rate, Rounding::Exact)
+                    .map_err(SampleLengthViolationKind::KernelDuration)
+            }
+        }
+    }
+
+    /// Check that the lengths of waveforms and captures agree with the `SAMPLE-RATE` of the
+    /// frames on which they are used:
+    ///
+    /// * each `DEFWAVEFORM` played by a `PULSE` has samples;
+    /// * each `RAW-CAPTURE` with a constant duration captures a whole number of samples, into a
+    ///   memory region declared long enough to hold them, as checked by
+    ///   [`Program::resolve_raw_capture_size`];
+    /// * each `CAPTURE` kernel fits its frame, as checked by [`Program::validate_capture_kernel`],
+    ///   and has as many samples as the first `RAW-CAPTURE` on the same frame captures.
+    ///
+    /// Frames without a constant `SAMPLE-RATE`, pulse templates, kernels which are neither a
+    /// `DEFWAVEFORM` nor a template with constant parameters, and `RAW-CAPTURE`s whose durations
+    /// are not constant are not checked. All violations are reported, in the order of
+    /// the instructions in which they occur.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::program::SampleLengthViolationKind;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DEFFRAME 0 \"ro_rx\":
+    ///     SAMPLE-RATE: 1e9
+    /// DEFWAVEFORM kernel:
+    ///     1, 1, 1
+    /// DECLARE iq REAL[2]
+    /// DECLARE raw REAL[8]
+    /// CAPTURE 0 \"ro_rx\" kernel iq
+    /// RAW-CAPTURE 0 \"ro_rx\" 4e-9 raw
+    /// ",
+    /// )
+    /// .unwrap();
+    /// let violations = program.validate_sample_lengths().unwrap_err();
+    /// assert_eq!(violations.len(), 1);
+    /// assert_eq!(violations[0].location, 0);
+    /// assert_eq!(
+    ///     violations[0].kind,
+    ///     SampleLengthViolationKind::KernelLength {
+    ///         waveform: "kernel".to_owned(),
+    ///         samples: 3,
+    ///         expected: 4,
+    ///     }
+    /// );
+    /// ```
+    pub fn validate_sample_lengths(&self) -> Result<(), Vec<SampleLengthViolation>> {
+        let mut violations = vec![];
+        let mut raw_capture_samples: HashMap<&FrameIdentifier, u64> = HashMap::new();
+
+        for (location, instruction) in self.instructions.iter().enumerate() {
+            if let Instruction::RawCapture(RawCapture {
+                frame,
+                duration,
+                memory_reference,
+                ..
+            }) = instruction
+            {
+                let (sample_rate, duration) = match (
+                    self.get_sample_rate(frame),
+                    evaluate_real_constant(duration),
+                ) {
+                    (Some(sample_rate), Some(duration)) => (sample_rate, duration),
+                    _ => continue,
+                };
+                let result = seconds_to_samples(duration, sample_rate, Rounding::Exact)
+                    .map_err(SampleLengthViolationKind::RawCaptureDuration)
+                    .and_then(|samples| {
+                        raw_capture_samples.entry(frame).or_insert(samples);
+                        self.check_raw_capture_memory(memory_reference, samples)
+                    });
+                if let Err(kind) = result {
+                    violations.push(SampleLengthViolation {
+                        location,
+                        instruction: instruction.clone(),
+                        frame: frame.clone(),
+                        kind,
+                    });
+                }
+            }
+        }
+
+        for (location, instruction) in self.instructions.iter().enumerate() {
+            let (frame, kind) = match instruction {
+                Instruction::Pulse(Pulse {
+                    frame, waveform, ..
+                }) => match self.waveforms.get(&waveform.name) {
+                    Some(definition)
+                        if definition.matrix.is_empty()
+                            && self.get_sample_rate(frame).is_some() =>
+                    {
+                        (
+                            frame,
+                            SampleLengthViolationKind::EmptyWaveform(waveform.name.clone()),
+                        )
+                    }
+                    _ => continue,
+                },
+                Instruction::Capture(capture) => {
+                    let kind = match self.validate_capture_kernel(capture) {
+                        Ok(samples) => match raw_capture_samples.get(&capture.frame) {
+                            Some(&expected) if samples != expected => {
+                                SampleLengthViolationKind::KernelLength {
+                                    waveform: capture.waveform.name.clone(),
+                                    samples,
+                                    expected,
+                                }
+                            }
+                            _ => continue,
+                        },
+                        Err(SampleLengthViolationKind::InvalidKernel(_))
+                        | Err(SampleLengthViolationKind::KernelDuration(
+                            DurationError::NoSampleRate(_),
+                        )) => continue,
+                        Err(kind) => kind,
+                    };
+                    (&capture.frame, kind)
+                }
+                _ => continue,
+            };
+            violations.push(SampleLengthViolation {
+                location,
+                instruction: instruction.clone(),
+                frame: frame.clone(),
+                kind,
+            });
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            violations.sort_by_key(|violation| violation.location);
+            Err(violations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use crate::expression::{EvaluationError, Expression};
+    use crate::instruction::{Delay, Qubit, Waveform};
+    use crate::{imag, real, Program};
+
+    use super::{seconds_to_samples, DurationError, Rounding, SampleLengthViolationKind};
+
+    fn delay(duration: Expression) -> Delay {
+        Delay {
+            duration,
+            frame_names: vec![],
+            qubits: vec![Qubit::Fixed(0)],
+        }
+    }
+
+    #[test]
+    fn rounding() {
+        assert_eq!(seconds_to_samples(1.4, 1.0, Rounding::Nearest), Ok(1));
+        assert_eq!(seconds_to_samples(1.5, 1.0, Rounding::Nearest), Ok(2));
+        assert_eq!(seconds_to_samples(1.1, 1.0, Rounding::Up), Ok(2));
+        assert_eq!(seconds_to_samples(1.9, 1.0, Rounding::Down), Ok(1));
+        // Floating-point error does not push a whole number of samples to the next one.
+        assert_eq!(seconds_to_samples(0.3, 10.0, Rounding::Up), Ok(3));
+        assert_eq!(seconds_to_samples(0.0, 10.0, Rounding::Exact), Ok(0));
+    }
+
+    #[test]
+    fn unrepresentable_durations() {
+        assert_eq!(
+            seconds_to_samples(-1.0, 1.0, Rounding::Nearest),
+            Err(DurationError::InvalidDuration(-1.0))
+        );
+        assert_eq!(
+            seconds_to_samples(1.0, 0.0, Rounding::Nearest),
+            Err(DurationError::InvalidSampleRate(0.0))
+        );
+        assert_eq!(
+            seconds_to_samples(1e10, 1e9, Rounding::Nearest),
+            Err(DurationError::TooManySamples {
+                duration: 1e10,
+                sample_rate: 1e9
+            })
+        );
+    }
+
+    #[test]
+    fn resolve_delays() {
+        let mut variables = HashMap::new();
+        variables.insert("t".to_owned(), real!(2e-9));
+        let mut memory = HashMap::new();
+        memory.insert("offset", vec![0.0, 1e-9]);
+
+        let resolved = delay("%t + offset[1]".parse().unwrap());
+        assert_eq!(
+            resolved.resolve_sample_count(1e9, Rounding::Exact, &variables, &memory),
+            Ok(3)
+        );
+
+        let unbound = delay("%t".parse().unwrap());
+        assert!(matches!(
+            unbound.resolve_duration(&HashMap::new(), &memory),
+            Err(DurationError::Evaluation {
+                source: EvaluationError::Incomplete { .. },
+                ..
+            })
+        ));
+
+        let complex = delay(Expression::from(real!(1.0) + imag!(1.0)));
+        assert!(matches!(
+            complex.resolve_duration(&variables, &memory),
+            Err(DurationError::Evaluation {
+                source: EvaluationError::NumberNotReal,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn sample_lengths() {
+        let mut program = Program::from_str(
+            "DEFFRAME 0 \"rf\":
+    SAMPLE-RATE: 1e9
+DEFFRAME 1 \"rf\":
+    INITIAL-FREQUENCY: 1e9
+DEFFRAME 0 \"ro\":
+    SAMPLE-RATE: 1e9
+DECLARE raw REAL[8]
+DECLARE iq REAL[2]
+PULSE 0 \"rf\" empty
+PULSE 1 \"rf\" empty
+RAW-CAPTURE 0 \"rf\" 2.5e-9 raw
+RAW-CAPTURE 1 \"rf\" 2.5e-9 raw
+RAW-CAPTURE 0 \"rf\" %t raw
+PULSE 0 \"rf\" flat(duration: 1e-9, iq: 1)
+RAW-CAPTURE 0 \"ro\" 4e-9 raw
+CAPTURE 0 \"ro\" boxcar_kernel(duration: 2e-9) iq
+CAPTURE 0 \"ro\" boxcar_kernel(duration: 4e-9) iq
+CAPTURE 0 \"ro\" boxcar_kernel(duration: %t) iq
+RAW-CAPTURE 0 \"ro\" 4e-9 raw[2]
+RAW-CAPTURE 0 \"ro\" 4e-9 missing
+",
+        )
+        .unwrap();
+        program.waveforms.insert(
+            "empty".to_owned(),
+            Waveform {
+                matrix: vec![],
+                parameters: vec![],
+            },
+        );
+
+        let violations = program.validate_sample_lengths().unwrap_err();
+        let described: Vec<(usize, SampleLengthViolationKind)> = violations
+            .into_iter()
+            .map(|violation| (violation.location, violation.kind))
+            .collect();
+        assert_eq!(
+            described,
+            vec![
+                (
+                    0,
+                    SampleLengthViolationKind::EmptyWaveform("empty".to_owned())
+                ),
+                (
+                    2,
+                    SampleLengthViolationKind::RawCaptureDuration(
+                        DurationError::InexactSampleCount {
+                            duration: 2.5e-9,
+                            sample_rate: 1e9
+                        }
+                    )
+                ),
+                (
+                    7,
+                    SampleLengthViolationKind::KernelLength {
+                        waveform: "boxcar_kernel".to_owned(),
+                        samples: 2,
+                        expected: 4
+                    }
+                ),
+                (
+                    10,
+                    SampleLengthViolationKind::InsufficientMemory {
+                        region: "raw".to_owned(),
+                        required: 10,
+                        declared: 8
+                    }
+                ),
+                (
+                    11,
+                    SampleLengthViolationKind::UndeclaredRegion("missing".to_owned())
+                ),
+            ]
+        );
+    }
+}
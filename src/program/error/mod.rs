@@ -20,7 +20,7 @@ use std::error::Error;
 use std::fmt;
 use std::fmt::Formatter;
 
-use crate::instruction::Instruction;
+use crate::instruction::{Calibration, Instruction};
 use crate::parser::{LexError, ParseError};
 pub use leftover::LeftoverError;
 pub use result::{disallow_leftover, map_parsed, recover};
@@ -29,11 +29,36 @@ pub use syntax::SyntaxError;
 /// Errors that may occur while parsing a [`Program`](crate::program::Program).
 #[derive(Debug, PartialEq)]
 pub enum ProgramError<T> {
+    /// More than one calibration matches an instruction with equal precedence.
+    AmbiguousCalibration {
+        instruction: Instruction,
+        calibrations: Vec<Calibration>,
+    },
     InvalidCalibration {
         instruction: Instruction,
         message: String,
     },
-    RecursiveCalibration(Instruction),
+    /// An instruction has no inverse, such as when inverting a program.
+    IrreversibleInstruction {
+        instruction: Instruction,
+    },
+    /// A definition, such as of a memory region, frame, waveform, or calibration, conflicts with
+    /// an existing definition of the same thing, such as when appending one program to another.
+    ConflictingDefinition {
+        existing: Instruction,
+        new: Instruction,
+    },
+    /// Calibrations were nested more deeply than allowed while expanding an instruction.
+    CalibrationDepthExceeded {
+        instruction: Instruction,
+        max_depth: usize,
+    },
+    /// An instruction expands, directly or indirectly, into itself. The cycle begins and ends
+    /// with that instruction, and includes each instruction expanded in between.
+    RecursiveCalibration {
+        instruction: Instruction,
+        cycle: Vec<Instruction>,
+    },
     Syntax(SyntaxError<T>),
 }
 
@@ -71,6 +96,13 @@ impl<T> ProgramError<T> {
     /// nothing but change the type signature otherwise.
     pub fn map_parsed<T2>(self, map: impl Fn(T) -> T2) -> ProgramError<T2> {
         match self {
+            Self::AmbiguousCalibration {
+                instruction,
+                calibrations,
+            } => ProgramError::AmbiguousCalibration {
+                instruction,
+                calibrations,
+            },
             Self::InvalidCalibration {
                 instruction,
                 message,
@@ -78,7 +110,22 @@ impl<T> ProgramError<T> {
                 instruction,
                 message,
             },
-            Self::RecursiveCalibration(inst) => ProgramError::RecursiveCalibration(inst),
+            Self::IrreversibleInstruction { instruction } => {
+                ProgramError::IrreversibleInstruction { instruction }
+            }
+            Self::ConflictingDefinition { existing, new } => {
+                ProgramError::ConflictingDefinition { existing, new }
+            }
+            Self::CalibrationDepthExceeded {
+                instruction,
+                max_depth,
+            } => ProgramError::CalibrationDepthExceeded {
+                instruction,
+                max_depth,
+            },
+            Self::RecursiveCalibration { instruction, cycle } => {
+                ProgramError::RecursiveCalibration { instruction, cycle }
+            }
             Self::Syntax(err) => ProgramError::Syntax(err.map_parsed(map)),
         }
     }
@@ -90,13 +137,43 @@ where
 {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
+            Self::AmbiguousCalibration {
+                instruction,
+                calibrations,
+            } => write!(
+                f,
+                "instruction {} matches {} calibrations with equal precedence",
+                instruction,
+                calibrations.len()
+            ),
             Self::InvalidCalibration {
                 instruction,
                 message,
             } => write!(f, "invalid calibration `{}`: {}", instruction, message),
-            Self::RecursiveCalibration(instruction) => {
-                write!(f, "instruction {} expands into itself", instruction)
+            Self::IrreversibleInstruction { instruction } => {
+                write!(f, "instruction {} cannot be inverted", instruction)
+            }
+            Self::ConflictingDefinition { existing, new } => {
+                write!(f, "definition `{}` conflicts with `{}`", new, existing)
             }
+            Self::CalibrationDepthExceeded {
+                instruction,
+                max_depth,
+            } => write!(
+                f,
+                "instruction {} expands through more than {} nested calibrations",
+                instruction, max_depth
+            ),
+            Self::RecursiveCalibration { instruction, cycle } => write!(
+                f,
+                "instruction {} expands into itself: {}",
+                instruction,
+                cycle
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            ),
             Self::Syntax(err) => fmt::Display::fmt(err, f),
         }
     }
@@ -108,8 +185,12 @@ where
 {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
+            Self::AmbiguousCalibration { .. } => None,
             Self::InvalidCalibration { .. } => None,
-            Self::RecursiveCalibration(_) => None,
+            Self::IrreversibleInstruction { .. } => None,
+            Self::ConflictingDefinition { .. } => None,
+            Self::CalibrationDepthExceeded { .. } => None,
+            Self::RecursiveCalibration { .. } => None,
             Self::Syntax(err) => Some(err),
         }
     }
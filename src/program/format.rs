@@ -0,0 +1,234 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::str::FromStr;
+
+use crate::instruction::Instruction;
+
+use super::{Program, SourceMap};
+
+/// Return true if the text is exactly the given instruction, as written in source.
+fn is_written_as(text: &str, instruction: &Instruction) -> bool {
+    Program::from_str(text)
+        .map(|parsed| parsed.instructions == vec![instruction.clone()])
+        .unwrap_or(false)
+}
+
+/// Return only the blank lines and comments within text found between instructions.
+fn comments_and_blank_lines(text: &str) -> String {
+    text.split_inclusive('\n')
+        .filter(|line| {
+            let line = line.trim();
+            line.is_empty() || line.starts_with('#')
+        })
+        .collect()
+}
+
+/// Builds formatted output, one instruction at a time.
+#[derive(Default)]
+struct Writer {
+    output: String,
+}
+
+impl Writer {
+    /// Write the text on a new line.
+    fn write_line(&mut self, text: &str) {
+        if !self.output.is_empty() && !self.output.ends_with('\n') {
+            self.output.push('\n');
+        }
+        self.output.push_str(text);
+    }
+}
+
+impl Program {
+    /// Format the program as Quil, preserving the formatting of the `source` from which it was
+    /// parsed as far as possible, so that reformatting a program does not change text which it
+    /// need not.
+    ///
+    /// `source_map` must map this program's instructions to their locations in `source`, as
+    /// returned by [`Program::parse_with_source_map`] and kept up to date by, for example,
+    /// [`Program::transform_with_source_map`].
+    ///
+    /// Each instruction which is unchanged from its source is written exactly as it was in the
+    /// source, along with the comments and whitespace around it, while any other instruction is
+    /// written as by [`Program::to_string`]. If the program's header definitions (such as
+    /// `DECLARE` and `DEFCAL`) are unchanged from the source, they too are written as they were;
+    /// otherwise, they are written at the top of the program, and only the comments and blank
+    /// lines from between the source's instructions are kept.
+    ///
+    /// ```rust
+    /// use quil_rs::{instruction::Instruction, Program};
+    ///
+    /// let source = "# Prepare\nRX(1.50) 0 # rotate\n\nH   0\nMEASURE 0\n";
+    /// let (program, source_map) = Program::parse_with_source_map(source).unwrap();
+    /// let x = Instruction::from(Instruction::gate("X").qubit(0));
+    /// let (program, source_map) = program.transform_with_source_map(
+    ///     &mut |instruction: Instruction| match instruction {
+    ///         Instruction::Gate(gate) if gate.name == "H" => vec![x.clone()],
+    ///         other => vec![other],
+    ///     },
+    ///     &source_map,
+    /// );
+    /// assert_eq!(
+    ///     program.to_string_preserving_format(source, &source_map),
+    ///     "# Prepare\nRX(1.50) 0 # rotate\n\nX 0\nMEASURE 0\n"
+    /// );
+    /// ```
+    pub fn to_string_preserving_format(&self, source: &str, source_map: &SourceMap) -> String {
+        let headers_unchanged = Program::from_str(source)
+            .map(|original| {
+                original.calibrations == self.calibrations
+                    && original.frames == self.frames
+                    && original.memory_regions == self.memory_regions
+                    && original.waveforms == self.waveforms
+            })
+            .unwrap_or(false);
+
+        let mut writer = Writer::default();
+        if !headers_unchanged {
+            for header in self.header_instructions() {
+                writer.write_line(&format!("{}\n", header));
+            }
+        }
+
+        // The end of the source text which has been written, or skipped over.
+        let mut position = 0;
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let location = source_map
+                .get(index)
+                .filter(|location| location.span.start >= position);
+            let location = match location {
+                Some(location) => location,
+                None => {
+                    writer.write_line(&instruction.to_string());
+                    continue;
+                }
+            };
+
+            let gap = &source[position..location.span.start];
+            let text = &source[location.span.clone()];
+            let text = if is_written_as(text, instruction) {
+                text.to_owned()
+            } else {
+                instruction.to_string()
+            };
+
+            if headers_unchanged {
+                writer.output.push_str(gap);
+                writer.output.push_str(&text);
+            } else {
+                writer.output.push_str(&comments_and_blank_lines(gap));
+                writer.write_line(&text);
+            }
+            position = location.span.end;
+        }
+
+        let rest = &source[position..];
+        if headers_unchanged {
+            writer.output.push_str(rest);
+        } else {
+            writer.output.push_str(&comments_and_blank_lines(rest));
+        }
+        if !writer.output.is_empty() && !writer.output.ends_with('\n') {
+            writer.output.push('\n');
+        }
+
+        writer.output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::instruction::{Declaration, Instruction, ScalarType, Vector};
+    use crate::Program;
+
+    const SOURCE: &str = "# Header
+DECLARE ro BIT[2]
+
+# Body
+RX(1.50) 0   # rotate
+CNOT 0 1; MEASURE 0 ro[0]
+
+# Done
+MEASURE 1 ro[1]
+";
+
+    #[test]
+    fn unchanged_program_is_unchanged() {
+        let (program, source_map) = Program::parse_with_source_map(SOURCE).unwrap();
+        assert_eq!(
+            program.to_string_preserving_format(SOURCE, &source_map),
+            SOURCE
+        );
+    }
+
+    #[test]
+    fn only_changed_instructions_are_normalized() {
+        let (program, source_map) = Program::parse_with_source_map(SOURCE).unwrap();
+        let (program, source_map) = program.transform_with_source_map(
+            &mut |instruction: Instruction| match instruction {
+                Instruction::Gate(gate) if gate.name == "CNOT" => vec![
+                    Instruction::gate("H").qubit(1).into(),
+                    Instruction::gate("CZ").qubit(0).qubit(1).into(),
+                    Instruction::gate("H").qubit(1).into(),
+                ],
+                other => vec![other],
+            },
+            &source_map,
+        );
+        assert_eq!(
+            program.to_string_preserving_format(SOURCE, &source_map),
+            "# Header
+DECLARE ro BIT[2]
+
+# Body
+RX(1.50) 0   # rotate
+H 1
+CZ 0 1
+H 1; MEASURE 0 ro[0]
+
+# Done
+MEASURE 1 ro[1]
+"
+        );
+    }
+
+    #[test]
+    fn changed_headers_are_normalized() {
+        let (mut program, source_map) = Program::parse_with_source_map(SOURCE).unwrap();
+        program.add_instruction(Instruction::Declaration(Declaration {
+            name: "theta".to_owned(),
+            size: Vector {
+                data_type: ScalarType::Real,
+                length: 1,
+            },
+            sharing: None,
+        }));
+        assert_eq!(
+            program.to_string_preserving_format(SOURCE, &source_map),
+            "DECLARE ro BIT[2]
+DECLARE theta REAL[1]
+# Header
+
+# Body
+RX(1.50) 0   # rotate
+CNOT 0 1
+MEASURE 0 ro[0]
+
+# Done
+MEASURE 1 ro[1]
+"
+        );
+    }
+}
@@ -14,19 +14,44 @@
 
 use std::collections::{HashMap, HashSet};
 
-use crate::instruction::{FrameAttributes, FrameDefinition, FrameIdentifier, Instruction, Qubit};
+#[cfg(feature = "serde")]
+use serde::de::DeserializeOwned;
+
+#[cfg(feature = "serde")]
+use crate::instruction::{AttributeValue, FrameAttributeError, FrameAttributes};
+use crate::instruction::{
+    FrameDefinition, FrameIdentifier, Instruction, Qubit, RawFrameAttributes,
+};
+
+use super::Shared;
 
 /// A collection of Quil frames (`DEFFRAME` instructions) with utility methods.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct FrameSet {
-    frames: HashMap<FrameIdentifier, FrameAttributes>,
+    frames: Shared<HashMap<FrameIdentifier, RawFrameAttributes>>,
 }
 
 impl FrameSet {
     pub fn new() -> Self {
         FrameSet {
-            frames: HashMap::new(),
+            frames: Shared::default(),
+        }
+    }
+
+    /// Build a set of frames from the `DEFFRAME` instructions among those given, ignoring all
+    /// others. Later definitions of a frame replace earlier ones.
+    pub fn from_instructions<I: IntoIterator<Item = Instruction>>(instructions: I) -> Self {
+        let mut frames = Self::new();
+        for instruction in instructions {
+            if let Instruction::FrameDefinition(FrameDefinition {
+                identifier,
+                attributes,
+            }) = instruction
+            {
+                frames.insert(identifier, attributes);
+            }
         }
+        frames
     }
 
     /// Return a list of all frame IDs described by this FrameSet.
@@ -69,6 +94,10 @@ impl FrameSet {
                     HashSet::new()
                 }
             }
+            FrameMatchCondition::AnyOf(conditions) => conditions
+                .into_iter()
+                .flat_map(|c| self.get_matching_keys(c))
+                .collect(),
             FrameMatchCondition::And(conditions) => conditions
                 .into_iter()
                 .map(|c| self.get_matching_keys(c))
@@ -77,18 +106,72 @@ impl FrameSet {
         }
     }
 
+    /// Return the frames in this set which the instruction executes on (when `include_blocked` is
+    /// `false`), or which it blocks other instructions from using while it executes (when
+    /// `include_blocked` is `true`). Blocked frames always include those used.
+    ///
+    /// Return `None` if the instruction does not execute in the context of a frame, such as
+    /// classical instructions.
+    pub fn get_frames_for_instruction<'a>(
+        &'a self,
+        instruction: &'a Instruction,
+        include_blocked: bool,
+    ) -> Option<HashSet<&'a FrameIdentifier>> {
+        instruction
+            .get_frame_match_condition(include_blocked)
+            .map(|condition| self.get_matching_keys(condition))
+    }
+
+    /// Return both the frames in this set which the instruction uses and those which it blocks,
+    /// or `None` if the instruction does not execute in the context of a frame.
+    pub fn get_frame_usage<'a>(&'a self, instruction: &'a Instruction) -> Option<FrameUsage<'a>> {
+        Some(FrameUsage {
+            used: self.get_frames_for_instruction(instruction, false)?,
+            blocked: self.get_frames_for_instruction(instruction, true)?,
+        })
+    }
+
     /// Retrieve the attributes of a frame by its identifier.
-    pub fn get(&self, identifier: &FrameIdentifier) -> Option<&FrameAttributes> {
+    pub fn get(&self, identifier: &FrameIdentifier) -> Option<&RawFrameAttributes> {
         self.frames.get(identifier)
     }
 
+    /// Parse the `HARDWARE-OBJECT` of a frame as JSON, as with
+    /// [`FrameAttributes::parse_hardware_object`]. Returns `None` if the frame is not in the set or
+    /// has no hardware object.
+    #[cfg(feature = "serde")]
+    pub fn get_hardware_object<T: DeserializeOwned>(
+        &self,
+        identifier: &FrameIdentifier,
+    ) -> Result<Option<T>, FrameAttributeError> {
+        let hardware_object = match self
+            .get(identifier)
+            .and_then(|attributes| attributes.get(FrameAttributes::HARDWARE_OBJECT))
+        {
+            Some(AttributeValue::String(hardware_object)) => hardware_object,
+            Some(AttributeValue::Expression(_)) => {
+                return Err(FrameAttributeError::ExpectedString(
+                    FrameAttributes::HARDWARE_OBJECT.to_owned(),
+                ))
+            }
+            None => return Ok(None),
+        };
+        FrameAttributes {
+            hardware_object: Some(hardware_object.clone()),
+            ..Default::default()
+        }
+        .parse_hardware_object()
+    }
+
     /// Insert a new frame by ID, overwriting any existing one.
-    pub fn insert(&mut self, identifier: FrameIdentifier, attributes: FrameAttributes) {
+    pub fn insert(&mut self, identifier: FrameIdentifier, attributes: RawFrameAttributes) {
         self.frames.insert(identifier, attributes);
     }
 
     /// Iterate through the contained frames.
-    pub fn iter(&self) -> std::collections::hash_map::Iter<'_, FrameIdentifier, FrameAttributes> {
+    pub fn iter(
+        &self,
+    ) -> std::collections::hash_map::Iter<'_, FrameIdentifier, RawFrameAttributes> {
         self.frames.iter()
     }
 
@@ -102,10 +185,13 @@ impl FrameSet {
         self.frames.is_empty()
     }
 
-    /// Return the Quil instructions which describe the contained frames.
+    /// Return the Quil instructions which describe the contained frames, ordered by identifier so
+    /// that the output is stable.
     pub fn to_instructions(&self) -> Vec<Instruction> {
-        self.frames
-            .iter()
+        let mut frames: Vec<_> = self.frames.iter().collect();
+        frames.sort_by_cached_key(|(identifier, _)| identifier.to_string());
+        frames
+            .into_iter()
             .map(|(identifier, attributes)| {
                 Instruction::FrameDefinition(FrameDefinition {
                     identifier: identifier.clone(),
@@ -116,6 +202,25 @@ impl FrameSet {
     }
 }
 
+/// The frames which an instruction executes on, and those which it blocks other instructions from
+/// using while it executes.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameUsage<'a> {
+    /// The frames which the instruction executes on.
+    pub used: HashSet<&'a FrameIdentifier>,
+
+    /// The frames which no other instruction may use while this one executes. This is a superset
+    /// of `used`.
+    pub blocked: HashSet<&'a FrameIdentifier>,
+}
+
+impl<'a> FrameUsage<'a> {
+    /// Return the frames which are blocked by the instruction without being used by it.
+    pub fn blocked_but_not_used(&self) -> HashSet<&'a FrameIdentifier> {
+        self.blocked.difference(&self.used).copied().collect()
+    }
+}
+
 pub(crate) enum FrameMatchCondition<'a> {
     /// Match all frames in the set
     All,
@@ -132,6 +237,9 @@ pub(crate) enum FrameMatchCondition<'a> {
     /// Return this specific frame, if present in the set
     Specific(&'a FrameIdentifier),
 
+    /// Return all frames which match any of these conditions
+    AnyOf(Vec<FrameMatchCondition<'a>>),
+
     /// Return all frames which match all of these conditions
     And(Vec<FrameMatchCondition<'a>>),
 }
@@ -0,0 +1,267 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use indexmap::IndexMap;
+
+use crate::instruction::{
+    AttributeValue, FrameAttributes, FrameIdentifier, Instruction, SetFrequency, SetPhase,
+    ShiftFrequency, ShiftPhase, SwapPhases,
+};
+
+use super::graph::ScheduledProgram;
+use super::timing::evaluate_real_constant;
+use super::Program;
+
+/// The phase and frequency of a frame at some point in a program. Either is `None` when it is not
+/// known statically: a frequency which was never set, or a value which depends on memory or on
+/// an unresolved expression.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameState {
+    /// The phase, in radians, accumulated since the start of the program. It is not reduced
+    /// modulo 2π.
+    pub phase: Option<f64>,
+
+    /// The frequency, in Hertz.
+    pub frequency: Option<f64>,
+}
+
+impl FrameState {
+    /// The state of a frame at the start of a program: zero phase and its initial frequency.
+    fn initial(frequency: Option<f64>) -> Self {
+        Self {
+            phase: Some(0.0),
+            frequency,
+        }
+    }
+}
+
+/// The states of all frames used by a program.
+pub type FrameStates = HashMap<FrameIdentifier, FrameState>;
+
+/// Return the state of the given frame, which starts in its initial state if it is not yet known.
+fn state<'a>(states: &'a mut FrameStates, frame: &FrameIdentifier) -> &'a mut FrameState {
+    states
+        .entry(frame.clone())
+        .or_insert_with(|| FrameState::initial(None))
+}
+
+/// Update the states of frames for the execution of an instruction.
+fn apply_instruction(states: &mut FrameStates, instruction: &Instruction) {
+    match instruction {
+        Instruction::SetPhase(SetPhase { frame, phase }) => {
+            state(states, frame).phase = evaluate_real_constant(phase);
+        }
+        Instruction::ShiftPhase(ShiftPhase { frame, phase }) => {
+            let frame_state = state(states, frame);
+            frame_state.phase = frame_state
+                .phase
+                .and_then(|current| Some(current + evaluate_real_constant(phase)?));
+        }
+        Instruction::SetFrequency(SetFrequency { frame, frequency }) => {
+            state(states, frame).frequency = evaluate_real_constant(frequency);
+        }
+        Instruction::ShiftFrequency(ShiftFrequency { frame, frequency }) => {
+            let frame_state = state(states, frame);
+            frame_state.frequency = frame_state
+                .frequency
+                .and_then(|current| Some(current + evaluate_real_constant(frequency)?));
+        }
+        Instruction::SwapPhases(SwapPhases { frame_1, frame_2 }) => {
+            let phase_1 = state(states, frame_1).phase;
+            let phase_2 = std::mem::replace(&mut state(states, frame_2).phase, phase_1);
+            state(states, frame_1).phase = phase_2;
+        }
+        _ => {}
+    }
+}
+
+/// The states of frames before and after each instruction of a [`ScheduledProgram`], as computed
+/// by [`ScheduledProgram::get_frame_states`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameStateHistory {
+    /// For each block, keyed on label, the states before each of its instructions followed by
+    /// the states after its last.
+    blocks: IndexMap<String, Vec<FrameStates>>,
+}
+
+impl FrameStateHistory {
+    /// Return the states of the frames just before the instruction at the given index within the
+    /// block with the given label. An index one past the last instruction gives the states at the
+    /// end of the block.
+    pub fn before(&self, block: &str, index: usize) -> Option<&FrameStates> {
+        self.blocks.get(block)?.get(index)
+    }
+
+    /// Return the states of the frames just after the instruction at the given index within the
+    /// block with the given label.
+    pub fn after(&self, block: &str, index: usize) -> Option<&FrameStates> {
+        self.before(block, index + 1)
+    }
+
+    /// Return the states of the frames at the end of the program.
+    pub fn final_states(&self) -> Option<&FrameStates> {
+        self.blocks.values().last()?.last()
+    }
+}
+
+impl ScheduledProgram {
+    /// Track the phase and frequency of each frame through the program's `SET-PHASE`,
+    /// `SHIFT-PHASE`, `SWAP-PHASES`, `SET-FREQUENCY`, and `SHIFT-FREQUENCY` instructions. Each
+    /// frame defined in the program starts with zero phase and its `INITIAL-FREQUENCY`, if any;
+    /// a frame which is used but not defined starts with zero phase and an unknown frequency.
+    ///
+    /// Within each block, instructions are applied in order, which is consistent with the order
+    /// of execution on each frame. Blocks are applied in order as well, as if the program were
+    /// executed straight through: jumps are not followed.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::instruction::{FrameIdentifier, Qubit};
+    /// use quil_rs::program::graph::ScheduledProgram;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DEFFRAME 0 \"rf\":\n    INITIAL-FREQUENCY: 5e9\n\
+    ///      SHIFT-PHASE 0 \"rf\" 0.5\nSHIFT-FREQUENCY 0 \"rf\" 1e6",
+    /// )
+    /// .unwrap();
+    /// let scheduled_program = ScheduledProgram::from_program(&program).unwrap();
+    /// let history = scheduled_program.get_frame_states(&program);
+    /// let frame = FrameIdentifier {
+    ///     name: "rf".to_owned(),
+    ///     qubits: vec![Qubit::Fixed(0)],
+    /// };
+    ///
+    /// let state = history.after("block_0", 0).unwrap()[&frame];
+    /// assert_eq!((state.phase, state.frequency), (Some(0.5), Some(5e9)));
+    /// let state = history.final_states().unwrap()[&frame];
+    /// assert_eq!(state.frequency, Some(5.001e9));
+    /// ```
+    pub fn get_frame_states(&self, program: &Program) -> FrameStateHistory {
+        let mut states: FrameStates = program
+            .frames
+            .iter()
+            .map(|(identifier, attributes)| {
+                let frequency = match attributes.get(FrameAttributes::INITIAL_FREQUENCY) {
+                    Some(AttributeValue::Expression(expression)) => {
+                        evaluate_real_constant(expression)
+                    }
+                    _ => None,
+                };
+                (identifier.clone(), FrameState::initial(frequency))
+            })
+            .collect();
+
+        let blocks = self
+            .blocks
+            .iter()
+            .map(|(label, block)| {
+                let mut history = Vec::with_capacity(block.instructions.len() + 1);
+                history.push(states.clone());
+                for instruction in &block.instructions {
+                    apply_instruction(&mut states, instruction);
+                    history.push(states.clone());
+                }
+                (label.clone(), history)
+            })
+            .collect();
+
+        FrameStateHistory { blocks }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::instruction::{FrameIdentifier, Instruction, Qubit, SwapPhases};
+    use crate::program::graph::ScheduledProgram;
+    use crate::Program;
+
+    use super::FrameState;
+
+    fn frame(name: &str, qubit: u64) -> FrameIdentifier {
+        FrameIdentifier {
+            name: name.to_owned(),
+            qubits: vec![Qubit::Fixed(qubit)],
+        }
+    }
+
+    #[test]
+    fn frame_states() {
+        let mut program = Program::from_str(
+            "DECLARE theta REAL
+DEFFRAME 0 \"rf\":
+    INITIAL-FREQUENCY: 5e9
+DEFFRAME 1 \"rf\":
+    SAMPLE-RATE: 1e9
+SHIFT-PHASE 0 \"rf\" 1.5
+SET-PHASE 1 \"rf\" 0.25
+SET-FREQUENCY 1 \"rf\" 6e9
+LABEL @next
+SHIFT-PHASE 0 \"rf\" theta
+SHIFT-FREQUENCY 0 \"rf\" -1e9
+SHIFT-FREQUENCY 2 \"ro\" 1e6
+",
+        )
+        .unwrap();
+        // SWAP-PHASES is not yet parsed, so it is added to the program directly.
+        program.instructions.insert(
+            2,
+            Instruction::SwapPhases(SwapPhases {
+                frame_1: frame("rf", 0),
+                frame_2: frame("rf", 1),
+            }),
+        );
+        let scheduled_program = ScheduledProgram::from_program(&program).unwrap();
+        let history = scheduled_program.get_frame_states(&program);
+
+        let initial = history.before("block_0", 0).unwrap();
+        assert_eq!(
+            initial[&frame("rf", 0)],
+            FrameState {
+                phase: Some(0.0),
+                frequency: Some(5e9)
+            }
+        );
+        assert_eq!(initial[&frame("rf", 1)].frequency, None);
+
+        let swapped = history.after("block_0", 2).unwrap();
+        assert_eq!(swapped[&frame("rf", 0)].phase, Some(0.25));
+        assert_eq!(swapped[&frame("rf", 1)].phase, Some(1.5));
+
+        // Blocks continue from the state at the end of the previous block.
+        let next = history.before("next", 0).unwrap();
+        assert_eq!(next[&frame("rf", 1)].frequency, Some(6e9));
+
+        let last = history.final_states().unwrap();
+        assert_eq!(
+            last[&frame("rf", 0)],
+            FrameState {
+                phase: None,
+                frequency: Some(4e9)
+            }
+        );
+        assert_eq!(
+            last[&frame("ro", 2)],
+            FrameState {
+                phase: Some(0.0),
+                frequency: None
+            }
+        );
+        assert_eq!(history.before("next", 4), None);
+    }
+}
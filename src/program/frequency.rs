@@ -0,0 +1,195 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::instruction::{
+    AttributeValue, FrameAttributes, FrameIdentifier, Instruction, SetFrequency, ShiftFrequency,
+};
+
+use super::timing::evaluate_real_constant;
+use super::Program;
+
+/// A `SET-FREQUENCY` or `SHIFT-FREQUENCY` which moves its frame outside of the band of
+/// frequencies which the frame can represent.
+#[derive(Clone, Debug, Error, PartialEq)]
+#[error("instruction {location} ({instruction}) sets frame {frame} to {frequency} Hz, outside of [{minimum}, {maximum}] Hz")]
+pub struct FrequencyViolation {
+    /// The index of the instruction within [`Program::instructions`].
+    pub location: usize,
+    pub instruction: Instruction,
+    pub frame: FrameIdentifier,
+    /// The frequency of the frame after the instruction, in Hertz.
+    pub frequency: f64,
+    pub minimum: f64,
+    pub maximum: f64,
+}
+
+impl Program {
+    /// Return the constant value of the named attribute of the given frame, if it is defined
+    /// within the program and has one.
+    fn get_real_attribute(&self, frame: &FrameIdentifier, attribute: &str) -> Option<f64> {
+        match self.frames.get(frame)?.get(attribute)? {
+            AttributeValue::Expression(expression) => evaluate_real_constant(expression),
+            AttributeValue::String(_) => None,
+        }
+    }
+
+    /// Return the lowest and highest frequencies, in Hertz, to which the given frame may be set:
+    /// those within half of its `BANDWIDTH` of its `CENTER-FREQUENCY`. A frame without a
+    /// `BANDWIDTH` may be set anywhere within the band which its `SAMPLE-RATE` can represent.
+    ///
+    /// Return `None` if the frame is not defined within the program, or does not have constant
+    /// values for these attributes.
+    pub fn get_frequency_bounds(&self, frame: &FrameIdentifier) -> Option<(f64, f64)> {
+        let center = self.get_real_attribute(frame, FrameAttributes::CENTER_FREQUENCY)?;
+        let bandwidth = self
+            .get_real_attribute(frame, FrameAttributes::BANDWIDTH)
+            .or_else(|| self.get_sample_rate(frame))?;
+        Some((center - bandwidth / 2.0, center + bandwidth / 2.0))
+    }
+
+    /// Check that every `SET-FREQUENCY` and `SHIFT-FREQUENCY` within the program keeps its frame
+    /// within the bounds given by [`Program::get_frequency_bounds`], reporting each instruction
+    /// which does not along with the frequency it leaves the frame at.
+    ///
+    /// Each frame starts at its `INITIAL-FREQUENCY`, and instructions are followed in order,
+    /// without following jumps. Once a frame is set or shifted by a non-constant frequency, its
+    /// frequency is unknown and is not checked until it is next set to a constant. Frames
+    /// without bounds are not checked.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     r#"DEFFRAME 0 "rf":
+    ///     CENTER-FREQUENCY: 100
+    ///     BANDWIDTH: 20
+    /// SET-FREQUENCY 0 "rf" 105
+    /// SHIFT-FREQUENCY 0 "rf" 10
+    /// "#,
+    /// )
+    /// .unwrap();
+    /// let violations = program.validate_frequencies().unwrap_err();
+    /// assert_eq!(violations.len(), 1);
+    /// assert_eq!(violations[0].location, 1);
+    /// assert_eq!(violations[0].frequency, 115.0);
+    /// ```
+    pub fn validate_frequencies(&self) -> Result<(), Vec<FrequencyViolation>> {
+        let mut violations = vec![];
+        let mut frequencies: HashMap<&FrameIdentifier, Option<f64>> = HashMap::new();
+
+        for (location, instruction) in self.instructions.iter().enumerate() {
+            let (frame, frequency) = match instruction {
+                Instruction::SetFrequency(SetFrequency { frame, frequency }) => {
+                    (frame, evaluate_real_constant(frequency))
+                }
+                Instruction::ShiftFrequency(ShiftFrequency { frame, frequency }) => {
+                    let current = *frequencies.entry(frame).or_insert_with(|| {
+                        self.get_real_attribute(frame, FrameAttributes::INITIAL_FREQUENCY)
+                    });
+                    let frequency = current
+                        .zip(evaluate_real_constant(frequency))
+                        .map(|(current, shift)| current + shift);
+                    (frame, frequency)
+                }
+                _ => continue,
+            };
+            frequencies.insert(frame, frequency);
+
+            if let (Some(frequency), Some((minimum, maximum))) =
+                (frequency, self.get_frequency_bounds(frame))
+            {
+                if frequency < minimum || frequency > maximum {
+                    violations.push(FrequencyViolation {
+                        location,
+                        instruction: instruction.clone(),
+                        frame: frame.clone(),
+                        frequency,
+                        minimum,
+                        maximum,
+                    });
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::Program;
+
+    #[test]
+    fn validate_frequencies() {
+        let program = Program::from_str(
+            r#"DECLARE detuning REAL
+DEFFRAME 0 "rf":
+    CENTER-FREQUENCY: 100
+    INITIAL-FREQUENCY: 95
+    SAMPLE-RATE: 40
+DEFFRAME 1 "rf":
+    CENTER-FREQUENCY: 100
+    BANDWIDTH: 10
+DEFFRAME 2 "rf":
+    SAMPLE-RATE: 40
+SHIFT-FREQUENCY 0 "rf" 30
+SHIFT-FREQUENCY 0 "rf" -10
+SHIFT-FREQUENCY 1 "rf" 1
+SET-FREQUENCY 1 "rf" 90
+SHIFT-FREQUENCY 1 "rf" detuning
+SHIFT-FREQUENCY 1 "rf" 100
+SET-FREQUENCY 1 "rf" 104
+SET-FREQUENCY 2 "rf" 1000
+"#,
+        )
+        .unwrap();
+
+        let violations = program.validate_frequencies().unwrap_err();
+        let found: Vec<(usize, f64, f64, f64)> = violations
+            .iter()
+            .map(|violation| {
+                (
+                    violation.location,
+                    violation.frequency,
+                    violation.minimum,
+                    violation.maximum,
+                )
+            })
+            .collect();
+        assert_eq!(found, vec![(0, 125.0, 80.0, 120.0), (3, 90.0, 95.0, 105.0)]);
+
+        assert!(Program::from_str(
+            r#"DEFFRAME 0 "rf":
+    CENTER-FREQUENCY: 100
+    BANDWIDTH: 10
+SET-FREQUENCY 0 "rf" 95
+SHIFT-FREQUENCY 0 "rf" 10
+"#
+        )
+        .unwrap()
+        .validate_frequencies()
+        .is_ok());
+    }
+}
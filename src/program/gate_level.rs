@@ -0,0 +1,59 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use crate::instruction::Instruction;
+
+use super::Program;
+
+impl Program {
+    /// Strip this program down to gate-level Quil, removing its calibrations, frames, and
+    /// waveforms, and every pulse-level instruction (see [`Instruction::is_quil_t`]). The gates,
+    /// measurements, declarations, and classical control flow of the program are kept, so that it
+    /// may be run by a simulator or other consumer which does not understand Quil-T.
+    ///
+    /// Gates are not expanded by their calibrations first; to run the pulses which implement a
+    /// gate rather than the gate itself, use [`Program::expand_calibrations`] instead.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     r#"DECLARE ro BIT
+    /// DEFFRAME 0 "rf":
+    ///     SAMPLE-RATE: 1
+    /// DEFCAL X 0:
+    ///     PULSE 0 "rf" flat(duration: 1, iq: 1)
+    /// X 0
+    /// FENCE 0
+    /// SHIFT-PHASE 0 "rf" 1
+    /// MEASURE 0 ro
+    /// "#,
+    /// )
+    /// .unwrap();
+    /// assert_eq!(
+    ///     program.into_gate_level().to_string(true),
+    ///     "DECLARE ro BIT[1]\nX 0\nMEASURE 0 ro[0]\n"
+    /// );
+    /// ```
+    pub fn into_gate_level(self) -> Self {
+        self.transform(&mut |instruction: Instruction| {
+            if instruction.is_quil_t() {
+                vec![]
+            } else {
+                vec![instruction]
+            }
+        })
+    }
+}
@@ -22,21 +22,25 @@ use petgraph::Directed;
 
 use crate::instruction::{
     FrameIdentifier, Instruction, Jump, JumpUnless, JumpWhen, Label, MeasureCalibrationDefinition,
-    MemoryReference,
+    MemoryReference, Target,
 };
 use crate::{instruction::InstructionRole, program::Program};
 
 pub use super::memory::MemoryAccessType;
+pub use super::timing::TimeWindow;
 
 #[derive(Debug, Clone)]
 pub enum ScheduleErrorVariant {
     DuplicateLabel,
     UncalibratedInstruction,
     UnschedulableInstruction,
-    // Note: these may be restored once enforced
-    // DurationNotRealConstant,
-    // DurationNotApplicable,
-    // InvalidFrame,
+    /// A duration, or a value from which one is computed, is not a real constant.
+    DurationNotRealConstant,
+    /// The duration of the instruction cannot be determined, such as for a waveform which is
+    /// neither defined within the program nor given a `duration` parameter.
+    DurationNotApplicable,
+    /// The frame on which a waveform plays is not defined or has no constant `SAMPLE-RATE`.
+    InvalidFrame,
 }
 
 #[derive(Debug, Clone)]
@@ -480,7 +484,7 @@ fn terminate_working_block(
     match blocks.insert(label.clone(), block) {
         Some(_) => Err(ScheduleError {
             instruction_index,
-            instruction: Instruction::Label(Label(label)),
+            instruction: Instruction::Label(Label(Target::Fixed(label))),
             variant: ScheduleErrorVariant::DuplicateLabel,
         }),
         None => Ok(()),
@@ -556,13 +560,13 @@ impl ScheduledProgram {
                         instruction_index,
                     )?;
 
-                    working_label = Some(value.clone());
+                    working_label = Some(value.to_string());
                     Ok(())
                 }
                 Instruction::Jump(Jump { target }) => {
                     terminate_working_block(
                         Some(BlockTerminator::Unconditional {
-                            target: target.clone(),
+                            target: target.to_string(),
                         }),
                         &mut working_instructions,
                         &mut blocks,
@@ -575,7 +579,7 @@ impl ScheduledProgram {
                 Instruction::JumpWhen(JumpWhen { target, condition }) => {
                     terminate_working_block(
                         Some(BlockTerminator::Conditional {
-                            target: target.clone(),
+                            target: target.to_string(),
                             condition: condition.clone(),
                             jump_if_condition_true: true,
                         }),
@@ -590,7 +594,7 @@ impl ScheduledProgram {
                 Instruction::JumpUnless(JumpUnless { target, condition }) => {
                     terminate_working_block(
                         Some(BlockTerminator::Conditional {
-                            target: target.clone(),
+                            target: target.to_string(),
                             condition: condition.clone(),
                             jump_if_condition_true: false,
                         }),
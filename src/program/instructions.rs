@@ -0,0 +1,312 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt;
+use std::iter::FromIterator;
+use std::ops::{Index, IndexMut};
+use std::sync::Arc;
+
+use crate::instruction::Instruction;
+
+/// Take ownership of a shared instruction, copying it only if it is still shared.
+fn unshare(instruction: Arc<Instruction>) -> Instruction {
+    Arc::try_unwrap(instruction).unwrap_or_else(|shared| (*shared).clone())
+}
+
+/// The instructions of a [`Program`](super::Program), stored so that they are cheap to clone.
+///
+/// A clone shares its instructions with the original, so cloning takes constant time however
+/// long the program. Instructions are copied on write: mutating an instruction through one of the
+/// clones, as with [`InstructionList::get_mut`] or indexing, copies only that instruction, while
+/// adding or removing instructions copies only the list of references to them.
+///
+/// ```rust
+/// use std::str::FromStr;
+/// use quil_rs::Program;
+///
+/// let program = Program::from_str("H 0\nCNOT 0 1").unwrap();
+/// let mut copy = program.clone();
+/// copy.instructions[0] = Program::from_str("X 0").unwrap().instructions.remove(0);
+/// assert_eq!(program.to_string(false), "H 0\nCNOT 0 1\n");
+/// assert_eq!(copy.to_string(false), "X 0\nCNOT 0 1\n");
+/// ```
+#[derive(Clone, Default)]
+pub struct InstructionList(Arc<Vec<Arc<Instruction>>>);
+
+impl InstructionList {
+    /// Create an empty list.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the number of instructions.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Return true if there are no instructions.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Return the instruction at the given index, if any.
+    pub fn get(&self, index: usize) -> Option<&Instruction> {
+        self.0.get(index).map(|instruction| &**instruction)
+    }
+
+    /// Return the instruction at the given index, if any, for mutation. If it is shared with
+    /// another list, it is first copied.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Instruction> {
+        Arc::make_mut(&mut self.0).get_mut(index).map(Arc::make_mut)
+    }
+
+    /// Add an instruction to the end of the list.
+    pub fn push(&mut self, instruction: Instruction) {
+        Arc::make_mut(&mut self.0).push(Arc::new(instruction));
+    }
+
+    /// Insert an instruction at the given index, shifting those after it.
+    pub fn insert(&mut self, index: usize, instruction: Instruction) {
+        Arc::make_mut(&mut self.0).insert(index, Arc::new(instruction));
+    }
+
+    /// Remove and return the instruction at the given index, shifting those after it.
+    pub fn remove(&mut self, index: usize) -> Instruction {
+        unshare(Arc::make_mut(&mut self.0).remove(index))
+    }
+
+    /// Remove all instructions.
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    /// Iterate through the instructions, in order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.0.iter())
+    }
+
+    /// Iterate through the instructions, in order, for mutation. Each instruction still shared
+    /// with another list is copied as it is reached.
+    pub fn iter_mut(&mut self) -> IterMut<'_> {
+        IterMut(Arc::make_mut(&mut self.0).iter_mut())
+    }
+
+    /// Copy the instructions into a `Vec`.
+    pub fn to_vec(&self) -> Vec<Instruction> {
+        self.iter().cloned().collect()
+    }
+}
+
+impl fmt::Debug for InstructionList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl PartialEq for InstructionList {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.iter().eq(other.iter())
+    }
+}
+
+impl PartialEq<Vec<Instruction>> for InstructionList {
+    fn eq(&self, other: &Vec<Instruction>) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl PartialEq<InstructionList> for Vec<Instruction> {
+    fn eq(&self, other: &InstructionList) -> bool {
+        other == self
+    }
+}
+
+impl Index<usize> for InstructionList {
+    type Output = Instruction;
+
+    fn index(&self, index: usize) -> &Self::Output {
+        &self.0[index]
+    }
+}
+
+impl IndexMut<usize> for InstructionList {
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        Arc::make_mut(&mut Arc::make_mut(&mut self.0)[index])
+    }
+}
+
+impl From<Vec<Instruction>> for InstructionList {
+    fn from(instructions: Vec<Instruction>) -> Self {
+        instructions.into_iter().collect()
+    }
+}
+
+impl From<InstructionList> for Vec<Instruction> {
+    fn from(instructions: InstructionList) -> Self {
+        instructions.into_iter().collect()
+    }
+}
+
+impl FromIterator<Instruction> for InstructionList {
+    fn from_iter<I: IntoIterator<Item = Instruction>>(iter: I) -> Self {
+        Self(Arc::new(iter.into_iter().map(Arc::new).collect()))
+    }
+}
+
+impl Extend<Instruction> for InstructionList {
+    fn extend<I: IntoIterator<Item = Instruction>>(&mut self, iter: I) {
+        Arc::make_mut(&mut self.0).extend(iter.into_iter().map(Arc::new));
+    }
+}
+
+/// An iterator through the instructions of an [`InstructionList`].
+#[derive(Clone, Debug)]
+pub struct Iter<'a>(std::slice::Iter<'a, Arc<Instruction>>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Instruction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|instruction| &**instruction)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(|instruction| &**instruction)
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+
+/// An iterator through the instructions of an [`InstructionList`], for mutation.
+#[derive(Debug)]
+pub struct IterMut<'a>(std::slice::IterMut<'a, Arc<Instruction>>);
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = &'a mut Instruction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(Arc::make_mut)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for IterMut<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(Arc::make_mut)
+    }
+}
+
+impl ExactSizeIterator for IterMut<'_> {}
+
+/// An iterator which takes ownership of the instructions of an [`InstructionList`].
+#[derive(Debug)]
+pub struct IntoIter(std::vec::IntoIter<Arc<Instruction>>);
+
+impl Iterator for IntoIter {
+    type Item = Instruction;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(unshare)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for IntoIter {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.0.next_back().map(unshare)
+    }
+}
+
+impl ExactSizeIterator for IntoIter {}
+
+impl IntoIterator for InstructionList {
+    type Item = Instruction;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let instructions = Arc::try_unwrap(self.0).unwrap_or_else(|shared| (*shared).clone());
+        IntoIter(instructions.into_iter())
+    }
+}
+
+impl<'a> IntoIterator for &'a InstructionList {
+    type Item = &'a Instruction;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut InstructionList {
+    type Item = &'a mut Instruction;
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    use crate::instruction::Instruction;
+    use crate::Program;
+
+    use super::InstructionList;
+
+    #[test]
+    fn copy_on_write() {
+        let program = Program::from_str("H 0\nCNOT 0 1\nMEASURE 1").unwrap();
+        let original: &InstructionList = &program.instructions;
+
+        let mut copy = original.clone();
+        assert!(Arc::ptr_eq(&original.0, &copy.0));
+        assert_eq!(&copy, original);
+
+        // Mutating one instruction copies only that instruction.
+        copy[1] = Instruction::gate("CZ").qubit(0).qubit(1).into();
+        assert!(!Arc::ptr_eq(&original.0, &copy.0));
+        assert!(Arc::ptr_eq(&original.0[0], &copy.0[0]));
+        assert!(!Arc::ptr_eq(&original.0[1], &copy.0[1]));
+        assert!(Arc::ptr_eq(&original.0[2], &copy.0[2]));
+        assert_eq!(original[1].to_string(), "CNOT 0 1");
+        assert_eq!(copy[1].to_string(), "CZ 0 1");
+
+        // Adding an instruction copies none of the existing ones.
+        let mut extended = original.clone();
+        extended.push(Instruction::measure_discard(0));
+        assert_eq!(extended.len(), 4);
+        assert_eq!(original.len(), 3);
+        assert!(Arc::ptr_eq(&original.0[0], &extended.0[0]));
+
+        let vec: Vec<Instruction> = extended.clone().into();
+        assert_eq!(extended, vec);
+        assert_eq!(InstructionList::from(vec), extended);
+    }
+}
@@ -0,0 +1,277 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use thiserror::Error;
+
+use crate::instruction::{Gate, GateModifier, Instruction, Qubit};
+
+use super::Program;
+
+/// The number of qubits and parameters taken by a gate native to a [`TargetIsa`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NativeGate {
+    pub qubits: usize,
+    pub parameters: usize,
+}
+
+/// A description of the instruction set architecture of a quantum processor: the gates which it
+/// supports natively, and the pairs of qubits on which it supports two-qubit gates.
+///
+/// ```rust
+/// use quil_rs::program::TargetIsa;
+///
+/// let isa = TargetIsa::new()
+///     .with_gate("RX", 1, 1)
+///     .with_gate("RZ", 1, 1)
+///     .with_gate("CZ", 2, 0)
+///     .with_qubit_pair(0, 1);
+/// assert!(isa.get_gate("CZ").is_some());
+/// assert!(isa.supports_qubit_pair(1, 0));
+/// assert!(!isa.supports_qubit_pair(1, 2));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TargetIsa {
+    gates: BTreeMap<String, NativeGate>,
+
+    /// The pairs of qubits which may be operated on together, each with its lesser qubit first,
+    /// or `None` if any pair of qubits may be.
+    qubit_pairs: Option<BTreeSet<(u64, u64)>>,
+}
+
+impl TargetIsa {
+    /// Create an ISA which supports no gates, and in which, once gates are added, any pair of
+    /// qubits may be operated on together.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a native gate which acts on the given number of qubits and takes the given number of
+    /// parameters, replacing any native gate of the same name.
+    pub fn with_gate(mut self, name: &str, qubits: usize, parameters: usize) -> Self {
+        self.gates
+            .insert(name.to_owned(), NativeGate { qubits, parameters });
+        self
+    }
+
+    /// Allow two-qubit gates to act on the given pair of qubits, in either order. Once any pair
+    /// is added, two-qubit gates may act only on the pairs added.
+    pub fn with_qubit_pair(mut self, first: u64, second: u64) -> Self {
+        self.qubit_pairs
+            .get_or_insert_with(BTreeSet::new)
+            .insert(ordered_pair(first, second));
+        self
+    }
+
+    /// Return the native gate of the given name, if there is one.
+    pub fn get_gate(&self, name: &str) -> Option<&NativeGate> {
+        self.gates.get(name)
+    }
+
+    /// Return true if two-qubit gates may act on the given pair of qubits, in either order.
+    pub fn supports_qubit_pair(&self, first: u64, second: u64) -> bool {
+        self.qubit_pairs
+            .as_ref()
+            .map_or(true, |pairs| pairs.contains(&ordered_pair(first, second)))
+    }
+
+    /// Return every way in which the gate does not conform to this ISA.
+    fn check_gate(&self, gate: &Gate) -> Vec<IsaViolationKind> {
+        let native = match self.gates.get(&gate.name) {
+            Some(native) => native,
+            None => return vec![IsaViolationKind::UnsupportedGate(gate.name.clone())],
+        };
+
+        let mut violations = vec![];
+        if !gate.modifiers.is_empty() {
+            violations.push(IsaViolationKind::UnsupportedModifiers(
+                gate.modifiers.clone(),
+            ));
+        }
+        if gate.qubits.len() != native.qubits {
+            violations.push(IsaViolationKind::WrongQubitCount {
+                expected: native.qubits,
+                found: gate.qubits.len(),
+            });
+        }
+        if gate.parameters.len() != native.parameters {
+            violations.push(IsaViolationKind::WrongParameterCount {
+                expected: native.parameters,
+                found: gate.parameters.len(),
+            });
+        }
+        if let [Qubit::Fixed(first), Qubit::Fixed(second)] = gate.qubits.as_slice() {
+            if !self.supports_qubit_pair(*first, *second) {
+                violations.push(IsaViolationKind::UnsupportedQubitPair(*first, *second));
+            }
+        }
+        violations
+    }
+}
+
+fn ordered_pair(first: u64, second: u64) -> (u64, u64) {
+    (first.min(second), first.max(second))
+}
+
+/// An instruction within a program which does not conform to a [`TargetIsa`].
+#[derive(Clone, Debug, Error, PartialEq)]
+#[error("instruction {location} ({instruction}): {kind}")]
+pub struct IsaViolation {
+    /// The index of the instruction within [`Program::instructions`].
+    pub location: usize,
+    pub instruction: Instruction,
+    pub kind: IsaViolationKind,
+}
+
+/// The ways in which a gate may not conform to a [`TargetIsa`].
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum IsaViolationKind {
+    #[error("gate {0} is not native to the target")]
+    UnsupportedGate(String),
+
+    #[error("gate modifiers {0:?} are not supported by the target")]
+    UnsupportedModifiers(Vec<GateModifier>),
+
+    #[error("expected {expected} qubits but found {found}")]
+    WrongQubitCount { expected: usize, found: usize },
+
+    #[error("expected {expected} parameters but found {found}")]
+    WrongParameterCount { expected: usize, found: usize },
+
+    #[error("qubits {0} and {1} may not be operated on together")]
+    UnsupportedQubitPair(u64, u64),
+}
+
+impl Program {
+    /// Check that every gate among the program's instructions is native to the given ISA, is
+    /// applied without modifiers to the expected numbers of qubits and parameters, and, if it
+    /// acts on two qubits, acts on a pair supported by the ISA. Each way in which an instruction
+    /// violates the ISA is reported, in the order of the instructions.
+    ///
+    /// Only gates are checked; other instructions, such as `MEASURE`, are left to the target.
+    pub fn validate_against(&self, isa: &TargetIsa) -> Result<(), Vec<IsaViolation>> {
+        let violations: Vec<IsaViolation> = self
+            .instructions
+            .iter()
+            .enumerate()
+            .flat_map(|(location, instruction)| {
+                let kinds = match instruction {
+                    Instruction::Gate(gate) => isa.check_gate(gate),
+                    _ => vec![],
+                };
+                kinds.into_iter().map(move |kind| IsaViolation {
+                    location,
+                    instruction: instruction.clone(),
+                    kind,
+                })
+            })
+            .collect();
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::instruction::GateModifier;
+    use crate::Program;
+
+    use super::{IsaViolationKind, TargetIsa};
+
+    fn isa() -> TargetIsa {
+        TargetIsa::new()
+            .with_gate("RX", 1, 1)
+            .with_gate("RZ", 1, 1)
+            .with_gate("CZ", 2, 0)
+            .with_qubit_pair(0, 1)
+            .with_qubit_pair(2, 1)
+    }
+
+    #[test]
+    fn conforming_program() {
+        let program = Program::from_str(
+            "DECLARE ro BIT
+RX(pi/2) 0
+RZ(pi) 2
+CZ 1 0
+CZ 1 2
+MEASURE 0 ro
+",
+        )
+        .unwrap();
+        assert_eq!(program.validate_against(&isa()), Ok(()));
+    }
+
+    #[test]
+    fn isa_violations() {
+        let program = Program::from_str(
+            "RX(pi/2) 0
+H 0
+RX 0
+CZ 0 2
+DAGGER RZ(pi) 0
+CZ(pi) 0
+",
+        )
+        .unwrap();
+        let violations = program.validate_against(&isa()).unwrap_err();
+        let kinds: Vec<(usize, IsaViolationKind)> = violations
+            .iter()
+            .map(|violation| (violation.location, violation.kind.clone()))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (1, IsaViolationKind::UnsupportedGate("H".to_owned())),
+                (
+                    2,
+                    IsaViolationKind::WrongParameterCount {
+                        expected: 1,
+                        found: 0
+                    }
+                ),
+                (3, IsaViolationKind::UnsupportedQubitPair(0, 2)),
+                (
+                    4,
+                    IsaViolationKind::UnsupportedModifiers(vec![GateModifier::Dagger])
+                ),
+                (
+                    5,
+                    IsaViolationKind::WrongQubitCount {
+                        expected: 2,
+                        found: 1
+                    }
+                ),
+                (
+                    5,
+                    IsaViolationKind::WrongParameterCount {
+                        expected: 0,
+                        found: 1
+                    }
+                ),
+            ]
+        );
+        assert_eq!(
+            violations[0].to_string(),
+            "instruction 1 (H 0): gate H is not native to the target"
+        );
+    }
+}
@@ -0,0 +1,1641 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The JSON wire format of a [`Program`], written by [`Program::to_json`].
+//!
+//! The types here describe the format itself, rather than deriving it from the AST, so that the
+//! AST may change without changing what other services read and write. Each is converted to and
+//! from its counterpart in [`crate::instruction`] or [`crate::expression`].
+
+use std::collections::{BTreeMap, HashMap};
+
+use num_complex::Complex64;
+use serde::de::{Error as _, Unexpected};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use thiserror::Error;
+
+use crate::expression::{Expression, ExpressionFunction, InfixOperator, PrefixOperator};
+use crate::instruction::{
+    Arithmetic, ArithmeticOperand, ArithmeticOperator, AttributeValue, BinaryLogic, BinaryOperand,
+    BinaryOperator, Calibration, Capture, CircuitDefinition, Comparison, ComparisonOperand,
+    ComparisonOperator, Declaration, Delay, Exchange, Fence, FrameDefinition, FrameIdentifier,
+    Gate, GateDefinition, GateModifier, GateType, Instruction, Jump, JumpUnless, JumpWhen, Label,
+    LabelPlaceholder, Load, MeasureCalibrationDefinition, Measurement, MemoryReference, Move,
+    Pragma, Pulse, Qubit, QubitPlaceholder, RawCapture, Reset, ScalarType, SetFrequency, SetPhase,
+    SetScale, ShiftFrequency, ShiftPhase, Store, SwapPhases, Target, UnaryLogic, UnaryOperator,
+    Vector, Waveform, WaveformDefinition, WaveformInvocation,
+};
+
+use super::Program;
+
+/// The version of the schema written by [`Program::to_json`], which is incremented whenever it
+/// changes in a way which existing readers would not understand.
+pub const PROGRAM_JSON_VERSION: u32 = 1;
+
+/// A problem in reading a program from JSON.
+#[derive(Debug, Error)]
+pub enum ProgramJsonError {
+    #[error(
+        "program JSON has version {version}, but only versions up to {supported} are supported"
+    )]
+    UnsupportedVersion { version: u32, supported: u32 },
+
+    #[error("invalid program JSON: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Just the version of a program, read before the rest so that a newer program is rejected for
+/// its version rather than for whatever else has changed.
+#[derive(Deserialize)]
+struct VersionJson {
+    version: u32,
+}
+
+/// A program: its header (memory declarations, frame definitions, waveform definitions, and
+/// calibrations, in that order) and its body.
+#[derive(Serialize, Deserialize)]
+struct ProgramJson {
+    version: u32,
+    #[serde(default)]
+    definitions: Vec<InstructionJson>,
+    #[serde(default)]
+    instructions: Vec<InstructionJson>,
+}
+
+/// Define a wire enum of Quil keywords, converted to and from the AST enum of the same variants.
+macro_rules! keyword_enum {
+    ($(#[$meta:meta])* $wire:ident <=> $ast:ident { $($variant:ident = $keyword:literal),+ $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Serialize, Deserialize)]
+        enum $wire {
+            $(
+                #[serde(rename = $keyword)]
+                $variant,
+            )+
+        }
+
+        impl From<&$ast> for $wire {
+            fn from(value: &$ast) -> Self {
+                match value {
+                    $($ast::$variant => Self::$variant,)+
+                }
+            }
+        }
+
+        impl From<$wire> for $ast {
+            fn from(value: $wire) -> Self {
+                match value {
+                    $($wire::$variant => Self::$variant,)+
+                }
+            }
+        }
+    };
+}
+
+keyword_enum!(ArithmeticOperatorJson <=> ArithmeticOperator {
+    Add = "ADD",
+    Subtract = "SUB",
+    Multiply = "MUL",
+    Divide = "DIV",
+});
+
+keyword_enum!(BinaryOperatorJson <=> BinaryOperator {
+    And = "AND",
+    Ior = "IOR",
+    Xor = "XOR",
+});
+
+keyword_enum!(UnaryOperatorJson <=> UnaryOperator {
+    Neg = "NEG",
+    Not = "NOT",
+});
+
+keyword_enum!(ComparisonOperatorJson <=> ComparisonOperator {
+    Equal = "EQ",
+    GreaterThanOrEqual = "GE",
+    GreaterThan = "GT",
+    LessThanOrEqual = "LE",
+    LessThan = "LT",
+});
+
+keyword_enum!(GateModifierJson <=> GateModifier {
+    Controlled = "CONTROLLED",
+    Dagger = "DAGGER",
+    Forked = "FORKED",
+});
+
+keyword_enum!(GateTypeJson <=> GateType {
+    Matrix = "MATRIX",
+    Permutation = "PERMUTATION",
+});
+
+keyword_enum!(ScalarTypeJson <=> ScalarType {
+    Bit = "BIT",
+    Integer = "INTEGER",
+    Octet = "OCTET",
+    Real = "REAL",
+});
+
+keyword_enum!(InfixOperatorJson <=> InfixOperator {
+    Caret = "^",
+    Plus = "+",
+    Minus = "-",
+    Slash = "/",
+    Star = "*",
+});
+
+keyword_enum!(PrefixOperatorJson <=> PrefixOperator {
+    Plus = "+",
+    Minus = "-",
+});
+
+/// A qubit: an integer if fixed, the name of a variable qubit, or a numbered placeholder.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum QubitJson {
+    Fixed(u64),
+    Variable(String),
+    Placeholder { placeholder: usize },
+}
+
+#[derive(Serialize, Deserialize)]
+struct MemoryReferenceJson {
+    name: String,
+    #[serde(default)]
+    index: u64,
+}
+
+impl From<&MemoryReference> for MemoryReferenceJson {
+    fn from(reference: &MemoryReference) -> Self {
+        Self {
+            name: reference.name.clone(),
+            index: reference.index,
+        }
+    }
+}
+
+impl From<MemoryReferenceJson> for MemoryReference {
+    fn from(reference: MemoryReferenceJson) -> Self {
+        Self {
+            name: reference.name,
+            index: reference.index,
+        }
+    }
+}
+
+/// A real number: a JSON number if finite, or else `"inf"`, `"-inf"`, or `"NaN"`, which JSON
+/// numbers cannot represent.
+#[derive(Clone, Copy, Default)]
+struct RealJson(f64);
+
+impl Serialize for RealJson {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            value if value.is_finite() => serializer.serialize_f64(value),
+            value if value.is_nan() => serializer.serialize_str("NaN"),
+            value if value > 0.0 => serializer.serialize_str("inf"),
+            _ => serializer.serialize_str("-inf"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RealJson {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Real {
+            Number(f64),
+            Text(String),
+        }
+
+        match Real::deserialize(deserializer)? {
+            Real::Number(value) => Ok(Self(value)),
+            Real::Text(text) => match text.as_str() {
+                "inf" => Ok(Self(f64::INFINITY)),
+                "-inf" => Ok(Self(f64::NEG_INFINITY)),
+                "NaN" => Ok(Self(f64::NAN)),
+                _ => Err(D::Error::invalid_value(
+                    Unexpected::Str(&text),
+                    &"a number, \"inf\", \"-inf\", or \"NaN\"",
+                )),
+            },
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FrameJson {
+    name: String,
+    qubits: Vec<QubitJson>,
+}
+
+/// An expression, with the kind of node under `type`.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ExpressionJson {
+    Number {
+        re: RealJson,
+        #[serde(default)]
+        im: RealJson,
+    },
+    Pi,
+    Variable {
+        name: String,
+    },
+    Address(MemoryReferenceJson),
+    Function {
+        function: String,
+        argument: Box<ExpressionJson>,
+    },
+    Prefix {
+        operator: PrefixOperatorJson,
+        operand: Box<ExpressionJson>,
+    },
+    Infix {
+        operator: InfixOperatorJson,
+        left: Box<ExpressionJson>,
+        right: Box<ExpressionJson>,
+    },
+}
+
+impl From<&Expression> for ExpressionJson {
+    fn from(expression: &Expression) -> Self {
+        match expression {
+            Expression::Number(number) => Self::Number {
+                re: RealJson(number.re),
+                im: RealJson(number.im),
+            },
+            Expression::PiConstant => Self::Pi,
+            Expression::Variable(name) => Self::Variable { name: name.clone() },
+            Expression::Address(reference) => Self::Address(reference.into()),
+            Expression::FunctionCall {
+                function,
+                expression,
+            } => Self::Function {
+                function: function.to_string(),
+                argument: Box::new(expression.as_ref().into()),
+            },
+            Expression::Prefix {
+                operator,
+                expression,
+            } => Self::Prefix {
+                operator: operator.into(),
+                operand: Box::new(expression.as_ref().into()),
+            },
+            Expression::Infix {
+                left,
+                operator,
+                right,
+            } => Self::Infix {
+                operator: operator.into(),
+                left: Box::new(left.as_ref().into()),
+                right: Box::new(right.as_ref().into()),
+            },
+        }
+    }
+}
+
+impl From<ExpressionJson> for Expression {
+    fn from(expression: ExpressionJson) -> Self {
+        match expression {
+            ExpressionJson::Number { re, im } => Self::Number(Complex64::new(re.0, im.0)),
+            ExpressionJson::Pi => Self::PiConstant,
+            ExpressionJson::Variable { name } => Self::Variable(name),
+            ExpressionJson::Address(reference) => Self::Address(reference.into()),
+            ExpressionJson::Function { function, argument } => Self::FunctionCall {
+                function: function_named(function),
+                expression: Box::new((*argument).into()),
+            },
+            ExpressionJson::Prefix { operator, operand } => Self::Prefix {
+                operator: operator.into(),
+                expression: Box::new((*operand).into()),
+            },
+            ExpressionJson::Infix {
+                operator,
+                left,
+                right,
+            } => Self::Infix {
+                left: Box::new((*left).into()),
+                operator: operator.into(),
+                right: Box::new((*right).into()),
+            },
+        }
+    }
+}
+
+/// The function of the given name: one built into Quil if there is one, or else a custom one.
+fn function_named(name: String) -> ExpressionFunction {
+    use ExpressionFunction::*;
+
+    match name.as_str() {
+        "abs" => AbsoluteValue,
+        "acos" => ArcCosine,
+        "asin" => ArcSine,
+        "atan" => ArcTangent,
+        "cis" => Cis,
+        "cos" => Cosine,
+        "exp" => Exponent,
+        "ln" => NaturalLogarithm,
+        "sin" => Sine,
+        "sqrt" => SquareRoot,
+        "tan" => Tangent,
+        _ => Custom(name),
+    }
+}
+
+/// An operand of arithmetic, comparison, or data movement.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum OperandJson {
+    Integer { value: i64 },
+    Real { value: RealJson },
+    Memory(MemoryReferenceJson),
+}
+
+impl From<&ArithmeticOperand> for OperandJson {
+    fn from(operand: &ArithmeticOperand) -> Self {
+        match operand {
+            ArithmeticOperand::LiteralInteger(value) => Self::Integer { value: *value },
+            ArithmeticOperand::LiteralReal(value) => Self::Real {
+                value: RealJson(*value),
+            },
+            ArithmeticOperand::MemoryReference(reference) => Self::Memory(reference.into()),
+        }
+    }
+}
+
+impl From<OperandJson> for ArithmeticOperand {
+    fn from(operand: OperandJson) -> Self {
+        match operand {
+            OperandJson::Integer { value } => Self::LiteralInteger(value),
+            OperandJson::Real { value } => Self::LiteralReal(value.0),
+            OperandJson::Memory(reference) => Self::MemoryReference(reference.into()),
+        }
+    }
+}
+
+impl From<&ComparisonOperand> for OperandJson {
+    fn from(operand: &ComparisonOperand) -> Self {
+        match operand {
+            ComparisonOperand::LiteralInteger(value) => Self::Integer { value: *value },
+            ComparisonOperand::LiteralReal(value) => Self::Real {
+                value: RealJson(*value),
+            },
+            ComparisonOperand::MemoryReference(reference) => Self::Memory(reference.into()),
+        }
+    }
+}
+
+impl From<OperandJson> for ComparisonOperand {
+    fn from(operand: OperandJson) -> Self {
+        match operand {
+            OperandJson::Integer { value } => Self::LiteralInteger(value),
+            OperandJson::Real { value } => Self::LiteralReal(value.0),
+            OperandJson::Memory(reference) => Self::MemoryReference(reference.into()),
+        }
+    }
+}
+
+/// An operand of binary logic, which may not be a real number.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum LogicOperandJson {
+    Integer { value: i64 },
+    Memory(MemoryReferenceJson),
+}
+
+impl From<&BinaryOperand> for LogicOperandJson {
+    fn from(operand: &BinaryOperand) -> Self {
+        match operand {
+            BinaryOperand::LiteralInteger(value) => Self::Integer { value: *value },
+            BinaryOperand::MemoryReference(reference) => Self::Memory(reference.into()),
+        }
+    }
+}
+
+impl From<LogicOperandJson> for BinaryOperand {
+    fn from(operand: LogicOperandJson) -> Self {
+        match operand {
+            LogicOperandJson::Integer { value } => Self::LiteralInteger(value),
+            LogicOperandJson::Memory(reference) => Self::MemoryReference(reference.into()),
+        }
+    }
+}
+
+/// The value of a frame attribute: a string, or otherwise an expression.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum AttributeJson {
+    String(String),
+    Expression(ExpressionJson),
+}
+
+impl From<&AttributeValue> for AttributeJson {
+    fn from(value: &AttributeValue) -> Self {
+        match value {
+            AttributeValue::String(value) => Self::String(value.clone()),
+            AttributeValue::Expression(expression) => Self::Expression(expression.into()),
+        }
+    }
+}
+
+impl From<AttributeJson> for AttributeValue {
+    fn from(value: AttributeJson) -> Self {
+        match value {
+            AttributeJson::String(value) => Self::String(value),
+            AttributeJson::Expression(expression) => Self::Expression(expression.into()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct WaveformInvocationJson {
+    name: String,
+    #[serde(default)]
+    parameters: BTreeMap<String, ExpressionJson>,
+}
+
+impl From<&WaveformInvocation> for WaveformInvocationJson {
+    fn from(invocation: &WaveformInvocation) -> Self {
+        Self {
+            name: invocation.name.clone(),
+            parameters: invocation
+                .parameters
+                .iter()
+                .map(|(name, value)| (name.clone(), value.into()))
+                .collect(),
+        }
+    }
+}
+
+impl From<WaveformInvocationJson> for WaveformInvocation {
+    fn from(invocation: WaveformInvocationJson) -> Self {
+        Self {
+            name: invocation.name,
+            parameters: invocation
+                .parameters
+                .into_iter()
+                .map(|(name, value)| (name, value.into()))
+                .collect(),
+        }
+    }
+}
+
+/// The target of a jump or label: the name of a label, or a placeholder for one not yet named.
+/// Placeholders are numbered in order of their first use within the program, and each number
+/// stands for the same placeholder wherever it is used.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum TargetJson {
+    Fixed(String),
+    Placeholder { placeholder: usize, base: String },
+}
+
+fn default_blocking() -> bool {
+    true
+}
+
+/// An instruction, with its kind under `type`.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum InstructionJson {
+    Gate {
+        name: String,
+        #[serde(default)]
+        parameters: Vec<ExpressionJson>,
+        qubits: Vec<QubitJson>,
+        #[serde(default)]
+        modifiers: Vec<GateModifierJson>,
+    },
+    CircuitDefinition {
+        name: String,
+        #[serde(default)]
+        parameters: Vec<String>,
+        qubit_variables: Vec<String>,
+        instructions: Vec<InstructionJson>,
+    },
+    GateDefinition {
+        name: String,
+        #[serde(default)]
+        parameters: Vec<String>,
+        gate_type: GateTypeJson,
+        matrix: Vec<Vec<ExpressionJson>>,
+    },
+    Declaration {
+        name: String,
+        data_type: ScalarTypeJson,
+        length: u64,
+        #[serde(default)]
+        sharing: Option<String>,
+    },
+    Measurement {
+        qubit: QubitJson,
+        #[serde(default)]
+        target: Option<MemoryReferenceJson>,
+    },
+    Reset {
+        #[serde(default)]
+        qubit: Option<QubitJson>,
+    },
+    CalibrationDefinition {
+        name: String,
+        #[serde(default)]
+        parameters: Vec<ExpressionJson>,
+        qubits: Vec<QubitJson>,
+        #[serde(default)]
+        modifiers: Vec<GateModifierJson>,
+        instructions: Vec<InstructionJson>,
+    },
+    MeasureCalibrationDefinition {
+        #[serde(default)]
+        qubit: Option<QubitJson>,
+        parameter: String,
+        instructions: Vec<InstructionJson>,
+    },
+    FrameDefinition {
+        frame: FrameJson,
+        attributes: BTreeMap<String, AttributeJson>,
+    },
+    WaveformDefinition {
+        name: String,
+        #[serde(default)]
+        parameters: Vec<String>,
+        matrix: Vec<ExpressionJson>,
+    },
+    Pragma {
+        name: String,
+        #[serde(default)]
+        arguments: Vec<String>,
+        #[serde(default)]
+        data: Option<String>,
+    },
+    Pulse {
+        #[serde(default = "default_blocking")]
+        blocking: bool,
+        frame: FrameJson,
+        waveform: WaveformInvocationJson,
+    },
+    Capture {
+        #[serde(default = "default_blocking")]
+        blocking: bool,
+        frame: FrameJson,
+        waveform: WaveformInvocationJson,
+        memory_reference: MemoryReferenceJson,
+    },
+    RawCapture {
+        #[serde(default = "default_blocking")]
+        blocking: bool,
+        frame: FrameJson,
+        duration: ExpressionJson,
+        memory_reference: MemoryReferenceJson,
+    },
+    Delay {
+        qubits: Vec<QubitJson>,
+        #[serde(default)]
+        frame_names: Vec<String>,
+        duration: ExpressionJson,
+    },
+    Fence {
+        #[serde(default)]
+        qubits: Vec<QubitJson>,
+    },
+    SetFrequency {
+        frame: FrameJson,
+        frequency: ExpressionJson,
+    },
+    ShiftFrequency {
+        frame: FrameJson,
+        frequency: ExpressionJson,
+    },
+    SetPhase {
+        frame: FrameJson,
+        phase: ExpressionJson,
+    },
+    ShiftPhase {
+        frame: FrameJson,
+        phase: ExpressionJson,
+    },
+    SwapPhases {
+        frame_1: FrameJson,
+        frame_2: FrameJson,
+    },
+    SetScale {
+        frame: FrameJson,
+        scale: ExpressionJson,
+    },
+    Arithmetic {
+        operator: ArithmeticOperatorJson,
+        destination: OperandJson,
+        source: OperandJson,
+    },
+    Comparison {
+        operator: ComparisonOperatorJson,
+        destination: MemoryReferenceJson,
+        left: MemoryReferenceJson,
+        right: OperandJson,
+    },
+    BinaryLogic {
+        operator: BinaryOperatorJson,
+        destination: MemoryReferenceJson,
+        source: LogicOperandJson,
+    },
+    UnaryLogic {
+        operator: UnaryOperatorJson,
+        operand: MemoryReferenceJson,
+    },
+    Move {
+        destination: OperandJson,
+        source: OperandJson,
+    },
+    Exchange {
+        left: OperandJson,
+        right: OperandJson,
+    },
+    Load {
+        destination: MemoryReferenceJson,
+        source: String,
+        offset: MemoryReferenceJson,
+    },
+    Store {
+        destination: String,
+        offset: MemoryReferenceJson,
+        source: OperandJson,
+    },
+    Label {
+        target: TargetJson,
+    },
+    Jump {
+        target: TargetJson,
+    },
+    JumpWhen {
+        target: TargetJson,
+        condition: MemoryReferenceJson,
+    },
+    JumpUnless {
+        target: TargetJson,
+        condition: MemoryReferenceJson,
+    },
+    Halt,
+}
+
+fn convert<'a, T: 'a, U: From<&'a T>>(values: &'a [T]) -> Vec<U> {
+    values.iter().map(U::from).collect()
+}
+
+fn convert_owned<T, U: From<T>>(values: Vec<T>) -> Vec<U> {
+    values.into_iter().map(U::from).collect()
+}
+
+/// Converts instructions into their wire format, numbering placeholders as it goes.
+#[derive(Default)]
+struct Writer {
+    qubit_placeholders: Vec<QubitPlaceholder>,
+    label_placeholders: Vec<LabelPlaceholder>,
+}
+
+/// The number of a placeholder among those seen so far, numbering it next if it is new.
+fn number<T: Clone + PartialEq>(seen: &mut Vec<T>, placeholder: &T) -> usize {
+    match seen.iter().position(|other| other == placeholder) {
+        Some(number) => number,
+        None => {
+            seen.push(placeholder.clone());
+            seen.len() - 1
+        }
+    }
+}
+
+impl Writer {
+    fn qubit(&mut self, qubit: &Qubit) -> QubitJson {
+        match qubit {
+            Qubit::Fixed(index) => QubitJson::Fixed(*index),
+            Qubit::Variable(name) => QubitJson::Variable(name.clone()),
+            Qubit::Placeholder(placeholder) => QubitJson::Placeholder {
+                placeholder: number(&mut self.qubit_placeholders, placeholder),
+            },
+        }
+    }
+
+    fn qubits(&mut self, qubits: &[Qubit]) -> Vec<QubitJson> {
+        qubits.iter().map(|qubit| self.qubit(qubit)).collect()
+    }
+
+    fn frame(&mut self, frame: &FrameIdentifier) -> FrameJson {
+        FrameJson {
+            name: frame.name.clone(),
+            qubits: self.qubits(&frame.qubits),
+        }
+    }
+
+    fn target(&mut self, target: &Target) -> TargetJson {
+        match target {
+            Target::Fixed(name) => TargetJson::Fixed(name.clone()),
+            Target::Placeholder(placeholder) => TargetJson::Placeholder {
+                placeholder: number(&mut self.label_placeholders, placeholder),
+                base: placeholder.base().to_owned(),
+            },
+        }
+    }
+
+    fn instructions<'a>(
+        &mut self,
+        instructions: impl IntoIterator<Item = &'a Instruction>,
+    ) -> Vec<InstructionJson> {
+        instructions
+            .into_iter()
+            .map(|instruction| self.instruction(instruction))
+            .collect()
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) -> InstructionJson {
+        match instruction {
+            Instruction::Gate(Gate {
+                name,
+                parameters,
+                qubits,
+                modifiers,
+            }) => InstructionJson::Gate {
+                name: name.clone(),
+                parameters: convert(parameters),
+                qubits: self.qubits(qubits),
+                modifiers: convert(modifiers),
+            },
+            Instruction::CircuitDefinition(CircuitDefinition {
+                name,
+                parameters,
+                qubit_variables,
+                instructions,
+            }) => InstructionJson::CircuitDefinition {
+                name: name.clone(),
+                parameters: parameters.clone(),
+                qubit_variables: qubit_variables.clone(),
+                instructions: self.instructions(instructions),
+            },
+            Instruction::GateDefinition(GateDefinition {
+                name,
+                parameters,
+                matrix,
+                r#type,
+            }) => InstructionJson::GateDefinition {
+                name: name.clone(),
+                parameters: parameters.clone(),
+                gate_type: r#type.into(),
+                matrix: matrix.iter().map(|row| convert(row)).collect(),
+            },
+            Instruction::Declaration(Declaration {
+                name,
+                size,
+                sharing,
+            }) => InstructionJson::Declaration {
+                name: name.clone(),
+                data_type: (&size.data_type).into(),
+                length: size.length,
+                sharing: sharing.clone(),
+            },
+            Instruction::Measurement(Measurement { qubit, target }) => {
+                InstructionJson::Measurement {
+                    qubit: self.qubit(qubit),
+                    target: target.as_ref().map(MemoryReferenceJson::from),
+                }
+            }
+            Instruction::Reset(Reset { qubit }) => InstructionJson::Reset {
+                qubit: qubit.as_ref().map(|qubit| self.qubit(qubit)),
+            },
+            Instruction::CalibrationDefinition(Calibration {
+                instructions,
+                modifiers,
+                name,
+                parameters,
+                qubits,
+            }) => InstructionJson::CalibrationDefinition {
+                name: name.clone(),
+                parameters: convert(parameters),
+                qubits: self.qubits(qubits),
+                modifiers: convert(modifiers),
+                instructions: self.instructions(instructions),
+            },
+            Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+                qubit,
+                parameter,
+                instructions,
+            }) => InstructionJson::MeasureCalibrationDefinition {
+                qubit: qubit.as_ref().map(|qubit| self.qubit(qubit)),
+                parameter: parameter.clone(),
+                instructions: self.instructions(instructions),
+            },
+            Instruction::FrameDefinition(FrameDefinition {
+                identifier,
+                attributes,
+            }) => InstructionJson::FrameDefinition {
+                frame: self.frame(identifier),
+                attributes: attributes
+                    .iter()
+                    .map(|(name, value)| (name.clone(), value.into()))
+                    .collect(),
+            },
+            Instruction::WaveformDefinition(WaveformDefinition { name, definition }) => {
+                InstructionJson::WaveformDefinition {
+                    name: name.clone(),
+                    parameters: definition.parameters.clone(),
+                    matrix: convert(&definition.matrix),
+                }
+            }
+            Instruction::Pragma(Pragma {
+                name,
+                arguments,
+                data,
+            }) => InstructionJson::Pragma {
+                name: name.clone(),
+                arguments: arguments.clone(),
+                data: data.clone(),
+            },
+            Instruction::Pulse(Pulse {
+                blocking,
+                frame,
+                waveform,
+            }) => InstructionJson::Pulse {
+                blocking: *blocking,
+                frame: self.frame(frame),
+                waveform: waveform.into(),
+            },
+            Instruction::Capture(Capture {
+                blocking,
+                frame,
+                memory_reference,
+                waveform,
+            }) => InstructionJson::Capture {
+                blocking: *blocking,
+                frame: self.frame(frame),
+                waveform: waveform.into(),
+                memory_reference: memory_reference.into(),
+            },
+            Instruction::RawCapture(RawCapture {
+                blocking,
+                frame,
+                duration,
+                memory_reference,
+            }) => InstructionJson::RawCapture {
+                blocking: *blocking,
+                frame: self.frame(frame),
+                duration: duration.into(),
+                memory_reference: memory_reference.into(),
+            },
+            Instruction::Delay(Delay {
+                duration,
+                frame_names,
+                qubits,
+            }) => InstructionJson::Delay {
+                qubits: self.qubits(qubits),
+                frame_names: frame_names.clone(),
+                duration: duration.into(),
+            },
+            Instruction::Fence(Fence { qubits }) => InstructionJson::Fence {
+                qubits: self.qubits(qubits),
+            },
+            Instruction::SetFrequency(SetFrequency { frame, frequency }) => {
+                InstructionJson::SetFrequency {
+                    frame: self.frame(frame),
+                    frequency: frequency.into(),
+                }
+            }
+            Instruction::ShiftFrequency(ShiftFrequency { frame, frequency }) => {
+                InstructionJson::ShiftFrequency {
+                    frame: self.frame(frame),
+                    frequency: frequency.into(),
+                }
+            }
+            Instruction::SetPhase(SetPhase { frame, phase }) => InstructionJson::SetPhase {
+                frame: self.frame(frame),
+                phase: phase.into(),
+            },
+            Instruction::ShiftPhase(ShiftPhase { frame, phase }) => InstructionJson::ShiftPhase {
+                frame: self.frame(frame),
+                phase: phase.into(),
+            },
+            Instruction::SwapPhases(SwapPhases { frame_1, frame_2 }) => {
+                InstructionJson::SwapPhases {
+                    frame_1: self.frame(frame_1),
+                    frame_2: self.frame(frame_2),
+                }
+            }
+            Instruction::SetScale(SetScale { frame, scale }) => InstructionJson::SetScale {
+                frame: self.frame(frame),
+                scale: scale.into(),
+            },
+            Instruction::Arithmetic(Arithmetic {
+                operator,
+                destination,
+                source,
+            }) => InstructionJson::Arithmetic {
+                operator: operator.into(),
+                destination: destination.into(),
+                source: source.into(),
+            },
+            Instruction::Comparison(Comparison {
+                operator,
+                operands: (destination, left, right),
+            }) => InstructionJson::Comparison {
+                operator: operator.into(),
+                destination: destination.into(),
+                left: left.into(),
+                right: right.into(),
+            },
+            Instruction::BinaryLogic(BinaryLogic {
+                operator,
+                operands: (destination, source),
+            }) => InstructionJson::BinaryLogic {
+                operator: operator.into(),
+                destination: destination.into(),
+                source: source.into(),
+            },
+            Instruction::UnaryLogic(UnaryLogic { operator, operand }) => {
+                InstructionJson::UnaryLogic {
+                    operator: operator.into(),
+                    operand: operand.into(),
+                }
+            }
+            Instruction::Move(Move {
+                destination,
+                source,
+            }) => InstructionJson::Move {
+                destination: destination.into(),
+                source: source.into(),
+            },
+            Instruction::Exchange(Exchange { left, right }) => InstructionJson::Exchange {
+                left: left.into(),
+                right: right.into(),
+            },
+            Instruction::Load(Load {
+                destination,
+                source,
+                offset,
+            }) => InstructionJson::Load {
+                destination: destination.into(),
+                source: source.clone(),
+                offset: offset.into(),
+            },
+            Instruction::Store(Store {
+                destination,
+                offset,
+                source,
+            }) => InstructionJson::Store {
+                destination: destination.clone(),
+                offset: offset.into(),
+                source: source.into(),
+            },
+            Instruction::Label(Label(target)) => InstructionJson::Label {
+                target: self.target(target),
+            },
+            Instruction::Jump(Jump { target }) => InstructionJson::Jump {
+                target: self.target(target),
+            },
+            Instruction::JumpWhen(JumpWhen { target, condition }) => InstructionJson::JumpWhen {
+                target: self.target(target),
+                condition: condition.into(),
+            },
+            Instruction::JumpUnless(JumpUnless { target, condition }) => {
+                InstructionJson::JumpUnless {
+                    target: self.target(target),
+                    condition: condition.into(),
+                }
+            }
+            Instruction::Halt => InstructionJson::Halt,
+        }
+    }
+}
+
+/// Converts instructions from their wire format, creating a placeholder for each number used
+/// for one.
+#[derive(Default)]
+struct Reader {
+    qubit_placeholders: HashMap<usize, QubitPlaceholder>,
+    label_placeholders: HashMap<usize, LabelPlaceholder>,
+}
+
+impl Reader {
+    fn qubit(&mut self, qubit: QubitJson) -> Qubit {
+        match qubit {
+            QubitJson::Fixed(index) => Qubit::Fixed(index),
+            QubitJson::Variable(name) => Qubit::Variable(name),
+            QubitJson::Placeholder { placeholder } => {
+                Qubit::Placeholder(*self.qubit_placeholders.entry(placeholder).or_default())
+            }
+        }
+    }
+
+    fn qubits(&mut self, qubits: Vec<QubitJson>) -> Vec<Qubit> {
+        qubits.into_iter().map(|qubit| self.qubit(qubit)).collect()
+    }
+
+    fn frame(&mut self, frame: FrameJson) -> FrameIdentifier {
+        FrameIdentifier {
+            name: frame.name,
+            qubits: self.qubits(frame.qubits),
+        }
+    }
+
+    fn target(&mut self, target: TargetJson) -> Target {
+        match target {
+            TargetJson::Fixed(name) => Target::Fixed(name),
+            TargetJson::Placeholder { placeholder, base } => Target::Placeholder(
+                self.label_placeholders
+                    .entry(placeholder)
+                    .or_insert_with(|| LabelPlaceholder::new(base))
+                    .clone(),
+            ),
+        }
+    }
+
+    fn instructions(&mut self, instructions: Vec<InstructionJson>) -> Vec<Instruction> {
+        instructions
+            .into_iter()
+            .map(|instruction| self.instruction(instruction))
+            .collect()
+    }
+
+    fn instruction(&mut self, instruction: InstructionJson) -> Instruction {
+        match instruction {
+            InstructionJson::Gate {
+                name,
+                parameters,
+                qubits,
+                modifiers,
+            } => Instruction::Gate(Gate {
+                name,
+                parameters: convert_owned(parameters),
+                qubits: self.qubits(qubits),
+                modifiers: convert_owned(modifiers),
+            }),
+            InstructionJson::CircuitDefinition {
+                name,
+                parameters,
+                qubit_variables,
+                instructions,
+            } => Instruction::CircuitDefinition(CircuitDefinition {
+                name,
+                parameters,
+                qubit_variables,
+                instructions: self.instructions(instructions),
+            }),
+            InstructionJson::GateDefinition {
+                name,
+                parameters,
+                gate_type,
+                matrix,
+            } => Instruction::GateDefinition(GateDefinition {
+                name,
+                parameters,
+                matrix: matrix.into_iter().map(convert_owned).collect(),
+                r#type: gate_type.into(),
+            }),
+            InstructionJson::Declaration {
+                name,
+                data_type,
+                length,
+                sharing,
+            } => Instruction::Declaration(Declaration {
+                name,
+                size: Vector {
+                    data_type: data_type.into(),
+                    length,
+                },
+                sharing,
+            }),
+            InstructionJson::Measurement { qubit, target } => {
+                Instruction::Measurement(Measurement {
+                    qubit: self.qubit(qubit),
+                    target: target.map(MemoryReference::from),
+                })
+            }
+            InstructionJson::Reset { qubit } => Instruction::Reset(Reset {
+                qubit: qubit.map(|qubit| self.qubit(qubit)),
+            }),
+            InstructionJson::CalibrationDefinition {
+                name,
+                parameters,
+                qubits,
+                modifiers,
+                instructions,
+            } => Instruction::CalibrationDefinition(Calibration {
+                instructions: self.instructions(instructions),
+                modifiers: convert_owned(modifiers),
+                name,
+                parameters: convert_owned(parameters),
+                qubits: self.qubits(qubits),
+            }),
+            InstructionJson::MeasureCalibrationDefinition {
+                qubit,
+                parameter,
+                instructions,
+            } => Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+                qubit: qubit.map(|qubit| self.qubit(qubit)),
+                parameter,
+                instructions: self.instructions(instructions),
+            }),
+            InstructionJson::FrameDefinition { frame, attributes } => {
+                Instruction::FrameDefinition(FrameDefinition {
+                    identifier: self.frame(frame),
+                    attributes: attributes
+                        .into_iter()
+                        .map(|(name, value)| (name, value.into()))
+                        .collect(),
+                })
+            }
+            InstructionJson::WaveformDefinition {
+                name,
+                parameters,
+                matrix,
+            } => Instruction::WaveformDefinition(WaveformDefinition {
+                name,
+                definition: Waveform {
+                    matrix: convert_owned(matrix),
+                    parameters,
+                },
+            }),
+            InstructionJson::Pragma {
+                name,
+                arguments,
+                data,
+            } => Instruction::Pragma(Pragma {
+                name,
+                arguments,
+                data,
+            }),
+            InstructionJson::Pulse {
+                blocking,
+                frame,
+                waveform,
+            } => Instruction::Pulse(Pulse {
+                blocking,
+                frame: self.frame(frame),
+                waveform: waveform.into(),
+            }),
+            InstructionJson::Capture {
+                blocking,
+                frame,
+                waveform,
+                memory_reference,
+            } => Instruction::Capture(Capture {
+                blocking,
+                frame: self.frame(frame),
+                memory_reference: memory_reference.into(),
+                waveform: waveform.into(),
+            }),
+            InstructionJson::RawCapture {
+                blocking,
+                frame,
+                duration,
+                memory_reference,
+            } => Instruction::RawCapture(RawCapture {
+                blocking,
+                frame: self.frame(frame),
+                duration: duration.into(),
+                memory_reference: memory_reference.into(),
+            }),
+            InstructionJson::Delay {
+                qubits,
+                frame_names,
+                duration,
+            } => Instruction::Delay(Delay {
+                duration: duration.into(),
+                frame_names,
+                qubits: self.qubits(qubits),
+            }),
+            InstructionJson::Fence { qubits } => Instruction::Fence(Fence {
+                qubits: self.qubits(qubits),
+            }),
+            InstructionJson::SetFrequency { frame, frequency } => {
+                Instruction::SetFrequency(SetFrequency {
+                    frame: self.frame(frame),
+                    frequency: frequency.into(),
+                })
+            }
+            InstructionJson::ShiftFrequency { frame, frequency } => {
+                Instruction::ShiftFrequency(ShiftFrequency {
+                    frame: self.frame(frame),
+                    frequency: frequency.into(),
+                })
+            }
+            InstructionJson::SetPhase { frame, phase } => Instruction::SetPhase(SetPhase {
+                frame: self.frame(frame),
+                phase: phase.into(),
+            }),
+            InstructionJson::ShiftPhase { frame, phase } => Instruction::ShiftPhase(ShiftPhase {
+                frame: self.frame(frame),
+                phase: phase.into(),
+            }),
+            InstructionJson::SwapPhases { frame_1, frame_2 } => {
+                Instruction::SwapPhases(SwapPhases {
+                    frame_1: self.frame(frame_1),
+                    frame_2: self.frame(frame_2),
+                })
+            }
+            InstructionJson::SetScale { frame, scale } => Instruction::SetScale(SetScale {
+                frame: self.frame(frame),
+                scale: scale.into(),
+            }),
+            InstructionJson::Arithmetic {
+                operator,
+                destination,
+                source,
+            } => Instruction::Arithmetic(Arithmetic {
+                operator: operator.into(),
+                destination: destination.into(),
+                source: source.into(),
+            }),
+            InstructionJson::Comparison {
+                operator,
+                destination,
+                left,
+                right,
+            } => Instruction::Comparison(Comparison {
+                operator: operator.into(),
+                operands: (destination.into(), left.into(), right.into()),
+            }),
+            InstructionJson::BinaryLogic {
+                operator,
+                destination,
+                source,
+            } => Instruction::BinaryLogic(BinaryLogic {
+                operator: operator.into(),
+                operands: (destination.into(), source.into()),
+            }),
+            InstructionJson::UnaryLogic { operator, operand } => {
+                Instruction::UnaryLogic(UnaryLogic {
+                    operator: operator.into(),
+                    operand: operand.into(),
+                })
+            }
+            InstructionJson::Move {
+                destination,
+                source,
+            } => Instruction::Move(Move {
+                destination: destination.into(),
+                source: source.into(),
+            }),
+            InstructionJson::Exchange { left, right } => Instruction::Exchange(Exchange {
+                left: left.into(),
+                right: right.into(),
+            }),
+            InstructionJson::Load {
+                destination,
+                source,
+                offset,
+            } => Instruction::Load(Load {
+                destination: destination.into(),
+                source,
+                offset: offset.into(),
+            }),
+            InstructionJson::Store {
+                destination,
+                offset,
+                source,
+            } => Instruction::Store(Store {
+                destination,
+                offset: offset.into(),
+                source: source.into(),
+            }),
+            InstructionJson::Label { target } => Instruction::Label(Label(self.target(target))),
+            InstructionJson::Jump { target } => Instruction::Jump(Jump {
+                target: self.target(target),
+            }),
+            InstructionJson::JumpWhen { target, condition } => Instruction::JumpWhen(JumpWhen {
+                target: self.target(target),
+                condition: condition.into(),
+            }),
+            InstructionJson::JumpUnless { target, condition } => {
+                Instruction::JumpUnless(JumpUnless {
+                    target: self.target(target),
+                    condition: condition.into(),
+                })
+            }
+            InstructionJson::Halt => Instruction::Halt,
+        }
+    }
+}
+
+impl Program {
+    /// Write the program as JSON, in a format defined apart from the Rust types of the program so
+    /// that it stays stable as they change.
+    ///
+    /// # Schema
+    ///
+    /// A program is an object of its `version` ([`PROGRAM_JSON_VERSION`]), its `definitions`
+    /// (memory declarations, frame definitions, waveform definitions, and calibrations, in that
+    /// order), and the `instructions` of its body, each a list of instructions.
+    ///
+    /// An instruction is an object with its kind under `type`, in `snake_case` (`"gate"`,
+    /// `"measurement"`, `"set_phase"`, `"jump_when"`, and so on), and its contents in other fields
+    /// named after the parts of the Quil instruction. For example, `RX(pi/2) 0` is written as
+    ///
+    /// ```json
+    /// {"type": "gate", "name": "RX", "parameters": [...], "qubits": [0], "modifiers": []}
+    /// ```
+    ///
+    /// Within instructions:
+    ///
+    /// * a qubit is an integer if fixed, a string naming a variable qubit, or
+    ///   `{"placeholder": 0}` for a placeholder, numbered in order of first use;
+    /// * a memory reference is `{"name": "ro", "index": 0}`, and a frame is
+    ///   `{"name": "rf", "qubits": [0]}`;
+    /// * keywords such as gate modifiers, data types, and classical operators are spelled as in
+    ///   Quil (`"DAGGER"`, `"REAL"`, `"ADD"`, `"EQ"`);
+    /// * a classical operand is `{"type": "integer", "value": 1}`,
+    ///   `{"type": "real", "value": 1.5}`, or `{"type": "memory", "name": "ro", "index": 0}`;
+    /// * a frame attribute is a string, or an expression;
+    /// * a real number is a JSON number, or `"inf"`, `"-inf"`, or `"NaN"` if it is not finite;
+    /// * a label or jump target is the name of the label, or
+    ///   `{"placeholder": 0, "base": "loop"}` for a placeholder, numbered in order of first use.
+    ///
+    /// An expression is an object with its kind under `type`:
+    ///
+    /// * `{"type": "number", "re": 1.5, "im": 0.0}`;
+    /// * `{"type": "pi"}`;
+    /// * `{"type": "variable", "name": "theta"}`;
+    /// * `{"type": "address", "name": "ro", "index": 0}`;
+    /// * `{"type": "function", "function": "cos", "argument": ...}`;
+    /// * `{"type": "prefix", "operator": "-", "operand": ...}`;
+    /// * `{"type": "infix", "operator": "*", "left": ..., "right": ...}`.
+    ///
+    /// # Compatibility
+    ///
+    /// The schema is kept compatible with existing readers as it grows:
+    ///
+    /// * new fields may be added to any object, and readers must ignore fields they do not know;
+    /// * a new kind of instruction or expression, a change to the meaning of an existing field,
+    ///   or the removal of a field or making one required, comes with a new version;
+    /// * a reader must reject a version greater than the one it supports, as
+    ///   [`Program::from_json`] does.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str("DECLARE ro BIT\nMEASURE 0 ro").unwrap();
+    /// let json = program.to_json();
+    /// assert!(json.starts_with("{\"version\":1,"));
+    /// assert_eq!(Program::from_json(&json).unwrap(), program);
+    /// ```
+    pub fn to_json(&self) -> String {
+        let mut writer = Writer::default();
+        let program = ProgramJson {
+            version: PROGRAM_JSON_VERSION,
+            definitions: writer.instructions(&self.header_instructions()),
+            instructions: writer.instructions(&self.instructions),
+        };
+        serde_json::to_string(&program).expect("a program is always valid JSON")
+    }
+
+    /// Read a program written by [`Program::to_json`], of any version up to
+    /// [`PROGRAM_JSON_VERSION`]. Fields which are not part of that version of the schema are
+    /// ignored.
+    pub fn from_json(json: &str) -> Result<Self, ProgramJsonError> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        let VersionJson { version } = VersionJson::deserialize(&value)?;
+        if version > PROGRAM_JSON_VERSION {
+            return Err(ProgramJsonError::UnsupportedVersion {
+                version,
+                supported: PROGRAM_JSON_VERSION,
+            });
+        }
+
+        let json = ProgramJson::deserialize(value)?;
+        let mut reader = Reader::default();
+        let mut program = Program::new();
+        for instruction in reader
+            .instructions(json.definitions)
+            .into_iter()
+            .chain(reader.instructions(json.instructions))
+        {
+            program.add_instruction(instruction);
+        }
+        Ok(program)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use serde_json::json;
+
+    use num_complex::Complex64;
+
+    use crate::expression::Expression;
+    use crate::instruction::{
+        ArithmeticOperand, FrameIdentifier, Gate, GateDefinition, GateType, Instruction, Jump,
+        Label, LabelPlaceholder, MemoryReference, Move, Qubit, QubitPlaceholder, SwapPhases,
+        Target,
+    };
+    use crate::Program;
+
+    use super::ProgramJsonError;
+
+    #[test]
+    fn json() {
+        let program = Program::from_str(
+            r#"DECLARE theta REAL
+DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1
+DEFCAL RX(%theta) 0:
+    SHIFT-PHASE 0 "rf" %theta
+RX(theta) 0
+"#,
+        )
+        .unwrap();
+        let json = program.to_json();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["definitions"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            value["instructions"][0],
+            json!({
+                "type": "gate",
+                "name": "RX",
+                "parameters": [{"type": "address", "name": "theta", "index": 0}],
+                "qubits": [0],
+                "modifiers": [],
+            })
+        );
+        assert_eq!(Program::from_json(&json).unwrap(), program);
+
+        let extended = json.replacen("{\"version\":1,", "{\"version\":1,\"comment\":\"new\",", 1);
+        assert_eq!(Program::from_json(&extended).unwrap(), program);
+
+        let newer = json.replacen("{\"version\":1,", "{\"version\":2,\"unknown\":[],", 1);
+        assert!(matches!(
+            Program::from_json(&newer),
+            Err(ProgramJsonError::UnsupportedVersion {
+                version: 2,
+                supported: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut program = Program::from_str(
+            r#"DECLARE ro BIT[2]
+DECLARE theta REAL
+DEFFRAME 0 "rf":
+    DIRECTION: "tx"
+    INITIAL-FREQUENCY: 5e9
+DEFWAVEFORM custom(%a):
+    1, %a*(1.0+2.0i)
+DEFCAL RX(%theta) q:
+    SHIFT-PHASE q "rf" -%theta
+DEFCAL MEASURE q addr:
+    CAPTURE q "rf" flat(duration: 1e-6, iq: 1) addr
+DEFCIRCUIT BELL a b:
+    H a
+    CNOT a b
+PRAGMA INITIAL_REWIRING "PARTIAL"
+DAGGER CONTROLLED RX(cos(theta)/2) 1 0
+BELL 0 1
+MEASURE 0 ro[1]
+RESET
+NONBLOCKING PULSE 0 "rf" custom(a: 0.5)
+RAW-CAPTURE 0 "rf" 1e-6 ro[0]
+DELAY 0 "rf" 1e-6
+FENCE
+SET-SCALE 0 "rf" 0.5
+SHIFT-FREQUENCY 0 "rf" 1e6
+ADD theta 1.5
+MOVE ro[0] 1
+EXCHANGE ro[0] ro[1]
+EQ ro[0] ro[1] 1
+AND ro[0] 1
+NOT ro[1]
+LOAD ro[0] ro theta
+STORE ro theta ro[1]
+LABEL @start
+JUMP-WHEN @start ro[0]
+JUMP-UNLESS @start ro[1]
+JUMP @start
+HALT
+"#,
+        )
+        .unwrap();
+        // DEFGATE and SWAP-PHASES are not yet parsed, so they are added to the program directly.
+        program.add_instruction(Instruction::GateDefinition(GateDefinition {
+            name: "SQRT-X".to_owned(),
+            parameters: vec![],
+            matrix: vec![
+                vec![Expression::from(0.5), Expression::from(-0.5)],
+                vec![Expression::from(-0.5), Expression::from(0.5)],
+            ],
+            r#type: GateType::Matrix,
+        }));
+        program.add_instruction(Instruction::SwapPhases(SwapPhases {
+            frame_1: FrameIdentifier {
+                name: "rf".to_owned(),
+                qubits: vec![Qubit::Fixed(0)],
+            },
+            frame_2: FrameIdentifier {
+                name: "rf".to_owned(),
+                qubits: vec![Qubit::Fixed(1)],
+            },
+        }));
+
+        assert_eq!(Program::from_json(&program.to_json()).unwrap(), program);
+    }
+
+    #[test]
+    fn non_finite_numbers() {
+        let mut program = Program::from_str("DECLARE theta REAL\n").unwrap();
+        program.add_instruction(Instruction::Gate(Gate {
+            name: "RX".to_owned(),
+            parameters: vec![Expression::Number(Complex64::new(
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+            ))],
+            qubits: vec![Qubit::Fixed(0)],
+            modifiers: vec![],
+        }));
+        program.add_instruction(Instruction::Move(Move {
+            destination: ArithmeticOperand::MemoryReference(MemoryReference {
+                name: "theta".to_owned(),
+                index: 0,
+            }),
+            source: ArithmeticOperand::LiteralReal(f64::NEG_INFINITY),
+        }));
+        let json = program.to_json();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value["instructions"][0]["parameters"][0],
+            json!({"type": "number", "re": "inf", "im": "-inf"})
+        );
+        assert_eq!(Program::from_json(&json).unwrap(), program);
+
+        let mut program = Program::new();
+        program.add_instruction(Instruction::Gate(Gate {
+            name: "RX".to_owned(),
+            parameters: vec![Expression::Number(Complex64::new(f64::NAN, 0.0))],
+            qubits: vec![Qubit::Fixed(0)],
+            modifiers: vec![],
+        }));
+        let json = program.to_json();
+        assert!(json.contains(r#"{"type":"number","re":"NaN","im":0.0}"#));
+        match Program::from_json(&json).unwrap().instructions[0].clone() {
+            Instruction::Gate(Gate { parameters, .. }) => assert!(matches!(
+                parameters[..],
+                [Expression::Number(number)] if number.re.is_nan() && number.im == 0.0
+            )),
+            other => panic!("expected a gate, got {}", other),
+        }
+
+        let invalid = json.replace("\"NaN\"", "\"nan\"");
+        assert!(matches!(
+            Program::from_json(&invalid),
+            Err(ProgramJsonError::Json(_))
+        ));
+    }
+
+    #[test]
+    fn placeholders() {
+        let label = LabelPlaceholder::new("loop");
+        let (first, second) = (QubitPlaceholder::new(), QubitPlaceholder::new());
+        let mut program = Program::new();
+        program.add_instruction(Instruction::Label(Label(Target::Placeholder(
+            label.clone(),
+        ))));
+        program.add_instruction(Instruction::gate("CZ").qubit(first).qubit(second).into());
+        program.add_instruction(Instruction::gate("X").qubit(first).into());
+        program.add_instruction(Instruction::Jump(Jump {
+            target: Target::Placeholder(label),
+        }));
+
+        let read = Program::from_json(&program.to_json()).unwrap();
+        match (
+            &read.instructions[0],
+            &read.instructions[1],
+            &read.instructions[2],
+            &read.instructions[3],
+        ) {
+            (
+                Instruction::Label(Label(Target::Placeholder(label))),
+                Instruction::Gate(cz),
+                Instruction::Gate(x),
+                Instruction::Jump(Jump {
+                    target: Target::Placeholder(target),
+                }),
+            ) => {
+                assert_eq!(label, target);
+                assert_eq!(label.base(), "loop");
+                assert!(matches!(cz.qubits[0], Qubit::Placeholder(_)));
+                assert_ne!(cz.qubits[0], cz.qubits[1]);
+                assert_eq!(cz.qubits[0], x.qubits[0]);
+            }
+            other => panic!("expected placeholders to be read back, got {:?}", other),
+        }
+    }
+}
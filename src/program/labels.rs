@@ -0,0 +1,405 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use thiserror::Error;
+
+use crate::instruction::{
+    Calibration, CircuitDefinition, Instruction, Jump, JumpUnless, JumpWhen, Label,
+    LabelPlaceholder, MeasureCalibrationDefinition, Target,
+};
+
+use super::Program;
+
+/// An index of the labels within a sequence of instructions and of the jumps which target them.
+///
+/// Locations are given as indices into that sequence of instructions, such as
+/// [`Program::instructions`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LabelTable {
+    /// For each label, the locations at which it is defined, in order.
+    definitions: BTreeMap<String, Vec<usize>>,
+
+    /// For each jump target, the locations of the jumps to it, in order.
+    jumps: BTreeMap<String, Vec<usize>>,
+}
+
+impl LabelTable {
+    /// Index the labels defined and targeted by the given instructions.
+    pub fn new<'a>(instructions: impl IntoIterator<Item = &'a Instruction>) -> Self {
+        let mut table = Self::default();
+        for (index, instruction) in instructions.into_iter().enumerate() {
+            match instruction {
+                Instruction::Label(Label(label)) => table
+                    .definitions
+                    .entry(label.to_string())
+                    .or_default()
+                    .push(index),
+                Instruction::Jump(Jump { target })
+                | Instruction::JumpWhen(JumpWhen { target, .. })
+                | Instruction::JumpUnless(JumpUnless { target, .. }) => table
+                    .jumps
+                    .entry(target.to_string())
+                    .or_default()
+                    .push(index),
+                _ => {}
+            }
+        }
+        table
+    }
+
+    /// Return the location at which the label is (first) defined, if it is defined at all.
+    pub fn get(&self, label: &str) -> Option<usize> {
+        self.definitions
+            .get(label)
+            .and_then(|locations| locations.first())
+            .copied()
+    }
+
+    /// Return the locations of all jumps which target the label.
+    pub fn get_jumps_to(&self, label: &str) -> &[usize] {
+        self.jumps.get(label).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Iterate through the defined labels, in order of name, along with the location at which
+    /// each is (first) defined.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.definitions
+            .iter()
+            .map(|(label, locations)| (label.as_str(), locations[0]))
+    }
+
+    /// Return the number of distinct labels defined.
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    /// Return true if no labels are defined.
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
+
+    /// Check that every jump targets a defined label, that every label is targeted by some jump,
+    /// and that no label is defined more than once. All violations are reported, in the order of
+    /// their locations.
+    pub fn validate(&self) -> Result<(), Vec<LabelViolation>> {
+        let mut violations = vec![];
+
+        for (label, locations) in &self.definitions {
+            if !self.jumps.contains_key(label) {
+                violations.push(LabelViolation::UnusedLabel {
+                    label: label.clone(),
+                    location: locations[0],
+                });
+            }
+            for &location in &locations[1..] {
+                violations.push(LabelViolation::DuplicateLabel {
+                    label: label.clone(),
+                    location,
+                    previous: locations[0],
+                });
+            }
+        }
+
+        for (label, locations) in &self.jumps {
+            if !self.definitions.contains_key(label) {
+                violations.extend(locations.iter().map(|&location| {
+                    LabelViolation::UndefinedLabel {
+                        label: label.clone(),
+                        location,
+                    }
+                }));
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            violations.sort_by_key(LabelViolation::location);
+            Err(violations)
+        }
+    }
+}
+
+/// A problem with the use of labels within a sequence of instructions. Each location is an index
+/// into that sequence.
+#[derive(Clone, Debug, Error, PartialEq, Eq)]
+pub enum LabelViolation {
+    #[error("instruction {location} jumps to undefined label @{label}")]
+    UndefinedLabel { label: String, location: usize },
+
+    #[error("label @{label} at instruction {location} is never the target of a jump")]
+    UnusedLabel { label: String, location: usize },
+
+    #[error(
+        "label @{label} at instruction {location} was already defined at instruction {previous}"
+    )]
+    DuplicateLabel {
+        label: String,
+        location: usize,
+        previous: usize,
+    },
+}
+
+impl LabelViolation {
+    /// Return the location of the instruction at fault.
+    pub fn location(&self) -> usize {
+        match self {
+            LabelViolation::UndefinedLabel { location, .. }
+            | LabelViolation::UnusedLabel { location, .. }
+            | LabelViolation::DuplicateLabel { location, .. } => *location,
+        }
+    }
+}
+
+/// A source of labels which are unique among those already in use, for building control flow
+/// without needing to know what labels are used elsewhere.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LabelGenerator {
+    taken: HashSet<String>,
+}
+
+impl LabelGenerator {
+    /// Create a generator which avoids the given labels.
+    pub fn new(taken: impl IntoIterator<Item = String>) -> Self {
+        Self {
+            taken: taken.into_iter().collect(),
+        }
+    }
+
+    /// Return a label, based on `base`, which is neither in use nor previously generated:
+    /// `base` itself if it is available, and otherwise `base` with the smallest numeric suffix
+    /// (such as `base_1`) which makes it unique.
+    pub fn fresh(&mut self, base: &str) -> String {
+        let label = if self.taken.contains(base) {
+            (1..)
+                .map(|suffix| format!("{}_{}", base, suffix))
+                .find(|candidate| !self.taken.contains(candidate))
+                .expect("an unused label always exists")
+        } else {
+            base.to_owned()
+        };
+        self.taken.insert(label.clone());
+        label
+    }
+}
+
+/// Add each fixed label defined or targeted by the instructions, including those within the
+/// bodies of blocks, to `labels`.
+fn collect_labels(instructions: &[Instruction], labels: &mut HashSet<String>) {
+    for instruction in instructions {
+        match instruction {
+            Instruction::Label(Label(Target::Fixed(label)))
+            | Instruction::Jump(Jump {
+                target: Target::Fixed(label),
+            })
+            | Instruction::JumpWhen(JumpWhen {
+                target: Target::Fixed(label),
+                ..
+            })
+            | Instruction::JumpUnless(JumpUnless {
+                target: Target::Fixed(label),
+                ..
+            }) => {
+                labels.insert(label.clone());
+            }
+            Instruction::CalibrationDefinition(Calibration { instructions, .. })
+            | Instruction::CircuitDefinition(CircuitDefinition { instructions, .. })
+            | Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+                instructions,
+                ..
+            }) => collect_labels(instructions, labels),
+            _ => {}
+        }
+    }
+}
+
+impl Program {
+    /// Index the labels defined and targeted by the program's instructions.
+    pub fn label_table(&self) -> LabelTable {
+        LabelTable::new(&self.instructions)
+    }
+
+    /// Return a generator of labels which are not used anywhere within the program, including
+    /// within its `DEFCAL` and `DEFCIRCUIT` blocks.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str("LABEL @loop\nJUMP @loop").unwrap();
+    /// let mut generator = program.label_generator();
+    /// assert_eq!(generator.fresh("loop"), "loop_1");
+    /// assert_eq!(generator.fresh("loop"), "loop_2");
+    /// assert_eq!(generator.fresh("end"), "end");
+    /// ```
+    pub fn label_generator(&self) -> LabelGenerator {
+        let mut labels = HashSet::new();
+        collect_labels(&self.to_instructions(true), &mut labels);
+        LabelGenerator { taken: labels }
+    }
+
+    /// Return a copy of the program in which each [`Target::Placeholder`] is replaced by a
+    /// unique label based on the placeholder's base name, as generated by
+    /// [`Program::label_generator`]. Every use of the same placeholder receives the same label.
+    ///
+    /// ```rust
+    /// use quil_rs::instruction::{Instruction, Jump, Label, LabelPlaceholder, Target};
+    /// use quil_rs::Program;
+    /// use std::str::FromStr;
+    ///
+    /// let mut program = Program::from_str("LABEL @loop\nJUMP @loop").unwrap();
+    /// let placeholder = Target::Placeholder(LabelPlaceholder::new("loop"));
+    /// program.add_instruction(Instruction::Label(Label(placeholder.clone())));
+    /// program.add_instruction(Instruction::Jump(Jump { target: placeholder }));
+    ///
+    /// let resolved = program.resolve_label_placeholders();
+    /// assert_eq!(
+    ///     resolved.to_string(true),
+    ///     "LABEL @loop\nJUMP @loop\nLABEL @loop_1\nJUMP @loop_1\n"
+    /// );
+    /// ```
+    pub fn resolve_label_placeholders(&self) -> Program {
+        let mut generator = self.label_generator();
+        let mut names: HashMap<LabelPlaceholder, String> = HashMap::new();
+        self.transform(&mut |mut instruction: Instruction| {
+            if let Some(target) = instruction.get_target_mut() {
+                if let Target::Placeholder(placeholder) = target {
+                    let name = names
+                        .entry(placeholder.clone())
+                        .or_insert_with(|| generator.fresh(placeholder.base()))
+                        .clone();
+                    *target = Target::Fixed(name);
+                }
+            }
+            vec![instruction]
+        })
+    }
+
+    /// Check the program's labels and jumps as described by [`LabelTable::validate`].
+    pub fn validate_labels(&self) -> Result<(), Vec<LabelViolation>> {
+        self.label_table().validate()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::instruction::{
+        Instruction, Jump, JumpWhen, Label, LabelPlaceholder, MemoryReference, Target,
+    };
+    use crate::Program;
+
+    use super::LabelViolation;
+
+    #[test]
+    fn label_table() {
+        let program = Program::from_str(
+            "DECLARE ro BIT
+LABEL @start
+MEASURE 0 ro
+JUMP-WHEN @end ro
+JUMP @start
+LABEL @end
+",
+        )
+        .unwrap();
+        let table = program.label_table();
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.get("start"), Some(0));
+        assert_eq!(table.get("end"), Some(4));
+        assert_eq!(table.get("middle"), None);
+        assert_eq!(table.get_jumps_to("start"), &[3]);
+        assert_eq!(table.get_jumps_to("middle"), &[] as &[usize]);
+        assert_eq!(program.validate_labels(), Ok(()));
+    }
+
+    #[test]
+    fn label_violations() {
+        let program = Program::from_str(
+            "LABEL @unused
+JUMP @missing
+LABEL @loop
+JUMP @loop
+LABEL @loop
+",
+        )
+        .unwrap();
+        let violations = program.validate_labels().unwrap_err();
+        assert_eq!(
+            violations,
+            vec![
+                LabelViolation::UnusedLabel {
+                    label: "unused".to_owned(),
+                    location: 0
+                },
+                LabelViolation::UndefinedLabel {
+                    label: "missing".to_owned(),
+                    location: 1
+                },
+                LabelViolation::DuplicateLabel {
+                    label: "loop".to_owned(),
+                    location: 4,
+                    previous: 2
+                },
+            ]
+        );
+        assert_eq!(
+            violations[1].to_string(),
+            "instruction 1 jumps to undefined label @missing"
+        );
+    }
+
+    #[test]
+    fn resolve_label_placeholders() {
+        let mut program = Program::from_str(
+            "DECLARE ro BIT
+DEFCIRCUIT RETRY q:
+    LABEL @retry
+    JUMP @retry
+LABEL @start
+",
+        )
+        .unwrap();
+        let retry = Target::Placeholder(LabelPlaceholder::new("retry"));
+        let other = Target::Placeholder(LabelPlaceholder::new("retry"));
+        program.add_instruction(Instruction::Label(Label(retry.clone())));
+        program.add_instruction(Instruction::Label(Label(other.clone())));
+        program.add_instruction(Instruction::JumpWhen(JumpWhen {
+            target: retry,
+            condition: MemoryReference::new("ro", 0),
+        }));
+        program.add_instruction(Instruction::Jump(Jump { target: other }));
+
+        let resolved = program.resolve_label_placeholders();
+        assert_eq!(
+            resolved
+                .instructions
+                .iter()
+                .skip(1)
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![
+                "LABEL @start",
+                "LABEL @retry_1",
+                "LABEL @retry_2",
+                "JUMP-WHEN @retry_1 ro[0]",
+                "JUMP @retry_2"
+            ]
+        );
+        assert_eq!(resolved.label_generator().fresh("start"), "start_1");
+    }
+}
@@ -0,0 +1,393 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use thiserror::Error;
+
+use crate::expression::{Expression, MemoryValues};
+use crate::instruction::{CircuitDefinition, Gate, Instruction, Label, Qubit, Target};
+
+use super::calibration::substitute_qubits;
+use super::graph::{ScheduleError, ScheduledProgram};
+use super::{
+    CalibrationSet, FrameConflict, FrequencyViolation, LabelGenerator, LabelViolation,
+    MemoryViolation, Program, ProgramError, SampleLengthViolation, DEFAULT_MAX_CALIBRATION_DEPTH,
+};
+
+/// Options for [`lower`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoweringOptions {
+    /// The number of circuits, or of calibrations, which may be nested while expanding a single
+    /// instruction.
+    pub max_depth: usize,
+    /// Values for the program's memory, patched into its instructions once they are expanded.
+    /// Memory without a value is left to be read at execution time.
+    pub memory_values: Option<MemoryValues>,
+}
+
+impl Default for LoweringOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: DEFAULT_MAX_CALIBRATION_DEPTH,
+            memory_values: None,
+        }
+    }
+}
+
+/// An error which prevents a program from being lowered at all.
+#[derive(Debug, Error)]
+pub enum LoweringError {
+    #[error("circuits nested more than {max_depth} deep while expanding {instruction}")]
+    CircuitDepthExceeded {
+        instruction: Instruction,
+        max_depth: usize,
+    },
+
+    #[error("failed to expand calibrations: {0}")]
+    Calibration(#[source] ProgramError<Program>),
+
+    #[error("failed to schedule instruction {}: {:?}", .0.instruction, .0.variant)]
+    Schedule(ScheduleError),
+}
+
+/// A problem found within a lowered program, which does not prevent it from being lowered but
+/// may prevent it from being executed as intended.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoweringDiagnostic {
+    Memory(MemoryViolation),
+    Label(LabelViolation),
+    SampleLength(SampleLengthViolation),
+    Frequency(FrequencyViolation),
+    FrameConflict(FrameConflict),
+}
+
+impl fmt::Display for LoweringDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Memory(violation) => write!(f, "{}", violation),
+            Self::Label(violation) => write!(f, "{}", violation),
+            Self::SampleLength(violation) => write!(f, "{}", violation),
+            Self::Frequency(violation) => write!(f, "{}", violation),
+            Self::FrameConflict(conflict) => write!(f, "{}", conflict),
+        }
+    }
+}
+
+/// A program lowered to pulse-level Quil-T by [`lower`].
+#[derive(Clone, Debug)]
+pub struct LoweredProgram {
+    /// The lowered program, which has no circuits and whose gates and measurements have been
+    /// expanded wherever a calibration matches them.
+    pub program: Program,
+    /// The schedule of [`LoweredProgram::program`].
+    pub schedule: ScheduledProgram,
+    /// Every problem found within the lowered program, in the order in which the validations
+    /// which found them are documented on [`lower`].
+    pub diagnostics: Vec<LoweringDiagnostic>,
+}
+
+/// Return the instructions to which the instruction expands, if it is a gate which invokes one of
+/// the circuits, recursively expanding any circuits invoked within. Each label defined within a
+/// circuit is renamed to a fresh one from `labels` for every expansion, so that a circuit may be
+/// invoked more than once.
+fn expand_circuit(
+    circuits: &[CircuitDefinition],
+    instruction: &Instruction,
+    labels: &mut LabelGenerator,
+    depth: usize,
+    max_depth: usize,
+) -> Result<Option<Vec<Instruction>>, LoweringError> {
+    let (circuit, parameters, qubits) = match instruction {
+        Instruction::Gate(Gate {
+            name,
+            modifiers,
+            parameters,
+            qubits,
+        }) if modifiers.is_empty() => {
+            match circuits.iter().rev().find(|circuit| {
+                &circuit.name == name
+                    && circuit.parameters.len() == parameters.len()
+                    && circuit.qubit_variables.len() == qubits.len()
+            }) {
+                Some(circuit) => (circuit, parameters, qubits),
+                None => return Ok(None),
+            }
+        }
+        _ => return Ok(None),
+    };
+    if depth >= max_depth {
+        return Err(LoweringError::CircuitDepthExceeded {
+            instruction: instruction.clone(),
+            max_depth,
+        });
+    }
+
+    let qubit_expansions: HashMap<&String, Qubit> = circuit
+        .qubit_variables
+        .iter()
+        .zip(qubits.iter().cloned())
+        .collect();
+    let variable_expansions: HashMap<String, Expression> = circuit
+        .parameters
+        .iter()
+        .cloned()
+        .zip(parameters.iter().cloned())
+        .collect();
+    let label_renames: HashMap<&String, String> = circuit
+        .instructions
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::Label(Label(Target::Fixed(label))) => Some((label, labels.fresh(label))),
+            _ => None,
+        })
+        .collect();
+
+    let mut expanded = vec![];
+    for mut instruction in circuit.instructions.iter().cloned() {
+        substitute_qubits(&mut instruction, &qubit_expansions);
+        instruction.apply_to_expressions(|expression| {
+            let previous = std::mem::replace(expression, Expression::PiConstant);
+            *expression = previous.substitute_variables(&variable_expansions);
+        });
+        if let Some(Target::Fixed(label)) = instruction.get_target_mut() {
+            if let Some(renamed) = label_renames.get(label) {
+                *label = renamed.clone();
+            }
+        }
+        match expand_circuit(circuits, &instruction, labels, depth + 1, max_depth)? {
+            Some(instructions) => expanded.extend(instructions),
+            None => expanded.push(instruction),
+        }
+    }
+    Ok(Some(expanded))
+}
+
+/// Lower a program to pulse-level Quil-T, ready to be executed, by running each of the passes
+/// needed to do so in turn:
+///
+/// 1. Each gate which invokes a `DEFCIRCUIT` is replaced by the circuit's body, and the circuit
+///    definitions are removed. The labels within each expanded body are renamed to be unique.
+/// 2. Each gate and measurement is expanded by its calibration, as by
+///    [`Program::expand_calibrations`]. Calibrations are drawn from both `calibrations` and the
+///    program itself, with the program's own replacing any with the same signature.
+/// 3. If [`LoweringOptions::memory_values`] is given, those values are patched into the program,
+///    as by [`Program::patch_parameters`].
+/// 4. The program is scheduled, as by [`ScheduledProgram::from_program`].
+/// 5. The program is validated, by [`Program::validate_memory_references`],
+///    [`Program::validate_labels`], [`Program::validate_sample_lengths`],
+///    [`Program::validate_frequencies`], and [`ScheduledProgram::get_frame_conflicts`].
+///
+/// The first four passes must succeed for lowering to succeed; the problems found by the last
+/// are returned as [`LoweredProgram::diagnostics`].
+///
+/// ```rust
+/// use std::str::FromStr;
+/// use quil_rs::program::{lower, CalibrationSet, LoweringOptions};
+/// use quil_rs::Program;
+///
+/// let program = Program::from_str(
+///     r#"DEFFRAME 0 "rf":
+///     SAMPLE-RATE: 1
+/// DEFCIRCUIT FLIP q:
+///     X q
+/// FLIP 0
+/// "#,
+/// )
+/// .unwrap();
+/// let calibrations = CalibrationSet::from_instructions(
+///     Program::from_str("DEFCAL X q:\n    PULSE q \"rf\" flat(duration: 1, iq: 1)\n")
+///         .unwrap()
+///         .to_instructions(true),
+/// );
+///
+/// let lowered = lower(&program, &calibrations, &LoweringOptions::default()).unwrap();
+/// assert_eq!(
+///     lowered.program.instructions[0].to_string(),
+///     "PULSE 0 \"rf\" flat(duration: 1, iq: 1)"
+/// );
+/// assert!(lowered.diagnostics.is_empty());
+/// ```
+pub fn lower(
+    program: &Program,
+    calibrations: &CalibrationSet,
+    options: &LoweringOptions,
+) -> Result<LoweredProgram, LoweringError> {
+    let circuits: Vec<CircuitDefinition> = program
+        .instructions
+        .iter()
+        .filter_map(|instruction| match instruction {
+            Instruction::CircuitDefinition(circuit) => Some(circuit.clone()),
+            _ => None,
+        })
+        .collect();
+    let mut labels = program.label_generator();
+    let mut instructions = vec![];
+    for instruction in &program.instructions {
+        if let Instruction::CircuitDefinition(_) = instruction {
+            continue;
+        }
+        match expand_circuit(&circuits, instruction, &mut labels, 0, options.max_depth)? {
+            Some(expanded) => instructions.extend(expanded),
+            None => instructions.push(instruction.clone()),
+        }
+    }
+
+    let mut merged_calibrations = calibrations.clone();
+    merged_calibrations.extend_replacing(&program.calibrations);
+
+    let mut lowered = Program {
+        calibrations: merged_calibrations,
+        instructions: instructions.into(),
+        ..program.clone()
+    }
+    .expand_calibrations_with_max_depth(options.max_depth)
+    .map_err(LoweringError::Calibration)?;
+
+    if let Some(values) = &options.memory_values {
+        lowered = lowered.patch_parameters(values);
+    }
+
+    let schedule = ScheduledProgram::from_program(&lowered).map_err(LoweringError::Schedule)?;
+
+    let mut diagnostics = vec![];
+    if let Err(violations) = lowered.validate_memory_references() {
+        diagnostics.extend(violations.into_iter().map(LoweringDiagnostic::Memory));
+    }
+    if let Err(violations) = lowered.validate_labels() {
+        diagnostics.extend(violations.into_iter().map(LoweringDiagnostic::Label));
+    }
+    if let Err(violations) = lowered.validate_sample_lengths() {
+        diagnostics.extend(violations.into_iter().map(LoweringDiagnostic::SampleLength));
+    }
+    if let Err(violations) = lowered.validate_frequencies() {
+        diagnostics.extend(violations.into_iter().map(LoweringDiagnostic::Frequency));
+    }
+    let conflicts = schedule
+        .get_frame_conflicts(&lowered)
+        .map_err(LoweringError::Schedule)?;
+    diagnostics.extend(conflicts.into_iter().map(LoweringDiagnostic::FrameConflict));
+
+    Ok(LoweredProgram {
+        program: lowered,
+        schedule,
+        diagnostics,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::expression::MemoryValues;
+    use crate::program::CalibrationSet;
+    use crate::Program;
+
+    use super::{lower, LoweringDiagnostic, LoweringError, LoweringOptions};
+
+    #[test]
+    fn lower_program() {
+        let program = Program::from_str(
+            r#"DECLARE theta REAL
+DECLARE ro BIT
+DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1
+DEFCIRCUIT ROTATE(%angle) q:
+    RX(%angle) q
+DEFCIRCUIT TWICE(%angle) q:
+    ROTATE(%angle) q
+    ROTATE(%angle) q
+DEFCAL RX(%angle) q:
+    SHIFT-PHASE q "rf" %angle
+    PULSE q "rf" flat(duration: 1, iq: 1)
+TWICE(2*theta) 0
+PULSE 0 "rf" flat(duration: 1, iq: 1)
+JUMP @missing
+"#,
+        )
+        .unwrap();
+        let mut memory_values = MemoryValues::new();
+        memory_values.set_region("theta", vec![0.25]);
+        let options = LoweringOptions {
+            memory_values: Some(memory_values),
+            ..Default::default()
+        };
+
+        let lowered = lower(&program, &CalibrationSet::default(), &options).unwrap();
+        assert_eq!(
+            lowered.program.to_string(false),
+            r#"SHIFT-PHASE 0 "rf" 0.5
+PULSE 0 "rf" flat(duration: 1, iq: 1)
+SHIFT-PHASE 0 "rf" 0.5
+PULSE 0 "rf" flat(duration: 1, iq: 1)
+PULSE 0 "rf" flat(duration: 1, iq: 1)
+JUMP @missing
+"#
+        );
+        assert_eq!(lowered.diagnostics.len(), 1);
+        assert!(matches!(
+            lowered.diagnostics[0],
+            LoweringDiagnostic::Label(_)
+        ));
+
+        let program = Program::from_str("DEFCAL X r:\n    PRAGMA PROGRAM\nX 0\n").unwrap();
+        let library = Program::from_str("DEFCAL X q:\n    PRAGMA LIBRARY\n").unwrap();
+        let lowered = lower(&program, &library.calibrations, &LoweringOptions::default()).unwrap();
+        assert_eq!(lowered.program.to_string(false), "PRAGMA PROGRAM\n");
+
+        let program = Program::from_str(
+            r#"DECLARE ro BIT
+DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1
+DEFFRAME 1 "rf":
+    SAMPLE-RATE: 1
+DEFCIRCUIT RETRY q:
+    LABEL @again
+    PULSE q "rf" flat(duration: 1, iq: 1)
+    JUMP-WHEN @again ro[0]
+RETRY 0
+RETRY 1
+"#,
+        )
+        .unwrap();
+        let lowered = lower(
+            &program,
+            &CalibrationSet::default(),
+            &LoweringOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            lowered.program.to_string(false),
+            r#"LABEL @again_1
+PULSE 0 "rf" flat(duration: 1, iq: 1)
+JUMP-WHEN @again_1 ro[0]
+LABEL @again_2
+PULSE 1 "rf" flat(duration: 1, iq: 1)
+JUMP-WHEN @again_2 ro[0]
+"#
+        );
+        assert!(lowered.diagnostics.is_empty());
+
+        let recursive = Program::from_str("DEFCIRCUIT LOOP q:\n    LOOP q\nLOOP 0\n").unwrap();
+        assert!(matches!(
+            lower(
+                &recursive,
+                &CalibrationSet::default(),
+                &LoweringOptions::default()
+            ),
+            Err(LoweringError::CircuitDepthExceeded { .. })
+        ));
+    }
+}
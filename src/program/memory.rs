@@ -12,17 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
-use crate::expression::Expression;
+use thiserror::Error;
+
+use crate::expression::{Expression, MemoryValues};
 use crate::instruction::{
-    Arithmetic, ArithmeticOperand, BinaryLogic, BinaryOperand, Capture, CircuitDefinition,
-    Comparison, ComparisonOperand, Delay, Exchange, Gate, GateDefinition, Instruction, Jump,
-    JumpUnless, JumpWhen, Label, Load, MeasureCalibrationDefinition, Measurement, MemoryReference,
-    Move, Pulse, RawCapture, SetPhase, SetScale, ShiftPhase, Store, UnaryLogic, Vector,
-    WaveformInvocation,
+    Arithmetic, ArithmeticOperand, BinaryLogic, BinaryOperand, Calibration, Capture,
+    CircuitDefinition, Comparison, ComparisonOperand, Delay, Exchange, Gate, GateDefinition,
+    Instruction, Jump, JumpUnless, JumpWhen, Label, Load, MeasureCalibrationDefinition,
+    Measurement, MemoryReference, Move, Pulse, RawCapture, SetFrequency, SetPhase, SetScale,
+    ShiftFrequency, ShiftPhase, Store, UnaryLogic, Vector, WaveformInvocation,
 };
 
+use super::Program;
+
 #[derive(Clone, Debug, Hash, PartialEq)]
 pub struct MemoryRegion {
     pub size: Vector,
@@ -267,6 +271,222 @@ impl Instruction {
     }
 }
 
+impl Instruction {
+    /// Return all of the memory references used directly by this instruction, whether within
+    /// expressions, as classical operands, or as readout targets. Does not traverse instructions
+    /// nested within blocks (such as within `DEFCAL`), nor include regions which are named
+    /// without an index, such as the source of a `LOAD`.
+    pub fn get_memory_references(&self) -> Vec<&MemoryReference> {
+        match self {
+            Instruction::Arithmetic(Arithmetic {
+                destination,
+                source,
+                ..
+            })
+            | Instruction::Move(Move {
+                destination,
+                source,
+            })
+            | Instruction::Exchange(Exchange {
+                left: destination,
+                right: source,
+            }) => destination
+                .get_memory_reference()
+                .into_iter()
+                .chain(source.get_memory_reference())
+                .collect(),
+            Instruction::BinaryLogic(BinaryLogic { operands, .. }) => {
+                let mut references = vec![&operands.0];
+                if let BinaryOperand::MemoryReference(reference) = &operands.1 {
+                    references.push(reference);
+                }
+                references
+            }
+            Instruction::Comparison(Comparison { operands, .. }) => {
+                let mut references = vec![&operands.0, &operands.1];
+                if let ComparisonOperand::MemoryReference(reference) = &operands.2 {
+                    references.push(reference);
+                }
+                references
+            }
+            Instruction::UnaryLogic(UnaryLogic { operand, .. }) => vec![operand],
+            Instruction::JumpWhen(JumpWhen { condition, .. })
+            | Instruction::JumpUnless(JumpUnless { condition, .. }) => vec![condition],
+            Instruction::Load(Load {
+                destination,
+                offset,
+                ..
+            }) => vec![destination, offset],
+            Instruction::Store(Store { offset, source, .. }) => {
+                let mut references = vec![offset];
+                references.extend(source.get_memory_reference());
+                references
+            }
+            Instruction::Measurement(Measurement { target, .. }) => target.iter().collect(),
+            Instruction::Capture(Capture {
+                memory_reference,
+                waveform,
+                ..
+            }) => {
+                let mut references = vec![memory_reference];
+                references.extend(waveform.get_memory_references());
+                references
+            }
+            Instruction::RawCapture(RawCapture {
+                duration,
+                memory_reference,
+                ..
+            }) => {
+                let mut references = vec![memory_reference];
+                references.extend(duration.get_memory_references());
+                references
+            }
+            Instruction::Pulse(Pulse { waveform, .. }) => waveform.get_memory_references(),
+            Instruction::Delay(Delay { duration: expr, .. })
+            | Instruction::SetFrequency(SetFrequency {
+                frequency: expr, ..
+            })
+            | Instruction::SetPhase(SetPhase { phase: expr, .. })
+            | Instruction::SetScale(SetScale { scale: expr, .. })
+            | Instruction::ShiftFrequency(ShiftFrequency {
+                frequency: expr, ..
+            })
+            | Instruction::ShiftPhase(ShiftPhase { phase: expr, .. }) => {
+                expr.get_memory_references()
+            }
+            Instruction::Gate(Gate { parameters, .. })
+            | Instruction::CalibrationDefinition(Calibration { parameters, .. }) => parameters
+                .iter()
+                .flat_map(|parameter| parameter.get_memory_references())
+                .collect(),
+            Instruction::GateDefinition(GateDefinition { matrix, .. }) => matrix
+                .iter()
+                .flat_map(|row| row.iter().flat_map(|cell| cell.get_memory_references()))
+                .collect(),
+            Instruction::CircuitDefinition(_)
+            | Instruction::Declaration(_)
+            | Instruction::Fence(_)
+            | Instruction::FrameDefinition(_)
+            | Instruction::Halt
+            | Instruction::Jump(_)
+            | Instruction::Label(_)
+            | Instruction::MeasureCalibrationDefinition(_)
+            | Instruction::Pragma(_)
+            | Instruction::Reset(_)
+            | Instruction::SwapPhases(_)
+            | Instruction::WaveformDefinition(_) => vec![],
+        }
+    }
+}
+
+/// A use of memory within a program which does not agree with the program's declarations.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum MemoryViolation {
+    #[error("in instruction {instruction}: memory region {name} is not declared")]
+    UndeclaredRegion {
+        instruction: Instruction,
+        name: String,
+    },
+
+    #[error(
+        "in instruction {instruction}: {reference} is out of bounds for a region of length {length}"
+    )]
+    IndexOutOfBounds {
+        instruction: Instruction,
+        reference: MemoryReference,
+        length: u64,
+    },
+}
+
+impl Program {
+    /// Return a copy of this program in which each memory reference within an expression, such
+    /// as a gate parameter, is replaced by its value in `values`. Each expression so changed is
+    /// then simplified, which reduces it to a numeric literal when all of its memory references
+    /// have values. References without values are left in place.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::{expression::MemoryValues, Program};
+    ///
+    /// let program = Program::from_str("DECLARE theta REAL[2]\nRX(2*theta[1]) 0\n").unwrap();
+    /// let mut values = MemoryValues::new();
+    /// values.set_region("theta", vec![0.0, 0.25]);
+    ///
+    /// let patched = program.patch_parameters(&values);
+    /// assert_eq!(patched.to_string(false), "RX(0.5) 0\n");
+    /// ```
+    pub fn patch_parameters(&self, values: &MemoryValues) -> Self {
+        let mut patched = self.clone();
+
+        for instruction in &mut patched.instructions {
+            instruction.apply_to_expressions(|expression| {
+                let substitutions: HashMap<MemoryReference, Expression> = expression
+                    .get_memory_references()
+                    .into_iter()
+                    .filter_map(|reference| {
+                        let value = values.get(reference)?;
+                        Some((reference.clone(), Expression::Number(value)))
+                    })
+                    .collect();
+
+                if !substitutions.is_empty() {
+                    let substituted = std::mem::replace(expression, Expression::PiConstant)
+                        .substitute_memory_references(&substitutions);
+                    *expression = substituted.into_simplified();
+                }
+            });
+        }
+
+        patched
+    }
+
+    /// Check that every memory reference within the program's instructions names a region
+    /// declared with `DECLARE`, at an index within that region's length. All violations are
+    /// reported, in the order of the instructions in which they occur.
+    pub fn validate_memory_references(&self) -> Result<(), Vec<MemoryViolation>> {
+        let mut violations = vec![];
+
+        for instruction in &self.instructions {
+            let region_names = match instruction {
+                Instruction::Load(Load { source, .. }) => vec![source],
+                Instruction::Store(Store { destination, .. }) => vec![destination],
+                _ => vec![],
+            };
+            for name in region_names {
+                if !self.memory_regions.contains_key(name) {
+                    violations.push(MemoryViolation::UndeclaredRegion {
+                        instruction: instruction.clone(),
+                        name: name.clone(),
+                    });
+                }
+            }
+
+            for reference in instruction.get_memory_references() {
+                match self.memory_regions.get(&reference.name) {
+                    None => violations.push(MemoryViolation::UndeclaredRegion {
+                        instruction: instruction.clone(),
+                        name: reference.name.clone(),
+                    }),
+                    Some(region) if reference.index >= region.size.length => {
+                        violations.push(MemoryViolation::IndexOutOfBounds {
+                            instruction: instruction.clone(),
+                            reference: reference.clone(),
+                            length: region.size.length,
+                        })
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
 impl ArithmeticOperand {
     pub fn get_memory_reference(&self) -> Option<&MemoryReference> {
         match self {
@@ -280,19 +500,7 @@ impl ArithmeticOperand {
 impl Expression {
     /// Return, if any, the memory references contained within this Expression.
     pub fn get_memory_references(&self) -> Vec<&MemoryReference> {
-        match self {
-            Expression::Address(reference) => vec![reference],
-            Expression::FunctionCall { expression, .. } => expression.get_memory_references(),
-            Expression::Infix { left, right, .. } => {
-                let mut result = left.get_memory_references();
-                result.extend(right.get_memory_references());
-                result
-            }
-            Expression::Number(_) => vec![],
-            Expression::PiConstant => vec![],
-            Expression::Prefix { expression, .. } => expression.get_memory_references(),
-            Expression::Variable(_) => vec![],
-        }
+        self.memory_references()
     }
 }
 
@@ -308,3 +516,119 @@ impl WaveformInvocation {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::expression::MemoryValues;
+    use crate::instruction::MemoryReference;
+    use crate::Program;
+
+    use super::MemoryViolation;
+
+    #[test]
+    fn patch_parameters() {
+        let program = Program::from_str(
+            "DECLARE theta REAL[2]
+DECLARE phi REAL
+RX(theta[0]) 0
+RZ(theta[1] + phi[0]) 1
+SHIFT-PHASE 0 \"rf\" theta[1]/3
+CPHASE(cos(theta[0]-0.5)) 0 1
+",
+        )
+        .unwrap();
+        let mut values = MemoryValues::new();
+        values.set_region("theta", vec![0.5, 1.5]);
+
+        let patched = program.patch_parameters(&values);
+        assert_eq!(
+            patched.to_string(false),
+            "RX(0.5) 0
+RZ(1.5 + phi[0]) 1
+SHIFT-PHASE 0 \"rf\" 0.5
+CPHASE(1) 0 1
+"
+        );
+        assert_eq!(patched.memory_regions, program.memory_regions);
+
+        let program = Program::from_str(
+            "DECLARE theta REAL
+DECLARE phi REAL
+RZ(phi/2/theta) 0
+RZ(theta/(phi/2)) 0
+",
+        )
+        .unwrap();
+        let mut values = MemoryValues::new();
+        values.set_region("theta", vec![4.0]);
+        assert_eq!(
+            program.patch_parameters(&values).to_string(false),
+            "RZ(phi[0] / 2 / 4) 0
+RZ(4 / (phi[0] / 2)) 0
+"
+        );
+    }
+
+    #[test]
+    fn validate_memory_references() {
+        let program = Program::from_str(
+            "DECLARE ro BIT[2]
+DECLARE theta REAL
+RX(theta) 0
+MEASURE 0 ro[1]
+",
+        )
+        .unwrap();
+        assert_eq!(program.validate_memory_references(), Ok(()));
+
+        let program = Program::from_str(
+            "DECLARE ro BIT[2]
+DECLARE theta REAL
+RX(theta[1]) 0
+MEASURE 0 ro[2]
+MOVE alpha[0] 1
+LOAD ro[0] beta theta[0]
+",
+        )
+        .unwrap();
+        let violations = program.validate_memory_references().unwrap_err();
+        let described: Vec<(String, String)> = violations
+            .iter()
+            .map(|violation| match violation {
+                MemoryViolation::UndeclaredRegion { instruction, name } => {
+                    (instruction.to_string(), name.clone())
+                }
+                MemoryViolation::IndexOutOfBounds {
+                    instruction,
+                    reference,
+                    length,
+                } => (instruction.to_string(), format!("{}/{}", reference, length)),
+            })
+            .collect();
+        assert_eq!(
+            described,
+            vec![
+                ("RX(theta[1]) 0".to_owned(), "theta[1]/1".to_owned()),
+                ("MEASURE 0 ro[2]".to_owned(), "ro[2]/2".to_owned()),
+                ("MOVE alpha[0] 1".to_owned(), "alpha".to_owned()),
+                ("LOAD ro[0] beta theta[0]".to_owned(), "beta".to_owned()),
+            ]
+        );
+
+        assert!(matches!(
+            &violations[1],
+            MemoryViolation::IndexOutOfBounds { reference, length: 2, .. }
+                if reference == &MemoryReference { name: "ro".to_owned(), index: 2 }
+        ));
+        assert_eq!(
+            violations[1].to_string(),
+            "in instruction MEASURE 0 ro[2]: ro[2] is out of bounds for a region of length 2"
+        );
+        assert_eq!(
+            violations[2].to_string(),
+            "in instruction MOVE alpha[0] 1: memory region alpha is not declared"
+        );
+    }
+}
@@ -12,25 +12,106 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::collections::{BTreeMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::str::FromStr;
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
 use crate::instruction::{
-    Declaration, FrameDefinition, FrameIdentifier, Instruction, Qubit, Waveform, WaveformDefinition,
+    Declaration, FrameDefinition, FrameIdentifier, Instruction, Pragma, Qubit, ShiftPhase,
+    Waveform, WaveformDefinition,
 };
-use crate::parser::{lex, parse_instructions};
+use crate::parser::{lex_with_policy, parse_instructions};
 
-pub use self::calibration::CalibrationSet;
+pub use self::calibration::{CalibrationMatch, CalibrationSet, DEFAULT_MAX_CALIBRATION_DEPTH};
+pub use self::conflicts::{FrameConflict, FrameConflictKind};
+pub use self::connectivity::InteractionGraph;
+pub use self::dataflow::{DataDependency, DataDependencyKind, DataFlowGraph, DataResource};
+pub use self::diff::{InstructionChange, ProgramDiff};
+pub use self::duration::{
+    seconds_to_samples, DurationError, RawCaptureSize, Rounding, SampleLengthViolation,
+    SampleLengthViolationKind,
+};
 pub use self::error::{disallow_leftover, map_parsed, recover, ProgramError};
-pub use self::frame::FrameSet;
-pub use self::memory::MemoryRegion;
+pub use self::frame::{FrameSet, FrameUsage};
+pub use self::frame_state::{FrameState, FrameStateHistory, FrameStates};
+pub use self::frequency::FrequencyViolation;
+pub use self::instructions::InstructionList;
+pub use self::isa::{IsaViolation, IsaViolationKind, NativeGate, TargetIsa};
+#[cfg(feature = "serde")]
+pub use self::json::{ProgramJsonError, PROGRAM_JSON_VERSION};
+pub use self::labels::{LabelGenerator, LabelTable, LabelViolation};
+pub use self::lowering::{
+    lower, LoweredProgram, LoweringDiagnostic, LoweringError, LoweringOptions,
+};
+pub use self::memory::{MemoryRegion, MemoryViolation};
+pub use self::placeholders::UnresolvedPlaceholder;
+pub use self::readout::{Readout, ReadoutMap};
+pub use self::resources::{ProgramResources, WAVEFORM_SAMPLE_BYTES};
+pub use self::shared::Shared;
+pub use self::signatures::CalibrationViolation;
+pub use self::source_map::{SourceLocation, SourceMap};
+pub use self::stats::ProgramStats;
+pub use self::subexpressions::CommonSubexpressions;
+pub use self::timeline::{
+    FrameTimeline, QubitTimeline, ScheduleExport, Timeline, TimelineEvent, TimelineEventKind,
+    SCHEDULE_JSON_VERSION,
+};
+pub use self::transform::InstructionRewriter;
+pub use self::unitary::{UnitaryError, MAX_UNITARY_QUBITS};
+pub use self::visitor::InstructionVisitor;
+pub use self::waveforms::{
+    WaveformReference, WaveformReferenceViolation, WaveformReferenceViolationKind,
+};
+pub use crate::parser::IdentifierPolicy;
 
+#[cfg(feature = "binary")]
+pub use self::binary::ProgramBinaryError;
+
+mod accessors;
+mod append;
+#[cfg(feature = "binary")]
+mod binary;
 mod calibration;
+mod canonical;
+mod conflicts;
+mod connectivity;
+mod constants;
+mod dataflow;
+mod delays;
+mod diff;
+mod duration;
 mod error;
+mod format;
 pub(crate) mod frame;
+mod frame_state;
+mod frequency;
+mod gate_level;
 pub mod graph;
+pub mod instructions;
+mod isa;
+#[cfg(feature = "serde")]
+mod json;
+mod labels;
+mod lowering;
 mod memory;
+mod phases;
+mod placeholders;
+mod readout;
+mod resources;
+mod shared;
+mod signatures;
+mod source_map;
+mod stats;
+mod subexpressions;
+mod timeline;
+mod timing;
+pub mod transform;
 pub mod type_check;
+mod unitary;
+pub mod visitor;
+mod waveforms;
 
 pub type Result<O> = std::result::Result<O, ProgramError<O>>;
 
@@ -42,13 +123,22 @@ pub mod graphviz_dot;
 /// This contains not only instructions which are executed in turn on the quantum processor, but
 /// also the "headers" used to describe and manipulate those instructions, such as calibrations
 /// and frame definitions.
+///
+/// A program is cheap to clone: its instructions, calibrations, frames, and waveforms are shared
+/// between clones, and copied on write only as far as one of the clones is mutated.
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Program {
     pub calibrations: CalibrationSet,
     pub frames: FrameSet,
     pub memory_regions: BTreeMap<String, MemoryRegion>,
-    pub waveforms: BTreeMap<String, Waveform>,
-    pub instructions: Vec<Instruction>,
+    /// The waveform definitions, by name. These may be read and mutated as a `BTreeMap` through
+    /// [`Shared`], and [`Shared::into_inner`] takes ownership of them.
+    pub waveforms: Shared<BTreeMap<String, Waveform>>,
+    /// The body of the program. This is an [`InstructionList`] rather than a
+    /// `Vec<Instruction>`, but may be read, indexed, iterated, and mutated in the same ways.
+    /// [`InstructionList::to_vec`] or `Vec::from` converts it into a `Vec` where one is needed,
+    /// and `InstructionList::from` converts a `Vec` into one.
+    pub instructions: InstructionList,
 }
 
 impl Program {
@@ -57,8 +147,8 @@ impl Program {
             calibrations: CalibrationSet::default(),
             frames: FrameSet::new(),
             memory_regions: BTreeMap::new(),
-            waveforms: BTreeMap::new(),
-            instructions: vec![],
+            waveforms: Shared::default(),
+            instructions: InstructionList::new(),
         }
     }
 
@@ -94,13 +184,45 @@ impl Program {
 
     /// Expand any instructions in the program which have a matching calibration, leaving the others
     /// unchanged. Recurses though each instruction while ensuring there is no cycle in the expansion
-    /// graph (i.e. no calibration expands directly or indirectly into itself)
+    /// graph (i.e. no calibration expands directly or indirectly into itself), and that no more
+    /// than [`DEFAULT_MAX_CALIBRATION_DEPTH`] calibrations are nested.
+    ///
+    /// Each `MEASURE` is expanded by its `DEFCAL MEASURE`, with the measured qubit substituted
+    /// for the calibration's qubit and the measurement's target for its memory parameter, so that
+    /// a program calibrated for all of its gates and measurements is lowered to pulse-level Quil-T.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(concat!(
+    ///     "DECLARE ro BIT[2]\n",
+    ///     "DEFCAL MEASURE q addr:\n",
+    ///     "    CAPTURE q \"ro_rx\" flat(duration: 1, iq: 1) addr\n",
+    ///     "MEASURE 1 ro[1]\n",
+    /// ))
+    /// .unwrap();
+    /// let expanded = program.expand_calibrations().unwrap();
+    /// assert_eq!(
+    ///     expanded.instructions[0].to_string(),
+    ///     "CAPTURE 1 \"ro_rx\" flat(duration: 1, iq: 1) ro[1]"
+    /// );
+    /// ```
     pub fn expand_calibrations(&self) -> Result<Self> {
+        self.expand_calibrations_with_max_depth(DEFAULT_MAX_CALIBRATION_DEPTH)
+    }
+
+    /// Expand calibrations as with [`Program::expand_calibrations`], but nesting no more than
+    /// `max_depth` calibrations deep.
+    pub fn expand_calibrations_with_max_depth(&self, max_depth: usize) -> Result<Self> {
         let mut expanded_instructions: Vec<Instruction> = vec![];
 
         // TODO: Do this more efficiently, possibly with Vec::splice
         for instruction in &self.instructions {
-            match self.calibrations.expand(instruction, &[])? {
+            match self
+                .calibrations
+                .expand_with_max_depth(instruction, &[], max_depth)?
+            {
                 Some(expanded) => {
                     expanded_instructions.extend(expanded.into_iter());
                 }
@@ -111,7 +233,7 @@ impl Program {
         }
 
         let mut new_program = self.clone();
-        new_program.instructions = vec![];
+        new_program.instructions = InstructionList::new();
 
         for instruction in expanded_instructions {
             new_program.add_instruction(instruction);
@@ -120,6 +242,81 @@ impl Program {
         Ok(new_program)
     }
 
+    /// Return the inverse of this program: its instructions in reverse order, with each gate
+    /// inverted as by [`Gate::dagger`](crate::instruction::Gate::dagger) and each `SHIFT-PHASE`
+    /// negated. `DELAY`, `FENCE`, and `PRAGMA` instructions are kept as they are, as are the
+    /// program's declarations and definitions. A block opened by a `PRAGMA` and closed by a
+    /// matching `END_` `PRAGMA`, such as `PRESERVE_BLOCK` and `END_PRESERVE_BLOCK`, still opens
+    /// before it closes. Definitions among the instructions, such as `DEFGATE` and `DEFCIRCUIT`,
+    /// stay ahead of the inverted instructions in their original order, so that they still
+    /// precede their uses.
+    ///
+    /// Return an error for any other instruction, such as `MEASURE`, `CAPTURE`, `RESET`, a pulse,
+    /// or a classical or control flow instruction, which cannot be inverted. `SHIFT-FREQUENCY`
+    /// is among these: negating it would restore the frame's frequency, but not the phase which
+    /// the frame accrued while its frequency was shifted.
+    pub fn dagger(&self) -> Result<Self> {
+        let mut inverse = Program {
+            instructions: InstructionList::new(),
+            ..self.clone()
+        };
+
+        for instruction in self.instructions.iter() {
+            if instruction.is_definition() {
+                inverse.instructions.push(instruction.clone());
+            }
+        }
+
+        let mut body: Vec<&Instruction> = self
+            .instructions
+            .iter()
+            .filter(|instruction| !instruction.is_definition())
+            .collect();
+
+        // Swap each block's opening and closing pragmas, so that once reversed the block is
+        // still opened first, with its opening pragma's arguments.
+        let mut open_blocks: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut blocks = vec![];
+        for (index, instruction) in body.iter().enumerate() {
+            if let Instruction::Pragma(Pragma { name, .. }) = instruction {
+                match name.strip_prefix("END_") {
+                    Some(opening) => {
+                        if let Some(start) = open_blocks.get_mut(opening).and_then(Vec::pop) {
+                            blocks.push((start, index));
+                        }
+                    }
+                    None => open_blocks.entry(name).or_default().push(index),
+                }
+            }
+        }
+        for (start, end) in blocks {
+            body.swap(start, end);
+        }
+
+        for instruction in body.into_iter().rev() {
+            let inverted = match instruction {
+                Instruction::Gate(gate) => Instruction::Gate(gate.clone().dagger()),
+                Instruction::ShiftPhase(ShiftPhase { frame, phase }) => {
+                    Instruction::ShiftPhase(ShiftPhase {
+                        frame: frame.clone(),
+                        phase: (-phase.clone()).into_simplified(),
+                    })
+                }
+                Instruction::Delay(_) | Instruction::Fence(_) | Instruction::Pragma(_) => {
+                    instruction.clone()
+                }
+                _ => {
+                    return Err(ProgramError::IrreversibleInstruction {
+                        instruction: instruction.clone(),
+                    })
+                }
+            };
+            inverse.instructions.push(inverted);
+        }
+
+        Ok(inverse)
+    }
+
     /// Return the frames which are either "used" or "blocked" by the given instruction.
     ///
     /// An instruction "uses" a frame if it plays on that frame; it "blocks" a frame
@@ -135,9 +332,14 @@ impl Program {
         instruction: &'a Instruction,
         include_blocked: bool,
     ) -> Option<HashSet<&'a FrameIdentifier>> {
-        instruction
-            .get_frame_match_condition(include_blocked)
-            .map(|condition| self.frames.get_matching_keys(condition))
+        self.frames
+            .get_frames_for_instruction(instruction, include_blocked)
+    }
+
+    /// Return both the frames used and those blocked by the given instruction, as described for
+    /// [`Program::get_frames_for_instruction`], among those defined in this program.
+    pub fn get_frame_usage<'a>(&'a self, instruction: &'a Instruction) -> Option<FrameUsage<'a>> {
+        self.frames.get_frame_usage(instruction)
     }
 
     /// Returns a HashSet consisting of every Qubit that is used in the program.
@@ -158,28 +360,80 @@ impl Program {
             .collect::<HashSet<_>>()
     }
 
+    /// Return every qubit, fixed or variable, used by the program's instructions, including those
+    /// of the frames on which pulse-level instructions play. Unlike
+    /// [`Program::get_used_qubits`], this covers all of the instructions described by
+    /// [`Instruction::qubits`].
+    pub fn qubits_used(&self) -> HashSet<&Qubit> {
+        self.instructions
+            .iter()
+            .flat_map(|instruction| instruction.qubits())
+            .collect()
+    }
+
+    /// Return the instructions which make up the program's header: its memory declarations,
+    /// frame definitions, waveform definitions, and calibrations, in that order.
+    fn header_instructions(&self) -> Vec<Instruction> {
+        let mut result = vec![];
+
+        result.extend(self.memory_regions.iter().map(|(name, descriptor)| {
+            Instruction::Declaration(Declaration {
+                name: name.clone(),
+                size: descriptor.size.clone(),
+                sharing: descriptor.sharing.clone(),
+            })
+        }));
+        result.extend(self.frames.to_instructions());
+        result.extend(self.waveforms.iter().map(|(name, definition)| {
+            Instruction::WaveformDefinition(WaveformDefinition {
+                name: name.clone(),
+                definition: definition.clone(),
+            })
+        }));
+        result.extend(self.calibrations.to_instructions());
+
+        result
+    }
+
+    /// Return all of the program's definitions: the header instructions (as included by
+    /// [`Program::to_instructions`]) followed by each `DEFGATE` and `DEFCIRCUIT`, in the order in
+    /// which they appear among the program's instructions.
+    pub fn definitions(&self) -> Vec<Instruction> {
+        let mut result = self.header_instructions();
+        result.extend(
+            self.instructions
+                .iter()
+                .filter(|instruction| instruction.is_definition())
+                .cloned(),
+        );
+        result
+    }
+
+    /// Return the program's executable body: its instructions other than definitions, in order.
+    pub fn body(&self) -> Vec<&Instruction> {
+        self.instructions
+            .iter()
+            .filter(|instruction| !instruction.is_definition())
+            .collect()
+    }
+
+    /// Partition the program into its [definitions](Program::definitions) and its executable
+    /// [body](Program::body), each of which keeps its own order.
+    pub fn split_definitions(&self) -> (Vec<Instruction>, Vec<Instruction>) {
+        (
+            self.definitions(),
+            self.body().into_iter().cloned().collect(),
+        )
+    }
+
     pub fn to_instructions(&self, include_headers: bool) -> Vec<Instruction> {
         let mut result = vec![];
 
         if include_headers {
-            result.extend(self.memory_regions.iter().map(|(name, descriptor)| {
-                Instruction::Declaration(Declaration {
-                    name: name.clone(),
-                    size: descriptor.size.clone(),
-                    sharing: descriptor.sharing.clone(),
-                })
-            }));
-            result.extend(self.frames.to_instructions());
-            result.extend(self.waveforms.iter().map(|(name, definition)| {
-                Instruction::WaveformDefinition(WaveformDefinition {
-                    name: name.clone(),
-                    definition: definition.clone(),
-                })
-            }));
-            result.extend(self.calibrations.to_instructions());
+            result.extend(self.header_instructions());
         }
 
-        result.extend(self.instructions.clone());
+        result.extend(self.instructions.iter().cloned());
 
         result
     }
@@ -190,12 +444,23 @@ impl Program {
             .map(|inst| format!("{}\n", inst))
             .collect()
     }
-}
 
-impl FromStr for Program {
-    type Err = ProgramError<Self>;
-    fn from_str(s: &str) -> Result<Self> {
-        let lexed = lex(s).map_err(ProgramError::from)?;
+    /// Parse a program from a string, accepting identifiers according to the given
+    /// [`IdentifierPolicy`].
+    ///
+    /// [`Program::from_str`] is equivalent to calling this with [`IdentifierPolicy::Strict`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::{program::IdentifierPolicy, Program};
+    ///
+    /// let input = "PRAGMA VENDOR_NOTE théta";
+    /// assert!(Program::parse_with_identifier_policy(input, IdentifierPolicy::Strict).is_err());
+    /// assert!(Program::parse_with_identifier_policy(input, IdentifierPolicy::Permissive).is_ok());
+    /// ```
+    pub fn parse_with_identifier_policy(s: &str, policy: IdentifierPolicy) -> Result<Self> {
+        let lexed = lex_with_policy(s, policy).map_err(ProgramError::from)?;
         map_parsed(
             disallow_leftover(parse_instructions(&lexed)),
             |instructions| {
@@ -209,14 +474,77 @@ impl FromStr for Program {
     }
 }
 
+impl FromStr for Program {
+    type Err = ProgramError<Self>;
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse_with_identifier_policy(s, IdentifierPolicy::Strict)
+    }
+}
+
+/// With the `serde` feature, a program serializes as the sequence of its instructions, headers first, as given by
+/// [`Program::to_instructions`], and deserializes by adding each instruction in turn with
+/// [`Program::add_instruction`].
+#[cfg(feature = "serde")]
+impl Serialize for Program {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        self.to_instructions(true).serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Program {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let mut program = Self::new();
+        for instruction in Vec::<Instruction>::deserialize(deserializer)? {
+            program.add_instruction(instruction);
+        }
+        Ok(program)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{collections::HashSet, str::FromStr};
 
+    use num_complex::Complex64;
+
+    use crate::expression::Expression;
     use crate::instruction::Instruction;
-    use crate::instruction::Qubit;
+    use crate::instruction::{FrameIdentifier, GateDefinition, GateType, Qubit, SwapPhases};
+
+    use super::{FrameSet, Program, ProgramError, Shared};
+
+    #[test]
+    fn split_definitions() {
+        let program = Program::from_str(
+            "DECLARE ro BIT
+H 0
+DEFCIRCUIT BELL a b:
+    H a
+    CNOT a b
+BELL 0 1
+DEFWAVEFORM w:
+    1.0
+MEASURE 0 ro
+",
+        )
+        .unwrap();
+        let (definitions, body) = program.split_definitions();
 
-    use super::Program;
+        assert_eq!(definitions.len(), 3);
+        assert!(matches!(definitions[0], Instruction::Declaration(_)));
+        assert!(matches!(definitions[1], Instruction::WaveformDefinition(_)));
+        assert!(matches!(definitions[2], Instruction::CircuitDefinition(_)));
+        assert_eq!(
+            body,
+            vec![
+                Instruction::parse("H 0").unwrap(),
+                Instruction::parse("BELL 0 1").unwrap(),
+                Instruction::parse("MEASURE 0 ro").unwrap(),
+            ]
+        );
+        assert_eq!(definitions, program.definitions());
+    }
 
     #[test]
     fn program_eq() {
@@ -314,6 +642,23 @@ I 0
         );
     }
 
+    #[test]
+    fn clone_shares_headers() {
+        let input = "
+DEFWAVEFORM custom:
+    1, 2
+I 0
+";
+        let program = Program::from_str(input).unwrap();
+        let mut clone = program.clone();
+        assert!(Shared::ptr_eq(&program.waveforms, &clone.waveforms));
+
+        clone.waveforms.clear();
+        assert!(!Shared::ptr_eq(&program.waveforms, &clone.waveforms));
+        assert_eq!(program.waveforms.len(), 1);
+        assert!(clone.waveforms.is_empty());
+    }
+
     #[test]
     fn program_deterministic_ordering() {
         let input = "
@@ -457,6 +802,193 @@ DEFFRAME 0 1 \"2q\":
         }
     }
 
+    #[test]
+    fn dagger() {
+        let mut program = Program::from_str(
+            "DECLARE ro BIT
+DEFCIRCUIT BELL a b:
+    H a
+    CNOT a b
+H 0
+RX(pi/4) 0
+DAGGER T 0
+PRAGMA DELAY_HINT
+SHIFT-PHASE 0 \"rf\" -pi
+ISWAP 0 1
+",
+        )
+        .unwrap();
+        // Definitions stay in place rather than being reversed along with the instructions.
+        let definition = Instruction::GateDefinition(GateDefinition {
+            name: "SQRT-X".to_owned(),
+            parameters: vec![],
+            matrix: vec![
+                vec![
+                    Expression::from(Complex64::new(0.5, 0.5)),
+                    Expression::from(Complex64::new(0.5, -0.5)),
+                ],
+                vec![
+                    Expression::from(Complex64::new(0.5, -0.5)),
+                    Expression::from(Complex64::new(0.5, 0.5)),
+                ],
+            ],
+            r#type: GateType::Matrix,
+        });
+        program.instructions.insert(0, definition.clone());
+        let inverse = program.dagger().unwrap();
+        let mut expected = Program::from_str(
+            "DECLARE ro BIT
+DEFCIRCUIT BELL a b:
+    H a
+    CNOT a b
+DAGGER ISWAP 0 1
+SHIFT-PHASE 0 \"rf\" pi
+PRAGMA DELAY_HINT
+T 0
+RX(-pi/4) 0
+H 0
+",
+        )
+        .unwrap();
+        expected.instructions.insert(0, definition);
+        assert_eq!(inverse.to_string(true), expected.to_string(true));
+        assert_eq!(
+            inverse.dagger().unwrap().to_string(true),
+            program.to_string(true)
+        );
+
+        let program = Program::from_str("DECLARE ro BIT\nX 0\nMEASURE 0 ro\n").unwrap();
+        assert_eq!(
+            program.dagger(),
+            Err(ProgramError::IrreversibleInstruction {
+                instruction: Instruction::parse("MEASURE 0 ro").unwrap()
+            })
+        );
+
+        let program = Program::from_str("SHIFT-FREQUENCY 0 \"rf\" 1e6\nX 0\n").unwrap();
+        assert!(matches!(
+            program.dagger(),
+            Err(ProgramError::IrreversibleInstruction {
+                instruction: Instruction::ShiftFrequency(_)
+            })
+        ));
+
+        let program = Program::from_str(
+            "PRAGMA PRESERVE_BLOCK
+X 0
+PRAGMA COMMUTING_BLOCKS
+PRAGMA BLOCK
+Y 1
+PRAGMA END_BLOCK
+PRAGMA BLOCK
+Z 2
+PRAGMA END_BLOCK
+PRAGMA END_COMMUTING_BLOCKS
+PRAGMA END_PRESERVE_BLOCK
+PRAGMA INITIAL_REWIRING \"PARTIAL\"
+",
+        )
+        .unwrap();
+        assert_eq!(
+            program.dagger().unwrap().to_string(true),
+            "PRAGMA INITIAL_REWIRING \"PARTIAL\"
+PRAGMA PRESERVE_BLOCK
+PRAGMA COMMUTING_BLOCKS
+PRAGMA BLOCK
+Z 2
+PRAGMA END_BLOCK
+PRAGMA BLOCK
+Y 1
+PRAGMA END_BLOCK
+PRAGMA END_COMMUTING_BLOCKS
+X 0
+PRAGMA END_PRESERVE_BLOCK
+"
+        );
+    }
+
+    #[test]
+    fn frame_usage() {
+        let program = Program::from_str(
+            "DEFFRAME 0 \"a\":
+\tHARDWARE-OBJECT: \"hardware\"
+
+DEFFRAME 0 \"b\":
+\tHARDWARE-OBJECT: \"hardware\"
+
+DEFFRAME 1 \"c\":
+\tHARDWARE-OBJECT: \"hardware\"
+",
+        )
+        .unwrap();
+        assert_eq!(
+            FrameSet::from_instructions(program.to_instructions(true)),
+            program.frames
+        );
+
+        let to_strings = |frames: HashSet<&FrameIdentifier>| -> HashSet<String> {
+            frames.into_iter().map(|f| f.to_string()).collect()
+        };
+
+        let pulse = Instruction::parse(r#"PULSE 0 "a" custom_waveform"#).unwrap();
+        let usage = program.get_frame_usage(&pulse).unwrap();
+        assert_eq!(
+            to_strings(usage.blocked_but_not_used()),
+            HashSet::from([r#"0 "b""#.to_owned()])
+        );
+
+        let set_phase = Instruction::parse(r#"SET-PHASE 1 "c" 1.0"#).unwrap();
+        let usage = program.get_frame_usage(&set_phase).unwrap();
+        assert!(usage.blocked_but_not_used().is_empty());
+        assert_eq!(
+            to_strings(usage.used),
+            HashSet::from([r#"1 "c""#.to_owned()])
+        );
+
+        let swap_phases = Instruction::SwapPhases(SwapPhases {
+            frame_1: FrameIdentifier {
+                name: "a".to_owned(),
+                qubits: vec![Qubit::Fixed(0)],
+            },
+            frame_2: FrameIdentifier {
+                name: "c".to_owned(),
+                qubits: vec![Qubit::Fixed(1)],
+            },
+        });
+        let usage = program.get_frame_usage(&swap_phases).unwrap();
+        assert_eq!(
+            to_strings(usage.used),
+            HashSet::from([r#"0 "a""#.to_owned(), r#"1 "c""#.to_owned()])
+        );
+
+        let halt = Instruction::Halt;
+        assert!(program.get_frame_usage(&halt).is_none());
+    }
+
+    #[test]
+    fn qubits_used() {
+        let program = Program::from_str(
+            "DECLARE ro BIT
+MEASURE 0 ro
+CZ 1 2
+PULSE 3 4 \"cz\" flat(duration: 1, iq: 1)
+SET-PHASE 5 \"rf\" 0.0
+DELAY 6 1.0
+FENCE 7
+RESET
+",
+        )
+        .unwrap();
+        let expected: HashSet<Qubit> = (0..8).map(Qubit::Fixed).collect();
+        assert_eq!(
+            program.qubits_used(),
+            expected.iter().collect::<HashSet<_>>()
+        );
+
+        let instruction = Instruction::parse("SHIFT-PHASE q \"xy\" pi").unwrap();
+        assert_eq!(instruction.qubits(), vec![&Qubit::Variable("q".to_owned())]);
+    }
+
     #[test]
     fn test_get_qubits() {
         let input = "
@@ -480,4 +1012,74 @@ I 0
         let actual = program.get_used_qubits();
         assert_eq!(expected, actual);
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_round_trip() {
+        let mut program = Program::from_str(
+            r#"DECLARE ro BIT[2]
+DECLARE theta REAL
+DEFFRAME 0 "rf":
+    SAMPLE-RATE: 1e9
+    HARDWARE-OBJECT: "{\"channel\": 1}"
+DEFWAVEFORM ramp(%a):
+    0, %a
+DEFCAL RX(%theta) q:
+    SHIFT-PHASE q "rf" -%theta
+    PULSE q "rf" ramp(a: 0.5)
+DEFCAL MEASURE q addr:
+    CAPTURE q "ro" flat(duration: 1, iq: 1) addr
+DEFCIRCUIT BELL a b:
+    H a
+    CNOT a b
+PRAGMA INITIAL_REWIRING "NAIVE"
+LABEL @start
+DAGGER CONTROLLED RX(2*theta) 0 1
+BELL 0 1
+FENCE
+DELAY 0 "rf" 1
+SET-SCALE 0 "rf" 0.5
+RAW-CAPTURE 0 "ro" 1 ro
+ADD theta 1
+GT ro[0] theta 0.5
+NOT ro[1]
+MOVE ro[0] 1
+MEASURE 0 ro[1]
+RESET
+JUMP-WHEN @start ro[0]
+HALT
+"#,
+        )
+        .unwrap();
+        // DEFGATE and SWAP-PHASES are not yet parsed, so they are added to the program directly.
+        program.add_instruction(Instruction::GateDefinition(GateDefinition {
+            name: "SQRT-X".to_owned(),
+            parameters: vec![],
+            matrix: vec![
+                vec![
+                    Expression::Number(Complex64::new(0.5, 0.5)),
+                    Expression::Number(Complex64::new(0.5, -0.5)),
+                ],
+                vec![
+                    Expression::Number(Complex64::new(0.5, -0.5)),
+                    Expression::Number(Complex64::new(0.5, 0.5)),
+                ],
+            ],
+            r#type: GateType::Matrix,
+        }));
+        program.add_instruction(Instruction::SwapPhases(SwapPhases {
+            frame_1: FrameIdentifier {
+                name: "rf".to_owned(),
+                qubits: vec![Qubit::Fixed(0)],
+            },
+            frame_2: FrameIdentifier {
+                name: "rf".to_owned(),
+                qubits: vec![Qubit::Fixed(1)],
+            },
+        }));
+
+        let json = serde_json::to_string(&program).unwrap();
+        let deserialized: Program = serde_json::from_str(&json).unwrap();
+        assert_eq!(deserialized, program);
+    }
 }
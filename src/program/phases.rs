@@ -0,0 +1,233 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use crate::expression::Expression;
+use crate::instruction::{
+    Delay, Fence, FrameIdentifier, Instruction, Pulse, Qubit, SetFrequency, SetPhase, SetScale,
+    ShiftFrequency, ShiftPhase, SwapPhases,
+};
+
+use super::Program;
+
+/// Whether the two sets of qubits have any qubit in common.
+fn overlaps(left: &[Qubit], right: &[Qubit]) -> bool {
+    left.iter().any(|qubit| right.contains(qubit))
+}
+
+/// Whether the instruction may observe or change the phase of the frame, such that a phase
+/// update on the frame may not be moved across it. Any instruction which is not a frame
+/// instruction may be calibrated into one, or may change the memory read by a phase, and so is
+/// assumed to do so.
+fn interrupts(instruction: &Instruction, frame: &FrameIdentifier) -> bool {
+    match instruction {
+        Instruction::Pulse(Pulse {
+            blocking,
+            frame: used,
+            ..
+        }) => used == frame || (*blocking && overlaps(&used.qubits, &frame.qubits)),
+        Instruction::Delay(Delay {
+            qubits,
+            frame_names,
+            ..
+        }) => {
+            overlaps(qubits, &frame.qubits)
+                && (frame_names.is_empty() || frame_names.contains(&frame.name))
+        }
+        Instruction::Fence(Fence { qubits }) => {
+            qubits.is_empty() || overlaps(qubits, &frame.qubits)
+        }
+        Instruction::SetFrequency(SetFrequency { frame: used, .. })
+        | Instruction::ShiftFrequency(ShiftFrequency { frame: used, .. })
+        | Instruction::SetPhase(SetPhase { frame: used, .. })
+        | Instruction::ShiftPhase(ShiftPhase { frame: used, .. })
+        | Instruction::SetScale(SetScale { frame: used, .. }) => used == frame,
+        Instruction::SwapPhases(SwapPhases { frame_1, frame_2 }) => {
+            frame_1 == frame || frame_2 == frame
+        }
+        _ => true,
+    }
+}
+
+/// The phase update made by a `SET-PHASE` or `SHIFT-PHASE`.
+enum PhaseUpdate<'a> {
+    Set(&'a FrameIdentifier, &'a Expression),
+    Shift(&'a FrameIdentifier, &'a Expression),
+}
+
+impl<'a> PhaseUpdate<'a> {
+    fn of(instruction: &'a Instruction) -> Option<Self> {
+        match instruction {
+            Instruction::SetPhase(SetPhase { frame, phase }) => Some(Self::Set(frame, phase)),
+            Instruction::ShiftPhase(ShiftPhase { frame, phase }) => Some(Self::Shift(frame, phase)),
+            _ => None,
+        }
+    }
+
+    fn frame(&self) -> &'a FrameIdentifier {
+        match self {
+            Self::Set(frame, _) | Self::Shift(frame, _) => frame,
+        }
+    }
+}
+
+/// Return the single instruction with the effect of the phase update `earlier` followed by
+/// `later`, on the same frame.
+fn merge(earlier: &Instruction, later: PhaseUpdate) -> Instruction {
+    match (earlier, later) {
+        (_, PhaseUpdate::Set(frame, phase)) => Instruction::SetPhase(SetPhase {
+            frame: frame.clone(),
+            phase: phase.clone(),
+        }),
+        (
+            Instruction::SetPhase(SetPhase { phase: earlier, .. }),
+            PhaseUpdate::Shift(frame, phase),
+        ) => Instruction::SetPhase(SetPhase {
+            frame: frame.clone(),
+            phase: (earlier.clone() + phase.clone()).into_simplified(),
+        }),
+        (earlier, PhaseUpdate::Shift(frame, phase)) => {
+            let earlier = match earlier {
+                Instruction::ShiftPhase(ShiftPhase { phase, .. }) => phase.clone(),
+                _ => unreachable!("only phase updates are merged"),
+            };
+            Instruction::ShiftPhase(ShiftPhase {
+                frame: frame.clone(),
+                phase: (earlier + phase.clone()).into_simplified(),
+            })
+        }
+    }
+}
+
+/// Whether the instruction is a `SHIFT-PHASE` by exactly zero, which has no effect.
+fn is_null_shift(instruction: &Instruction) -> bool {
+    matches!(
+        instruction,
+        Instruction::ShiftPhase(ShiftPhase {
+            phase: Expression::Number(value),
+            ..
+        }) if value.re == 0.0 && value.im == 0.0
+    )
+}
+
+impl Program {
+    /// Return a copy of this program in which each run of `SET-PHASE` and `SHIFT-PHASE`
+    /// instructions on the same frame is folded into a single instruction, at the position of the
+    /// first: a `SET-PHASE` replaces any update before it, and `SHIFT-PHASE`s add to the update
+    /// before them. A `SHIFT-PHASE` by zero is removed.
+    ///
+    /// A run on one frame may span instructions on unrelated frames, but is ended by any
+    /// instruction which may observe or change the frame's phase: a pulse, capture, delay, or
+    /// fence which uses or blocks the frame, and any instruction which is not a frame instruction,
+    /// such as a gate, a label, or a classical instruction, which may be calibrated into pulses
+    /// or change the memory read by a phase. Only the program's own instructions are folded, not
+    /// the bodies of its calibrations.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "SHIFT-PHASE 0 \"rf\" 0.5
+    /// SHIFT-PHASE 1 \"rf\" 1
+    /// SHIFT-PHASE 0 \"rf\" 0.25
+    /// PULSE 1 \"rf\" flat(duration: 1, iq: 1)
+    /// SHIFT-PHASE 0 \"rf\" -0.75
+    /// ",
+    /// )
+    /// .unwrap();
+    /// assert_eq!(
+    ///     program.fold_phases().to_string(false),
+    ///     "SHIFT-PHASE 1 \"rf\" 1\nPULSE 1 \"rf\" flat(duration: 1, iq: 1)\n"
+    /// );
+    /// ```
+    pub fn fold_phases(&self) -> Self {
+        let mut instructions: Vec<Instruction> = Vec::with_capacity(self.instructions.len());
+        // The position within `instructions` of the phase update which ends the current run on
+        // each frame, if the run has not been interrupted.
+        let mut pending: HashMap<FrameIdentifier, usize> = HashMap::new();
+
+        for instruction in &self.instructions {
+            match PhaseUpdate::of(instruction) {
+                Some(update) => {
+                    let frame = update.frame();
+                    match pending.get(frame) {
+                        Some(&position) => {
+                            instructions[position] = merge(&instructions[position], update);
+                        }
+                        None => {
+                            pending.insert(frame.clone(), instructions.len());
+                            instructions.push(instruction.clone());
+                        }
+                    }
+                }
+                None => {
+                    pending.retain(|frame, _| !interrupts(instruction, frame));
+                    instructions.push(instruction.clone());
+                }
+            }
+        }
+
+        instructions.retain(|instruction| !is_null_shift(instruction));
+
+        Program {
+            instructions: instructions.into(),
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::Program;
+
+    #[test]
+    fn fold_phases() {
+        let program = Program::from_str(
+            "DECLARE theta REAL
+SET-PHASE 0 \"rf\" 1
+SHIFT-PHASE 0 1 \"cz\" 0.5
+SHIFT-PHASE 0 \"rf\" 0.5
+SET-FREQUENCY 0 1 \"cz\" 2
+SHIFT-PHASE 0 1 \"cz\" 0.5
+SHIFT-PHASE 0 \"rf\" 0.5
+FENCE 1
+SHIFT-PHASE 0 1 \"cz\" 0.5
+SHIFT-PHASE 0 \"rf\" -2
+SHIFT-PHASE 0 1 \"cz\" -0.5
+X 0
+SHIFT-PHASE 0 \"rf\" theta
+MOVE theta[0] 1
+SHIFT-PHASE 0 \"rf\" theta
+",
+        )
+        .unwrap();
+        assert_eq!(
+            program.fold_phases().to_string(false),
+            "SET-PHASE 0 \"rf\" 0
+SHIFT-PHASE 0 1 \"cz\" 0.5
+SET-FREQUENCY 0 1 \"cz\" 2
+SHIFT-PHASE 0 1 \"cz\" 0.5
+FENCE 1
+X 0
+SHIFT-PHASE 0 \"rf\" theta[0]
+MOVE theta[0] 1
+SHIFT-PHASE 0 \"rf\" theta[0]
+"
+        );
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use crate::instruction::{
+    Calibration, FrameDefinition, Instruction, MeasureCalibrationDefinition, Qubit,
+    QubitPlaceholder,
+};
+
+use super::Program;
+
+/// A qubit placeholder left without a resolution by [`Program::resolve_placeholders`].
+#[derive(Clone, Debug, Error, PartialEq)]
+#[error("no qubit was given for {placeholder}, used in {instruction}")]
+pub struct UnresolvedPlaceholder {
+    pub placeholder: QubitPlaceholder,
+    pub instruction: Instruction,
+}
+
+/// Return mutable references to all of the qubits used by the instruction itself, including the
+/// qubits of the definitions, but not those within the bodies of blocks.
+fn qubits_mut(instruction: &mut Instruction) -> Vec<&mut Qubit> {
+    match instruction {
+        Instruction::CalibrationDefinition(Calibration { qubits, .. }) => {
+            qubits.iter_mut().collect()
+        }
+        Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+            qubit, ..
+        }) => qubit.iter_mut().collect(),
+        Instruction::FrameDefinition(FrameDefinition { identifier, .. }) => {
+            identifier.qubits.iter_mut().collect()
+        }
+        instruction => instruction.get_qubits_mut(),
+    }
+}
+
+impl Program {
+    /// Return a copy of the program in which each [`Qubit::Placeholder`] is replaced by the qubit
+    /// to which it is mapped. Placeholders may be used anywhere a qubit may, including within
+    /// calibrations and frame definitions, and so allow a program to be built before its qubits
+    /// are allocated.
+    ///
+    /// Fails, reporting the first such instruction, if any placeholder has no mapping.
+    ///
+    /// ```rust
+    /// use std::collections::HashMap;
+    /// use quil_rs::instruction::{Instruction, Qubit, QubitPlaceholder};
+    /// use quil_rs::Program;
+    ///
+    /// let (control, target) = (QubitPlaceholder::new(), QubitPlaceholder::new());
+    /// let mut program = Program::new();
+    /// program.add_instruction(Instruction::gate("CNOT").qubit(control).qubit(target).into());
+    ///
+    /// let mapping = vec![(control, Qubit::Fixed(4)), (target, Qubit::Fixed(7))]
+    ///     .into_iter()
+    ///     .collect::<HashMap<_, _>>();
+    /// let resolved = program.resolve_placeholders(&mapping).unwrap();
+    /// assert_eq!(resolved.to_string(true), "CNOT 4 7\n");
+    /// ```
+    pub fn resolve_placeholders(
+        &self,
+        mapping: &HashMap<QubitPlaceholder, Qubit>,
+    ) -> Result<Program, UnresolvedPlaceholder> {
+        let mut unresolved = None;
+        let program = self.transform(&mut |mut instruction: Instruction| {
+            let original = instruction.clone();
+            for qubit in qubits_mut(&mut instruction) {
+                if let Qubit::Placeholder(placeholder) = qubit {
+                    match mapping.get(placeholder) {
+                        Some(resolution) => *qubit = resolution.clone(),
+                        None => {
+                            unresolved.get_or_insert(UnresolvedPlaceholder {
+                                placeholder: *placeholder,
+                                instruction: original.clone(),
+                            });
+                        }
+                    }
+                }
+            }
+            vec![instruction]
+        });
+
+        match unresolved {
+            Some(error) => Err(error),
+            None => Ok(program),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::instruction::{
+        Calibration, FrameIdentifier, Instruction, Pulse, Qubit, QubitPlaceholder,
+        WaveformInvocation,
+    };
+    use crate::Program;
+
+    use super::UnresolvedPlaceholder;
+
+    #[test]
+    fn resolve_placeholders() {
+        let (first, second) = (QubitPlaceholder::new(), QubitPlaceholder::new());
+        let pulse = Instruction::Pulse(Pulse {
+            blocking: true,
+            frame: FrameIdentifier {
+                name: "xy".to_owned(),
+                qubits: vec![Qubit::Placeholder(first)],
+            },
+            waveform: WaveformInvocation {
+                name: "wf".to_owned(),
+                parameters: HashMap::new(),
+            },
+        });
+        let mut program = Program::new();
+        program.add_instruction(Instruction::CalibrationDefinition(Calibration {
+            instructions: vec![pulse],
+            modifiers: vec![],
+            name: "X".to_owned(),
+            parameters: vec![],
+            qubits: vec![Qubit::Placeholder(first)],
+        }));
+        program.add_instruction(Instruction::gate("H").qubit(first).into());
+        program.add_instruction(Instruction::measure_discard(second));
+
+        let mut mapping = HashMap::new();
+        mapping.insert(first, Qubit::Fixed(2));
+        assert_eq!(
+            program.resolve_placeholders(&mapping),
+            Err(UnresolvedPlaceholder {
+                placeholder: second,
+                instruction: Instruction::measure_discard(second),
+            })
+        );
+
+        mapping.insert(second, Qubit::Fixed(3));
+        let resolved = program.resolve_placeholders(&mapping).unwrap();
+        assert_eq!(
+            resolved.to_string(true),
+            "DEFCAL X 2:\n\tPULSE 2 \"xy\" wf()\nH 2\nMEASURE 3\n"
+        );
+    }
+}
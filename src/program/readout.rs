@@ -0,0 +1,187 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+
+use crate::instruction::{Capture, Instruction, Measurement, MemoryReference, Qubit, RawCapture};
+
+use super::Program;
+
+/// A write of readout results into memory, by a `MEASURE`, `CAPTURE`, or `RAW-CAPTURE`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Readout {
+    /// The index of the instruction within the sequence of instructions from which the readout
+    /// was found, such as [`Program::instructions`].
+    pub instruction_index: usize,
+
+    /// The qubits which are read out: the measured qubit, or the qubits of the capture frame.
+    pub qubits: Vec<Qubit>,
+
+    /// The memory written. For a `RAW-CAPTURE`, which writes many samples, this is the first.
+    pub memory_reference: MemoryReference,
+}
+
+impl Readout {
+    /// Return the readout performed by the instruction, if any. A `MEASURE` without a target
+    /// performs none.
+    fn from_instruction(instruction_index: usize, instruction: &Instruction) -> Option<Self> {
+        let (qubits, memory_reference) = match instruction {
+            Instruction::Measurement(Measurement {
+                qubit,
+                target: Some(target),
+            }) => (vec![qubit.clone()], target),
+            Instruction::Capture(Capture {
+                frame,
+                memory_reference,
+                ..
+            })
+            | Instruction::RawCapture(RawCapture {
+                frame,
+                memory_reference,
+                ..
+            }) => (frame.qubits.clone(), memory_reference),
+            _ => return None,
+        };
+
+        Some(Self {
+            instruction_index,
+            qubits,
+            memory_reference: memory_reference.clone(),
+        })
+    }
+}
+
+/// The readouts within a sequence of instructions, in order, relating qubits to the memory into
+/// which their results are written.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReadoutMap {
+    readouts: Vec<Readout>,
+}
+
+impl ReadoutMap {
+    /// Collect the readouts performed by the given instructions. Instructions nested within
+    /// blocks (such as within `DEFCAL`) are not traversed.
+    pub fn new<'a>(instructions: impl IntoIterator<Item = &'a Instruction>) -> Self {
+        Self {
+            readouts: instructions
+                .into_iter()
+                .enumerate()
+                .filter_map(|(index, instruction)| Readout::from_instruction(index, instruction))
+                .collect(),
+        }
+    }
+
+    /// Iterate through all readouts, in order.
+    pub fn iter(&self) -> std::slice::Iter<'_, Readout> {
+        self.readouts.iter()
+    }
+
+    /// Return the readouts which write into the given memory, in order.
+    pub fn get_readouts_into<'a, 'b>(
+        &'a self,
+        memory_reference: &'b MemoryReference,
+    ) -> impl Iterator<Item = &'a Readout> + 'b
+    where
+        'a: 'b,
+    {
+        self.readouts
+            .iter()
+            .filter(move |readout| &readout.memory_reference == memory_reference)
+    }
+
+    /// Return the qubits whose results are written into the given memory.
+    pub fn get_qubits_for(&self, memory_reference: &MemoryReference) -> HashSet<&Qubit> {
+        self.get_readouts_into(memory_reference)
+            .flat_map(|readout| &readout.qubits)
+            .collect()
+    }
+
+    /// Return the memory into which the given qubit's results are written, in order of readout.
+    pub fn get_memory_references_for(&self, qubit: &Qubit) -> Vec<&MemoryReference> {
+        self.readouts
+            .iter()
+            .filter(|readout| readout.qubits.contains(qubit))
+            .map(|readout| &readout.memory_reference)
+            .collect()
+    }
+
+    /// Return the number of readouts.
+    pub fn len(&self) -> usize {
+        self.readouts.len()
+    }
+
+    /// Return true if there are no readouts.
+    pub fn is_empty(&self) -> bool {
+        self.readouts.is_empty()
+    }
+}
+
+impl Program {
+    /// Collect the readouts performed by the program's instructions, as with [`ReadoutMap::new`].
+    pub fn get_readout_map(&self) -> ReadoutMap {
+        ReadoutMap::new(&self.instructions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::str::FromStr;
+
+    use crate::instruction::{MemoryReference, Qubit};
+    use crate::Program;
+
+    #[test]
+    fn readout_map() {
+        let program = Program::from_str(
+            "DECLARE ro BIT[2]
+DECLARE iq REAL[2]
+MEASURE 0 ro[0]
+MEASURE 1
+MEASURE 1 ro[1]
+CAPTURE 0 1 \"ro_rx\" flat(duration: 1e-6, iq: 1) iq[0]
+RAW-CAPTURE 2 \"ro_rx\" 1e-6 iq[1]
+MEASURE 2 ro[1]
+",
+        )
+        .unwrap();
+        let readouts = program.get_readout_map();
+        let reference = |name: &str, index| MemoryReference {
+            name: name.to_owned(),
+            index,
+        };
+
+        assert_eq!(readouts.len(), 5);
+        assert_eq!(
+            readouts
+                .iter()
+                .map(|readout| readout.instruction_index)
+                .collect::<Vec<_>>(),
+            vec![0, 2, 3, 4, 5]
+        );
+        assert_eq!(
+            readouts.get_qubits_for(&reference("ro", 1)),
+            HashSet::from([&Qubit::Fixed(1), &Qubit::Fixed(2)])
+        );
+        assert_eq!(
+            readouts.get_qubits_for(&reference("iq", 0)),
+            HashSet::from([&Qubit::Fixed(0), &Qubit::Fixed(1)])
+        );
+        assert!(readouts.get_qubits_for(&reference("ro", 2)).is_empty());
+        assert_eq!(
+            readouts.get_memory_references_for(&Qubit::Fixed(1)),
+            vec![&reference("ro", 1), &reference("iq", 0)]
+        );
+    }
+}
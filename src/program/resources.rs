@@ -0,0 +1,152 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+
+use crate::instruction::ScalarType;
+
+use super::{Program, ReadoutMap};
+
+/// The number of bytes taken by each waveform sample: a complex number of two 64-bit floats.
+pub const WAVEFORM_SAMPLE_BYTES: u64 = 16;
+
+/// The number of bytes taken by each element of a memory region of the given type. Each `BIT`
+/// takes a whole byte.
+fn element_bytes(data_type: &ScalarType) -> u64 {
+    match data_type {
+        ScalarType::Bit | ScalarType::Octet => 1,
+        ScalarType::Integer | ScalarType::Real => 8,
+    }
+}
+
+/// The resources which a program requires of the hardware which runs it, as computed by
+/// [`Program::resources`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProgramResources {
+    /// The number of bytes of classical memory taken by each declared region, by name.
+    pub memory_bytes: BTreeMap<String, u64>,
+
+    /// The number of readouts (`MEASURE` with a target, `CAPTURE`, and `RAW-CAPTURE`) written
+    /// into memory.
+    pub readout_values: usize,
+
+    /// The number of distinct qubits used.
+    pub qubit_count: usize,
+
+    /// The total number of samples among the program's `DEFWAVEFORM` definitions.
+    pub waveform_samples: u64,
+}
+
+impl ProgramResources {
+    /// Return the total number of bytes of classical memory declared.
+    pub fn total_memory_bytes(&self) -> u64 {
+        self.memory_bytes.values().sum()
+    }
+
+    /// Return the number of bytes of waveform memory taken by the program's `DEFWAVEFORM`
+    /// definitions, at [`WAVEFORM_SAMPLE_BYTES`] per sample.
+    pub fn waveform_bytes(&self) -> u64 {
+        self.waveform_samples * WAVEFORM_SAMPLE_BYTES
+    }
+}
+
+impl Program {
+    /// Estimate the resources which the program requires of the hardware which runs it, so that
+    /// it may be checked against the limits of that hardware before it is submitted.
+    ///
+    /// Waveform memory counts only waveforms defined with `DEFWAVEFORM`. Template waveforms, such
+    /// as `flat`, are generated by the control hardware and are not counted.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DECLARE ro BIT[2]\nDECLARE theta REAL\nRX(theta) 0\nMEASURE 0 ro[0]\nMEASURE 1 ro[1]",
+    /// )
+    /// .unwrap();
+    /// let resources = program.resources();
+    /// assert_eq!(resources.memory_bytes["theta"], 8);
+    /// assert_eq!(resources.total_memory_bytes(), 10);
+    /// assert_eq!(resources.readout_values, 2);
+    /// assert_eq!(resources.qubit_count, 2);
+    /// ```
+    pub fn resources(&self) -> ProgramResources {
+        ProgramResources {
+            memory_bytes: self
+                .memory_regions
+                .iter()
+                .map(|(name, region)| {
+                    (
+                        name.clone(),
+                        region.size.length * element_bytes(&region.size.data_type),
+                    )
+                })
+                .collect(),
+            readout_values: ReadoutMap::new(&self.instructions).iter().count(),
+            qubit_count: self.qubits_used().len(),
+            waveform_samples: self
+                .waveforms
+                .values()
+                .map(|waveform| waveform.matrix.len() as u64)
+                .sum(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::Program;
+
+    use super::ProgramResources;
+
+    #[test]
+    fn resources() {
+        let program = Program::from_str(
+            r#"DECLARE ro BIT[3]
+DECLARE iq REAL[2]
+DECLARE count INTEGER
+DECLARE bytes OCTET[5]
+DEFFRAME 0 "ro_rx":
+    SAMPLE-RATE: 1.0
+DEFWAVEFORM kernel:
+    1.0, 1.0, 1.0, 1.0
+DEFWAVEFORM pulse:
+    0.5, 0.5
+MEASURE 0 ro[0]
+MEASURE 1
+CAPTURE 0 "ro_rx" kernel iq[0]
+RX(pi) 2
+"#,
+        )
+        .unwrap();
+
+        let resources: ProgramResources = program.resources();
+        assert_eq!(
+            resources.memory_bytes.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("bytes".to_owned(), 5),
+                ("count".to_owned(), 8),
+                ("iq".to_owned(), 16),
+                ("ro".to_owned(), 3)
+            ]
+        );
+        assert_eq!(resources.readout_values, 2);
+        assert_eq!(resources.qubit_count, 3);
+        assert_eq!(resources.waveform_samples, 6);
+        assert_eq!(program.resources().waveform_bytes(), 96);
+    }
+}
@@ -0,0 +1,337 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+
+use thiserror::Error;
+
+use crate::expression::Expression;
+use crate::instruction::{Calibration, Gate, Instruction, MeasureCalibrationDefinition, Qubit};
+
+use super::{CalibrationSet, Program};
+
+/// A problem with the signature of a calibration, or with a gate which cannot be expanded against
+/// the calibrations of the same name. Calibrations are identified by their signature, such as
+/// `RX(%theta) q` or `MEASURE q addr`.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum CalibrationViolation {
+    #[error("calibration {calibration} binds %{variable} more than once")]
+    DuplicateVariable {
+        calibration: String,
+        variable: String,
+    },
+
+    #[error("calibration {calibration} binds qubit {qubit} more than once")]
+    DuplicateQubit { calibration: String, qubit: String },
+
+    #[error("calibration {calibration} uses %{variable}, which its signature does not bind")]
+    UnboundVariable {
+        calibration: String,
+        variable: String,
+    },
+
+    #[error("calibration {calibration} uses qubit {qubit}, which its signature does not bind")]
+    UnboundQubit { calibration: String, qubit: String },
+
+    #[error("instruction {location} ({instruction}) has {parameters} parameters and {qubits} qubits, but no calibration of the same name does: {}", .calibrations.join(", "))]
+    ArityMismatch {
+        /// The index of the instruction within [`Program::instructions`].
+        location: usize,
+        instruction: Instruction,
+        parameters: usize,
+        qubits: usize,
+        /// The signatures of the calibrations of the same name.
+        calibrations: Vec<String>,
+    },
+}
+
+/// Return the signature of a calibration: the line which introduces it, without `DEFCAL` or the
+/// trailing colon.
+fn signature(calibration: &Instruction) -> String {
+    let text = calibration.to_string();
+    let header = text.lines().next().unwrap_or_default();
+    header
+        .trim_start_matches("DEFCAL ")
+        .trim_end_matches(':')
+        .to_owned()
+}
+
+/// Return the names of the variables and variable qubits used directly by the instructions, in
+/// order of name.
+fn used_names(instructions: &[Instruction]) -> (BTreeSet<String>, BTreeSet<String>) {
+    let mut variables = BTreeSet::new();
+    let mut qubits = BTreeSet::new();
+    for instruction in instructions {
+        let mut instruction = instruction.clone();
+        instruction.apply_to_expressions(|expression| {
+            variables.extend(expression.variables().into_iter().map(str::to_owned))
+        });
+        for qubit in instruction.qubits() {
+            if let Qubit::Variable(name) = qubit {
+                qubits.insert(name.clone());
+            }
+        }
+    }
+    (variables, qubits)
+}
+
+/// Check the body of a calibration against the names bound by its signature, returning any
+/// violations in order of kind and then of name.
+fn check_signature<'a>(
+    calibration: String,
+    variables: impl IntoIterator<Item = &'a String>,
+    qubits: impl IntoIterator<Item = &'a String>,
+    body: &[Instruction],
+) -> Vec<CalibrationViolation> {
+    let mut violations = vec![];
+    let mut bound_variables = BTreeSet::new();
+    let mut bound_qubits = BTreeSet::new();
+
+    for variable in variables {
+        if !bound_variables.insert(variable.clone()) {
+            violations.push(CalibrationViolation::DuplicateVariable {
+                calibration: calibration.clone(),
+                variable: variable.clone(),
+            });
+        }
+    }
+    for qubit in qubits {
+        if !bound_qubits.insert(qubit.clone()) {
+            violations.push(CalibrationViolation::DuplicateQubit {
+                calibration: calibration.clone(),
+                qubit: qubit.clone(),
+            });
+        }
+    }
+
+    let (used_variables, used_qubits) = used_names(body);
+    for variable in used_variables.difference(&bound_variables) {
+        violations.push(CalibrationViolation::UnboundVariable {
+            calibration: calibration.clone(),
+            variable: variable.clone(),
+        });
+    }
+    for qubit in used_qubits.difference(&bound_qubits) {
+        violations.push(CalibrationViolation::UnboundQubit {
+            calibration: calibration.clone(),
+            qubit: qubit.clone(),
+        });
+    }
+
+    violations
+}
+
+impl CalibrationSet {
+    /// Check that the signature of every calibration in the set is consistent with itself and
+    /// with its body: no variable or variable qubit is bound more than once, and every variable
+    /// and variable qubit used within the body is bound by the signature. A `DEFCAL MEASURE`
+    /// binds only its qubit, if variable; its memory parameter names a memory reference rather
+    /// than a variable.
+    ///
+    /// Violations are reported in the order of [`CalibrationSet::to_instructions`].
+    pub fn validate_signatures(&self) -> Result<(), Vec<CalibrationViolation>> {
+        let mut violations = vec![];
+
+        for instruction in self.to_instructions() {
+            match &instruction {
+                Instruction::CalibrationDefinition(Calibration {
+                    instructions,
+                    parameters,
+                    qubits,
+                    ..
+                }) => {
+                    let variables = parameters.iter().filter_map(|parameter| match parameter {
+                        Expression::Variable(name) => Some(name),
+                        _ => None,
+                    });
+                    let qubits = qubits.iter().filter_map(|qubit| match qubit {
+                        Qubit::Variable(name) => Some(name),
+                        _ => None,
+                    });
+                    violations.extend(check_signature(
+                        signature(&instruction),
+                        variables,
+                        qubits,
+                        instructions,
+                    ));
+                }
+                Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+                    qubit,
+                    instructions,
+                    ..
+                }) => {
+                    let qubits = match qubit {
+                        Some(Qubit::Variable(name)) => Some(name),
+                        _ => None,
+                    };
+                    violations.extend(check_signature(
+                        signature(&instruction),
+                        None::<&String>,
+                        qubits,
+                        instructions,
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+impl Program {
+    /// Check the signatures of the program's calibrations as with
+    /// [`CalibrationSet::validate_signatures`], then check that every gate which shares its name
+    /// with a calibration has the number of parameters and qubits of at least one such
+    /// calibration, so that it can be expanded against them. Gates without any calibration of
+    /// the same name are not checked.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::program::CalibrationViolation;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DEFCAL RX(%theta) q:\n    SHIFT-PHASE q \"rf\" %phi\nRX(pi) 0 1\n",
+    /// )
+    /// .unwrap();
+    /// let violations = program.validate_calibrations().unwrap_err();
+    /// assert_eq!(
+    ///     violations[0],
+    ///     CalibrationViolation::UnboundVariable {
+    ///         calibration: "RX(%theta) q".to_owned(),
+    ///         variable: "phi".to_owned(),
+    ///     }
+    /// );
+    /// assert!(matches!(
+    ///     violations[1],
+    ///     CalibrationViolation::ArityMismatch { location: 0, parameters: 1, qubits: 2, .. }
+    /// ));
+    /// ```
+    pub fn validate_calibrations(&self) -> Result<(), Vec<CalibrationViolation>> {
+        let mut violations = match self.calibrations.validate_signatures() {
+            Ok(()) => vec![],
+            Err(violations) => violations,
+        };
+        let calibrations: Vec<(String, Calibration)> = self
+            .calibrations
+            .to_instructions()
+            .into_iter()
+            .filter_map(|instruction| {
+                let signature = signature(&instruction);
+                match instruction {
+                    Instruction::CalibrationDefinition(calibration) => {
+                        Some((signature, calibration))
+                    }
+                    _ => None,
+                }
+            })
+            .collect();
+
+        for (location, instruction) in self.instructions.iter().enumerate() {
+            if let Instruction::Gate(Gate {
+                name,
+                parameters,
+                qubits,
+                ..
+            }) = instruction
+            {
+                let named: Vec<&(String, Calibration)> = calibrations
+                    .iter()
+                    .filter(|(_, calibration)| &calibration.name == name)
+                    .collect();
+                let matches_arity = named.iter().any(|(_, calibration)| {
+                    calibration.parameters.len() == parameters.len()
+                        && calibration.qubits.len() == qubits.len()
+                });
+                if !named.is_empty() && !matches_arity {
+                    violations.push(CalibrationViolation::ArityMismatch {
+                        location,
+                        instruction: instruction.clone(),
+                        parameters: parameters.len(),
+                        qubits: qubits.len(),
+                        calibrations: named
+                            .into_iter()
+                            .map(|(signature, _)| signature.clone())
+                            .collect(),
+                    });
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::Program;
+
+    use super::CalibrationViolation;
+
+    #[test]
+    fn validate_calibrations() {
+        let program = Program::from_str(
+            r#"DEFCAL CZ(%a) q q:
+    SHIFT-PHASE q "rf" %a
+DEFCAL CZ p q:
+    FENCE p q
+DEFCAL MEASURE q addr:
+    CAPTURE r "ro" flat(duration: 1, iq: 1) addr
+CZ 0 1
+CZ(1) 0
+RX(1) 0
+"#,
+        )
+        .unwrap();
+        let violations = program.validate_calibrations().unwrap_err();
+        assert_eq!(
+            violations,
+            vec![
+                CalibrationViolation::DuplicateQubit {
+                    calibration: "CZ(%a) q q".to_owned(),
+                    qubit: "q".to_owned(),
+                },
+                CalibrationViolation::UnboundQubit {
+                    calibration: "MEASURE q addr".to_owned(),
+                    qubit: "r".to_owned(),
+                },
+                CalibrationViolation::ArityMismatch {
+                    location: 1,
+                    instruction: program.instructions[1].clone(),
+                    parameters: 1,
+                    qubits: 1,
+                    calibrations: vec!["CZ(%a) q q".to_owned(), "CZ p q".to_owned()],
+                },
+            ]
+        );
+
+        let program =
+            Program::from_str("DEFCAL RZ(%a, %a) 0:\n    SHIFT-PHASE 0 \"rf\" %a\n").unwrap();
+        assert!(matches!(
+            program.validate_calibrations().unwrap_err().as_slice(),
+            [CalibrationViolation::DuplicateVariable { variable, .. }] if variable == "a"
+        ));
+    }
+}
@@ -0,0 +1,218 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::ops::Range;
+
+use crate::parser::{
+    lex_with_policy, parse_instructions_with_tokens, IdentifierPolicy, Token, TokenWithLocation,
+};
+
+use super::{disallow_leftover, map_parsed, Program, ProgramError, Result};
+
+/// The location within source text from which an instruction was parsed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// The line on which the instruction begins, counting from 1.
+    pub line: u32,
+
+    /// The column at which the instruction begins, counting from 1.
+    pub column: usize,
+
+    /// The line on which the instruction ends. This differs from `line` for an instruction with
+    /// an indented body, such as `DEFCIRCUIT`.
+    pub end_line: u32,
+
+    /// The range of byte offsets within the source text which the instruction occupies.
+    pub span: Range<usize>,
+}
+
+impl SourceLocation {
+    /// Return the location spanned by the given tokens, ignoring any surrounding whitespace and
+    /// comments, or `None` if there are no other tokens.
+    fn from_tokens(tokens: &[TokenWithLocation]) -> Option<Self> {
+        let is_significant = |token: &&TokenWithLocation| {
+            !matches!(
+                token.as_token(),
+                Token::Comment(_) | Token::Indentation | Token::NewLine | Token::Semicolon
+            )
+        };
+        let first = tokens.iter().find(is_significant)?;
+        let last = tokens.iter().rev().find(is_significant)?;
+
+        Some(Self {
+            line: first.line(),
+            column: first.column(),
+            end_line: last.line(),
+            span: first.span().start..last.span().end,
+        })
+    }
+}
+
+/// A map from the index of each of a program's [instructions](Program::instructions) to the
+/// location in source text from which it was parsed, so that problems found later, such as by
+/// [`Program::validate_labels`], may be reported against the original source.
+///
+/// Instructions which were not parsed from source, such as those added by a transformation, have
+/// no location. Header definitions, which a [`Program`] stores apart from its instructions, are
+/// not mapped.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap {
+    locations: Vec<Option<SourceLocation>>,
+}
+
+impl SourceMap {
+    /// Return the location of the instruction at the given index, if it is known.
+    pub fn get(&self, index: usize) -> Option<&SourceLocation> {
+        self.locations.get(index).and_then(Option::as_ref)
+    }
+
+    /// Iterate through the indices of instructions which have known locations, in order, along
+    /// with those locations.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &SourceLocation)> {
+        self.locations
+            .iter()
+            .enumerate()
+            .filter_map(|(index, location)| Some((index, location.as_ref()?)))
+    }
+
+    /// Return the number of instructions covered by the map, whether or not their locations are
+    /// known.
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    /// Return true if the map covers no instructions.
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// Record the location of an instruction appended to the program.
+    pub fn push(&mut self, location: Option<SourceLocation>) {
+        self.locations.push(location)
+    }
+
+    /// Record the location of an instruction inserted into the program at the given index,
+    /// shifting the locations of later instructions to match.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, location: Option<SourceLocation>) {
+        self.locations.insert(index, location)
+    }
+
+    /// Remove the location of the instruction at the given index, shifting the locations of later
+    /// instructions to match, and return it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn remove(&mut self, index: usize) -> Option<SourceLocation> {
+        self.locations.remove(index)
+    }
+}
+
+impl Program {
+    /// Parse a program from a string as with [`Program::from_str`](std::str::FromStr), also
+    /// returning the location from which each of its instructions was parsed.
+    ///
+    /// ```rust
+    /// use quil_rs::Program;
+    ///
+    /// let source = "DECLARE ro BIT\n# Entangle\nH 0\nCNOT 0 1\n";
+    /// let (program, source_map) = Program::parse_with_source_map(source).unwrap();
+    /// let location = source_map.get(1).unwrap();
+    /// assert_eq!(location.line, 4);
+    /// assert_eq!(&source[location.span.clone()], "CNOT 0 1");
+    /// ```
+    pub fn parse_with_source_map(s: &str) -> Result<(Self, SourceMap)> {
+        let lexed = lex_with_policy(s, IdentifierPolicy::Strict).map_err(ProgramError::from)?;
+        map_parsed(
+            disallow_leftover(parse_instructions_with_tokens(&lexed)),
+            |instructions| {
+                let mut program = Self::new();
+                let mut source_map = SourceMap::default();
+                for (instruction, tokens) in instructions {
+                    let count = program.instructions.len();
+                    program.add_instruction(instruction);
+                    if program.instructions.len() > count {
+                        source_map.push(SourceLocation::from_tokens(tokens));
+                    }
+                }
+                (program, source_map)
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Program;
+
+    use super::{SourceLocation, SourceMap};
+
+    const SOURCE: &str = "DECLARE ro BIT
+DEFCIRCUIT BELL a b:
+    H a
+    CNOT a b
+
+# Comment
+LABEL @start; BELL 0 1
+MEASURE 0 ro # Trailing comment
+";
+
+    #[test]
+    fn parse_with_source_map() {
+        let (program, source_map) = Program::parse_with_source_map(SOURCE).unwrap();
+        assert_eq!(program.instructions.len(), 4);
+        assert_eq!(source_map.len(), 4);
+
+        let snippets: Vec<(u32, u32, &str)> = source_map
+            .iter()
+            .map(|(_, location)| {
+                (
+                    location.line,
+                    location.end_line,
+                    &SOURCE[location.span.clone()],
+                )
+            })
+            .collect();
+        assert_eq!(
+            snippets,
+            vec![
+                (2, 4, "DEFCIRCUIT BELL a b:\n    H a\n    CNOT a b"),
+                (7, 7, "LABEL @start"),
+                (7, 7, "BELL 0 1"),
+                (8, 8, "MEASURE 0 ro"),
+            ]
+        );
+        assert_eq!(source_map.get(2).unwrap().column, 15);
+        assert_eq!(source_map.get(4), None);
+    }
+
+    #[test]
+    fn update_source_map() {
+        let (_, mut source_map) = Program::parse_with_source_map(SOURCE).unwrap();
+        let measurement = source_map.get(3).cloned();
+
+        source_map.insert(0, None);
+        assert_eq!(source_map.get(0), None);
+        assert_eq!(source_map.get(4), measurement.as_ref());
+
+        let removed: Option<SourceLocation> = source_map.remove(1);
+        assert_eq!(removed.unwrap().line, 2);
+        assert_eq!(source_map.get(3), measurement.as_ref());
+        assert_ne!(source_map, SourceMap::default());
+    }
+}
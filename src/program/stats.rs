@@ -0,0 +1,144 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::instruction::{Instruction, Measurement, Qubit};
+
+use super::Program;
+
+/// Summary statistics about the gates and measurements within a program, as computed by
+/// [`Program::stats`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProgramStats {
+    /// The number of times each gate is applied, by name, regardless of its modifiers.
+    pub gate_counts: BTreeMap<String, usize>,
+
+    /// The number of gates applied to a single qubit.
+    pub single_qubit_gates: usize,
+
+    /// The number of gates applied to more than one qubit.
+    pub multi_qubit_gates: usize,
+
+    /// The number of `MEASURE` instructions.
+    pub measurements: usize,
+
+    /// For each qubit, the number of layers of gates and measurements up to and including the
+    /// last one which acts on that qubit.
+    pub qubit_depths: HashMap<Qubit, usize>,
+
+    /// The depth of the program as a whole: the greatest depth of any qubit.
+    pub depth: usize,
+}
+
+impl Program {
+    /// Compute statistics about the gates and measurements among the program's instructions.
+    ///
+    /// Depth is computed by placing each gate or measurement in the layer after the last one
+    /// occupied on any of its qubits. Other instructions, including control flow, are ignored, so
+    /// the depth of a program with loops is that of a single pass through its instructions.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::{instruction::Qubit, Program};
+    ///
+    /// let program = Program::from_str("H 0\nH 1\nCNOT 0 1\nX 2\nMEASURE 0").unwrap();
+    /// let stats = program.stats();
+    /// assert_eq!(stats.gate_counts["H"], 2);
+    /// assert_eq!(stats.multi_qubit_gates, 1);
+    /// assert_eq!(stats.qubit_depths[&Qubit::Fixed(2)], 1);
+    /// assert_eq!(stats.depth, 3);
+    /// ```
+    pub fn stats(&self) -> ProgramStats {
+        let mut stats = ProgramStats::default();
+
+        for instruction in &self.instructions {
+            let qubits = match instruction {
+                Instruction::Gate(gate) => {
+                    *stats.gate_counts.entry(gate.name.clone()).or_default() += 1;
+                    if gate.qubits.len() > 1 {
+                        stats.multi_qubit_gates += 1;
+                    } else {
+                        stats.single_qubit_gates += 1;
+                    }
+                    gate.qubits.iter().collect()
+                }
+                Instruction::Measurement(Measurement { qubit, .. }) => {
+                    stats.measurements += 1;
+                    vec![qubit]
+                }
+                _ => continue,
+            };
+
+            let layer = 1 + qubits
+                .iter()
+                .filter_map(|qubit| stats.qubit_depths.get(*qubit))
+                .max()
+                .copied()
+                .unwrap_or(0);
+            for qubit in qubits {
+                stats.qubit_depths.insert(qubit.clone(), layer);
+            }
+            stats.depth = stats.depth.max(layer);
+        }
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::instruction::Qubit;
+    use crate::Program;
+
+    #[test]
+    fn stats() {
+        let program = Program::from_str(
+            "DECLARE ro BIT[2]
+RX(pi/2) 0
+RX(pi/2) 1
+DAGGER RX(pi/2) 2
+CZ 0 1
+CONTROLLED RX(pi) 3 2
+RESET
+MEASURE 0 ro[0]
+MEASURE 3 ro[1]
+",
+        )
+        .unwrap();
+        let stats = program.stats();
+
+        assert_eq!(
+            stats.gate_counts.clone().into_iter().collect::<Vec<_>>(),
+            vec![("CZ".to_owned(), 1), ("RX".to_owned(), 4)]
+        );
+        assert_eq!(stats.single_qubit_gates, 3);
+        assert_eq!(stats.multi_qubit_gates, 2);
+        assert_eq!(stats.measurements, 2);
+
+        let depths: Vec<usize> = (0..5)
+            .map(|index| {
+                stats
+                    .qubit_depths
+                    .get(&Qubit::Fixed(index))
+                    .copied()
+                    .unwrap_or(0)
+            })
+            .collect();
+        assert_eq!(depths, vec![3, 2, 2, 3, 0]);
+        assert_eq!(stats.depth, 3);
+    }
+}
@@ -0,0 +1,269 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{HashMap, HashSet};
+
+use num_complex::Complex64;
+
+use crate::expression::{EvaluationError, Expression};
+
+use super::Program;
+
+/// The subexpressions shared among the instructions of a program, as found by
+/// [`Program::common_subexpressions`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommonSubexpressions {
+    /// Each shared subexpression, along with the name of the variable which stands in for it. A
+    /// subexpression may refer to the variables of those before it, but never after it.
+    pub temporaries: Vec<(String, Expression)>,
+    /// The program, with every shared subexpression within its instructions replaced by the
+    /// variable standing in for it.
+    pub program: Program,
+}
+
+impl CommonSubexpressions {
+    /// Evaluate each temporary in turn, adding its value to `variables` so that those of the
+    /// rewritten program may then be evaluated with the same `variables`.
+    pub fn evaluate_temporaries(
+        &self,
+        variables: &mut HashMap<String, Complex64>,
+        memory_references: &HashMap<&str, Vec<f64>>,
+    ) -> Result<(), EvaluationError> {
+        for (name, expression) in &self.temporaries {
+            let value = expression.evaluate(variables, memory_references)?;
+            variables.insert(name.clone(), value);
+        }
+        Ok(())
+    }
+}
+
+/// Whether an expression is worth sharing: it must be an operation, rather than a lone leaf,
+/// and depend on some variable or memory reference, since constants are better simplified away.
+fn is_shareable(expression: &Expression) -> bool {
+    use Expression::*;
+
+    match expression {
+        Address(_) | Number(_) | PiConstant | Variable(_) => false,
+        FunctionCall { .. } | Infix { .. } | Prefix { .. } => expression
+            .iter()
+            .any(|node| matches!(node, Address(_) | Variable(_))),
+    }
+}
+
+/// The direct subexpressions of an expression.
+fn children(expression: &Expression) -> Vec<&Expression> {
+    match expression {
+        Expression::FunctionCall { expression, .. } | Expression::Prefix { expression, .. } => {
+            vec![expression]
+        }
+        Expression::Infix { left, right, .. } => vec![left, right],
+        _ => vec![],
+    }
+}
+
+/// Count the occurrences of each shareable subexpression. Those within a subexpression which was
+/// already counted are counted only the first time, so that a subexpression is only shared if
+/// it appears in more than one distinct context.
+fn count_occurrences(expression: &Expression, counts: &mut HashMap<Expression, usize>) {
+    if !is_shareable(expression) {
+        return;
+    }
+    let count = counts.entry(expression.clone()).or_insert(0);
+    *count += 1;
+    if *count == 1 {
+        for child in children(expression) {
+            count_occurrences(child, counts);
+        }
+    }
+}
+
+struct Sharing {
+    counts: HashMap<Expression, usize>,
+    names: HashMap<Expression, String>,
+    temporaries: Vec<(String, Expression)>,
+    used_names: HashSet<String>,
+}
+
+impl Sharing {
+    /// Replace every shared subexpression with its variable, from the top down.
+    fn share(&mut self, expression: Expression) -> Expression {
+        if self.counts.get(&expression).copied().unwrap_or(0) < 2 {
+            return self.share_children(expression);
+        }
+        if let Some(name) = self.names.get(&expression) {
+            return Expression::Variable(name.clone());
+        }
+
+        let key = expression.clone();
+        // Any temporaries within this one are defined first.
+        let definition = self.share_children(expression);
+        let name = self.next_name();
+        self.temporaries.push((name.clone(), definition));
+        self.names.insert(key, name.clone());
+        Expression::Variable(name)
+    }
+
+    fn share_children(&mut self, expression: Expression) -> Expression {
+        use Expression::*;
+
+        match expression {
+            FunctionCall {
+                function,
+                expression,
+            } => FunctionCall {
+                function,
+                expression: Box::new(self.share(*expression)),
+            },
+            Infix {
+                left,
+                operator,
+                right,
+            } => {
+                let left = Box::new(self.share(*left));
+                let right = Box::new(self.share(*right));
+                Infix {
+                    left,
+                    operator,
+                    right,
+                }
+            }
+            Prefix {
+                operator,
+                expression,
+            } => Prefix {
+                operator,
+                expression: Box::new(self.share(*expression)),
+            },
+            leaf => leaf,
+        }
+    }
+
+    /// A variable name not otherwise used in the program.
+    fn next_name(&mut self) -> String {
+        let mut index = self.temporaries.len();
+        loop {
+            let name = format!("cse{}", index);
+            if self.used_names.insert(name.clone()) {
+                return name;
+            }
+            index += 1;
+        }
+    }
+}
+
+impl Program {
+    /// Find the subexpressions which appear more than once among the expressions of this
+    /// program's instructions, such as gate parameters, and replace each of them with a variable
+    /// standing in for it. Evaluating each shared subexpression once, with
+    /// [`CommonSubexpressions::evaluate_temporaries`], then saves evaluating it everywhere it
+    /// appears.
+    ///
+    /// Only subexpressions which depend on a variable or memory reference are shared. Those in
+    /// calibrations and other definitions are left alone.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use quil_rs::{expression::Expression, Program};
+    /// use std::str::FromStr;
+    ///
+    /// let input = "RX(theta[0]/2) 0\nRZ(-(theta[0]/2)) 1\nRY(pi/2) 0";
+    /// let shared = Program::from_str(input).unwrap().common_subexpressions();
+    ///
+    /// assert_eq!(
+    ///     shared.temporaries,
+    ///     vec![("cse0".to_owned(), Expression::from_str("theta[0]/2").unwrap())]
+    /// );
+    /// assert_eq!(
+    ///     shared.program.to_string(false),
+    ///     "RX(%cse0) 0\nRZ(-%cse0) 1\nRY(pi / 2) 0\n"
+    /// );
+    /// ```
+    pub fn common_subexpressions(&self) -> CommonSubexpressions {
+        let mut instructions = self.instructions.clone();
+
+        let mut counts = HashMap::new();
+        let mut used_names = HashSet::new();
+        for instruction in &mut instructions {
+            instruction.apply_to_expressions(|expression| {
+                count_occurrences(expression, &mut counts);
+                used_names.extend(expression.variables().into_iter().map(String::from));
+            });
+        }
+
+        let mut sharing = Sharing {
+            counts,
+            names: HashMap::new(),
+            temporaries: vec![],
+            used_names,
+        };
+        for instruction in &mut instructions {
+            instruction.apply_to_expressions(|expression| {
+                let original = std::mem::replace(expression, Expression::PiConstant);
+                *expression = sharing.share(original);
+            });
+        }
+
+        let mut program = self.clone();
+        program.instructions = instructions;
+        CommonSubexpressions {
+            temporaries: sharing.temporaries,
+            program,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::str::FromStr;
+
+    use num_complex::Complex64;
+
+    use crate::{expression::Expression, Program};
+
+    #[test]
+    fn nested_subexpressions() {
+        let input = "RX(cos(%a*%b) + 1) 0
+RX(cos(%a*%b) + 1) 1
+RY(sin(%b*%a)) 0
+RZ(%cse0) 0
+RZ(%a*2) 1";
+        let shared = Program::from_str(input).unwrap().common_subexpressions();
+
+        // `%a*%b` is shared with `%b*%a`, but `cos(%a*%b)` only appears within the shared sum,
+        // and `cse0` is already in use.
+        let temporary =
+            |name: &str, expression| (name.to_owned(), Expression::from_str(expression).unwrap());
+        assert_eq!(
+            shared.temporaries,
+            vec![
+                temporary("cse1", "%a*%b"),
+                temporary("cse2", "cos(%cse1)+1")
+            ]
+        );
+        assert_eq!(
+            shared.program.to_string(false),
+            "RX(%cse2) 0\nRX(%cse2) 1\nRY(sin(%cse1)) 0\nRZ(%cse0) 0\nRZ(%a * 2) 1\n"
+        );
+
+        let mut variables = HashMap::new();
+        variables.insert("a".to_owned(), Complex64::from(2.0));
+        variables.insert("b".to_owned(), Complex64::from(0.0));
+        shared
+            .evaluate_temporaries(&mut variables, &HashMap::new())
+            .unwrap();
+        assert_eq!(variables["cse2"], Complex64::from(2.0));
+    }
+}
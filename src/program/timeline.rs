@@ -0,0 +1,545 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::instruction::{Capture, FrameIdentifier, Instruction, Pulse, Qubit};
+
+use super::graph::{ScheduleResult, ScheduledProgram};
+use super::Program;
+
+/// The kind of instruction which produced a [`TimelineEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum TimelineEventKind {
+    Pulse,
+    Capture,
+    RawCapture,
+    Delay,
+    Fence,
+    SetFrequency,
+    ShiftFrequency,
+    SetPhase,
+    ShiftPhase,
+    SwapPhases,
+    SetScale,
+}
+
+impl TimelineEventKind {
+    fn of(instruction: &Instruction) -> Option<Self> {
+        Some(match instruction {
+            Instruction::Pulse(_) => Self::Pulse,
+            Instruction::Capture(_) => Self::Capture,
+            Instruction::RawCapture(_) => Self::RawCapture,
+            Instruction::Delay(_) => Self::Delay,
+            Instruction::Fence(_) => Self::Fence,
+            Instruction::SetFrequency(_) => Self::SetFrequency,
+            Instruction::ShiftFrequency(_) => Self::ShiftFrequency,
+            Instruction::SetPhase(_) => Self::SetPhase,
+            Instruction::ShiftPhase(_) => Self::ShiftPhase,
+            Instruction::SwapPhases(_) => Self::SwapPhases,
+            Instruction::SetScale(_) => Self::SetScale,
+            _ => return None,
+        })
+    }
+}
+
+/// One instruction's use of a frame, placed in time.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TimelineEvent {
+    /// The time at which the instruction starts, in seconds since the start of the program.
+    pub start: f64,
+    /// The duration of the instruction, in seconds.
+    pub duration: f64,
+    pub kind: TimelineEventKind,
+    /// The waveform played or captured with, as it is written in Quil, for pulses and captures.
+    pub waveform: Option<String>,
+    /// The instruction, as it is written in Quil.
+    pub instruction: String,
+    /// The label of the block which contains the instruction.
+    pub block: String,
+    /// The index of the instruction within its block.
+    pub index: usize,
+}
+
+/// The events on a single frame, in order of their start times.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FrameTimeline {
+    pub frame: FrameIdentifier,
+    pub events: Vec<TimelineEvent>,
+}
+
+/// The events on every frame of a program, for plotting its pulse sequence.
+#[derive(Clone, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Timeline {
+    /// The timeline of each frame which has any events, ordered by frame.
+    pub frames: Vec<FrameTimeline>,
+    /// The total duration of the program, in seconds.
+    pub duration: f64,
+}
+
+impl TimelineEventKind {
+    /// The character with which events of this kind are drawn by [`Timeline::to_ascii`].
+    fn symbol(self) -> char {
+        match self {
+            Self::Pulse => 'P',
+            Self::Capture => 'C',
+            Self::RawCapture => 'R',
+            Self::Delay => '.',
+            Self::Fence => ':',
+            Self::SetFrequency
+            | Self::ShiftFrequency
+            | Self::SetPhase
+            | Self::ShiftPhase
+            | Self::SwapPhases
+            | Self::SetScale => '^',
+        }
+    }
+}
+
+impl Timeline {
+    /// Draw the timeline as text, with one row per frame and the program's duration divided
+    /// evenly among `width` columns, followed by an axis giving the duration. Each event is drawn
+    /// across the columns it overlaps with a character for its kind: `P` for a pulse, `C` for a
+    /// capture, `R` for a raw capture, and `.` for a delay. Instantaneous events are drawn in the
+    /// column in which they occur, if it is not otherwise used: `:` for a fence and `^` for any
+    /// update of the frame's phase, frequency, or scale.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::program::graph::ScheduledProgram;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DEFFRAME 0 \"rf\":
+    ///     SAMPLE-RATE: 1e9
+    /// DEFFRAME 1 \"rf\":
+    ///     SAMPLE-RATE: 1e9
+    /// PULSE 0 \"rf\" flat(duration: 1e-6, iq: 1)
+    /// DELAY 1 \"rf\" 5e-7
+    /// PULSE 1 \"rf\" flat(duration: 5e-7, iq: 1)
+    /// ",
+    /// )
+    /// .unwrap();
+    /// let scheduled_program = ScheduledProgram::from_program(&program).unwrap();
+    /// let timeline = scheduled_program.get_timeline(&program).unwrap();
+    /// assert_eq!(
+    ///     timeline.to_ascii(8),
+    ///     concat!(
+    ///         "0 \"rf\" |PPPPPPPP|\n",
+    ///         "1 \"rf\" |....PPPP|\n",
+    ///         "       0      1us\n",
+    ///     )
+    /// );
+    /// ```
+    pub fn to_ascii(&self, width: usize) -> String {
+        let width = width.max(1);
+        let labels: Vec<String> = self
+            .frames
+            .iter()
+            .map(|timeline| timeline.frame.to_string())
+            .collect();
+        let label_width = labels
+            .iter()
+            .map(|label| label.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        // The column in which the given time falls, rounded down or up.
+        let column = |time: f64, round: fn(f64) -> f64| -> usize {
+            if self.duration > 0.0 {
+                (round(time / self.duration * width as f64).max(0.0) as usize).min(width)
+            } else {
+                0
+            }
+        };
+
+        let mut diagram = String::new();
+        for (label, timeline) in labels.iter().zip(&self.frames) {
+            let mut row = vec![' '; width];
+            for event in &timeline.events {
+                let symbol = event.kind.symbol();
+                let start = column(event.start, f64::floor).min(width - 1);
+                let end = column(event.start + event.duration, f64::ceil).max(start + 1);
+                let instantaneous = event.duration == 0.0;
+                for cell in &mut row[start..end] {
+                    // Delays and instantaneous events never hide anything else.
+                    if *cell == ' ' || !(instantaneous || event.kind == TimelineEventKind::Delay) {
+                        *cell = symbol;
+                    }
+                }
+            }
+            diagram.push_str(&format!(
+                "{:width$} |{}|\n",
+                label,
+                row.into_iter().collect::<String>(),
+                width = label_width
+            ));
+        }
+
+        diagram.push_str(&format!(
+            "{:label_width$} 0{:>width$}\n",
+            "",
+            format_seconds(self.duration),
+            label_width = label_width,
+            width = width + 1
+        ));
+        diagram
+    }
+}
+
+/// Format a non-negative number of seconds briefly, in the largest of seconds, milliseconds,
+/// microseconds, or nanoseconds in which it is at least one.
+fn format_seconds(seconds: f64) -> String {
+    let (value, unit) = if seconds == 0.0 || seconds >= 1.0 {
+        (seconds, "s")
+    } else if seconds >= 1e-3 {
+        (seconds * 1e3, "ms")
+    } else if seconds >= 1e-6 {
+        (seconds * 1e6, "us")
+    } else {
+        (seconds * 1e9, "ns")
+    };
+    let value = format!("{:.3}", value);
+    let value = value.trim_end_matches('0').trim_end_matches('.');
+    format!("{}{}", value, unit)
+}
+
+/// The version of the schema written by `ScheduledProgram::to_json`, with the `serde` feature,
+/// which is incremented whenever it changes in a way which existing readers would not understand.
+pub const SCHEDULE_JSON_VERSION: u32 = 1;
+
+/// The events on every frame of a single qubit, in order of their start times.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct QubitTimeline {
+    pub qubit: Qubit,
+    /// The events on any of the qubit's frames, each appearing once however many of those frames
+    /// it uses.
+    pub events: Vec<TimelineEvent>,
+}
+
+/// The schedule of a program, as written by `ScheduledProgram::to_json`, with the `serde` feature,
+/// for rendering as a pulse diagram.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScheduleExport {
+    /// The version of the schema, [`SCHEDULE_JSON_VERSION`] when written by this crate.
+    pub version: u32,
+    /// The total duration of the program, in seconds.
+    pub duration: f64,
+    /// The timeline of each frame which has any events, ordered by frame.
+    pub frames: Vec<FrameTimeline>,
+    /// The timeline of each qubit with a frame which has any events, ordered by qubit.
+    pub qubits: Vec<QubitTimeline>,
+}
+
+impl From<Timeline> for ScheduleExport {
+    fn from(timeline: Timeline) -> Self {
+        let mut events: BTreeMap<String, (Qubit, Vec<TimelineEvent>)> = BTreeMap::new();
+        let mut seen: HashSet<(Qubit, String, usize)> = HashSet::new();
+        for frame in &timeline.frames {
+            for qubit in &frame.frame.qubits {
+                for event in &frame.events {
+                    if seen.insert((qubit.clone(), event.block.clone(), event.index)) {
+                        events
+                            .entry(qubit.to_string())
+                            .or_insert_with(|| (qubit.clone(), vec![]))
+                            .1
+                            .push(event.clone());
+                    }
+                }
+            }
+        }
+
+        let qubits = events
+            .into_values()
+            .map(|(qubit, mut events)| {
+                events.sort_by(|a, b| {
+                    a.start
+                        .partial_cmp(&b.start)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                QubitTimeline { qubit, events }
+            })
+            .collect();
+
+        Self {
+            version: SCHEDULE_JSON_VERSION,
+            duration: timeline.duration,
+            frames: timeline.frames,
+            qubits,
+        }
+    }
+}
+
+impl ScheduledProgram {
+    /// Lay out the frame-level instructions of the program in time, frame by frame. Each
+    /// instruction appears on every frame it uses, as found by
+    /// [`Program::get_frames_for_instruction`], at the time given by
+    /// [`InstructionBlock::get_time_windows`](super::graph::InstructionBlock::get_time_windows).
+    /// Blocks follow one another in order, as if the program were executed straight through.
+    ///
+    /// The result may be serialized, such as to JSON, for plotting.
+    pub fn get_timeline(&self, program: &Program) -> ScheduleResult<Timeline> {
+        let mut events: HashMap<&FrameIdentifier, Vec<TimelineEvent>> = HashMap::new();
+        let mut offset = 0.0;
+
+        for (label, block) in &self.blocks {
+            let windows = block.get_time_windows(program)?;
+            for (index, (instruction, window)) in
+                block.instructions.iter().zip(windows.iter()).enumerate()
+            {
+                let kind = match TimelineEventKind::of(instruction) {
+                    Some(kind) => kind,
+                    None => continue,
+                };
+                let waveform = match instruction {
+                    Instruction::Pulse(Pulse { waveform, .. })
+                    | Instruction::Capture(Capture { waveform, .. }) => Some(waveform.to_string()),
+                    _ => None,
+                };
+                let event = TimelineEvent {
+                    start: offset + window.start,
+                    duration: window.duration(),
+                    kind,
+                    waveform,
+                    instruction: instruction.to_string(),
+                    block: label.clone(),
+                    index,
+                };
+                for frame in program
+                    .get_frames_for_instruction(instruction, false)
+                    .unwrap_or_default()
+                {
+                    events.entry(frame).or_default().push(event.clone());
+                }
+            }
+            offset += windows.iter().map(|window| window.end).fold(0.0, f64::max);
+        }
+
+        let mut frames: Vec<FrameTimeline> = events
+            .into_iter()
+            .map(|(frame, mut events)| {
+                // Blocks are laid out in order, so a stable sort keeps ties in program order.
+                events.sort_by(|a, b| {
+                    a.start
+                        .partial_cmp(&b.start)
+                        .unwrap_or(std::cmp::Ordering::Equal)
+                });
+                FrameTimeline {
+                    frame: frame.clone(),
+                    events,
+                }
+            })
+            .collect();
+        frames.sort_by_cached_key(|timeline| timeline.frame.to_string());
+
+        Ok(Timeline {
+            frames,
+            duration: offset,
+        })
+    }
+
+    /// Write the schedule of the program as JSON, for rendering as a Gantt-style pulse diagram.
+    /// The events of [`ScheduledProgram::get_timeline`] are grouped both by frame and by qubit,
+    /// following the schema of [`ScheduleExport`], whose `version` is [`SCHEDULE_JSON_VERSION`].
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::program::graph::ScheduledProgram;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DEFFRAME 0 \"rf\":\n    SAMPLE-RATE: 1e9\nPULSE 0 \"rf\" flat(duration: 1e-6, iq: 1)",
+    /// )
+    /// .unwrap();
+    /// let scheduled_program = ScheduledProgram::from_program(&program).unwrap();
+    /// let json = scheduled_program.to_json(&program).unwrap();
+    /// assert!(json.starts_with("{\"version\":1,"));
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self, program: &Program) -> ScheduleResult<String> {
+        let export = ScheduleExport::from(self.get_timeline(program)?);
+        Ok(serde_json::to_string(&export).expect("a schedule export is always valid JSON"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::program::graph::ScheduledProgram;
+    use crate::Program;
+
+    use super::{format_seconds, ScheduleExport, TimelineEventKind};
+
+    #[test]
+    fn timeline() {
+        let program = Program::from_str(
+            "DEFFRAME 0 \"rf\":
+    SAMPLE-RATE: 1.0
+DEFFRAME 0 \"ro\":
+    SAMPLE-RATE: 1.0
+DEFFRAME 1 \"rf\":
+    SAMPLE-RATE: 1.0
+PULSE 0 \"rf\" flat(duration: 2.0, iq: 1)
+SHIFT-PHASE 1 \"rf\" 0.5
+FENCE 0
+LABEL @second
+NONBLOCKING PULSE 0 \"ro\" flat(duration: 1.0, iq: 1)
+",
+        )
+        .unwrap();
+        let scheduled_program = ScheduledProgram::from_program(&program).unwrap();
+        let timeline = scheduled_program.get_timeline(&program).unwrap();
+        assert_eq!(timeline.duration, 3.0);
+
+        type Event = (f64, f64, TimelineEventKind);
+        let summary: Vec<(String, Vec<Event>)> = timeline
+            .frames
+            .iter()
+            .map(|frame| {
+                (
+                    frame.frame.to_string(),
+                    frame
+                        .events
+                        .iter()
+                        .map(|event| (event.start, event.duration, event.kind))
+                        .collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (
+                    "0 \"rf\"".to_owned(),
+                    vec![
+                        (0.0, 2.0, TimelineEventKind::Pulse),
+                        (2.0, 0.0, TimelineEventKind::Fence)
+                    ]
+                ),
+                (
+                    "0 \"ro\"".to_owned(),
+                    vec![
+                        (2.0, 0.0, TimelineEventKind::Fence),
+                        (2.0, 1.0, TimelineEventKind::Pulse)
+                    ]
+                ),
+                (
+                    "1 \"rf\"".to_owned(),
+                    vec![(0.0, 0.0, TimelineEventKind::ShiftPhase)]
+                ),
+            ]
+        );
+
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_value(&timeline).unwrap();
+            let event = &json["frames"][0]["events"][0];
+            assert_eq!(event["kind"], "pulse");
+            assert_eq!(event["waveform"], "flat(duration: 2, iq: 1)");
+            assert_eq!(event["block"], "block_0");
+            assert_eq!(json["frames"][1]["events"][1]["block"], "second");
+        }
+    }
+
+    #[test]
+    fn export() {
+        let program = Program::from_str(
+            "DEFFRAME 0 \"rf\":
+    SAMPLE-RATE: 1.0
+DEFFRAME 0 1 \"cz\":
+    SAMPLE-RATE: 1.0
+DEFFRAME 1 \"rf\":
+    SAMPLE-RATE: 1.0
+PULSE 0 1 \"cz\" flat(duration: 2.0, iq: 1)
+PULSE 0 \"rf\" flat(duration: 1.0, iq: 1)
+FENCE 0 1
+",
+        )
+        .unwrap();
+        let scheduled_program = ScheduledProgram::from_program(&program).unwrap();
+        let export = ScheduleExport::from(scheduled_program.get_timeline(&program).unwrap());
+
+        let summary: Vec<(String, Vec<usize>)> = export
+            .qubits
+            .iter()
+            .map(|qubit| {
+                (
+                    qubit.qubit.to_string(),
+                    qubit.events.iter().map(|event| event.index).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("0".to_owned(), vec![0, 1, 2]),
+                ("1".to_owned(), vec![0, 2]),
+            ]
+        );
+
+        #[cfg(feature = "serde")]
+        {
+            let json: serde_json::Value =
+                serde_json::from_str(&scheduled_program.to_json(&program).unwrap()).unwrap();
+            assert_eq!(json["version"], 1);
+            assert_eq!(json["duration"], 3.0);
+            assert_eq!(json["qubits"][1]["events"][1]["kind"], "fence");
+        }
+    }
+
+    #[test]
+    fn ascii() {
+        let program = Program::from_str(
+            "DEFFRAME 0 \"rf\":
+    SAMPLE-RATE: 1.0
+DEFFRAME 0 \"ro\":
+    SAMPLE-RATE: 1.0
+DEFFRAME 1 \"rf\":
+    SAMPLE-RATE: 1.0
+PULSE 0 \"rf\" flat(duration: 2.0, iq: 1)
+SHIFT-PHASE 1 \"rf\" 0.5
+FENCE 0
+LABEL @second
+NONBLOCKING PULSE 0 \"ro\" flat(duration: 1.0, iq: 1)
+",
+        )
+        .unwrap();
+        let scheduled_program = ScheduledProgram::from_program(&program).unwrap();
+        let timeline = scheduled_program.get_timeline(&program).unwrap();
+        assert_eq!(
+            timeline.to_ascii(6),
+            concat!(
+                "0 \"rf\" |PPPP: |\n",
+                "0 \"ro\" |    PP|\n",
+                "1 \"rf\" |^     |\n",
+                "       0     3s\n",
+            )
+        );
+        assert_eq!(format_seconds(0.0), "0s");
+        assert_eq!(format_seconds(2.5e-3), "2.5ms");
+        assert_eq!(format_seconds(1.0001e-6), "1us");
+        assert_eq!(format_seconds(40e-9), "40ns");
+    }
+}
@@ -0,0 +1,470 @@
+//! Utilities for computing when the instructions of a scheduled Quil-T program execute
+
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use indexmap::IndexMap;
+use petgraph::Direction;
+
+use crate::expression::Expression;
+use crate::instruction::{
+    AttributeValue, Capture, Delay, FrameIdentifier, Instruction, Jump, JumpUnless, JumpWhen,
+    Pulse, RawCapture, Target, WaveformInvocation,
+};
+
+use super::graph::{
+    BlockTerminator, InstructionBlock, ScheduleError, ScheduleErrorVariant, ScheduleResult,
+    ScheduledGraphNode, ScheduledProgram,
+};
+use super::Program;
+
+/// The span of time during which an instruction executes, in seconds since the start of the
+/// block which contains it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeWindow {
+    pub start: f64,
+    pub end: f64,
+}
+
+impl TimeWindow {
+    /// Return the length of the window, in seconds.
+    pub fn duration(&self) -> f64 {
+        self.end - self.start
+    }
+}
+
+/// Evaluate an expression which must be a real constant, such as a duration or a sample rate.
+pub(super) fn evaluate_real_constant(expression: &Expression) -> Option<f64> {
+    let value = expression.evaluate(&HashMap::new(), &HashMap::new()).ok()?;
+    if value.im == 0.0 {
+        Some(value.re)
+    } else {
+        None
+    }
+}
+
+impl Program {
+    /// Return the duration, in seconds, of the given instruction when executed within this
+    /// program.
+    ///
+    /// The duration of a `PULSE` or `CAPTURE` is that of its waveform. For a waveform defined
+    /// within the program with `DEFWAVEFORM`, that is its number of samples divided by the
+    /// `SAMPLE-RATE` of the frame on which it plays; otherwise, it is the value of the waveform's
+    /// `duration` parameter, extended by its `pad_left` and `pad_right` parameters if present. The
+    /// duration of a `DELAY` or `RAW-CAPTURE` is the one given. All other instructions which may
+    /// be scheduled, such as `FENCE`, frame updates, and classical instructions, take no time.
+    ///
+    /// The returned error carries no instruction index.
+    pub fn get_instruction_duration(&self, instruction: &Instruction) -> ScheduleResult<f64> {
+        let error = |variant| ScheduleError {
+            instruction_index: None,
+            instruction: instruction.clone(),
+            variant,
+        };
+
+        match instruction {
+            Instruction::Pulse(Pulse {
+                frame, waveform, ..
+            })
+            | Instruction::Capture(Capture {
+                frame, waveform, ..
+            }) => self.get_waveform_duration(frame, waveform).map_err(error),
+            Instruction::Delay(Delay { duration, .. })
+            | Instruction::RawCapture(RawCapture { duration, .. }) => {
+                evaluate_real_constant(duration)
+                    .ok_or_else(|| error(ScheduleErrorVariant::DurationNotRealConstant))
+            }
+            Instruction::Gate(_) | Instruction::Measurement(_) => {
+                Err(error(ScheduleErrorVariant::UncalibratedInstruction))
+            }
+            _ => Ok(0.0),
+        }
+    }
+
+    /// Return the total duration, in seconds, of a straight-line program: the sum of the durations
+    /// of the blocks which it executes in turn, up to any `HALT`. See
+    /// [`Program::get_instruction_duration`] for how the duration of each instruction is found.
+    ///
+    /// Return an error if the program contains any jump, any gate or measurement without a
+    /// calibration, or any duration which is not a real constant (such as one which depends on a
+    /// memory reference or an unresolved expression).
+    pub fn duration(&self) -> ScheduleResult<f64> {
+        let scheduled_program = ScheduledProgram::from_program(self)?;
+        let mut duration = 0.0;
+
+        for block in scheduled_program.blocks.values() {
+            duration += block.get_duration(self)?;
+
+            let jump = match &block.terminator {
+                BlockTerminator::Continue => continue,
+                BlockTerminator::Halt => break,
+                BlockTerminator::Unconditional { target } => Instruction::Jump(Jump {
+                    target: Target::Fixed(target.clone()),
+                }),
+                BlockTerminator::Conditional {
+                    condition,
+                    target,
+                    jump_if_condition_true: true,
+                } => Instruction::JumpWhen(JumpWhen {
+                    target: Target::Fixed(target.clone()),
+                    condition: condition.clone(),
+                }),
+                BlockTerminator::Conditional {
+                    condition,
+                    target,
+                    jump_if_condition_true: false,
+                } => Instruction::JumpUnless(JumpUnless {
+                    target: Target::Fixed(target.clone()),
+                    condition: condition.clone(),
+                }),
+            };
+            return Err(ScheduleError {
+                instruction_index: None,
+                instruction: jump,
+                variant: ScheduleErrorVariant::UnschedulableInstruction,
+            });
+        }
+
+        Ok(duration)
+    }
+
+    /// Return the duration, in seconds, of the waveform when played on the given frame.
+    fn get_waveform_duration(
+        &self,
+        frame: &FrameIdentifier,
+        waveform: &WaveformInvocation,
+    ) -> Result<f64, ScheduleErrorVariant> {
+        match self.waveforms.get(&waveform.name) {
+            Some(definition) => {
+                let sample_rate = self
+                    .frames
+                    .get(frame)
+                    .and_then(|attributes| attributes.get("SAMPLE-RATE"))
+                    .and_then(|value| match value {
+                        AttributeValue::Expression(expression) => {
+                            evaluate_real_constant(expression)
+                        }
+                        AttributeValue::String(_) => None,
+                    })
+                    .filter(|sample_rate| *sample_rate > 0.0)
+                    .ok_or(ScheduleErrorVariant::InvalidFrame)?;
+                Ok(definition.matrix.len() as f64 / sample_rate)
+            }
+            None => {
+                let parameter = |name: &str| -> Result<Option<f64>, ScheduleErrorVariant> {
+                    waveform
+                        .parameters
+                        .get(name)
+                        .map(|value| {
+                            evaluate_real_constant(value)
+                                .ok_or(ScheduleErrorVariant::DurationNotRealConstant)
+                        })
+                        .transpose()
+                };
+                let duration =
+                    parameter("duration")?.ok_or(ScheduleErrorVariant::DurationNotApplicable)?;
+                let padding =
+                    parameter("pad_left")?.unwrap_or(0.0) + parameter("pad_right")?.unwrap_or(0.0);
+                Ok(duration + padding)
+            }
+        }
+    }
+}
+
+impl InstructionBlock {
+    /// Compute the time window of each instruction within the block, in the same order as the
+    /// block's instructions. Each instruction starts as soon as every instruction on which it
+    /// depends has ended, so that blocking pulses and `FENCE`s delay later instructions on the
+    /// frames they block, while `NONBLOCKING` pulses do not.
+    ///
+    /// Any error carries the index of the instruction within this block.
+    pub fn get_time_windows(&self, program: &Program) -> ScheduleResult<Vec<TimeWindow>> {
+        let mut windows: Vec<TimeWindow> = Vec::with_capacity(self.instructions.len());
+
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let duration = program
+                .get_instruction_duration(instruction)
+                .map_err(|error| ScheduleError {
+                    instruction_index: Some(index),
+                    ..error
+                })?;
+
+            // Dependencies are only ever taken on earlier instructions, which have already
+            // been assigned windows.
+            let start = self
+                .get_direct_dependencies(index)
+                .map(|dependency| windows[dependency].end)
+                .fold(0.0, f64::max);
+
+            windows.push(TimeWindow {
+                start,
+                end: start + duration,
+            });
+        }
+
+        Ok(windows)
+    }
+
+    /// Return the indices of the instructions within the block on which the instruction at the
+    /// given index directly depends: those which must end before it may start.
+    fn get_direct_dependencies(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.graph
+            .neighbors_directed(
+                ScheduledGraphNode::InstructionIndex(index),
+                Direction::Incoming,
+            )
+            .filter_map(|node| match node {
+                ScheduledGraphNode::InstructionIndex(dependency) => Some(dependency),
+                _ => None,
+            })
+    }
+
+    /// Return true if the instruction at index `earlier` within the block happens before the one
+    /// at index `later`: that is, if `later` depends on `earlier`, directly or through other
+    /// instructions, and so may not start until `earlier` has ended.
+    ///
+    /// Under the Quil-T timing model, an instruction depends on the previous instructions which
+    /// use any frame it uses; a blocking pulse or capture also uses every frame on its qubits,
+    /// while a `NONBLOCKING` one uses only its own, and a `FENCE` uses every frame on the qubits it
+    /// lists, or on all qubits if it lists none. Instructions which are not ordered either way
+    /// may execute concurrently.
+    pub fn happens_before(&self, earlier: usize, later: usize) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![later];
+        while let Some(index) = stack.pop() {
+            for dependency in self.get_direct_dependencies(index) {
+                if dependency == earlier {
+                    return true;
+                }
+                if dependency > earlier && visited.insert(dependency) {
+                    stack.push(dependency);
+                }
+            }
+        }
+        false
+    }
+
+    /// Return the whole happens-before relation of the block, as described for
+    /// [`InstructionBlock::happens_before`]: for each instruction, in order, the indices of all
+    /// instructions which happen before it.
+    pub fn get_happens_before(&self) -> Vec<BTreeSet<usize>> {
+        let mut relation: Vec<BTreeSet<usize>> = Vec::with_capacity(self.instructions.len());
+        for index in 0..self.instructions.len() {
+            // Dependencies are only ever taken on earlier instructions, whose sets are complete.
+            let mut predecessors = BTreeSet::new();
+            for dependency in self.get_direct_dependencies(index) {
+                predecessors.insert(dependency);
+                predecessors.extend(relation[dependency].iter().copied());
+            }
+            relation.push(predecessors);
+        }
+        relation
+    }
+
+    /// Return the duration of the block, in seconds: the time at which its last instruction ends.
+    pub fn get_duration(&self, program: &Program) -> ScheduleResult<f64> {
+        Ok(self
+            .get_time_windows(program)?
+            .iter()
+            .map(|window| window.end)
+            .fold(0.0, f64::max))
+    }
+}
+
+impl ScheduledProgram {
+    /// Compute the time windows of the instructions within each block as described by
+    /// [`InstructionBlock::get_time_windows`], keyed on block label as are the blocks themselves.
+    pub fn get_time_windows(
+        &self,
+        program: &Program,
+    ) -> ScheduleResult<IndexMap<String, Vec<TimeWindow>>> {
+        self.blocks
+            .iter()
+            .map(|(label, block)| Ok((label.clone(), block.get_time_windows(program)?)))
+            .collect()
+    }
+
+    /// Compute the happens-before relation within each block as described by
+    /// [`InstructionBlock::get_happens_before`], keyed on block label as are the blocks themselves.
+    pub fn get_happens_before(&self) -> IndexMap<String, Vec<BTreeSet<usize>>> {
+        self.blocks
+            .iter()
+            .map(|(label, block)| (label.clone(), block.get_happens_before()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::program::graph::{ScheduleErrorVariant, ScheduledProgram};
+    use crate::program::Program;
+
+    use super::TimeWindow;
+
+    const FRAME_DEFINITIONS: &str = "
+DEFFRAME 0 \"rf\":
+    SAMPLE-RATE: 4.0
+DEFFRAME 0 \"ro\":
+    SAMPLE-RATE: 4.0
+DEFFRAME 1 \"rf\":
+    SAMPLE-RATE: 4.0
+DEFWAVEFORM custom:
+    1, 1, 1, 1
+";
+
+    #[test]
+    fn time_windows() {
+        let program = Program::from_str(&format!(
+            "{}
+PULSE 0 \"rf\" flat(duration: 2.0, iq: 1)
+NONBLOCKING PULSE 1 \"rf\" custom
+DELAY 0 \"ro\" 0.5
+SET-PHASE 0 \"rf\" 1.0
+FENCE
+PULSE 1 \"rf\" custom
+",
+            FRAME_DEFINITIONS
+        ))
+        .unwrap();
+        let scheduled_program = ScheduledProgram::from_program(&program).unwrap();
+        let time_windows = scheduled_program.get_time_windows(&program).unwrap();
+        let windows: Vec<(f64, f64)> = time_windows["block_0"]
+            .iter()
+            .map(|TimeWindow { start, end }| (*start, *end))
+            .collect();
+
+        assert_eq!(
+            windows,
+            vec![
+                (0.0, 2.0),
+                (0.0, 1.0),
+                (2.0, 2.5),
+                (2.0, 2.0),
+                (2.5, 2.5),
+                (2.5, 3.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn happens_before() {
+        let program = Program::from_str(&format!(
+            "{}
+PULSE 0 \"rf\" flat(duration: 2.0, iq: 1)
+NONBLOCKING PULSE 1 \"rf\" custom
+DELAY 0 \"ro\" 0.5
+SET-PHASE 0 \"rf\" 1.0
+FENCE 1
+NONBLOCKING PULSE 0 \"ro\" custom
+",
+            FRAME_DEFINITIONS
+        ))
+        .unwrap();
+        let scheduled_program = ScheduledProgram::from_program(&program).unwrap();
+        let block = &scheduled_program.blocks["block_0"];
+
+        let relation: Vec<Vec<usize>> = scheduled_program.get_happens_before()["block_0"]
+            .iter()
+            .map(|predecessors| predecessors.iter().copied().collect())
+            .collect();
+        assert_eq!(
+            relation,
+            vec![vec![], vec![], vec![0], vec![0], vec![1], vec![0, 2]]
+        );
+
+        // The blocking pulse on qubit 0 holds back the later pulse on another of its frames.
+        assert!(block.happens_before(0, 5));
+        assert!(!block.happens_before(5, 0));
+        // The fence on qubit 1 does not synchronize qubit 0.
+        assert!(!block.happens_before(1, 5));
+        assert!(!block.happens_before(3, 5));
+    }
+
+    #[test]
+    fn program_duration() {
+        let program = Program::from_str(&format!(
+            "{}
+PULSE 0 \"rf\" flat(duration: 2.0, iq: 1)
+NONBLOCKING PULSE 1 \"rf\" custom
+LABEL @second
+DELAY 0 \"ro\" 0.5
+HALT
+PULSE 1 \"rf\" custom
+",
+            FRAME_DEFINITIONS
+        ))
+        .unwrap();
+        assert_eq!(program.duration().unwrap(), 2.5);
+
+        let program = Program::from_str(&format!(
+            "{}
+LABEL @loop
+PULSE 0 \"rf\" custom
+JUMP @loop
+",
+            FRAME_DEFINITIONS
+        ))
+        .unwrap();
+        assert!(matches!(
+            program.duration().unwrap_err().variant,
+            ScheduleErrorVariant::UnschedulableInstruction
+        ));
+
+        let program = Program::from_str(&format!(
+            "{}\nPULSE 0 \"rf\" flat(duration: %t, iq: 1)\n",
+            FRAME_DEFINITIONS
+        ))
+        .unwrap();
+        assert!(matches!(
+            program.duration().unwrap_err().variant,
+            ScheduleErrorVariant::DurationNotRealConstant
+        ));
+    }
+
+    #[test]
+    fn unresolved_durations() {
+        for (input, expected) in [
+            (
+                "DECLARE d REAL\nDELAY 0 \"rf\" d[0]",
+                ScheduleErrorVariant::DurationNotRealConstant,
+            ),
+            (
+                "PULSE 0 \"rf\" unknown",
+                ScheduleErrorVariant::DurationNotApplicable,
+            ),
+            (
+                "DEFFRAME 2 \"rf\":\n    HARDWARE-OBJECT: \"q2\"\nPULSE 2 \"rf\" custom",
+                ScheduleErrorVariant::InvalidFrame,
+            ),
+        ] {
+            let program =
+                Program::from_str(&format!("{}\n{}\n", FRAME_DEFINITIONS, input)).unwrap();
+            let error = ScheduledProgram::from_program(&program)
+                .unwrap()
+                .get_time_windows(&program)
+                .unwrap_err();
+            assert_eq!(
+                std::mem::discriminant(&error.variant),
+                std::mem::discriminant(&expected),
+                "{}: {:?}",
+                input,
+                error
+            );
+        }
+    }
+}
@@ -0,0 +1,237 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Rewriting of programs, one instruction at a time.
+//!
+//! An [`InstructionRewriter`] replaces a single instruction with any number of instructions
+//! (including none, to remove it). [`Program::transform`] applies a rewriter to every instruction
+//! of a program, including its header definitions and the bodies of its `DEFCAL` and
+//! `DEFCIRCUIT` blocks.
+
+use crate::instruction::{
+    Calibration, CircuitDefinition, Instruction, MeasureCalibrationDefinition,
+};
+
+use super::{Program, SourceMap};
+
+/// A pass which rewrites instructions, as applied by [`Program::transform`].
+///
+/// Any closure taking an [`Instruction`] and returning a `Vec<Instruction>` is a rewriter.
+pub trait InstructionRewriter {
+    /// Return the instructions which replace the given one. Return `vec![instruction]` to keep it
+    /// unchanged, or an empty `Vec` to remove it.
+    ///
+    /// The body of a `DEFCAL` or `DEFCIRCUIT` has already been rewritten by the time the block
+    /// itself is passed to this method.
+    fn rewrite(&mut self, instruction: Instruction) -> Vec<Instruction>;
+}
+
+impl<F> InstructionRewriter for F
+where
+    F: FnMut(Instruction) -> Vec<Instruction>,
+{
+    fn rewrite(&mut self, instruction: Instruction) -> Vec<Instruction> {
+        self(instruction)
+    }
+}
+
+/// Rewrite each of the instructions in turn, recursing into block bodies first.
+pub fn rewrite_instructions<R: InstructionRewriter + ?Sized>(
+    pass: &mut R,
+    instructions: Vec<Instruction>,
+) -> Vec<Instruction> {
+    instructions
+        .into_iter()
+        .flat_map(|instruction| rewrite_instruction(pass, instruction))
+        .collect()
+}
+
+/// Rewrite the body of the instruction, if it has one, and then the instruction itself.
+pub fn rewrite_instruction<R: InstructionRewriter + ?Sized>(
+    pass: &mut R,
+    mut instruction: Instruction,
+) -> Vec<Instruction> {
+    match &mut instruction {
+        Instruction::CalibrationDefinition(Calibration { instructions, .. })
+        | Instruction::CircuitDefinition(CircuitDefinition { instructions, .. })
+        | Instruction::MeasureCalibrationDefinition(MeasureCalibrationDefinition {
+            instructions,
+            ..
+        }) => {
+            let body = std::mem::take(instructions);
+            *instructions = rewrite_instructions(pass, body);
+        }
+        _ => {}
+    }
+
+    pass.rewrite(instruction)
+}
+
+impl Program {
+    /// Return a copy of this program in which every instruction has been rewritten by the pass.
+    ///
+    /// Each instruction is rewritten in the order given by [`Program::to_instructions`], headers
+    /// included, and the results are collected into a new program as by
+    /// [`Program::add_instruction`]. So, for instance, a `DEFCAL` returned by the pass is stored
+    /// among the new program's calibrations.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::{instruction::Instruction, Program};
+    ///
+    /// let program = Program::from_str("DECLARE ro BIT\nPRAGMA INITIAL_REWIRING \"NAIVE\"\nH 0\n")
+    ///     .unwrap();
+    /// let stripped = program.transform(&mut |instruction: Instruction| match instruction {
+    ///     Instruction::Pragma(_) => vec![],
+    ///     other => vec![other],
+    /// });
+    /// assert_eq!(stripped.to_string(true), "DECLARE ro BIT[1]\nH 0\n");
+    /// ```
+    pub fn transform<R: InstructionRewriter + ?Sized>(&self, pass: &mut R) -> Self {
+        self.transform_with_source_map(pass, &SourceMap::default())
+            .0
+    }
+
+    /// Transform the program as with [`Program::transform`], also updating the map of its
+    /// instructions to their source locations. Each instruction produced by the pass takes the
+    /// location of the instruction it replaced.
+    pub fn transform_with_source_map<R: InstructionRewriter + ?Sized>(
+        &self,
+        pass: &mut R,
+        source_map: &SourceMap,
+    ) -> (Self, SourceMap) {
+        let headers = self
+            .header_instructions()
+            .into_iter()
+            .map(|instruction| (instruction, None));
+        let body = self
+            .instructions
+            .iter()
+            .enumerate()
+            .map(|(index, instruction)| (instruction.clone(), source_map.get(index)));
+
+        let mut program = Program::new();
+        let mut new_source_map = SourceMap::default();
+        for (instruction, location) in headers.chain(body) {
+            for instruction in rewrite_instruction(pass, instruction) {
+                let count = program.instructions.len();
+                program.add_instruction(instruction);
+                if program.instructions.len() > count {
+                    new_source_map.push(location.cloned());
+                }
+            }
+        }
+
+        (program, new_source_map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::instruction::{Gate, Instruction};
+    use crate::Program;
+
+    /// Decompose each `CNOT` into `H`, `CZ`, `H`.
+    fn decompose_cnot(instruction: Instruction) -> Vec<Instruction> {
+        match instruction {
+            Instruction::Gate(Gate {
+                name,
+                parameters,
+                qubits,
+                modifiers,
+            }) if name == "CNOT" && modifiers.is_empty() => {
+                let target = qubits[1].clone();
+                let h = Instruction::Gate(Gate {
+                    name: "H".to_owned(),
+                    parameters: vec![],
+                    qubits: vec![target],
+                    modifiers: vec![],
+                });
+                vec![
+                    h.clone(),
+                    Instruction::Gate(Gate {
+                        name: "CZ".to_owned(),
+                        parameters,
+                        qubits,
+                        modifiers,
+                    }),
+                    h,
+                ]
+            }
+            other => vec![other],
+        }
+    }
+
+    #[test]
+    fn transform_rewrites_block_bodies() {
+        let program = Program::from_str(
+            "DEFCAL SWAP 0 1:
+    CNOT 0 1
+    CNOT 1 0
+    CNOT 0 1
+DEFCIRCUIT BELL a b:
+    H a
+    CNOT a b
+BELL 0 1
+CNOT 1 2
+",
+        )
+        .unwrap();
+
+        let expected = Program::from_str(
+            "DEFCAL SWAP 0 1:
+    H 1
+    CZ 0 1
+    H 1
+    H 0
+    CZ 1 0
+    H 0
+    H 1
+    CZ 0 1
+    H 1
+DEFCIRCUIT BELL a b:
+    H a
+    H b
+    CZ a b
+    H b
+BELL 0 1
+H 2
+CZ 1 2
+H 2
+",
+        )
+        .unwrap();
+
+        assert_eq!(program.transform(&mut decompose_cnot), expected);
+    }
+
+    #[test]
+    fn transform_with_source_map() {
+        let source = "DECLARE ro BIT\nCNOT 0 1\nMEASURE 0 ro\n";
+        let (program, source_map) = Program::parse_with_source_map(source).unwrap();
+        let (transformed, transformed_map) =
+            program.transform_with_source_map(&mut decompose_cnot, &source_map);
+
+        assert_eq!(transformed.instructions.len(), 4);
+        assert_eq!(
+            transformed_map
+                .iter()
+                .map(|(index, location)| (index, location.line))
+                .collect::<Vec<_>>(),
+            vec![(0, 2), (1, 2), (2, 2), (3, 3)]
+        );
+    }
+}
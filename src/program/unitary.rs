@@ -0,0 +1,262 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::{BTreeSet, HashSet};
+
+use num_complex::Complex64;
+use thiserror::Error;
+
+use crate::gates::{identity, GateError, Matrix};
+use crate::instruction::{GateDefinition, Instruction, Qubit};
+
+use super::Program;
+
+/// The greatest number of qubits for which [`Program::to_unitary`] will compute a unitary, whose
+/// size is exponential in the number of qubits.
+pub const MAX_UNITARY_QUBITS: usize = 10;
+
+/// A reason that the unitary of a program could not be computed.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum UnitaryError {
+    #[error(transparent)]
+    Gate(#[from] GateError),
+
+    #[error("the unitary of {instruction} cannot be computed")]
+    UnsupportedInstruction { instruction: Instruction },
+
+    #[error("{instruction} acts on qubit {qubit}, which is not fixed")]
+    VariableQubit {
+        instruction: Instruction,
+        qubit: Qubit,
+    },
+
+    #[error("{instruction} acts on the same qubit more than once")]
+    RepeatedQubit { instruction: Instruction },
+
+    #[error(
+        "the program acts on {count} qubits, more than the maximum of {}",
+        MAX_UNITARY_QUBITS
+    )]
+    TooManyQubits { count: usize },
+}
+
+/// Return a copy of `unitary`, over qubits at the given bit positions, with `gate` applied after
+/// it to the qubits at `positions`. The first of `positions` is the most significant qubit of
+/// the gate's matrix.
+fn apply_gate(unitary: &Matrix, gate: &Matrix, positions: &[usize]) -> Matrix {
+    let count = positions.len();
+    let mask = positions
+        .iter()
+        .fold(0usize, |mask, position| mask | (1 << position));
+    // The index into the gate's matrix of the gate's qubits within a full basis state.
+    let extract = |state: usize| {
+        positions
+            .iter()
+            .enumerate()
+            .fold(0, |index, (i, position)| {
+                index | (((state >> position) & 1) << (count - 1 - i))
+            })
+    };
+    // The bits of a full basis state which hold the gate's qubits at the given gate index.
+    let deposit = |index: usize| {
+        positions
+            .iter()
+            .enumerate()
+            .fold(0, |state, (i, position)| {
+                state | (((index >> (count - 1 - i)) & 1) << position)
+            })
+    };
+
+    let size = unitary.len();
+    let mut result = vec![vec![Complex64::new(0.0, 0.0); size]; size];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        let base = row & !mask;
+        let gate_row = &gate[extract(row)];
+        for (gate_column, factor) in gate_row.iter().enumerate() {
+            if factor.norm_sqr() == 0.0 {
+                continue;
+            }
+            let source = &unitary[base | deposit(gate_column)];
+            for (entry, value) in result_row.iter_mut().zip(source) {
+                *entry += factor * value;
+            }
+        }
+    }
+    result
+}
+
+impl Program {
+    /// Compute the unitary of the program: the product of the unitaries of its gates, as by
+    /// [`Gate::to_unitary`](crate::instruction::Gate::to_unitary), in order. This is useful for
+    /// checking that a rewrite of a small program preserves its meaning.
+    ///
+    /// The unitary acts on the distinct fixed qubits used by the program, in ascending order from
+    /// the least significant bit of the matrix's indices. So, for instance, index 1 is the basis
+    /// state in which only the lowest-numbered qubit is 1.
+    ///
+    /// Gates are looked up among the program's `DEFGATE`s and then among the
+    /// [standard gates](crate::gates::standard_gates). `PRAGMA`s are ignored. Any other instruction,
+    /// such as `MEASURE` or a jump, is an error, as are variable qubits and programs acting on
+    /// more than [`MAX_UNITARY_QUBITS`] qubits.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::Program;
+    ///
+    /// let cnot = Program::from_str("CNOT 0 1").unwrap();
+    /// let decomposed = Program::from_str("H 1\nCZ 0 1\nH 1").unwrap();
+    /// let (expected, actual) = (cnot.to_unitary().unwrap(), decomposed.to_unitary().unwrap());
+    /// for (expected_row, actual_row) in expected.iter().zip(&actual) {
+    ///     for (expected, actual) in expected_row.iter().zip(actual_row) {
+    ///         assert!((expected - actual).norm() < 1e-12);
+    ///     }
+    /// }
+    /// ```
+    pub fn to_unitary(&self) -> Result<Matrix, UnitaryError> {
+        let mut definitions: Vec<GateDefinition> = vec![];
+        let mut gates = vec![];
+        let mut qubits = BTreeSet::new();
+
+        for instruction in &self.instructions {
+            match instruction {
+                Instruction::Gate(gate) => {
+                    for qubit in &gate.qubits {
+                        match qubit {
+                            Qubit::Fixed(index) => {
+                                qubits.insert(*index);
+                            }
+                            Qubit::Variable(_) | Qubit::Placeholder(_) => {
+                                return Err(UnitaryError::VariableQubit {
+                                    instruction: instruction.clone(),
+                                    qubit: qubit.clone(),
+                                })
+                            }
+                        }
+                    }
+                    let distinct: HashSet<&Qubit> = gate.qubits.iter().collect();
+                    if distinct.len() != gate.qubits.len() {
+                        return Err(UnitaryError::RepeatedQubit {
+                            instruction: instruction.clone(),
+                        });
+                    }
+                    gates.push(gate);
+                }
+                Instruction::GateDefinition(definition) => definitions.push(definition.clone()),
+                Instruction::Pragma(_) => {}
+                _ => {
+                    return Err(UnitaryError::UnsupportedInstruction {
+                        instruction: instruction.clone(),
+                    })
+                }
+            }
+        }
+
+        if qubits.len() > MAX_UNITARY_QUBITS {
+            return Err(UnitaryError::TooManyQubits {
+                count: qubits.len(),
+            });
+        }
+        let qubits: Vec<u64> = qubits.into_iter().collect();
+
+        let mut unitary = identity(1 << qubits.len());
+        for gate in gates {
+            let matrix = gate.to_unitary(&definitions)?;
+            let positions: Vec<usize> = gate
+                .qubits
+                .iter()
+                .filter_map(|qubit| match qubit {
+                    Qubit::Fixed(index) => qubits.binary_search(index).ok(),
+                    Qubit::Variable(_) | Qubit::Placeholder(_) => None,
+                })
+                .collect();
+            unitary = apply_gate(&unitary, &matrix, &positions);
+        }
+
+        Ok(unitary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::gates::{standard_gate, GateError, Matrix};
+    use crate::Program;
+
+    use super::UnitaryError;
+
+    fn assert_close(actual: &Matrix, expected: &Matrix) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual_row, expected_row) in actual.iter().zip(expected) {
+            for (actual, expected) in actual_row.iter().zip(expected_row) {
+                assert!((actual - expected).norm() < 1e-12);
+            }
+        }
+    }
+
+    #[test]
+    fn to_unitary() {
+        // With qubit 0 as the least significant bit, CNOT 1 0 has the matrix of CNOT.
+        let cnot = standard_gate("CNOT").unwrap().to_matrix(&[]).unwrap();
+        let program = Program::from_str("CNOT 1 0").unwrap();
+        assert_close(&program.to_unitary().unwrap(), &cnot);
+
+        // Three CNOTs make a SWAP, on qubits which need not be contiguous.
+        let swap = standard_gate("SWAP").unwrap().to_matrix(&[]).unwrap();
+        let program = Program::from_str("CNOT 3 7\nCNOT 7 3\nCNOT 3 7").unwrap();
+        assert_close(&program.to_unitary().unwrap(), &swap);
+
+        // Gates on disjoint qubits commute, and pragmas have no effect.
+        let first = Program::from_str("PRAGMA A\nRX(0.3) 0\nRY(1.1) 1\nCZ 1 2").unwrap();
+        let second = Program::from_str("RY(1.1) 1\nCZ 1 2\nRX(0.3) 0").unwrap();
+        let third = Program::from_str("CZ 2 1\nDAGGER RY(-1.1) 1\nRX(0.3) 0").unwrap();
+        let unitary = first.to_unitary().unwrap();
+        assert_close(&second.to_unitary().unwrap(), &unitary);
+
+        // Gates on shared qubits do not.
+        let reordered = third.to_unitary().unwrap();
+        assert!((reordered[6][4] - unitary[6][4]).norm() > 0.1);
+    }
+
+    #[test]
+    fn unitary_errors() {
+        let cases = vec![
+            (
+                "DECLARE ro BIT\nH 0\nMEASURE 0 ro",
+                "UnsupportedInstruction",
+            ),
+            ("H q", "VariableQubit"),
+            ("CNOT 0 0", "RepeatedQubit"),
+            ("FOO 0", "Gate"),
+            (
+                "H 0\nH 1\nH 2\nH 3\nH 4\nH 5\nH 6\nH 7\nH 8\nH 9\nH 10",
+                "TooManyQubits",
+            ),
+        ];
+        for (source, expected) in cases {
+            let error = Program::from_str(source).unwrap().to_unitary().unwrap_err();
+            let matches = match &error {
+                UnitaryError::UnsupportedInstruction { .. } => expected == "UnsupportedInstruction",
+                UnitaryError::VariableQubit { .. } => expected == "VariableQubit",
+                UnitaryError::RepeatedQubit { .. } => expected == "RepeatedQubit",
+                UnitaryError::Gate(GateError::UndefinedGate(_)) => expected == "Gate",
+                UnitaryError::TooManyQubits { count } => {
+                    *count == 11 && expected == "TooManyQubits"
+                }
+                _ => false,
+            };
+            assert!(matches, "{}: {:?}", source, error);
+        }
+    }
+}
@@ -0,0 +1,476 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Read-only traversal of instructions and programs.
+//!
+//! An [`InstructionVisitor`] has one method per [`Instruction`] variant, plus methods for the
+//! expressions and memory references nested within them. Each method's default implementation
+//! recurses into the node's children by calling the matching `walk_*` function of this module, so
+//! an implementation need only override the methods for the nodes it cares about. An overriding
+//! method may call the `walk_*` function itself to continue the recursion.
+
+use crate::expression::Expression;
+use crate::instruction::{
+    Arithmetic, ArithmeticOperand, AttributeValue, BinaryLogic, BinaryOperand, Calibration,
+    Capture, CircuitDefinition, Comparison, ComparisonOperand, Declaration, Delay, Exchange, Fence,
+    FrameDefinition, Gate, GateDefinition, Instruction, Jump, JumpUnless, JumpWhen, Label, Load,
+    MeasureCalibrationDefinition, Measurement, MemoryReference, Move, Pragma, Pulse, RawCapture,
+    Reset, SetFrequency, SetPhase, SetScale, ShiftFrequency, ShiftPhase, Store, SwapPhases,
+    UnaryLogic, WaveformDefinition, WaveformInvocation,
+};
+
+use super::Program;
+
+/// A visitor over instructions and their contents. See the [module documentation](self).
+#[allow(unused_variables)]
+pub trait InstructionVisitor {
+    /// Visit any instruction, dispatching to the method for its variant.
+    fn visit_instruction(&mut self, instruction: &Instruction) {
+        walk_instruction(self, instruction)
+    }
+
+    /// Visit an expression, and by default each expression and memory reference within it.
+    fn visit_expression(&mut self, expression: &Expression) {
+        walk_expression(self, expression)
+    }
+
+    /// Visit a memory reference, whether within an expression or used as an operand.
+    fn visit_memory_reference(&mut self, memory_reference: &MemoryReference) {}
+
+    fn visit_arithmetic(&mut self, arithmetic: &Arithmetic) {
+        walk_arithmetic(self, arithmetic)
+    }
+
+    fn visit_binary_logic(&mut self, binary_logic: &BinaryLogic) {
+        walk_binary_logic(self, binary_logic)
+    }
+
+    fn visit_calibration_definition(&mut self, calibration: &Calibration) {
+        walk_calibration_definition(self, calibration)
+    }
+
+    fn visit_capture(&mut self, capture: &Capture) {
+        walk_capture(self, capture)
+    }
+
+    fn visit_circuit_definition(&mut self, circuit: &CircuitDefinition) {
+        walk_circuit_definition(self, circuit)
+    }
+
+    fn visit_comparison(&mut self, comparison: &Comparison) {
+        walk_comparison(self, comparison)
+    }
+
+    fn visit_declaration(&mut self, declaration: &Declaration) {}
+
+    fn visit_delay(&mut self, delay: &Delay) {
+        walk_delay(self, delay)
+    }
+
+    fn visit_exchange(&mut self, exchange: &Exchange) {
+        walk_exchange(self, exchange)
+    }
+
+    fn visit_fence(&mut self, fence: &Fence) {}
+
+    fn visit_frame_definition(&mut self, frame_definition: &FrameDefinition) {
+        walk_frame_definition(self, frame_definition)
+    }
+
+    fn visit_gate(&mut self, gate: &Gate) {
+        walk_gate(self, gate)
+    }
+
+    fn visit_gate_definition(&mut self, gate_definition: &GateDefinition) {
+        walk_gate_definition(self, gate_definition)
+    }
+
+    fn visit_halt(&mut self) {}
+
+    fn visit_jump(&mut self, jump: &Jump) {}
+
+    fn visit_jump_when(&mut self, jump_when: &JumpWhen) {
+        self.visit_memory_reference(&jump_when.condition)
+    }
+
+    fn visit_jump_unless(&mut self, jump_unless: &JumpUnless) {
+        self.visit_memory_reference(&jump_unless.condition)
+    }
+
+    fn visit_label(&mut self, label: &Label) {}
+
+    fn visit_load(&mut self, load: &Load) {
+        walk_load(self, load)
+    }
+
+    fn visit_measure_calibration_definition(&mut self, calibration: &MeasureCalibrationDefinition) {
+        walk_measure_calibration_definition(self, calibration)
+    }
+
+    fn visit_measurement(&mut self, measurement: &Measurement) {
+        walk_measurement(self, measurement)
+    }
+
+    fn visit_move(&mut self, r#move: &Move) {
+        walk_move(self, r#move)
+    }
+
+    fn visit_pragma(&mut self, pragma: &Pragma) {}
+
+    fn visit_pulse(&mut self, pulse: &Pulse) {
+        walk_waveform_invocation(self, &pulse.waveform)
+    }
+
+    fn visit_raw_capture(&mut self, raw_capture: &RawCapture) {
+        walk_raw_capture(self, raw_capture)
+    }
+
+    fn visit_reset(&mut self, reset: &Reset) {}
+
+    fn visit_set_frequency(&mut self, set_frequency: &SetFrequency) {
+        self.visit_expression(&set_frequency.frequency)
+    }
+
+    fn visit_set_phase(&mut self, set_phase: &SetPhase) {
+        self.visit_expression(&set_phase.phase)
+    }
+
+    fn visit_set_scale(&mut self, set_scale: &SetScale) {
+        self.visit_expression(&set_scale.scale)
+    }
+
+    fn visit_shift_frequency(&mut self, shift_frequency: &ShiftFrequency) {
+        self.visit_expression(&shift_frequency.frequency)
+    }
+
+    fn visit_shift_phase(&mut self, shift_phase: &ShiftPhase) {
+        self.visit_expression(&shift_phase.phase)
+    }
+
+    fn visit_store(&mut self, store: &Store) {
+        walk_store(self, store)
+    }
+
+    fn visit_swap_phases(&mut self, swap_phases: &SwapPhases) {}
+
+    fn visit_unary_logic(&mut self, unary_logic: &UnaryLogic) {
+        self.visit_memory_reference(&unary_logic.operand)
+    }
+
+    fn visit_waveform_definition(&mut self, waveform_definition: &WaveformDefinition) {
+        walk_waveform_definition(self, waveform_definition)
+    }
+}
+
+/// Visit every instruction of the program: first its headers, as included by
+/// [`Program::to_instructions`], and then its body.
+pub fn walk_program<V: InstructionVisitor + ?Sized>(visitor: &mut V, program: &Program) {
+    for instruction in program.to_instructions(true) {
+        visitor.visit_instruction(&instruction);
+    }
+}
+
+/// Dispatch to the visitor method for the instruction's variant.
+pub fn walk_instruction<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    instruction: &Instruction,
+) {
+    match instruction {
+        Instruction::Arithmetic(arithmetic) => visitor.visit_arithmetic(arithmetic),
+        Instruction::BinaryLogic(binary_logic) => visitor.visit_binary_logic(binary_logic),
+        Instruction::CalibrationDefinition(calibration) => {
+            visitor.visit_calibration_definition(calibration)
+        }
+        Instruction::Capture(capture) => visitor.visit_capture(capture),
+        Instruction::CircuitDefinition(circuit) => visitor.visit_circuit_definition(circuit),
+        Instruction::Comparison(comparison) => visitor.visit_comparison(comparison),
+        Instruction::Declaration(declaration) => visitor.visit_declaration(declaration),
+        Instruction::Delay(delay) => visitor.visit_delay(delay),
+        Instruction::Exchange(exchange) => visitor.visit_exchange(exchange),
+        Instruction::Fence(fence) => visitor.visit_fence(fence),
+        Instruction::FrameDefinition(frame_definition) => {
+            visitor.visit_frame_definition(frame_definition)
+        }
+        Instruction::Gate(gate) => visitor.visit_gate(gate),
+        Instruction::GateDefinition(gate_definition) => {
+            visitor.visit_gate_definition(gate_definition)
+        }
+        Instruction::Halt => visitor.visit_halt(),
+        Instruction::Jump(jump) => visitor.visit_jump(jump),
+        Instruction::JumpWhen(jump_when) => visitor.visit_jump_when(jump_when),
+        Instruction::JumpUnless(jump_unless) => visitor.visit_jump_unless(jump_unless),
+        Instruction::Label(label) => visitor.visit_label(label),
+        Instruction::Load(load) => visitor.visit_load(load),
+        Instruction::MeasureCalibrationDefinition(calibration) => {
+            visitor.visit_measure_calibration_definition(calibration)
+        }
+        Instruction::Measurement(measurement) => visitor.visit_measurement(measurement),
+        Instruction::Move(r#move) => visitor.visit_move(r#move),
+        Instruction::Pragma(pragma) => visitor.visit_pragma(pragma),
+        Instruction::Pulse(pulse) => visitor.visit_pulse(pulse),
+        Instruction::RawCapture(raw_capture) => visitor.visit_raw_capture(raw_capture),
+        Instruction::Reset(reset) => visitor.visit_reset(reset),
+        Instruction::SetFrequency(set_frequency) => visitor.visit_set_frequency(set_frequency),
+        Instruction::SetPhase(set_phase) => visitor.visit_set_phase(set_phase),
+        Instruction::SetScale(set_scale) => visitor.visit_set_scale(set_scale),
+        Instruction::ShiftFrequency(shift_frequency) => {
+            visitor.visit_shift_frequency(shift_frequency)
+        }
+        Instruction::ShiftPhase(shift_phase) => visitor.visit_shift_phase(shift_phase),
+        Instruction::Store(store) => visitor.visit_store(store),
+        Instruction::SwapPhases(swap_phases) => visitor.visit_swap_phases(swap_phases),
+        Instruction::UnaryLogic(unary_logic) => visitor.visit_unary_logic(unary_logic),
+        Instruction::WaveformDefinition(waveform_definition) => {
+            visitor.visit_waveform_definition(waveform_definition)
+        }
+    }
+}
+
+/// Visit each expression and memory reference directly within the expression.
+pub fn walk_expression<V: InstructionVisitor + ?Sized>(visitor: &mut V, expression: &Expression) {
+    match expression {
+        Expression::Address(memory_reference) => visitor.visit_memory_reference(memory_reference),
+        Expression::FunctionCall { expression, .. } | Expression::Prefix { expression, .. } => {
+            visitor.visit_expression(expression)
+        }
+        Expression::Infix { left, right, .. } => {
+            visitor.visit_expression(left);
+            visitor.visit_expression(right);
+        }
+        Expression::Number(_) | Expression::PiConstant | Expression::Variable(_) => {}
+    }
+}
+
+fn walk_arithmetic_operand<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    operand: &ArithmeticOperand,
+) {
+    if let ArithmeticOperand::MemoryReference(memory_reference) = operand {
+        visitor.visit_memory_reference(memory_reference)
+    }
+}
+
+pub fn walk_arithmetic<V: InstructionVisitor + ?Sized>(visitor: &mut V, arithmetic: &Arithmetic) {
+    walk_arithmetic_operand(visitor, &arithmetic.destination);
+    walk_arithmetic_operand(visitor, &arithmetic.source);
+}
+
+pub fn walk_binary_logic<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    binary_logic: &BinaryLogic,
+) {
+    let (destination, source) = &binary_logic.operands;
+    visitor.visit_memory_reference(destination);
+    if let BinaryOperand::MemoryReference(source) = source {
+        visitor.visit_memory_reference(source);
+    }
+}
+
+/// Visit the parameters of the calibration and then each instruction of its body.
+pub fn walk_calibration_definition<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    calibration: &Calibration,
+) {
+    for parameter in &calibration.parameters {
+        visitor.visit_expression(parameter);
+    }
+    for instruction in &calibration.instructions {
+        visitor.visit_instruction(instruction);
+    }
+}
+
+pub fn walk_capture<V: InstructionVisitor + ?Sized>(visitor: &mut V, capture: &Capture) {
+    walk_waveform_invocation(visitor, &capture.waveform);
+    visitor.visit_memory_reference(&capture.memory_reference);
+}
+
+/// Visit each instruction of the circuit's body.
+pub fn walk_circuit_definition<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    circuit: &CircuitDefinition,
+) {
+    for instruction in &circuit.instructions {
+        visitor.visit_instruction(instruction);
+    }
+}
+
+pub fn walk_comparison<V: InstructionVisitor + ?Sized>(visitor: &mut V, comparison: &Comparison) {
+    let (destination, left, right) = &comparison.operands;
+    visitor.visit_memory_reference(destination);
+    visitor.visit_memory_reference(left);
+    if let ComparisonOperand::MemoryReference(right) = right {
+        visitor.visit_memory_reference(right);
+    }
+}
+
+pub fn walk_delay<V: InstructionVisitor + ?Sized>(visitor: &mut V, delay: &Delay) {
+    visitor.visit_expression(&delay.duration);
+}
+
+pub fn walk_exchange<V: InstructionVisitor + ?Sized>(visitor: &mut V, exchange: &Exchange) {
+    walk_arithmetic_operand(visitor, &exchange.left);
+    walk_arithmetic_operand(visitor, &exchange.right);
+}
+
+/// Visit each attribute of the frame definition whose value is an expression.
+pub fn walk_frame_definition<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    frame_definition: &FrameDefinition,
+) {
+    for value in frame_definition.attributes.values() {
+        if let AttributeValue::Expression(expression) = value {
+            visitor.visit_expression(expression);
+        }
+    }
+}
+
+pub fn walk_gate<V: InstructionVisitor + ?Sized>(visitor: &mut V, gate: &Gate) {
+    for parameter in &gate.parameters {
+        visitor.visit_expression(parameter);
+    }
+}
+
+/// Visit each entry of the gate definition's matrix, row by row.
+pub fn walk_gate_definition<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    gate_definition: &GateDefinition,
+) {
+    for row in &gate_definition.matrix {
+        for cell in row {
+            visitor.visit_expression(cell);
+        }
+    }
+}
+
+pub fn walk_load<V: InstructionVisitor + ?Sized>(visitor: &mut V, load: &Load) {
+    visitor.visit_memory_reference(&load.destination);
+    visitor.visit_memory_reference(&load.offset);
+}
+
+/// Visit each instruction of the calibration's body.
+pub fn walk_measure_calibration_definition<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    calibration: &MeasureCalibrationDefinition,
+) {
+    for instruction in &calibration.instructions {
+        visitor.visit_instruction(instruction);
+    }
+}
+
+pub fn walk_measurement<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    measurement: &Measurement,
+) {
+    if let Some(target) = &measurement.target {
+        visitor.visit_memory_reference(target);
+    }
+}
+
+pub fn walk_move<V: InstructionVisitor + ?Sized>(visitor: &mut V, r#move: &Move) {
+    walk_arithmetic_operand(visitor, &r#move.destination);
+    walk_arithmetic_operand(visitor, &r#move.source);
+}
+
+pub fn walk_raw_capture<V: InstructionVisitor + ?Sized>(visitor: &mut V, raw_capture: &RawCapture) {
+    visitor.visit_expression(&raw_capture.duration);
+    visitor.visit_memory_reference(&raw_capture.memory_reference);
+}
+
+pub fn walk_store<V: InstructionVisitor + ?Sized>(visitor: &mut V, store: &Store) {
+    visitor.visit_memory_reference(&store.offset);
+    walk_arithmetic_operand(visitor, &store.source);
+}
+
+/// Visit each parameter of the waveform invocation.
+pub fn walk_waveform_invocation<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    waveform: &WaveformInvocation,
+) {
+    for parameter in waveform.parameters.values() {
+        visitor.visit_expression(parameter);
+    }
+}
+
+/// Visit each sample of the waveform definition.
+pub fn walk_waveform_definition<V: InstructionVisitor + ?Sized>(
+    visitor: &mut V,
+    waveform_definition: &WaveformDefinition,
+) {
+    for sample in &waveform_definition.definition.matrix {
+        visitor.visit_expression(sample);
+    }
+}
+
+impl Program {
+    /// Visit every instruction of the program, as with [`walk_program`].
+    pub fn visit<V: InstructionVisitor + ?Sized>(&self, visitor: &mut V) {
+        walk_program(visitor, self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use std::str::FromStr;
+
+    use crate::instruction::{Gate, MemoryReference};
+    use crate::Program;
+
+    use super::{walk_gate, InstructionVisitor};
+
+    #[derive(Default)]
+    struct Collector {
+        gates: Vec<String>,
+        memory: BTreeSet<String>,
+    }
+
+    impl InstructionVisitor for Collector {
+        fn visit_gate(&mut self, gate: &Gate) {
+            self.gates.push(gate.name.clone());
+            walk_gate(self, gate);
+        }
+
+        fn visit_memory_reference(&mut self, memory_reference: &MemoryReference) {
+            self.memory.insert(memory_reference.to_string());
+        }
+    }
+
+    #[test]
+    fn visit_recurses_into_bodies_and_expressions() {
+        let program = Program::from_str(
+            "DECLARE theta REAL[2]
+DECLARE ro BIT
+DEFCAL RX(%angle) 0:
+    SHIFT-PHASE 0 \"rf\" theta[1]
+    RZ(%angle) 0
+DEFCIRCUIT BELL a b:
+    H a
+    CNOT a b
+RX(2*cos(theta[0])) 0
+MEASURE 0 ro
+JUMP-WHEN @end ro[0]
+LABEL @end
+",
+        )
+        .unwrap();
+        let mut collector = Collector::default();
+        program.visit(&mut collector);
+
+        assert_eq!(collector.gates, vec!["RZ", "H", "CNOT", "RX"]);
+        assert_eq!(
+            collector.memory.into_iter().collect::<Vec<_>>(),
+            vec!["ro[0]", "theta[0]", "theta[1]"]
+        );
+    }
+}
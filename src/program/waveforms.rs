@@ -0,0 +1,201 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use thiserror::Error;
+
+use crate::instruction::{Capture, Instruction, Pulse, Waveform, WaveformInvocation};
+use crate::waveform::WaveformError;
+
+use super::Program;
+
+/// The definition of a waveform invoked within a program.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WaveformReference<'a> {
+    /// A standard template, such as `flat` or `gaussian`.
+    Template(&'a str),
+    /// A `DEFWAVEFORM` within the program.
+    Defined(&'a Waveform),
+}
+
+/// An instruction within a program which invokes a waveform that cannot be resolved.
+#[derive(Clone, Debug, Error, PartialEq)]
+#[error("instruction {location} ({instruction}): {kind}")]
+pub struct WaveformReferenceViolation {
+    /// The index of the instruction within [`Program::instructions`].
+    pub location: usize,
+    pub instruction: Instruction,
+    pub kind: WaveformReferenceViolationKind,
+}
+
+/// The ways in which a waveform invocation may fail to resolve.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum WaveformReferenceViolationKind {
+    #[error("waveform {0} is neither a DEFWAVEFORM nor a standard template")]
+    UnknownWaveform(String),
+
+    #[error("waveform {waveform} takes parameters ({}), but is given ({})", .expected.join(", "), .found.join(", "))]
+    ParameterMismatch {
+        waveform: String,
+        expected: Vec<String>,
+        found: Vec<String>,
+    },
+
+    #[error(transparent)]
+    Template(WaveformError),
+}
+
+impl Program {
+    /// Resolve a waveform invocation to the `DEFWAVEFORM` of the same name within this program,
+    /// or else to the standard template of that name. An invocation of a `DEFWAVEFORM` must give
+    /// exactly the parameters it declares, and one of a template must give each of its required
+    /// parameters, as with [`WaveformInvocation::check_template_parameters`].
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::instruction::Instruction;
+    /// use quil_rs::program::WaveformReference;
+    /// use quil_rs::Program;
+    ///
+    /// let program = Program::from_str(
+    ///     "DEFWAVEFORM ramp(%a):\n    0, %a\nPULSE 0 \"rf\" ramp(a: 1)\nPULSE 0 \"rf\" flat(duration: 1, iq: 1)",
+    /// )
+    /// .unwrap();
+    /// let references: Vec<_> = program
+    ///     .instructions
+    ///     .iter()
+    ///     .map(|instruction| match instruction {
+    ///         Instruction::Pulse(pulse) => program.resolve_waveform(&pulse.waveform).unwrap(),
+    ///         _ => unreachable!(),
+    ///     })
+    ///     .collect();
+    /// assert_eq!(references[0], WaveformReference::Defined(&program.waveforms["ramp"]));
+    /// assert_eq!(references[1], WaveformReference::Template("flat"));
+    /// ```
+    pub fn resolve_waveform<'a>(
+        &'a self,
+        invocation: &'a WaveformInvocation,
+    ) -> Result<WaveformReference<'a>, WaveformReferenceViolationKind> {
+        match self.waveforms.get(&invocation.name) {
+            Some(definition) => {
+                let mut expected = definition.parameters.clone();
+                expected.sort();
+                let mut found: Vec<String> = invocation.parameters.keys().cloned().collect();
+                found.sort();
+                if expected == found {
+                    Ok(WaveformReference::Defined(definition))
+                } else {
+                    Err(WaveformReferenceViolationKind::ParameterMismatch {
+                        waveform: invocation.name.clone(),
+                        expected,
+                        found,
+                    })
+                }
+            }
+            None => match invocation.check_template_parameters() {
+                Ok(()) => Ok(WaveformReference::Template(&invocation.name)),
+                Err(WaveformError::UnknownTemplate(name)) => {
+                    Err(WaveformReferenceViolationKind::UnknownWaveform(name))
+                }
+                Err(error) => Err(WaveformReferenceViolationKind::Template(error)),
+            },
+        }
+    }
+
+    /// Check that the waveform of every `PULSE` and `CAPTURE` within the program resolves, as by
+    /// [`Program::resolve_waveform`], reporting each which does not in order. Only the program's
+    /// own instructions are checked, so calibrations should be expanded first to check the
+    /// waveforms played by its gates.
+    pub fn validate_waveform_references(&self) -> Result<(), Vec<WaveformReferenceViolation>> {
+        let violations: Vec<WaveformReferenceViolation> = self
+            .instructions
+            .iter()
+            .enumerate()
+            .filter_map(|(location, instruction)| {
+                let waveform = match instruction {
+                    Instruction::Pulse(Pulse { waveform, .. })
+                    | Instruction::Capture(Capture { waveform, .. }) => waveform,
+                    _ => return None,
+                };
+                self.resolve_waveform(waveform)
+                    .err()
+                    .map(|kind| WaveformReferenceViolation {
+                        location,
+                        instruction: instruction.clone(),
+                        kind,
+                    })
+            })
+            .collect();
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use crate::waveform::WaveformError;
+    use crate::Program;
+
+    use super::WaveformReferenceViolationKind;
+
+    #[test]
+    fn validate_waveform_references() {
+        let program = Program::from_str(
+            r#"DEFWAVEFORM ramp(%a, %b):
+    %a, %b
+PULSE 0 "rf" ramp(a: 1, b: 2)
+PULSE 0 "rf" ramp(a: 1)
+PULSE 0 "rf" flat(duration: 1, iq: 1, scale: 0.5)
+PULSE 0 "rf" flat(duration: 1)
+CAPTURE 0 "ro" unknown(duration: 1) ro
+"#,
+        )
+        .unwrap();
+        let violations: Vec<(usize, WaveformReferenceViolationKind)> = program
+            .validate_waveform_references()
+            .unwrap_err()
+            .into_iter()
+            .map(|violation| (violation.location, violation.kind))
+            .collect();
+        assert_eq!(
+            violations,
+            vec![
+                (
+                    1,
+                    WaveformReferenceViolationKind::ParameterMismatch {
+                        waveform: "ramp".to_owned(),
+                        expected: vec!["a".to_owned(), "b".to_owned()],
+                        found: vec!["a".to_owned()],
+                    }
+                ),
+                (
+                    3,
+                    WaveformReferenceViolationKind::Template(WaveformError::MissingParameter {
+                        waveform: "flat".to_owned(),
+                        parameter: "iq".to_owned(),
+                    })
+                ),
+                (
+                    4,
+                    WaveformReferenceViolationKind::UnknownWaveform("unknown".to_owned())
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,239 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Python bindings to [`Program`], [`Instruction`], and [`Expression`], so that Python tooling
+//! may parse, expand, and analyze Quil with this crate rather than reimplementing it.
+//!
+//! The bindings form an extension module named `quil`, which is built as a `cdylib`:
+//!
+//! ```text
+//! cargo rustc --release --features python,pyo3/extension-module --crate-type cdylib
+//! ```
+//!
+//! and then imported from Python once the library is renamed to `quil.so`:
+//!
+//! ```python
+//! from quil import Program
+//!
+//! program = Program.parse("DECLARE ro BIT\nMEASURE 0 ro")
+//! print(program.memory_regions)  # {'ro': 'BIT[1]'}
+//! ```
+//!
+//! Errors raised by this crate, such as those in parsing or in expanding calibrations, are
+//! raised in Python as `ValueError`s carrying their message.
+
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use num_complex::Complex64;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::expression::Expression;
+use crate::instruction::Instruction;
+use crate::Program;
+
+/// Raise an error from this crate in Python as a `ValueError`.
+fn value_error(error: impl ToString) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+/// A Quil program.
+#[pyclass(name = "Program", module = "quil")]
+#[derive(Clone, Default)]
+pub struct PyProgram(pub Program);
+
+#[pymethods]
+impl PyProgram {
+    /// Create an empty program.
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a program from Quil.
+    #[staticmethod]
+    fn parse(quil: &str) -> PyResult<Self> {
+        Program::from_str(quil).map(Self).map_err(value_error)
+    }
+
+    /// The instructions of the body of the program, without its declarations and definitions.
+    #[getter]
+    fn instructions(&self) -> Vec<PyInstruction> {
+        self.0
+            .instructions
+            .iter()
+            .cloned()
+            .map(PyInstruction)
+            .collect()
+    }
+
+    /// Add an instruction to the end of the program, or to its definitions if it is one.
+    fn add_instruction(&mut self, instruction: PyInstruction) {
+        self.0.add_instruction(instruction.0);
+    }
+
+    /// Expand the calibrations of the program, returning the expanded program.
+    fn expand_calibrations(&self) -> PyResult<Self> {
+        self.0.expand_calibrations().map(Self).map_err(value_error)
+    }
+
+    /// The memory regions declared by the program, by name, each as its type and length such as
+    /// `BIT[2]`.
+    #[getter]
+    fn memory_regions(&self) -> HashMap<String, String> {
+        self.0
+            .memory_regions
+            .iter()
+            .map(|(name, region)| (name.clone(), region.size.to_string()))
+            .collect()
+    }
+
+    /// Check the program's uses of memory against its declarations, returning a description of
+    /// each use which does not agree with them, or an empty list if all do.
+    fn validate_memory_references(&self) -> Vec<String> {
+        match self.0.validate_memory_references() {
+            Ok(()) => vec![],
+            Err(violations) => violations.iter().map(ToString::to_string).collect(),
+        }
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string(true)
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Program.parse({:?})", self.0.to_string(true))
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+/// A single Quil instruction.
+#[pyclass(name = "Instruction", module = "quil")]
+#[derive(Clone)]
+pub struct PyInstruction(pub Instruction);
+
+#[pymethods]
+impl PyInstruction {
+    /// Parse exactly one instruction from Quil.
+    #[staticmethod]
+    fn parse(quil: &str) -> PyResult<Self> {
+        let program = Program::from_str(quil).map_err(value_error)?;
+        let mut instructions = program.to_instructions(true);
+        match instructions.len() {
+            1 => Ok(Self(instructions.remove(0))),
+            count => Err(PyValueError::new_err(format!(
+                "expected exactly one instruction, found {}",
+                count
+            ))),
+        }
+    }
+
+    /// Whether the instruction is specific to Quil-T, as with [`Instruction::is_quil_t`].
+    fn is_quil_t(&self) -> bool {
+        self.0.is_quil_t()
+    }
+
+    /// The names of the memory regions the instruction accesses, by the kind of access:
+    /// `captures`, `reads`, and `writes`.
+    fn memory_accesses(&self) -> HashMap<&'static str, HashSet<String>> {
+        let accesses = self.0.get_memory_accesses();
+        vec![
+            ("captures", accesses.captures),
+            ("reads", accesses.reads),
+            ("writes", accesses.writes),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Instruction.parse({:?})", self.0.to_string())
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+/// A Quil expression.
+#[pyclass(name = "Expression", module = "quil")]
+#[derive(Clone)]
+pub struct PyExpression(pub Expression);
+
+#[pymethods]
+impl PyExpression {
+    /// Parse an expression from Quil.
+    #[staticmethod]
+    fn parse(quil: &str) -> PyResult<Self> {
+        Expression::from_str(quil).map(Self).map_err(value_error)
+    }
+
+    /// Return the expression simplified, as with [`Expression::simplify`].
+    fn simplify(&self) -> Self {
+        Self(self.0.clone().into_simplified())
+    }
+
+    /// Evaluate the expression, given the values of its variables and of the memory regions it
+    /// references.
+    fn evaluate(
+        &self,
+        variables: Option<HashMap<String, Complex64>>,
+        memory: Option<HashMap<String, Vec<f64>>>,
+    ) -> PyResult<Complex64> {
+        let variables = variables.unwrap_or_default();
+        let memory = memory.unwrap_or_default();
+        let memory_references = memory
+            .iter()
+            .map(|(name, values)| (name.as_str(), values.clone()))
+            .collect();
+        self.0
+            .evaluate(&variables, &memory_references)
+            .map_err(value_error)
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Expression.parse({:?})", self.0.to_string())
+    }
+
+    fn __eq__(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+/// The `quil` extension module.
+#[pymodule]
+fn quil(_py: Python, module: &PyModule) -> PyResult<()> {
+    register(module)
+}
+
+/// Add the classes of this module to a Python module, for crates which build their own
+/// extension module around these bindings.
+pub fn register(module: &PyModule) -> PyResult<()> {
+    module.add_class::<PyProgram>()?;
+    module.add_class::<PyInstruction>()?;
+    module.add_class::<PyExpression>()?;
+    Ok(())
+}
@@ -0,0 +1,1198 @@
+// Copyright 2021 Rigetti Computing
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+// http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The standard Quil-T waveform templates, such as `flat` and `gaussian`, and their sampling into
+//! IQ values.
+//!
+//! Each template is built from its required parameters, which are validated, and may then be
+//! modulated by the optional `scale`, `phase`, and `detuning` parameters common to all templates.
+//! Times are in seconds, frequencies in Hertz, and phases in radians.
+//!
+//! Sampled waveforms of any origin may be further transformed with the functions of this module,
+//! such as [`rotate_phase`] and [`pad_to_multiple`].
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::fmt::Debug;
+
+use num_complex::Complex64;
+use thiserror::Error;
+
+use crate::expression::{EvaluationError, Expression};
+use crate::instruction::WaveformInvocation;
+
+/// A problem with the parameters of a waveform template.
+#[derive(Clone, Debug, Error, PartialEq)]
+pub enum WaveformError {
+    #[error("parameter {parameter} of waveform {waveform} is invalid: {reason}")]
+    InvalidParameter {
+        waveform: String,
+        parameter: String,
+        reason: String,
+    },
+
+    #[error("{0} is not a standard waveform template")]
+    UnknownTemplate(String),
+
+    #[error("waveform {waveform} requires parameter {parameter}")]
+    MissingParameter { waveform: String, parameter: String },
+
+    #[error("waveform {waveform} takes no parameter {parameter}")]
+    UnexpectedParameter { waveform: String, parameter: String },
+
+    #[error("could not evaluate parameter {parameter} of waveform {waveform}: {source}")]
+    Evaluation {
+        waveform: String,
+        parameter: String,
+        source: EvaluationError,
+    },
+}
+
+/// The modulation common to all waveform templates, applied to each sample after the template's
+/// own envelope.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Modulation {
+    /// A factor by which each sample is multiplied.
+    pub scale: f64,
+
+    /// A phase, in radians, by which each sample is rotated.
+    pub phase: f64,
+
+    /// A frequency, in Hertz, by which the phase of the samples advances over time.
+    pub detuning: f64,
+}
+
+impl Default for Modulation {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            phase: 0.0,
+            detuning: 0.0,
+        }
+    }
+}
+
+/// A waveform template which may be sampled at a given rate.
+pub trait WaveformTemplate: Debug {
+    /// The name by which the template is invoked in Quil-T, such as `gaussian`.
+    fn name(&self) -> &'static str;
+
+    /// The duration of the waveform, in seconds.
+    fn duration(&self) -> f64;
+
+    fn modulation(&self) -> &Modulation;
+
+    fn modulation_mut(&mut self) -> &mut Modulation;
+
+    /// Sample the waveform's envelope, without its [`Modulation`], at the given rate in Hertz.
+    fn envelope(&self, sample_rate: f64) -> Vec<Complex64>;
+
+    /// Sample the waveform at the given rate in Hertz. The `i`th sample is taken at time
+    /// `i / sample_rate`, and there are enough samples to cover the waveform's duration; a
+    /// sample rate which is not finite and positive yields no samples.
+    fn sample(&self, sample_rate: f64) -> Vec<Complex64> {
+        let Modulation {
+            scale,
+            phase,
+            detuning,
+        } = *self.modulation();
+        self.envelope(sample_rate)
+            .into_iter()
+            .enumerate()
+            .map(|(index, value)| {
+                let time = index as f64 / sample_rate;
+                value * scale * Complex64::from_polar(1.0, phase + 2.0 * PI * detuning * time)
+            })
+            .collect()
+    }
+
+    fn with_scale(mut self, scale: f64) -> Self
+    where
+        Self: Sized,
+    {
+        self.modulation_mut().scale = scale;
+        self
+    }
+
+    fn with_phase(mut self, phase: f64) -> Self
+    where
+        Self: Sized,
+    {
+        self.modulation_mut().phase = phase;
+        self
+    }
+
+    fn with_detuning(mut self, detuning: f64) -> Self
+    where
+        Self: Sized,
+    {
+        self.modulation_mut().detuning = detuning;
+        self
+    }
+}
+
+/// Return the number of samples needed to cover the given duration at the given rate: the
+/// product, rounded up unless it is within floating-point error of an integer.
+pub fn sample_count(duration: f64, sample_rate: f64) -> usize {
+    if !(sample_rate.is_finite() && sample_rate > 0.0 && duration.is_finite() && duration > 0.0) {
+        return 0;
+    }
+    let exact = duration * sample_rate;
+    let rounded = exact.round();
+    if (exact - rounded).abs() <= 1e-9 * rounded.max(1.0) {
+        rounded as usize
+    } else {
+        exact.ceil() as usize
+    }
+}
+
+/// Return the times, in seconds, at which the samples of the given duration are taken.
+fn sample_times(duration: f64, sample_rate: f64) -> impl Iterator<Item = f64> {
+    (0..sample_count(duration, sample_rate)).map(move |index| index as f64 / sample_rate)
+}
+
+/// The error function, to within a fractional error of 1.2e-7, after Numerical Recipes.
+fn erf(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.5 * z);
+    let erfc = t
+        * (-z * z - 1.265_512_23
+            + t * (1.000_023_68
+                + t * (0.374_091_96
+                    + t * (0.096_784_18
+                        + t * (-0.186_288_06
+                            + t * (0.278_868_07
+                                + t * (-1.135_203_98
+                                    + t * (1.488_515_87
+                                        + t * (-0.822_152_23 + t * 0.170_872_77)))))))))
+            .exp();
+    if x >= 0.0 {
+        1.0 - erfc
+    } else {
+        erfc - 1.0
+    }
+}
+
+/// The standard deviation of a Gaussian with the given full width at half maximum.
+fn sigma(fwhm: f64) -> f64 {
+    0.5 * fwhm / (2.0 * 2f64.ln()).sqrt()
+}
+
+fn invalid(waveform: &str, parameter: &str, reason: &str) -> WaveformError {
+    WaveformError::InvalidParameter {
+        waveform: waveform.to_owned(),
+        parameter: parameter.to_owned(),
+        reason: reason.to_owned(),
+    }
+}
+
+fn check_finite(waveform: &str, parameter: &str, value: f64) -> Result<(), WaveformError> {
+    if value.is_finite() {
+        Ok(())
+    } else {
+        Err(invalid(waveform, parameter, "must be finite"))
+    }
+}
+
+fn check_positive(waveform: &str, parameter: &str, value: f64) -> Result<(), WaveformError> {
+    if value.is_finite() && value > 0.0 {
+        Ok(())
+    } else {
+        Err(invalid(waveform, parameter, "must be finite and positive"))
+    }
+}
+
+fn check_non_negative(waveform: &str, parameter: &str, value: f64) -> Result<(), WaveformError> {
+    if value.is_finite() && value >= 0.0 {
+        Ok(())
+    } else {
+        Err(invalid(
+            waveform,
+            parameter,
+            "must be finite and non-negative",
+        ))
+    }
+}
+
+/// Implement the parts of [`WaveformTemplate`] common to all templates.
+macro_rules! template_accessors {
+    ($name:literal) => {
+        fn name(&self) -> &'static str {
+            $name
+        }
+
+        fn duration(&self) -> f64 {
+            self.duration
+        }
+
+        fn modulation(&self) -> &Modulation {
+            &self.modulation
+        }
+
+        fn modulation_mut(&mut self) -> &mut Modulation {
+            &mut self.modulation
+        }
+    };
+}
+
+/// The `flat` template: a constant IQ value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Flat {
+    duration: f64,
+    iq: Complex64,
+    modulation: Modulation,
+}
+
+impl Flat {
+    pub fn new(duration: f64, iq: Complex64) -> Result<Self, WaveformError> {
+        check_positive("flat", "duration", duration)?;
+        check_finite("flat", "iq", iq.norm())?;
+        Ok(Self {
+            duration,
+            iq,
+            modulation: Modulation::default(),
+        })
+    }
+
+    pub fn iq(&self) -> Complex64 {
+        self.iq
+    }
+}
+
+impl WaveformTemplate for Flat {
+    template_accessors!("flat");
+
+    fn envelope(&self, sample_rate: f64) -> Vec<Complex64> {
+        vec![self.iq; sample_count(self.duration, sample_rate)]
+    }
+}
+
+/// The `gaussian` template: a Gaussian with the given full width at half maximum, centered at
+/// time `t0`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Gaussian {
+    duration: f64,
+    fwhm: f64,
+    t0: f64,
+    modulation: Modulation,
+}
+
+impl Gaussian {
+    pub fn new(duration: f64, fwhm: f64, t0: f64) -> Result<Self, WaveformError> {
+        check_positive("gaussian", "duration", duration)?;
+        check_positive("gaussian", "fwhm", fwhm)?;
+        check_finite("gaussian", "t0", t0)?;
+        Ok(Self {
+            duration,
+            fwhm,
+            t0,
+            modulation: Modulation::default(),
+        })
+    }
+
+    pub fn fwhm(&self) -> f64 {
+        self.fwhm
+    }
+
+    pub fn t0(&self) -> f64 {
+        self.t0
+    }
+}
+
+impl WaveformTemplate for Gaussian {
+    template_accessors!("gaussian");
+
+    fn envelope(&self, sample_rate: f64) -> Vec<Complex64> {
+        let sigma = sigma(self.fwhm);
+        sample_times(self.duration, sample_rate)
+            .map(|time| {
+                let offset = time - self.t0;
+                Complex64::from((-0.5 * offset * offset / (sigma * sigma)).exp())
+            })
+            .collect()
+    }
+}
+
+/// The `drag_gaussian` template: a Gaussian with a DRAG correction in quadrature, proportional to
+/// its derivative, for a qubit of anharmonicity `anh` (in Hertz) and with correction strength
+/// `alpha`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DragGaussian {
+    duration: f64,
+    fwhm: f64,
+    t0: f64,
+    anh: f64,
+    alpha: f64,
+    modulation: Modulation,
+}
+
+impl DragGaussian {
+    pub fn new(
+        duration: f64,
+        fwhm: f64,
+        t0: f64,
+        anh: f64,
+        alpha: f64,
+    ) -> Result<Self, WaveformError> {
+        check_positive("drag_gaussian", "duration", duration)?;
+        check_positive("drag_gaussian", "fwhm", fwhm)?;
+        check_finite("drag_gaussian", "t0", t0)?;
+        check_finite("drag_gaussian", "anh", anh)?;
+        if anh == 0.0 {
+            return Err(invalid("drag_gaussian", "anh", "must not be zero"));
+        }
+        check_finite("drag_gaussian", "alpha", alpha)?;
+        Ok(Self {
+            duration,
+            fwhm,
+            t0,
+            anh,
+            alpha,
+            modulation: Modulation::default(),
+        })
+    }
+
+    pub fn fwhm(&self) -> f64 {
+        self.fwhm
+    }
+
+    pub fn t0(&self) -> f64 {
+        self.t0
+    }
+
+    pub fn anh(&self) -> f64 {
+        self.anh
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+}
+
+impl WaveformTemplate for DragGaussian {
+    template_accessors!("drag_gaussian");
+
+    fn envelope(&self, sample_rate: f64) -> Vec<Complex64> {
+        let sigma = sigma(self.fwhm);
+        let derivative_factor = self.alpha / (2.0 * PI * self.anh * sigma * sigma);
+        sample_times(self.duration, sample_rate)
+            .map(|time| {
+                let offset = time - self.t0;
+                let envelope = (-0.5 * offset * offset / (sigma * sigma)).exp();
+                Complex64::new(envelope, derivative_factor * offset * envelope)
+            })
+            .collect()
+    }
+}
+
+/// The `hrm_gaussian` template: a Hermite Gaussian, with the given second-order Hermite
+/// coefficient, and with a DRAG correction as for [`DragGaussian`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct HrmGaussian {
+    duration: f64,
+    fwhm: f64,
+    t0: f64,
+    anh: f64,
+    alpha: f64,
+    second_order_hrm_coeff: f64,
+    modulation: Modulation,
+}
+
+impl HrmGaussian {
+    pub fn new(
+        duration: f64,
+        fwhm: f64,
+        t0: f64,
+        anh: f64,
+        alpha: f64,
+        second_order_hrm_coeff: f64,
+    ) -> Result<Self, WaveformError> {
+        check_positive("hrm_gaussian", "duration", duration)?;
+        check_positive("hrm_gaussian", "fwhm", fwhm)?;
+        check_finite("hrm_gaussian", "t0", t0)?;
+        check_finite("hrm_gaussian", "anh", anh)?;
+        if anh == 0.0 {
+            return Err(invalid("hrm_gaussian", "anh", "must not be zero"));
+        }
+        check_finite("hrm_gaussian", "alpha", alpha)?;
+        check_finite(
+            "hrm_gaussian",
+            "second_order_hrm_coeff",
+            second_order_hrm_coeff,
+        )?;
+        Ok(Self {
+            duration,
+            fwhm,
+            t0,
+            anh,
+            alpha,
+            second_order_hrm_coeff,
+            modulation: Modulation::default(),
+        })
+    }
+
+    pub fn fwhm(&self) -> f64 {
+        self.fwhm
+    }
+
+    pub fn t0(&self) -> f64 {
+        self.t0
+    }
+
+    pub fn anh(&self) -> f64 {
+        self.anh
+    }
+
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+
+    pub fn second_order_hrm_coeff(&self) -> f64 {
+        self.second_order_hrm_coeff
+    }
+}
+
+impl WaveformTemplate for HrmGaussian {
+    template_accessors!("hrm_gaussian");
+
+    fn envelope(&self, sample_rate: f64) -> Vec<Complex64> {
+        let sigma = sigma(self.fwhm);
+        let coeff = self.second_order_hrm_coeff;
+        let derivative_factor = -self.alpha / (2.0 * PI * self.anh * sigma * sigma);
+        sample_times(self.duration, sample_rate)
+            .map(|time| {
+                let offset = time - self.t0;
+                let exponent = 0.5 * offset * offset / (sigma * sigma);
+                let gaussian = (-exponent).exp();
+                Complex64::new(
+                    (1.0 - coeff * exponent) * gaussian,
+                    derivative_factor * offset * gaussian * (coeff * (exponent - 1.0) - 1.0),
+                )
+            })
+            .collect()
+    }
+}
+
+/// The `erf_square` template: a square pulse whose edges each rise over `risetime` as error
+/// functions, padded with `pad_left` and `pad_right` seconds of zeros. The padding is not
+/// included in the `duration`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErfSquare {
+    duration: f64,
+    risetime: f64,
+    pad_left: f64,
+    pad_right: f64,
+    modulation: Modulation,
+}
+
+impl ErfSquare {
+    pub fn new(
+        duration: f64,
+        risetime: f64,
+        pad_left: f64,
+        pad_right: f64,
+    ) -> Result<Self, WaveformError> {
+        check_positive("erf_square", "duration", duration)?;
+        check_positive("erf_square", "risetime", risetime)?;
+        if risetime > duration {
+            return Err(invalid(
+                "erf_square",
+                "risetime",
+                "must not exceed the duration",
+            ));
+        }
+        check_non_negative("erf_square", "pad_left", pad_left)?;
+        check_non_negative("erf_square", "pad_right", pad_right)?;
+        Ok(Self {
+            duration,
+            risetime,
+            pad_left,
+            pad_right,
+            modulation: Modulation::default(),
+        })
+    }
+
+    pub fn risetime(&self) -> f64 {
+        self.risetime
+    }
+
+    pub fn pad_left(&self) -> f64 {
+        self.pad_left
+    }
+
+    pub fn pad_right(&self) -> f64 {
+        self.pad_right
+    }
+}
+
+impl WaveformTemplate for ErfSquare {
+    template_accessors!("erf_square");
+
+    fn envelope(&self, sample_rate: f64) -> Vec<Complex64> {
+        let fwhm = 0.5 * self.risetime;
+        let (rise, fall) = (fwhm, self.duration - fwhm);
+        let sigma = sigma(fwhm);
+        let zero = Complex64::from(0.0);
+
+        let mut samples = vec![zero; sample_count(self.pad_left, sample_rate)];
+        samples.extend(sample_times(self.duration, sample_rate).map(|time| {
+            Complex64::from(0.5 * (erf((time - rise) / sigma) - erf((time - fall) / sigma)))
+        }));
+        samples.extend(vec![zero; sample_count(self.pad_right, sample_rate)]);
+        samples
+    }
+}
+
+/// The `boxcar_kernel` template: a constant integration kernel whose samples sum to one.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoxcarKernel {
+    duration: f64,
+    modulation: Modulation,
+}
+
+impl BoxcarKernel {
+    pub fn new(duration: f64) -> Result<Self, WaveformError> {
+        check_positive("boxcar_kernel", "duration", duration)?;
+        Ok(Self {
+            duration,
+            modulation: Modulation::default(),
+        })
+    }
+
+    /// Build a kernel which integrates over the given duration, in seconds, lengthened to cover a
+    /// whole number of samples at the given rate in Hertz, as counted by [`sample_count`].
+    ///
+    /// ```rust
+    /// use quil_rs::waveform::{BoxcarKernel, KnownWaveform, WaveformTemplate};
+    ///
+    /// let kernel = BoxcarKernel::with_sample_rate(1.5e-9, 1e9).unwrap();
+    /// assert_eq!(kernel.duration(), 2e-9);
+    /// assert_eq!(
+    ///     kernel.to_invocation().to_template(),
+    ///     Ok(KnownWaveform::BoxcarKernel(kernel))
+    /// );
+    /// ```
+    pub fn with_sample_rate(duration: f64, sample_rate: f64) -> Result<Self, WaveformError> {
+        check_positive("boxcar_kernel", "duration", duration)?;
+        check_positive("boxcar_kernel", "sample_rate", sample_rate)?;
+        Self::new(sample_count(duration, sample_rate) as f64 / sample_rate)
+    }
+
+    /// Return the invocation of this kernel, as used by a `CAPTURE`, including any of its
+    /// [`Modulation`] which differs from the default.
+    pub fn to_invocation(&self) -> WaveformInvocation {
+        let mut parameters = HashMap::new();
+        parameters.insert("duration".to_owned(), Expression::from(self.duration));
+
+        let Modulation {
+            scale,
+            phase,
+            detuning,
+        } = self.modulation;
+        let default = Modulation::default();
+        for (parameter, value, default) in [
+            ("scale", scale, default.scale),
+            ("phase", phase, default.phase),
+            ("detuning", detuning, default.detuning),
+        ] {
+            if value != default {
+                parameters.insert(parameter.to_owned(), Expression::from(value));
+            }
+        }
+
+        WaveformInvocation {
+            name: self.name().to_owned(),
+            parameters,
+        }
+    }
+}
+
+impl WaveformTemplate for BoxcarKernel {
+    template_accessors!("boxcar_kernel");
+
+    fn envelope(&self, sample_rate: f64) -> Vec<Complex64> {
+        let count = sample_count(self.duration, sample_rate);
+        vec![Complex64::from(1.0 / count as f64); count]
+    }
+}
+
+/// The side of a waveform on which [`pad_to_multiple`] adds samples.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PadSide {
+    /// Before the first sample.
+    Left,
+    /// After the last sample.
+    Right,
+}
+
+/// Return the samples, each multiplied by the given factor.
+pub fn scale(samples: &[Complex64], factor: f64) -> Vec<Complex64> {
+    samples.iter().map(|sample| sample * factor).collect()
+}
+
+/// Return the samples, each rotated by the given phase in radians.
+pub fn rotate_phase(samples: &[Complex64], phase: f64) -> Vec<Complex64> {
+    let rotation = Complex64::from_polar(1.0, phase);
+    samples.iter().map(|sample| sample * rotation).collect()
+}
+
+/// Return the samples, taken at the given rate in Hertz, modulated by the given detuning in
+/// Hertz: the `i`th sample is rotated by `2π * detuning * i / sample_rate` radians.
+pub fn detune(samples: &[Complex64], detuning: f64, sample_rate: f64) -> Vec<Complex64> {
+    samples
+        .iter()
+        .enumerate()
+        .map(|(index, sample)| {
+            let time = index as f64 / sample_rate;
+            sample * Complex64::from_polar(1.0, 2.0 * PI * detuning * time)
+        })
+        .collect()
+}
+
+/// Return the samples of the given waveforms, one after another.
+pub fn concatenate<'a>(waveforms: impl IntoIterator<Item = &'a [Complex64]>) -> Vec<Complex64> {
+    waveforms.into_iter().flatten().copied().collect()
+}
+
+/// Return the samples, padded with zeros on the given side to the next multiple of the given
+/// number of samples, as hardware which plays waveforms in fixed-size chunks may require. A
+/// `multiple` of zero leaves the samples unpadded.
+///
+/// ```rust
+/// use num_complex::Complex64;
+/// use quil_rs::waveform::{pad_to_multiple, PadSide};
+///
+/// let samples = vec![Complex64::new(1.0, 0.0); 5];
+/// let padded = pad_to_multiple(&samples, 4, PadSide::Left);
+/// assert_eq!(padded.len(), 8);
+/// assert_eq!(padded[2], Complex64::new(0.0, 0.0));
+/// assert_eq!(padded[3], Complex64::new(1.0, 0.0));
+/// ```
+pub fn pad_to_multiple(samples: &[Complex64], multiple: usize, side: PadSide) -> Vec<Complex64> {
+    let padding = match multiple {
+        0 => 0,
+        _ => (multiple - samples.len() % multiple) % multiple,
+    };
+    let zeros = std::iter::repeat(Complex64::from(0.0)).take(padding);
+    match side {
+        PadSide::Left => zeros.chain(samples.iter().copied()).collect(),
+        PadSide::Right => samples.iter().copied().chain(zeros).collect(),
+    }
+}
+
+/// The parameters which may be given to every template, to set its [`Modulation`].
+const MODULATION_PARAMETERS: [&str; 3] = ["scale", "phase", "detuning"];
+
+/// Return the required parameters of the standard template with the given name, if it is one.
+fn required_parameters(name: &str) -> Option<&'static [&'static str]> {
+    match name {
+        "flat" => Some(&["duration", "iq"]),
+        "gaussian" => Some(&["duration", "fwhm", "t0"]),
+        "drag_gaussian" => Some(&["duration", "fwhm", "t0", "anh", "alpha"]),
+        "hrm_gaussian" => Some(&[
+            "duration",
+            "fwhm",
+            "t0",
+            "anh",
+            "alpha",
+            "second_order_hrm_coeff",
+        ]),
+        "erf_square" => Some(&["duration", "risetime", "pad_left", "pad_right"]),
+        "boxcar_kernel" => Some(&["duration"]),
+        _ => None,
+    }
+}
+
+/// Any of the standard waveform templates.
+#[derive(Clone, Debug, PartialEq)]
+pub enum KnownWaveform {
+    Flat(Flat),
+    Gaussian(Gaussian),
+    DragGaussian(DragGaussian),
+    HrmGaussian(HrmGaussian),
+    ErfSquare(ErfSquare),
+    BoxcarKernel(BoxcarKernel),
+}
+
+/// Delegate the methods of [`WaveformTemplate`] to each variant of [`KnownWaveform`], and
+/// convert each template into it.
+macro_rules! known_waveforms {
+    ($($variant:ident),*) => {
+        impl WaveformTemplate for KnownWaveform {
+            fn name(&self) -> &'static str {
+                match self {
+                    $(KnownWaveform::$variant(template) => template.name(),)*
+                }
+            }
+
+            fn duration(&self) -> f64 {
+                match self {
+                    $(KnownWaveform::$variant(template) => template.duration(),)*
+                }
+            }
+
+            fn modulation(&self) -> &Modulation {
+                match self {
+                    $(KnownWaveform::$variant(template) => template.modulation(),)*
+                }
+            }
+
+            fn modulation_mut(&mut self) -> &mut Modulation {
+                match self {
+                    $(KnownWaveform::$variant(template) => template.modulation_mut(),)*
+                }
+            }
+
+            fn envelope(&self, sample_rate: f64) -> Vec<Complex64> {
+                match self {
+                    $(KnownWaveform::$variant(template) => template.envelope(sample_rate),)*
+                }
+            }
+        }
+
+        $(
+            impl From<$variant> for KnownWaveform {
+                fn from(template: $variant) -> Self {
+                    KnownWaveform::$variant(template)
+                }
+            }
+        )*
+    };
+}
+
+known_waveforms!(
+    Flat,
+    Gaussian,
+    DragGaussian,
+    HrmGaussian,
+    ErfSquare,
+    BoxcarKernel
+);
+
+impl WaveformInvocation {
+    /// Check that this invokes a standard template with each of its required parameters, and no
+    /// parameters other than those and the optional `scale`, `phase`, and `detuning`. Unlike
+    /// [`WaveformInvocation::to_template`], the values of the parameters are not evaluated, and so
+    /// may refer to memory.
+    pub fn check_template_parameters(&self) -> Result<(), WaveformError> {
+        let required = required_parameters(&self.name)
+            .ok_or_else(|| WaveformError::UnknownTemplate(self.name.clone()))?;
+
+        if let Some(parameter) = required
+            .iter()
+            .find(|parameter| !self.parameters.contains_key(**parameter))
+        {
+            return Err(WaveformError::MissingParameter {
+                waveform: self.name.clone(),
+                parameter: (*parameter).to_owned(),
+            });
+        }
+
+        let mut unexpected = self
+            .parameters
+            .keys()
+            .filter(|parameter| {
+                !required.contains(&parameter.as_str())
+                    && !MODULATION_PARAMETERS.contains(&parameter.as_str())
+            })
+            .collect::<Vec<_>>();
+        unexpected.sort();
+        match unexpected.first() {
+            Some(parameter) => Err(WaveformError::UnexpectedParameter {
+                waveform: self.name.clone(),
+                parameter: (*parameter).clone(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Return the standard template invoked, with its parameters, if this invokes one with a
+    /// valid set of numeric parameters. See [`WaveformInvocation::to_template`] for the reason
+    /// that an invocation is not recognized.
+    pub fn as_template(&self) -> Option<KnownWaveform> {
+        self.to_template().ok()
+    }
+
+    /// Convert this invocation into the standard template it invokes. Each of the template's
+    /// required parameters must be given, along with any of the optional `scale`, `phase`, and
+    /// `detuning`, and each must evaluate to a number, which is real for all but the `iq` of
+    /// `flat`.
+    ///
+    /// ```rust
+    /// use std::str::FromStr;
+    /// use quil_rs::instruction::Instruction;
+    /// use quil_rs::Program;
+    /// use quil_rs::waveform::{KnownWaveform, WaveformError, WaveformTemplate};
+    ///
+    /// let program = Program::from_str(
+    ///     "PULSE 0 \"xy\" gaussian(duration: 1e-6, fwhm: 2e-7, t0: 5e-7, scale: 0.5)\n\
+    ///      PULSE 0 \"xy\" gaussian(duration: 1e-6, fwhm: 2e-7)",
+    /// )
+    /// .unwrap();
+    /// let waveforms: Vec<_> = program
+    ///     .instructions
+    ///     .iter()
+    ///     .map(|instruction| match instruction {
+    ///         Instruction::Pulse(pulse) => pulse.waveform.to_template(),
+    ///         _ => unreachable!(),
+    ///     })
+    ///     .collect();
+    ///
+    /// let gaussian = waveforms[0].as_ref().unwrap();
+    /// assert!(matches!(gaussian, KnownWaveform::Gaussian(_)));
+    /// assert_eq!(gaussian.modulation().scale, 0.5);
+    /// assert_eq!(gaussian.sample(1e9).len(), 1000);
+    /// assert_eq!(
+    ///     waveforms[1],
+    ///     Err(WaveformError::MissingParameter {
+    ///         waveform: "gaussian".to_owned(),
+    ///         parameter: "t0".to_owned(),
+    ///     })
+    /// );
+    /// ```
+    pub fn to_template(&self) -> Result<KnownWaveform, WaveformError> {
+        let name = self.name.as_str();
+        self.check_template_parameters()?;
+
+        let complex = |parameter: &str| -> Result<Option<Complex64>, WaveformError> {
+            self.parameters
+                .get(parameter)
+                .map(|expression| {
+                    expression
+                        .evaluate(&HashMap::new(), &HashMap::new())
+                        .map_err(|source| WaveformError::Evaluation {
+                            waveform: self.name.clone(),
+                            parameter: parameter.to_owned(),
+                            source,
+                        })
+                })
+                .transpose()
+        };
+        let optional = |parameter: &str| -> Result<Option<f64>, WaveformError> {
+            complex(parameter)?
+                .map(|value| {
+                    Expression::from(value)
+                        .to_real()
+                        .map_err(|source| WaveformError::Evaluation {
+                            waveform: self.name.clone(),
+                            parameter: parameter.to_owned(),
+                            source,
+                        })
+                })
+                .transpose()
+        };
+        // Required parameters are known to be present.
+        let real = |parameter: &str| optional(parameter).map(|value| value.unwrap_or_default());
+
+        let mut template: KnownWaveform = match name {
+            "flat" => Flat::new(real("duration")?, complex("iq")?.unwrap_or_default())?.into(),
+            "gaussian" => Gaussian::new(real("duration")?, real("fwhm")?, real("t0")?)?.into(),
+            "drag_gaussian" => DragGaussian::new(
+                real("duration")?,
+                real("fwhm")?,
+                real("t0")?,
+                real("anh")?,
+                real("alpha")?,
+            )?
+            .into(),
+            "hrm_gaussian" => HrmGaussian::new(
+                real("duration")?,
+                real("fwhm")?,
+                real("t0")?,
+                real("anh")?,
+                real("alpha")?,
+                real("second_order_hrm_coeff")?,
+            )?
+            .into(),
+            "erf_square" => ErfSquare::new(
+                real("duration")?,
+                real("risetime")?,
+                real("pad_left")?,
+                real("pad_right")?,
+            )?
+            .into(),
+            // The only remaining template is `boxcar_kernel`.
+            _ => BoxcarKernel::new(real("duration")?)?.into(),
+        };
+
+        let modulation = template.modulation_mut();
+        if let Some(scale) = optional("scale")? {
+            modulation.scale = scale;
+        }
+        if let Some(phase) = optional("phase")? {
+            modulation.phase = phase;
+        }
+        if let Some(detuning) = optional("detuning")? {
+            modulation.detuning = detuning;
+        }
+        Ok(template)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::f64::consts::PI;
+
+    use num_complex::Complex64;
+
+    use crate::expression::{EvaluationError, Expression};
+    use crate::instruction::WaveformInvocation;
+    use crate::{imag, real};
+
+    use super::{
+        concatenate, detune, erf, pad_to_multiple, rotate_phase, sample_count, scale, BoxcarKernel,
+        DragGaussian, ErfSquare, Flat, Gaussian, HrmGaussian, KnownWaveform, PadSide,
+        WaveformError, WaveformTemplate,
+    };
+
+    fn assert_close(actual: Complex64, expected: Complex64) {
+        assert!(
+            (actual - expected).norm() < 1e-6,
+            "{:?} != {:?}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn sample_counts() {
+        assert_eq!(sample_count(1e-6, 1e9), 1000);
+        assert_eq!(sample_count(1.5e-9, 1e9), 2);
+        assert_eq!(sample_count(1e-6, 0.0), 0);
+        assert_eq!(sample_count(1e-6, f64::NAN), 0);
+    }
+
+    #[test]
+    fn erf_values() {
+        assert!(erf(0.0).abs() < 1e-7);
+        assert!((erf(1.0) - 0.842_700_79).abs() < 1e-7);
+        assert!((erf(-1.0) + 0.842_700_79).abs() < 1e-7);
+    }
+
+    #[test]
+    fn flat_with_modulation() {
+        let samples = Flat::new(4.0, real!(2.0))
+            .unwrap()
+            .with_scale(0.5)
+            .with_phase(PI / 2.0)
+            .with_detuning(0.25)
+            .sample(1.0);
+        assert_eq!(samples.len(), 4);
+        assert_close(samples[0], imag!(1.0));
+        assert_close(samples[1], real!(-1.0));
+        assert_close(samples[2], imag!(-1.0));
+        assert_close(samples[3], real!(1.0));
+    }
+
+    #[test]
+    fn gaussians() {
+        let gaussian = Gaussian::new(10.0, 2.0, 5.0).unwrap();
+        let samples = gaussian.sample(1.0);
+        assert_eq!(samples.len(), 10);
+        assert_close(samples[5], real!(1.0));
+        // The full width at half maximum spans one sample either side of the center.
+        assert_close(samples[4], real!(0.5));
+        assert_close(samples[6], real!(0.5));
+
+        let drag = DragGaussian::new(10.0, 2.0, 5.0, -1.0, 0.0).unwrap();
+        assert_eq!(drag.sample(1.0), samples);
+        let drag = DragGaussian::new(10.0, 2.0, 5.0, -1.0, 1.0).unwrap();
+        let drag_samples = drag.sample(1.0);
+        assert_close(drag_samples[5], real!(1.0));
+        assert!(drag_samples[4].im > 0.0 && drag_samples[6].im < 0.0);
+
+        let hermite = HrmGaussian::new(10.0, 2.0, 5.0, -1.0, 0.0, 0.0).unwrap();
+        assert_eq!(hermite.sample(1.0), samples);
+    }
+
+    #[test]
+    fn erf_square() {
+        let samples = ErfSquare::new(100.0, 10.0, 3.0, 2.0).unwrap().sample(1.0);
+        assert_eq!(samples.len(), 105);
+        assert_eq!(samples[0], real!(0.0));
+        assert_close(samples[3 + 50], real!(1.0));
+        assert_close(samples[3 + 5], real!(0.5));
+        assert_eq!(samples[104], real!(0.0));
+    }
+
+    #[test]
+    fn boxcar_kernel() {
+        let samples = BoxcarKernel::new(8.0).unwrap().sample(1.0);
+        assert_eq!(samples.len(), 8);
+        assert_close(samples.iter().sum(), real!(1.0));
+
+        let kernel = BoxcarKernel::with_sample_rate(7.5, 1.0).unwrap();
+        assert_eq!(kernel.duration(), 8.0);
+        assert!(BoxcarKernel::with_sample_rate(7.5, 0.0).is_err());
+
+        let invocation = kernel.with_scale(0.5).to_invocation();
+        assert_eq!(
+            invocation.to_string(),
+            "boxcar_kernel(duration: 8, scale: 0.5)"
+        );
+        assert_eq!(
+            invocation.to_template(),
+            Ok(KnownWaveform::BoxcarKernel(
+                BoxcarKernel::new(8.0).unwrap().with_scale(0.5)
+            ))
+        );
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert_eq!(
+            Gaussian::new(1.0, -1.0, 0.0),
+            Err(WaveformError::InvalidParameter {
+                waveform: "gaussian".to_owned(),
+                parameter: "fwhm".to_owned(),
+                reason: "must be finite and positive".to_owned(),
+            })
+        );
+        assert!(Flat::new(0.0, real!(1.0)).is_err());
+        assert!(DragGaussian::new(1.0, 1.0, 0.0, 0.0, 1.0).is_err());
+        assert!(ErfSquare::new(1.0, 2.0, 0.0, 0.0).is_err());
+        assert!(ErfSquare::new(1.0, 0.5, -1.0, 0.0).is_err());
+    }
+
+    #[test]
+    fn iq_operations() {
+        let samples = vec![real!(1.0), imag!(2.0)];
+
+        assert_eq!(scale(&samples, 0.5), vec![real!(0.5), imag!(1.0)]);
+
+        let rotated = rotate_phase(&samples, PI / 2.0);
+        assert_close(rotated[0], imag!(1.0));
+        assert_close(rotated[1], real!(-2.0));
+
+        // A detuning of a quarter of the sample rate rotates each sample a quarter turn further.
+        let detuned = detune(&[real!(1.0); 3], 0.25, 1.0);
+        assert_close(detuned[0], real!(1.0));
+        assert_close(detuned[1], imag!(1.0));
+        assert_close(detuned[2], real!(-1.0));
+
+        // Modulating the envelope by hand matches a modulated template.
+        let flat = Flat::new(3.0, real!(1.0)).unwrap();
+        let by_hand = detune(&rotate_phase(&scale(&flat.sample(1.0), 2.0), 0.5), 0.1, 1.0);
+        let modulated = flat
+            .with_scale(2.0)
+            .with_phase(0.5)
+            .with_detuning(0.1)
+            .sample(1.0);
+        for (by_hand, modulated) in by_hand.into_iter().zip(modulated) {
+            assert_close(by_hand, modulated);
+        }
+
+        let joined = concatenate(vec![&samples[..], &[real!(3.0)][..]]);
+        assert_eq!(joined, vec![real!(1.0), imag!(2.0), real!(3.0)]);
+
+        assert_eq!(
+            pad_to_multiple(&joined, 2, PadSide::Right),
+            vec![real!(1.0), imag!(2.0), real!(3.0), real!(0.0)]
+        );
+        assert_eq!(pad_to_multiple(&joined, 3, PadSide::Left), joined);
+        assert_eq!(pad_to_multiple(&joined, 0, PadSide::Left), joined);
+    }
+
+    fn invocation(name: &str, parameters: &[(&str, Expression)]) -> WaveformInvocation {
+        WaveformInvocation {
+            name: name.to_owned(),
+            parameters: parameters
+                .iter()
+                .map(|(name, value)| ((*name).to_owned(), value.clone()))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn invocation_to_template() {
+        let flat = invocation(
+            "flat",
+            &[
+                ("duration", Expression::from(2.0)),
+                ("iq", Expression::from(imag!(1.0))),
+                ("phase", Expression::PiConstant),
+            ],
+        );
+        assert_eq!(
+            flat.to_template(),
+            Ok(KnownWaveform::Flat(
+                Flat::new(2.0, imag!(1.0)).unwrap().with_phase(PI)
+            ))
+        );
+
+        let boxcar = invocation("boxcar_kernel", &[("duration", Expression::from(4.0))]);
+        assert_eq!(
+            boxcar.as_template(),
+            Some(BoxcarKernel::new(4.0).unwrap().into())
+        );
+    }
+
+    #[test]
+    fn invalid_invocations() {
+        assert_eq!(
+            invocation("custom", &[]).to_template(),
+            Err(WaveformError::UnknownTemplate("custom".to_owned()))
+        );
+        assert_eq!(
+            invocation("boxcar_kernel", &[]).to_template(),
+            Err(WaveformError::MissingParameter {
+                waveform: "boxcar_kernel".to_owned(),
+                parameter: "duration".to_owned(),
+            })
+        );
+        assert_eq!(
+            invocation(
+                "boxcar_kernel",
+                &[
+                    ("duration", Expression::from(1.0)),
+                    ("width", Expression::from(1.0)),
+                    ("fwhm", Expression::from(1.0)),
+                ]
+            )
+            .to_template(),
+            Err(WaveformError::UnexpectedParameter {
+                waveform: "boxcar_kernel".to_owned(),
+                parameter: "fwhm".to_owned(),
+            })
+        );
+        assert_eq!(
+            invocation(
+                "boxcar_kernel",
+                &[("duration", Expression::from(imag!(1.0)))]
+            )
+            .to_template(),
+            Err(WaveformError::Evaluation {
+                waveform: "boxcar_kernel".to_owned(),
+                parameter: "duration".to_owned(),
+                source: EvaluationError::NumberNotReal,
+            })
+        );
+        assert!(invocation(
+            "gaussian",
+            &[
+                ("duration", Expression::from(1.0)),
+                ("fwhm", Expression::Variable("width".to_owned())),
+                ("t0", Expression::from(0.5)),
+            ]
+        )
+        .as_template()
+        .is_none());
+        assert!(
+            invocation("boxcar_kernel", &[("duration", Expression::from(-1.0))])
+                .as_template()
+                .is_none()
+        );
+    }
+}